@@ -0,0 +1,659 @@
+//! Drawing on the host screen: pen, arrow, highlighter and laser pointer.
+//!
+//! The viewer sends its strokes as points (`Msg::Annot`, 0..10000 over the
+//! shared screen) and paints them over the picture itself. The host shows
+//! them in a transparent, click-through window on top of everything that
+//! is kept out of the capture (`WDA_EXCLUDEFROMCAPTURE`), so they never
+//! come back as part of the picture; where Windows cannot keep the window
+//! out, the host shows no strokes at all. Strokes fade out after `LIFE`, the
+//! laser after `LASER_LIFE`; `Msg::AnnotClear` wipes everything at once.
+//!
+//! Both sides use the same `Board` and the same geometry (`paths`), so the
+//! viewer sees what the person at the host sees.
+
+use std::time::{Duration, Instant};
+
+use crate::proto::{Msg, ANNOT_ARROW, ANNOT_LASER, ANNOT_MARK, ANNOT_PEN, MAX_ANNOT_POINTS};
+
+/// How long a stroke stays after its last point.
+const LIFE: Duration = Duration::from_secs(8);
+/// The laser is a pointer, not a drawing.
+const LASER_LIFE: Duration = Duration::from_millis(1200);
+/// The last part of the life is spent fading out.
+const FADE: Duration = Duration::from_secs(1);
+/// Points of the laser trail that are drawn.
+const LASER_TRAIL: usize = 8;
+/// Strokes on the board at most; the oldest goes first.
+const MAX_STROKES: usize = 200;
+/// Points of one stroke at most.
+const MAX_POINTS: usize = 8192;
+
+pub struct Stroke {
+    pub id: u32,
+    pub tool: u8,
+    pub points: Vec<(u16, u16)>,
+    /// When the last point came.
+    pub at: Instant,
+}
+
+/// Colour, line width (pixels at a screen 1080 pixels high) and opacity
+/// of a tool.
+pub fn style(tool: u8) -> ([u8; 3], f32, f32) {
+    match tool {
+        ANNOT_MARK => ([255, 220, 0], 22.0, 0.35),
+        ANNOT_LASER => ([255, 40, 40], 10.0, 0.9),
+        _ => ([230, 40, 40], 4.0, 1.0),
+    }
+}
+
+impl Stroke {
+    fn life(&self) -> Duration {
+        if self.tool == ANNOT_LASER {
+            LASER_LIFE
+        } else {
+            LIFE
+        }
+    }
+
+    /// Opacity right now: the tool's, fading towards the end of the life.
+    pub fn alpha(&self, now: Instant) -> f32 {
+        let life = self.life();
+        let age = now.saturating_duration_since(self.at);
+        if age >= life {
+            return 0.0;
+        }
+        let fade = FADE.min(life);
+        let left = (life - age).as_secs_f32() / fade.as_secs_f32();
+        style(self.tool).2 * left.min(1.0)
+    }
+}
+
+/// The strokes currently shown.
+#[derive(Default)]
+pub struct Board {
+    pub strokes: Vec<Stroke>,
+}
+
+impl Board {
+    /// Adds points to stroke `id`, starting it if it is new.
+    pub fn add(&mut self, id: u32, tool: u8, points: &[(u16, u16)], now: Instant) {
+        let clamp = |&(x, y): &(u16, u16)| (x.min(10000), y.min(10000));
+        if let Some(s) = self.strokes.iter_mut().rev().find(|s| s.id == id) {
+            let room = MAX_POINTS.saturating_sub(s.points.len());
+            s.points.extend(points.iter().take(room).map(clamp));
+            s.at = now;
+            return;
+        }
+        if self.strokes.len() >= MAX_STROKES {
+            self.strokes.remove(0);
+        }
+        self.strokes.push(Stroke {
+            id,
+            tool,
+            points: points.iter().take(MAX_POINTS).map(clamp).collect(),
+            at: now,
+        });
+    }
+
+    /// Takes `Annot` and `AnnotClear`; true if the board changed.
+    pub fn on_msg(&mut self, m: &Msg, now: Instant) -> bool {
+        match m {
+            Msg::Annot { id, tool, points } => {
+                self.add(*id, *tool, points, now);
+                true
+            }
+            Msg::AnnotClear => {
+                let had = !self.strokes.is_empty();
+                self.strokes.clear();
+                had
+            }
+            _ => false,
+        }
+    }
+
+    /// Drops what has faded out; true if something went.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let n = self.strokes.len();
+        self.strokes.retain(|s| s.alpha(now) > 0.0);
+        self.strokes.len() != n
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty()
+    }
+}
+
+/// True for every message of the drawing channel.
+pub fn is_annot_msg(m: &Msg) -> bool {
+    matches!(m, Msg::Annot { .. } | Msg::AnnotClear)
+}
+
+/// The lines of a stroke in pixels of a `w` x `h` picture. An arrow is its
+/// shaft from the first to the last point plus the two sides of the head.
+pub fn paths(s: &Stroke, w: f32, h: f32) -> Vec<Vec<(f32, f32)>> {
+    let px = |&(x, y): &(u16, u16)| (x as f32 / 10000.0 * w, y as f32 / 10000.0 * h);
+    match s.tool {
+        ANNOT_ARROW => {
+            let (Some(a), Some(b)) = (s.points.first(), s.points.last()) else {
+                return Vec::new();
+            };
+            let (a, b) = (px(a), px(b));
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let len = (dx * dx + dy * dy).sqrt();
+            if len < 1.0 {
+                return vec![vec![a, b]];
+            }
+            let head = (h * 0.025).min(len * 0.5);
+            let (ux, uy) = (dx / len, dy / len);
+            // the head's sides at +-28 degrees from the shaft
+            let (c, sn) = (0.883_f32, 0.469_f32);
+            let side = |sg: f32| {
+                let (rx, ry) = (ux * c - uy * sn * sg, uy * c + ux * sn * sg);
+                (b.0 - rx * head, b.1 - ry * head)
+            };
+            vec![vec![a, b], vec![side(1.0), b, side(-1.0)]]
+        }
+        ANNOT_LASER => {
+            let from = s.points.len().saturating_sub(LASER_TRAIL);
+            vec![s.points[from..].iter().map(px).collect()]
+        }
+        _ => vec![s.points.iter().map(px).collect()],
+    }
+}
+
+/// Paints the board into a `w` x `h` BGRA picture with premultiplied alpha
+/// (what a layered window wants). `buf` is reused between frames.
+pub fn render(board: &Board, w: u32, h: u32, now: Instant, buf: &mut Vec<u8>) {
+    let (w, h) = (w as usize, h as usize);
+    buf.clear();
+    buf.resize(w * h * 4, 0);
+    for s in &board.strokes {
+        let alpha = s.alpha(now);
+        if alpha <= 0.0 {
+            continue;
+        }
+        let (rgb, width, _) = style(s.tool);
+        let r = (width * h as f32 / 1080.0 / 2.0).max(1.0);
+        for path in paths(s, w as f32, h as f32) {
+            if path.len() == 1 {
+                capsule(buf, w, h, path[0], path[0], r, rgb, alpha);
+            }
+            for seg in path.windows(2) {
+                // long lines in short pieces: the box around a diagonal
+                // across the screen would be the whole screen
+                let (a, b) = (seg[0], seg[1]);
+                let len = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+                let n = (len / 32.0).ceil().max(1.0);
+                let at = |k: f32| (a.0 + (b.0 - a.0) * k / n, a.1 + (b.1 - a.1) * k / n);
+                let mut k = 0.0;
+                while k < n {
+                    capsule(buf, w, h, at(k), at(k + 1.0), r, rgb, alpha);
+                    k += 1.0;
+                }
+            }
+        }
+    }
+}
+
+/// A thick line with round ends. Where strokes overlap the stronger one
+/// wins instead of adding up, so a highlighter stays see-through.
+#[allow(clippy::too_many_arguments)]
+fn capsule(
+    buf: &mut [u8],
+    w: usize,
+    h: usize,
+    a: (f32, f32),
+    b: (f32, f32),
+    r: f32,
+    rgb: [u8; 3],
+    alpha: f32,
+) {
+    let x0 = (a.0.min(b.0) - r - 1.0).floor().max(0.0) as usize;
+    let y0 = (a.1.min(b.1) - r - 1.0).floor().max(0.0) as usize;
+    let x1 = ((a.0.max(b.0) + r + 1.0).ceil() as usize).min(w);
+    let y1 = ((a.1.max(b.1) + r + 1.0).ceil() as usize).min(h);
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len2 = dx * dx + dy * dy;
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let t = if len2 > 0.0 {
+                (((px - a.0) * dx + (py - a.1) * dy) / len2).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let (ex, ey) = (px - a.0 - t * dx, py - a.1 - t * dy);
+            let d = (ex * ex + ey * ey).sqrt();
+            // one pixel of soft edge
+            let cover = (r + 0.5 - d).clamp(0.0, 1.0);
+            if cover <= 0.0 {
+                continue;
+            }
+            let a8 = (cover * alpha * 255.0).round() as u8;
+            let i = (y * w + x) * 4;
+            if a8 > buf[i + 3] {
+                let pm = |c: u8| (c as u16 * a8 as u16 / 255) as u8;
+                buf[i] = pm(rgb[2]);
+                buf[i + 1] = pm(rgb[1]);
+                buf[i + 2] = pm(rgb[0]);
+                buf[i + 3] = a8;
+            }
+        }
+    }
+}
+
+// ---------------------------------------------------------------- viewer --
+
+/// Points wait this long to be sent together.
+const BATCH: Duration = Duration::from_millis(30);
+/// A mouse held still says so this often, or the host would let the
+/// stroke (above all the laser) fade away under it.
+const KEEP_ALIVE: Duration = Duration::from_millis(400);
+
+/// The drawing side in the viewer: the chosen tool, the stroke being
+/// drawn and the board the picture is painted with.
+#[derive(Default)]
+pub struct Pad {
+    /// `ANNOT_*`, or `None` when the mouse drives the host as usual.
+    pub tool: Option<u8>,
+    pub board: Board,
+    next_id: u32,
+    /// The stroke under the pressed mouse button.
+    cur: Option<u32>,
+    pending: Vec<(u16, u16)>,
+    sent: Option<Instant>,
+}
+
+impl Pad {
+    /// The mouse is down at `p` (0..10000). Returns points to send once
+    /// enough came together.
+    pub fn point(&mut self, p: (u16, u16)) -> Option<Msg> {
+        let tool = self.tool?;
+        let now = Instant::now();
+        let id = match self.cur {
+            Some(id) => id,
+            None => {
+                self.next_id = self.next_id.wrapping_add(1);
+                self.cur = Some(self.next_id);
+                self.sent = None;
+                self.next_id
+            }
+        };
+        let last = self
+            .board
+            .strokes
+            .iter()
+            .rev()
+            .find(|s| s.id == id)
+            .and_then(|s| s.points.last().copied());
+        if last == Some(p) {
+            // the mouse did not move; keep the stroke alive all the same
+            self.board.add(id, tool, &[], now);
+            if self.sent.is_some_and(|t| now.duration_since(t) >= KEEP_ALIVE) {
+                self.pending.push(p);
+                return self.flush();
+            }
+            return None;
+        }
+        self.board.add(id, tool, &[p], now);
+        self.pending.push(p);
+        let due = self.sent.is_none_or(|t| now.duration_since(t) >= BATCH);
+        if due || self.pending.len() >= MAX_ANNOT_POINTS {
+            return self.flush();
+        }
+        None
+    }
+
+    /// The mouse button came up: the rest of the stroke.
+    pub fn release(&mut self) -> Option<Msg> {
+        let m = self.flush();
+        self.cur = None;
+        m
+    }
+
+    /// True while a stroke is being drawn.
+    pub fn drawing(&self) -> bool {
+        self.cur.is_some()
+    }
+
+    /// Wipes both boards.
+    pub fn clear(&mut self) -> Msg {
+        self.board.strokes.clear();
+        self.pending.clear();
+        self.cur = None;
+        Msg::AnnotClear
+    }
+
+    fn flush(&mut self) -> Option<Msg> {
+        let (id, tool) = (self.cur?, self.tool?);
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.sent = Some(Instant::now());
+        Some(Msg::Annot {
+            id,
+            tool,
+            points: std::mem::take(&mut self.pending),
+        })
+    }
+}
+
+/// Tools in the order of the menu, with their i18n keys.
+pub const TOOLS: [(u8, &str); 4] = [
+    (ANNOT_PEN, "draw.pen"),
+    (ANNOT_ARROW, "draw.arrow"),
+    (ANNOT_MARK, "draw.mark"),
+    (ANNOT_LASER, "draw.laser"),
+];
+
+// ------------------------------------------------------------------ host --
+
+/// The transparent window on the host that shows the strokes. One per
+/// session; the window comes with the first stroke and goes with the
+/// session.
+#[derive(Default)]
+pub struct Overlay {
+    #[cfg(windows)]
+    inner: Option<std::sync::Arc<win::Shared>>,
+}
+
+impl Overlay {
+    /// A drawing message from the viewer; `rect` is the shared screen in
+    /// desktop pixels.
+    pub fn on_msg(&mut self, m: &Msg, rect: crate::input::ScreenRect) {
+        #[cfg(windows)]
+        {
+            let inner = self.inner.get_or_insert_with(win::start);
+            *inner.rect.lock().unwrap() = rect;
+            inner.board.lock().unwrap().on_msg(m, Instant::now());
+        }
+        #[cfg(not(windows))]
+        {
+            let _ = (m, rect);
+        }
+    }
+}
+
+impl Drop for Overlay {
+    fn drop(&mut self) {
+        #[cfg(windows)]
+        if let Some(inner) = self.inner.as_ref() {
+            inner.stop.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod win {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use windows::core::w;
+    use windows::Win32::Foundation::{
+        COLORREF, HINSTANCE, HWND, LPARAM, LRESULT, POINT, SIZE, WPARAM,
+    };
+    use windows::Win32::Graphics::Gdi::{
+        CreateCompatibleDC, CreateDIBSection, DeleteDC, DeleteObject, SelectObject, AC_SRC_ALPHA,
+        AC_SRC_OVER, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, BLENDFUNCTION, DIB_RGB_COLORS, HDC,
+        HGDIOBJ,
+    };
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    use super::Board;
+    use crate::input::ScreenRect;
+
+    /// What the session and the window thread share.
+    pub struct Shared {
+        pub board: Mutex<Board>,
+        pub rect: Mutex<ScreenRect>,
+        pub stop: AtomicBool,
+    }
+
+    /// One redraw per this while something is on the board.
+    const TICK: Duration = Duration::from_millis(40);
+
+    pub fn start() -> Arc<Shared> {
+        let sh = Arc::new(Shared {
+            board: Mutex::new(Board::default()),
+            rect: Mutex::new(ScreenRect::default()),
+            stop: AtomicBool::new(false),
+        });
+        let s2 = sh.clone();
+        std::thread::spawn(move || unsafe { run(&s2) });
+        sh
+    }
+
+    unsafe extern "system" fn wndproc(hwnd: HWND, msg: u32, wp: WPARAM, lp: LPARAM) -> LRESULT {
+        DefWindowProcW(hwnd, msg, wp, lp)
+    }
+
+    unsafe fn run(sh: &Shared) {
+        let Ok(module) = GetModuleHandleW(None) else {
+            return;
+        };
+        let hinst = HINSTANCE(module.0);
+        let class = w!("FreeViewerAnnot");
+        let wc = WNDCLASSW {
+            lpfnWndProc: Some(wndproc),
+            hInstance: hinst,
+            lpszClassName: class,
+            ..Default::default()
+        };
+        RegisterClassW(&wc);
+        // layered + transparent = clicks fall through to what is below
+        let hwnd = match CreateWindowExW(
+            WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+            class,
+            w!(""),
+            WS_POPUP,
+            0,
+            0,
+            1,
+            1,
+            HWND::default(),
+            HMENU::default(),
+            hinst,
+            None,
+        ) {
+            Ok(h) => h,
+            Err(e) => {
+                crate::capture::log_line(&format!("Zeichenfenster: {}", e));
+                return;
+            }
+        };
+        // the strokes must stay out of the picture (Windows 10 2004 and
+        // later) - if they would show up in the stream, no overlay at all
+        if let Err(e) = SetWindowDisplayAffinity(hwnd, WDA_EXCLUDEFROMCAPTURE) {
+            let _ = DestroyWindow(hwnd);
+            crate::capture::log_line(&format!(
+                "Zeichenfenster nicht vom Aufnehmen ausnehmbar: {}",
+                e
+            ));
+            return;
+        }
+
+        let mem = CreateCompatibleDC(HDC::default());
+        let mut dib: Option<(u32, u32, windows::Win32::Graphics::Gdi::HBITMAP, *mut u8)> = None;
+        let mut buf = Vec::new();
+        let mut shown = false;
+        while !sh.stop.load(Ordering::Relaxed) {
+            let mut msg = MSG::default();
+            while PeekMessageW(&mut msg, HWND::default(), 0, 0, PM_REMOVE).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+            let now = Instant::now();
+            let rect = *sh.rect.lock().unwrap();
+            let (w, h) = (rect.w.max(1), rect.h.max(1));
+            {
+                let mut board = sh.board.lock().unwrap();
+                board.expire(now);
+                if board.is_empty() {
+                    if shown {
+                        let _ = ShowWindow(hwnd, SW_HIDE);
+                        shown = false;
+                    }
+                    drop(board);
+                    std::thread::sleep(TICK);
+                    continue;
+                }
+                super::render(&board, w, h, now, &mut buf);
+            }
+            // a fresh bitmap whenever the shared screen changes size
+            if dib.is_none_or(|(dw, dh, ..)| (dw, dh) != (w, h)) {
+                if let Some((.., old, _)) = dib.take() {
+                    let _ = DeleteObject(HGDIOBJ(old.0));
+                }
+                let mut info = BITMAPINFO::default();
+                info.bmiHeader = BITMAPINFOHEADER {
+                    biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: w as i32,
+                    biHeight: -(h as i32),
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..Default::default()
+                };
+                let mut bits: *mut core::ffi::c_void = std::ptr::null_mut();
+                match CreateDIBSection(mem, &info, DIB_RGB_COLORS, &mut bits, None, 0) {
+                    Ok(b) => {
+                        SelectObject(mem, HGDIOBJ(b.0));
+                        dib = Some((w, h, b, bits as *mut u8));
+                    }
+                    Err(e) => {
+                        crate::capture::log_line(&format!("Zeichenfenster: {}", e));
+                        std::thread::sleep(TICK);
+                        continue;
+                    }
+                }
+            }
+            let Some((.., bits)) = dib else {
+                continue;
+            };
+            std::ptr::copy_nonoverlapping(buf.as_ptr(), bits, buf.len());
+            let pos = POINT {
+                x: rect.x,
+                y: rect.y,
+            };
+            let size = SIZE {
+                cx: w as i32,
+                cy: h as i32,
+            };
+            let src = POINT { x: 0, y: 0 };
+            let blend = BLENDFUNCTION {
+                BlendOp: AC_SRC_OVER as u8,
+                BlendFlags: 0,
+                SourceConstantAlpha: 255,
+                AlphaFormat: AC_SRC_ALPHA as u8,
+            };
+            let _ = UpdateLayeredWindow(
+                hwnd,
+                HDC::default(),
+                Some(&pos as *const POINT),
+                Some(&size as *const SIZE),
+                mem,
+                Some(&src as *const POINT),
+                COLORREF(0),
+                Some(&blend as *const BLENDFUNCTION),
+                ULW_ALPHA,
+            );
+            if !shown {
+                let _ = ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+                shown = true;
+            }
+            std::thread::sleep(TICK);
+        }
+        if let Some((.., b, _)) = dib {
+            let _ = DeleteObject(HGDIOBJ(b.0));
+        }
+        let _ = DeleteDC(mem);
+        let _ = DestroyWindow(hwnd);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strokes_grow_fade_and_clear() {
+        let t0 = Instant::now();
+        let mut b = Board::default();
+        b.on_msg(
+            &Msg::Annot {
+                id: 1,
+                tool: ANNOT_PEN,
+                points: vec![(0, 0), (20000, 5000)],
+            },
+            t0,
+        );
+        b.add(1, ANNOT_PEN, &[(100, 100)], t0);
+        b.add(2, ANNOT_LASER, &[(5000, 5000)], t0);
+        assert_eq!(b.strokes.len(), 2);
+        // points beyond the screen are pulled onto its edge
+        assert_eq!(b.strokes[0].points, vec![(0, 0), (10000, 5000), (100, 100)]);
+        // the laser is gone long before the pen, which fades at the end
+        assert!(b.expire(t0 + LASER_LIFE));
+        assert_eq!(b.strokes.len(), 1);
+        let late = t0 + LIFE - FADE / 2;
+        assert!((b.strokes[0].alpha(late) - 0.5).abs() < 0.01);
+        assert!(b.on_msg(&Msg::AnnotClear, t0));
+        assert!(b.is_empty());
+    }
+
+    #[test]
+    fn arrow_is_a_shaft_and_a_head() {
+        let s = Stroke {
+            id: 1,
+            tool: ANNOT_ARROW,
+            points: vec![(0, 5000), (3000, 4000), (10000, 5000)],
+            at: Instant::now(),
+        };
+        let p = paths(&s, 1000.0, 1000.0);
+        assert_eq!(p[0], vec![(0.0, 500.0), (1000.0, 500.0)]);
+        // both sides end at the tip and point back along the shaft
+        assert_eq!(p[1].len(), 3);
+        assert_eq!(p[1][1], (1000.0, 500.0));
+        assert!(p[1][0].0 < 1000.0 && p[1][2].0 < 1000.0);
+        assert!(p[1][0].1 != p[1][2].1);
+    }
+
+    #[test]
+    fn render_paints_premultiplied_pixels() {
+        let t0 = Instant::now();
+        let mut b = Board::default();
+        b.add(1, ANNOT_MARK, &[(0, 5000), (10000, 5000)], t0);
+        let mut buf = Vec::new();
+        render(&b, 200, 100, t0, &mut buf);
+        let px = |x: usize, y: usize| &buf[(y * 200 + x) * 4..(y * 200 + x) * 4 + 4];
+        // on the line: yellow at a third of full strength, premultiplied
+        let on = px(100, 50);
+        assert_eq!(on[3], (0.35f32 * 255.0).round() as u8);
+        assert_eq!(on[0], 0);
+        assert!(on[2] <= on[3] && on[2] > 0);
+        // far away: nothing
+        assert_eq!(px(100, 5), &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn pad_batches_points_and_starts_new_strokes() {
+        let mut p = Pad::default();
+        assert!(p.point((1, 1)).is_none(), "no tool, no drawing");
+        p.tool = Some(ANNOT_PEN);
+        let first = p.point((10, 10));
+        assert!(matches!(first, Some(Msg::Annot { id: 1, .. })));
+        // right after that the points wait for the next batch
+        assert!(p.point((20, 20)).is_none());
+        assert!(p.point((20, 20)).is_none());
+        let Some(Msg::Annot { id, points, .. }) = p.release() else {
+            panic!("rest of the stroke missing");
+        };
+        assert_eq!((id, points), (1, vec![(20, 20)]));
+        assert!(!p.drawing());
+        assert!(matches!(p.point((5, 5)), Some(Msg::Annot { id: 2, .. })));
+        assert!(matches!(p.clear(), Msg::AnnotClear));
+        assert!(p.board.is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+//! Session audit log: when a session began and ended, with whom, and what
+//! was said in its chat.
+//!
+//! `audit.log` in the config dir, plain text, one line per event:
+//!
+//! ```text
+//! 2026-10-18_14-03-22  Anna-PC  Sitzung beginnt (Anfrage)
+//! 2026-10-18_14-04-10  Anna-PC  Chat erhalten: Drucker geht wieder nicht
+//! ```
+//!
+//! Times are UTC like the names of recordings. At `MAX_SIZE` the file moves
+//! to `audit.log.1` (replacing the one before), so it never eats the disk.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_SIZE: u64 = 1 << 20;
+
+/// Host loop, input loop and GUI all write; lines must not interleave.
+static LOCK: Mutex<()> = Mutex::new(());
+
+pub fn path() -> PathBuf {
+    crate::ident::config_dir().join("audit.log")
+}
+
+/// Appends one event. Failing to write is not worth stopping a session for.
+pub fn log(peer: &str, what: &str) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let _guard = LOCK.lock().unwrap();
+    let p = path();
+    if std::fs::metadata(&p).is_ok_and(|m| m.len() > MAX_SIZE) {
+        let _ = std::fs::rename(&p, p.with_extension("log.1"));
+    }
+    if let Ok(mut f) = std::fs::OpenOptions::new().create(true).append(true).open(&p) {
+        let _ = f.write_all(line(secs, peer, what).as_bytes());
+    }
+}
+
+/// One line of the log. Line breaks and other control characters in chat
+/// text become spaces so every event stays a single line.
+fn line(unix_secs: u64, peer: &str, what: &str) -> String {
+    let flat = |s: &str| -> String {
+        s.chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect()
+    };
+    let peer = if peer.trim().is_empty() { "?" } else { peer.trim() };
+    format!(
+        "{}  {}  {}\n",
+        crate::record::stamp(unix_secs),
+        flat(peer),
+        flat(what)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_event_one_line() {
+        assert_eq!(
+            line(1_792_300_000, "Anna-PC", "Chat erhalten: erste\nzweite\tZeile"),
+            "2026-10-18_05-06-40  Anna-PC  Chat erhalten: erste zweite Zeile\n"
+        );
+        assert!(line(0, " ", "x").contains("  ?  x"));
+    }
+}
@@ -0,0 +1,262 @@
+//! A minimal AVI 1.0 writer: Motion-JPEG pictures and optional 16-bit mono
+//! PCM sound, with an `idx1` index so players can seek. Every player on
+//! every system opens that, which is the point of exporting recordings.
+//!
+//! AVI 1.0 counts in 32 bits, so a file stops at 4 GB; `frame` and `audio`
+//! say so with an error instead of writing a broken file.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// AVIIF_KEYFRAME: every MJPEG picture stands on its own.
+const KEYFRAME: u32 = 0x10;
+/// AVIF_HASINDEX | AVIF_ISINTERLEAVED
+const AVIF: u32 = 0x10 | 0x100;
+const LIMIT: u64 = u32::MAX as u64 - (1 << 20);
+
+pub struct AviWriter {
+    out: BufWriter<File>,
+    w: u32,
+    h: u32,
+    fps: u32,
+    /// Sample rate of the sound stream, if there is one.
+    rate: Option<u32>,
+    frames: u32,
+    samples: u64,
+    biggest: u32,
+    /// (chunk id, offset from "movi", size)
+    index: Vec<([u8; 4], u32, u32)>,
+    /// Bytes inside the movi list after its "movi" id.
+    movi: u64,
+}
+
+fn u16le(v: &mut Vec<u8>, x: u16) {
+    v.extend_from_slice(&x.to_le_bytes());
+}
+fn u32le(v: &mut Vec<u8>, x: u32) {
+    v.extend_from_slice(&x.to_le_bytes());
+}
+fn chunk(v: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+    v.extend_from_slice(id);
+    u32le(v, body.len() as u32);
+    v.extend_from_slice(body);
+}
+fn list(v: &mut Vec<u8>, kind: &[u8; 4], body: &[u8]) {
+    v.extend_from_slice(b"LIST");
+    u32le(v, body.len() as u32 + 4);
+    v.extend_from_slice(kind);
+    v.extend_from_slice(body);
+}
+
+impl AviWriter {
+    pub fn create(path: &Path, w: u32, h: u32, fps: u32, rate: Option<u32>) -> io::Result<Self> {
+        let mut me = Self {
+            out: BufWriter::new(File::create(path)?),
+            w,
+            h,
+            fps: fps.max(1),
+            rate,
+            frames: 0,
+            samples: 0,
+            biggest: 0,
+            index: Vec::new(),
+            movi: 0,
+        };
+        // written again with the real numbers by `finish`
+        let head = me.header(0);
+        me.out.write_all(&head)?;
+        Ok(me)
+    }
+
+    /// Everything up to and including the "movi" id. Same length every time.
+    fn header(&self, riff: u32) -> Vec<u8> {
+        let mut avih = Vec::new();
+        u32le(&mut avih, 1_000_000 / self.fps);
+        u32le(&mut avih, 0);
+        u32le(&mut avih, 0);
+        u32le(&mut avih, AVIF);
+        u32le(&mut avih, self.frames);
+        u32le(&mut avih, 0);
+        u32le(&mut avih, 1 + self.rate.is_some() as u32);
+        u32le(&mut avih, self.biggest);
+        u32le(&mut avih, self.w);
+        u32le(&mut avih, self.h);
+        avih.extend_from_slice(&[0; 16]);
+
+        let mut strh = Vec::new();
+        strh.extend_from_slice(b"vidsMJPG");
+        u32le(&mut strh, 0); // flags
+        u32le(&mut strh, 0); // priority, language
+        u32le(&mut strh, 0); // initial frames
+        u32le(&mut strh, 1); // scale
+        u32le(&mut strh, self.fps); // rate
+        u32le(&mut strh, 0); // start
+        u32le(&mut strh, self.frames); // length
+        u32le(&mut strh, self.biggest);
+        u32le(&mut strh, u32::MAX); // quality: default
+        u32le(&mut strh, 0); // sample size
+        u16le(&mut strh, 0);
+        u16le(&mut strh, 0);
+        u16le(&mut strh, self.w as u16);
+        u16le(&mut strh, self.h as u16);
+        let mut strf = Vec::new();
+        u32le(&mut strf, 40);
+        u32le(&mut strf, self.w);
+        u32le(&mut strf, self.h);
+        u16le(&mut strf, 1);
+        u16le(&mut strf, 24);
+        strf.extend_from_slice(b"MJPG");
+        u32le(&mut strf, self.w * self.h * 3);
+        strf.extend_from_slice(&[0; 16]);
+        let mut strl = Vec::new();
+        chunk(&mut strl, b"strh", &strh);
+        chunk(&mut strl, b"strf", &strf);
+
+        let mut hdrl = Vec::new();
+        chunk(&mut hdrl, b"avih", &avih);
+        list(&mut hdrl, b"strl", &strl);
+
+        if let Some(rate) = self.rate {
+            let mut strh = Vec::new();
+            strh.extend_from_slice(b"auds");
+            u32le(&mut strh, 0); // handler
+            u32le(&mut strh, 0);
+            u32le(&mut strh, 0);
+            u32le(&mut strh, 0);
+            u32le(&mut strh, 1); // scale: one sample ...
+            u32le(&mut strh, rate); // ... of `rate` per second
+            u32le(&mut strh, 0);
+            u32le(&mut strh, self.samples as u32);
+            u32le(&mut strh, rate / 5);
+            u32le(&mut strh, u32::MAX);
+            u32le(&mut strh, 2); // sample size: 16 bit mono
+            strh.extend_from_slice(&[0; 8]);
+            let mut strf = Vec::new();
+            u16le(&mut strf, 1); // PCM
+            u16le(&mut strf, 1);
+            u32le(&mut strf, rate);
+            u32le(&mut strf, rate * 2);
+            u16le(&mut strf, 2);
+            u16le(&mut strf, 16);
+            u16le(&mut strf, 0);
+            let mut strl = Vec::new();
+            chunk(&mut strl, b"strh", &strh);
+            chunk(&mut strl, b"strf", &strf);
+            list(&mut hdrl, b"strl", &strl);
+        }
+
+        let mut v = Vec::new();
+        v.extend_from_slice(b"RIFF");
+        u32le(&mut v, riff);
+        v.extend_from_slice(b"AVI ");
+        list(&mut v, b"hdrl", &hdrl);
+        v.extend_from_slice(b"LIST");
+        u32le(&mut v, self.movi as u32 + 4);
+        v.extend_from_slice(b"movi");
+        v
+    }
+
+    fn put(&mut self, id: &[u8; 4], data: &[u8]) -> io::Result<()> {
+        let padded = data.len() as u64 + (data.len() & 1) as u64;
+        if self.movi + 8 + padded + 16 * (self.index.len() as u64 + 1) > LIMIT {
+            return Err(io::Error::other("AVI-Datei wuerde groesser als 4 GB"));
+        }
+        self.index
+            .push((*id, self.movi as u32 + 4, data.len() as u32));
+        self.out.write_all(id)?;
+        self.out.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.out.write_all(data)?;
+        if data.len() & 1 == 1 {
+            self.out.write_all(&[0])?;
+        }
+        self.movi += 8 + padded;
+        Ok(())
+    }
+
+    /// One picture, a complete JPEG of the size given to `create`.
+    pub fn frame(&mut self, jpeg: &[u8]) -> io::Result<()> {
+        self.put(b"00dc", jpeg)?;
+        self.frames += 1;
+        self.biggest = self.biggest.max(jpeg.len() as u32);
+        Ok(())
+    }
+
+    /// Sound that belongs to the time since the last picture.
+    pub fn audio(&mut self, pcm: &[i16]) -> io::Result<()> {
+        if self.rate.is_none() || pcm.is_empty() {
+            return Ok(());
+        }
+        let bytes: Vec<u8> = pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.put(b"01wb", &bytes)?;
+        self.samples += pcm.len() as u64;
+        Ok(())
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Writes the index and the final numbers into the header.
+    pub fn finish(mut self) -> io::Result<()> {
+        let mut idx = Vec::with_capacity(self.index.len() * 16);
+        for (id, offset, size) in &self.index {
+            idx.extend_from_slice(id);
+            u32le(&mut idx, if id == b"00dc" { KEYFRAME } else { 0 });
+            u32le(&mut idx, *offset);
+            u32le(&mut idx, *size);
+        }
+        let mut tail = Vec::new();
+        chunk(&mut tail, b"idx1", &idx);
+        self.out.write_all(&tail)?;
+        let head_len = self.header(0).len() as u64;
+        let riff = head_len - 8 + self.movi + tail.len() as u64;
+        let head = self.header(riff as u32);
+        self.out.seek(SeekFrom::Start(0))?;
+        self.out.write_all(&head)?;
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn le(b: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]])
+    }
+
+    #[test]
+    fn riff_sizes_and_index_add_up() {
+        let path = std::env::temp_dir().join(format!("fv-avi-{}.avi", std::process::id()));
+        let mut a = AviWriter::create(&path, 64, 48, 10, Some(24_000)).unwrap();
+        a.frame(&[0xFF, 0xD8, 1, 2, 3]).unwrap(); // odd length gets a pad byte
+        a.audio(&[1, -1, 2400]).unwrap();
+        a.frame(&[0xFF, 0xD8, 4, 5]).unwrap();
+        assert_eq!(a.frames(), 2);
+        a.finish().unwrap();
+
+        let b = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(&b[..4], b"RIFF");
+        assert_eq!(&b[8..12], b"AVI ");
+        assert_eq!(le(&b, 4) as usize, b.len() - 8);
+        // avih: total frames, streams, width, height
+        let avih = b.windows(4).position(|w| w == b"avih").unwrap() + 8;
+        assert_eq!(le(&b, avih + 16), 2);
+        assert_eq!(le(&b, avih + 24), 2);
+        assert_eq!((le(&b, avih + 32), le(&b, avih + 36)), (64, 48));
+        // the index points at the chunks, counted from the "movi" id
+        let movi = b.windows(4).position(|w| w == b"movi").unwrap();
+        let idx = b.windows(4).rposition(|w| w == b"idx1").unwrap();
+        assert_eq!(le(&b, idx + 4), 3 * 16);
+        for e in 0..3 {
+            let at = idx + 8 + e * 16;
+            let off = movi + le(&b, at + 8) as usize;
+            assert_eq!(&b[off..off + 4], &b[at..at + 4]);
+            assert_eq!(le(&b, off + 4), le(&b, at + 12));
+        }
+        // movi list size covers exactly the chunks
+        assert_eq!(le(&b, movi - 4) as usize, idx - movi);
+    }
+}
@@ -0,0 +1,229 @@
+//! Text chat inside a session, for when talking is not an option.
+//!
+//! Either side sends `Chat` with its own line counter and clock; the other
+//! side shows the line and answers `ChatAck`, which marks it "delivered" on
+//! the sender's screen. Both sides keep the transcript in the audit log.
+//!
+//! The viewer shows the chat as a side panel of the session window; the
+//! host gets a small window that stays on top of whatever is being worked
+//! on, plus a tray balloon when a line comes in.
+
+use std::sync::Arc;
+
+use crate::proto::{Msg, MAX_CHAT};
+
+/// A long session does not need to keep every line on screen; the audit
+/// log has them all.
+const MAX_LINES: usize = 500;
+
+/// Sends into a running session; how the host's chat window reaches it.
+pub type Sender = Arc<dyn Fn(Msg) + Send + Sync>;
+
+/// True for every message of the chat channel.
+pub fn is_chat_msg(m: &Msg) -> bool {
+    matches!(m, Msg::Chat { .. } | Msg::ChatAck { .. })
+}
+
+pub struct Line {
+    /// Typed here (otherwise it came from the other side).
+    pub mine: bool,
+    pub id: u32,
+    /// Sender's clock, unix ms.
+    pub ts: u64,
+    pub text: String,
+    /// Own lines: the other side confirmed it.
+    pub delivered: bool,
+}
+
+/// The chat of one session, either end.
+#[derive(Default)]
+pub struct Chat {
+    pub lines: Vec<Line>,
+    /// What is being typed.
+    pub draft: String,
+    /// Lines from the other side nobody looked at yet.
+    pub unread: u32,
+    /// Who is at the other end, for the audit log.
+    pub peer: String,
+    next_id: u32,
+}
+
+impl Chat {
+    /// A fresh chat for a new session with `peer`.
+    pub fn start(&mut self, peer: &str) {
+        *self = Chat {
+            peer: peer.to_string(),
+            ..Default::default()
+        };
+    }
+
+    /// Sends the draft. `None` if there is nothing to send.
+    pub fn say(&mut self) -> Option<Msg> {
+        let mut text = std::mem::take(&mut self.draft).trim().to_string();
+        if text.is_empty() {
+            return None;
+        }
+        if text.len() > MAX_CHAT {
+            let mut end = MAX_CHAT;
+            while !text.is_char_boundary(end) {
+                end -= 1;
+            }
+            text.truncate(end);
+        }
+        self.next_id += 1;
+        let (id, ts) = (self.next_id, unix_ms());
+        crate::audit::log(&self.peer, &format!("Chat gesendet: {}", text));
+        self.push(Line {
+            mine: true,
+            id,
+            ts,
+            text: text.clone(),
+            delivered: false,
+        });
+        Some(Msg::Chat { id, ts, text })
+    }
+
+    /// Takes a chat message of the other side. Returns the receipt to send.
+    pub fn on_msg(&mut self, m: Msg) -> Option<Msg> {
+        match m {
+            Msg::Chat { id, ts, text } => {
+                crate::audit::log(&self.peer, &format!("Chat erhalten: {}", text));
+                self.push(Line {
+                    mine: false,
+                    id,
+                    ts,
+                    text,
+                    delivered: true,
+                });
+                self.unread += 1;
+                Some(Msg::ChatAck { id })
+            }
+            Msg::ChatAck { id } => {
+                if let Some(l) = self.lines.iter_mut().rev().find(|l| l.mine && l.id == id) {
+                    l.delivered = true;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// The text of the newest line from the other side.
+    pub fn last_in(&self) -> Option<&str> {
+        self.lines.iter().rev().find(|l| !l.mine).map(|l| l.text.as_str())
+    }
+
+    fn push(&mut self, l: Line) {
+        if self.lines.len() >= MAX_LINES {
+            self.lines.remove(0);
+        }
+        self.lines.push(l);
+    }
+}
+
+fn unix_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// "14:03" (UTC, like the audit log) for a line's clock.
+pub fn clock(ts_ms: u64) -> String {
+    let s = ts_ms / 1000 % 86400;
+    format!("{:02}:{:02}", s / 3600, s % 3600 / 60)
+}
+
+/// The transcript and the line to type into. `other` names the other
+/// side. Returns what goes to it.
+pub fn widget(ui: &mut egui::Ui, chat: &mut Chat, other: &str) -> Option<Msg> {
+    use crate::i18n::t;
+    chat.unread = 0;
+    let mut out = None;
+
+    // the input line sits at the bottom, the transcript takes the rest
+    egui::TopBottomPanel::bottom(ui.id().with("fv_chat_input"))
+        .frame(egui::Frame::NONE.inner_margin(egui::Margin::symmetric(0, 6)))
+        .show_inside(ui, |ui| {
+            ui.horizontal(|ui| {
+                let send = ui.button(t("chat.send"));
+                let r = ui.add(
+                    egui::TextEdit::singleline(&mut chat.draft)
+                        .hint_text(t("chat.hint"))
+                        .desired_width(ui.available_width()),
+                );
+                let enter = r.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if send.clicked() || enter {
+                    out = chat.say();
+                    r.request_focus();
+                }
+            });
+        });
+
+    egui::ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+            if chat.lines.is_empty() {
+                ui.label(egui::RichText::new(t("chat.empty")).weak());
+            }
+            for l in &chat.lines {
+                ui.horizontal(|ui| {
+                    let who = if l.mine { t("chat.me") } else { other };
+                    ui.label(egui::RichText::new(who).strong());
+                    ui.label(egui::RichText::new(clock(l.ts)).weak().size(11.0));
+                    if l.mine {
+                        let state = if l.delivered {
+                            t("chat.delivered")
+                        } else {
+                            t("chat.sent")
+                        };
+                        ui.label(egui::RichText::new(state).weak().size(11.0));
+                    }
+                });
+                ui.add(egui::Label::new(&l.text).wrap());
+                ui.add_space(4.0);
+            }
+        });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_are_acknowledged_and_counted() {
+        let (mut a, mut b) = (Chat::default(), Chat::default());
+        a.draft = "  Hallo  ".to_string();
+        let m = a.say().unwrap();
+        assert!(a.draft.is_empty());
+        assert!(!a.lines[0].delivered);
+        let ack = b.on_msg(m).unwrap();
+        assert_eq!(b.unread, 1);
+        assert_eq!(b.last_in(), Some("Hallo"));
+        assert!(a.on_msg(ack).is_none());
+        assert!(a.lines[0].delivered);
+        // nothing typed, nothing sent
+        a.draft = " ".to_string();
+        assert!(a.say().is_none());
+    }
+
+    #[test]
+    fn long_lines_are_cut_on_a_char_boundary() {
+        let mut c = Chat {
+            draft: "ä".repeat(MAX_CHAT),
+            ..Default::default()
+        };
+        let Some(Msg::Chat { text, .. }) = c.say() else {
+            panic!("no line");
+        };
+        assert!(text.len() <= MAX_CHAT);
+        assert_eq!(text.chars().count(), MAX_CHAT / 2);
+    }
+
+    #[test]
+    fn clock_shows_hours_and_minutes() {
+        assert_eq!(clock(1_792_300_000_000), "05:06");
+    }
+}
@@ -1,67 +1,850 @@
-//! Clipboard synchronisation (text) for both sides of a session.
+//! Clipboard synchronisation for both sides of a session: text, HTML, RTF
+//! and images (as PNG).
 //!
-//! Both ends poll their own clipboard. When it changed, the new text is sent
-//! to the peer, which writes it into its own clipboard. `last` remembers the
-//! text we saw or wrote ourselves, so the two machines cannot ping-pong the
-//! same string forever.
+//! Both ends poll their own clipboard. When it changed, the content is sent
+//! to the peer, which writes it into its own clipboard. Plain text goes as
+//! `Msg::Clipboard` like it always did; anything richer additionally as a
+//! `Msg::ClipOffer` listing every format with its size. Small formats ride
+//! along, big ones (a screenshot) stay with the sender until the receiver
+//! asks for them with `ClipFetch` - and it only asks for the newest offer
+//! once it stopped changing, so copying five screenshots in a row moves one.
+//!
+//! Copied files and folders are only announced (`ClipFiles`, names and
+//! sizes). They move when the other side pastes: it sends `ClipPull`, the
+//! owner answers each transfer with `ClipPulling` right before handing the
+//! path to the regular `xfer` engine, so the receiver knows which incoming
+//! transfers are the paste and where they go. On the host a Ctrl+V of the
+//! viewer is held back until the files are in a staging folder and in the
+//! host clipboard, then replayed, so Explorer or Nautilus pastes real files.
+//! The viewer's files only go when the viewer really pressed Ctrl+V a
+//! moment before (`guard_pulls`); a host cannot help itself to them.
+//!
+//! `last` is a fingerprint of what we saw or wrote ourselves - read back
+//! after writing, because the system may hand the content back slightly
+//! different - so the two machines cannot ping-pong the same thing forever.
+//!
+//! Reading a big picture out of the clipboard is not free, so `poll` looks
+//! first: on Windows at the clipboard's sequence number, elsewhere at the
+//! small formats, with the picture read only every few rounds.
 
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
+use crate::proto::{
+    clip_limit, ClipItem, FsEntry, Msg, CLIP_HTML, CLIP_INLINE, CLIP_PNG, CLIP_RTF, CLIP_TEXT,
+    KEY_CTRL, KEY_META, MAX_CLIP_FILES,
+};
+use crate::xfer::{List, Xfer};
+
+/// The receiver waits this long for a calmer clipboard before it fetches.
+const SETTLE: Duration = Duration::from_millis(300);
+/// A paste whose transfers were not even announced by then is given up.
+const PULL_TIMEOUT: Duration = Duration::from_secs(10);
+/// A pull of our files is answered this long after the paste key went out.
+const PULL_GRANT: Duration = Duration::from_secs(5);
+/// The local clipboard is looked at this often.
+const POLL_EVERY: Duration = Duration::from_millis(600);
+/// Without a change counter the whole clipboard is read only every this
+/// many polls, unless the small formats changed.
+const FULL_EVERY: u32 = 5;
+
+/// True for every message `Clip::on_msg` understands.
+pub fn is_clip_msg(m: &Msg) -> bool {
+    matches!(
+        m,
+        Msg::Clipboard { .. }
+            | Msg::ClipOffer { .. }
+            | Msg::ClipFetch { .. }
+            | Msg::ClipData { .. }
+            | Msg::ClipFiles { .. }
+            | Msg::ClipPull { .. }
+            | Msg::ClipPulling { .. }
+    )
+}
+
+/// What the local clipboard holds right now.
+#[derive(Default)]
+struct Content {
+    text: String,
+    html: String,
+    rtf: Vec<u8>,
+    /// RGBA pixels, encoded to PNG only when they are sent
+    image: Option<(usize, usize, Vec<u8>)>,
+    files: Vec<PathBuf>,
+}
+
+impl Content {
+    fn fingerprint(&self) -> u64 {
+        let mut h = DefaultHasher::new();
+        self.text.hash(&mut h);
+        self.html.hash(&mut h);
+        self.rtf.hash(&mut h);
+        self.image.hash(&mut h);
+        self.files.hash(&mut h);
+        h.finish()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty()
+            && self.html.is_empty()
+            && self.rtf.is_empty()
+            && self.image.is_none()
+            && self.files.is_empty()
+    }
+}
+
+/// An offer of the peer whose big formats are still on their way.
+struct Pending {
+    seq: u32,
+    items: Vec<ClipItem>,
+    since: Instant,
+    asked: bool,
+}
+
+/// A paste of the peer's files that is under way.
+struct Paste {
+    seq: u32,
+    dir: PathBuf,
+    /// the transfers the peer announced with `ClipPulling`
+    ids: Vec<u32>,
+    want: usize,
+    started: Instant,
+}
+
 pub struct Clip {
     cb: Option<arboard::Clipboard>,
-    last: String,
+    last: u64,
+    /// the text of `last`, so the same text from the peer is not rewritten
+    last_text: String,
+    /// the system's change counter when we last read or wrote
+    seen: Option<u32>,
+    /// without a change counter: fingerprint of the small formats
+    quick: u64,
+    polls: u32,
     next_poll: Instant,
+    /// our newest offer, for the peer's `ClipFetch`
+    seq: u32,
+    held: Vec<ClipItem>,
+    held_files: Vec<PathBuf>,
+    pending: Option<Pending>,
+    /// the files the peer copied last, with the seq of its offer
+    remote: Option<(u32, Vec<FsEntry>)>,
+    paste: Option<Paste>,
+    /// pulls of our files need a paste key of our own first
+    guarded: bool,
+    /// when we sent the last paste key
+    granted: Option<Instant>,
 }
 
 impl Clip {
     pub fn new() -> Self {
         Self {
             cb: arboard::Clipboard::new().ok(),
-            last: String::new(),
+            last: 0,
+            last_text: String::new(),
+            seen: None,
+            quick: 0,
+            polls: 0,
             next_poll: Instant::now(),
+            seq: 0,
+            held: Vec::new(),
+            held_files: Vec::new(),
+            pending: None,
+            remote: None,
+            paste: None,
+            guarded: false,
+            granted: None,
+        }
+    }
+
+    /// Answers the peer's pulls only right after `allow_pull`.
+    pub fn guard_pulls(&mut self) {
+        self.guarded = true;
+    }
+
+    /// We just sent a paste key: the one pull that follows may have our
+    /// files.
+    pub fn allow_pull(&mut self, at: Instant) {
+        self.granted = Some(at);
+    }
+
+    /// Whether a pull of offer `seq` gets our files; uses up the grant.
+    fn may_serve(&mut self, seq: u32) -> bool {
+        if seq != self.seq || self.held_files.is_empty() {
+            return false;
+        }
+        if !self.guarded {
+            return true;
         }
+        self.granted
+            .take()
+            .is_some_and(|at| at.elapsed() < PULL_GRANT)
     }
 
     pub fn available(&self) -> bool {
         self.cb.is_some()
     }
 
-    /// Returns the local clipboard text when it changed since the last call.
-    /// Rate limited internally, so it can be called from a hot loop.
-    pub fn poll(&mut self) -> Option<String> {
+    fn read(&mut self) -> Option<Content> {
+        let cb = self.cb.as_mut()?;
+        // a missing format is not an error worth logging
+        Some(Content {
+            text: cb.get_text().unwrap_or_default(),
+            html: cb.get().html().unwrap_or_default(),
+            rtf: rtf::read().unwrap_or_default(),
+            image: cb
+                .get_image()
+                .ok()
+                .map(|i| (i.width, i.height, i.bytes.into_owned())),
+            files: cb.get().file_list().unwrap_or_default(),
+        })
+    }
+
+    /// Fingerprint of the formats that are cheap to read.
+    fn glance(&mut self) -> u64 {
+        let Some(cb) = self.cb.as_mut() else {
+            return 0;
+        };
+        let mut h = DefaultHasher::new();
+        cb.get_text().unwrap_or_default().hash(&mut h);
+        cb.get().html().unwrap_or_default().hash(&mut h);
+        cb.get().file_list().unwrap_or_default().hash(&mut h);
+        h.finish()
+    }
+
+    /// True when the clipboard may have changed since we last looked.
+    fn maybe_changed(&mut self) -> bool {
+        self.polls = self.polls.wrapping_add(1);
+        if let Some(n) = change_count() {
+            return self.seen.replace(n) != Some(n);
+        }
+        let quick = self.glance();
+        let changed = quick != self.quick;
+        self.quick = quick;
+        changed || self.polls.is_multiple_of(FULL_EVERY)
+    }
+
+    /// Takes what is in the clipboard now (read back after writing) as ours.
+    fn remember(&mut self) {
+        let c = self.read();
+        self.last = c.as_ref().map(Content::fingerprint).unwrap_or(0);
+        self.last_text = c.map(|c| c.text).unwrap_or_default();
+        self.seen = change_count();
+        if self.seen.is_none() {
+            self.quick = self.glance();
+        }
+    }
+
+    /// Messages for the peer when the local clipboard changed since the
+    /// last call, plus fetches for an offer that has settled. Rate limited
+    /// internally, so it can be called from a hot loop.
+    pub fn poll(&mut self) -> Vec<Msg> {
+        let mut out = Vec::new();
+        if let Some(p) = self.pending.as_mut() {
+            if !p.asked && p.since.elapsed() >= SETTLE {
+                p.asked = true;
+                for i in p.items.iter().filter(|i| i.data.is_empty()) {
+                    out.push(Msg::ClipFetch {
+                        seq: p.seq,
+                        kind: i.kind,
+                    });
+                }
+            }
+        }
         if Instant::now() < self.next_poll {
-            return None;
+            return out;
         }
-        self.next_poll = Instant::now() + Duration::from_millis(600);
-        let cb = self.cb.as_mut()?;
-        // an empty or non-text clipboard is not an error worth logging
-        let text = cb.get_text().ok()?;
-        if text.is_empty() || text == self.last {
-            return None;
+        self.next_poll = Instant::now() + POLL_EVERY;
+        if !self.maybe_changed() {
+            return out;
         }
-        if text.len() > crate::proto::MAX_CLIP {
+        let Some(c) = self.read() else {
+            return out;
+        };
+        let fp = c.fingerprint();
+        if c.is_empty() || fp == self.last {
+            return out;
+        }
+        self.last = fp;
+        self.last_text = c.text.clone();
+        // whatever the peer copied before is no longer what a paste means
+        self.remote = None;
+        if !c.files.is_empty() {
+            // the names only; on Linux the text is the same list as paths
+            self.seq = self.seq.wrapping_add(1);
+            self.held.clear();
+            self.held_files.clear();
+            let mut files = Vec::new();
+            for p in c.files.into_iter().take(MAX_CLIP_FILES) {
+                // a pull sends exactly what was announced
+                if let Some(e) = entry_of(&p) {
+                    files.push(e);
+                    self.held_files.push(p);
+                }
+            }
+            out.push(Msg::ClipFiles {
+                seq: self.seq,
+                files,
+            });
+            return out;
+        }
+        let items = items_of(c);
+        if items.is_empty() {
+            return out;
+        }
+        self.seq = self.seq.wrapping_add(1);
+        out.extend(offer(self.seq, &items));
+        self.held = items;
+        self.held_files.clear();
+        out
+    }
+
+    /// Handles a clipboard message of the peer; answers go out through
+    /// `send`. File transfers of a pull start on the session's `xfer`.
+    pub fn on_msg(&mut self, m: Msg, xfer: &Mutex<Option<Xfer>>, send: &dyn Fn(Msg)) {
+        match m {
+            Msg::Clipboard { text } => {
+                self.remote = None;
+                self.set(&text);
+            }
+            Msg::ClipOffer { seq, items } => {
+                self.remote = None;
+                let complete = items.iter().all(|i| i.data.len() == i.size as usize);
+                // what is already here goes in right away, a quick paste
+                // gets at least the text
+                self.write(&items);
+                self.pending = (!complete).then(|| Pending {
+                    seq,
+                    items,
+                    since: Instant::now(),
+                    asked: false,
+                });
+            }
+            // an older offer is gone, only the newest can be fetched
+            Msg::ClipFetch { seq, kind } if seq == self.seq => {
+                if let Some(i) = self.held.iter().find(|i| i.kind == kind) {
+                    send(Msg::ClipData {
+                        seq,
+                        kind,
+                        data: i.data.clone(),
+                    });
+                }
+            }
+            Msg::ClipData { seq, kind, data } => {
+                let Some(p) = self.pending.as_mut().filter(|p| p.seq == seq) else {
+                    return;
+                };
+                if let Some(i) = p.items.iter_mut().find(|i| i.kind == kind) {
+                    if data.len() == i.size as usize {
+                        i.data = data;
+                    } else {
+                        // broken: leave the format out rather than guess
+                        i.size = 0;
+                    }
+                }
+                if p.items.iter().all(|i| i.data.len() == i.size as usize) {
+                    if let Some(p) = self.pending.take() {
+                        self.write(&p.items);
+                    }
+                }
+            }
+            Msg::ClipFiles { seq, files } => {
+                self.remote = (!files.is_empty()).then_some((seq, files));
+            }
+            Msg::ClipPull { seq } if self.may_serve(seq) => {
+                let mut xfer = xfer.lock().unwrap();
+                let Some(x) = xfer.as_mut() else {
+                    return;
+                };
+                for path in &self.held_files {
+                    let id = x.reserve_id();
+                    // before the offer, so the peer knows where it goes
+                    send(Msg::ClipPulling { seq, id });
+                    x.send_path_as(id, path.clone());
+                }
+            }
+            Msg::ClipPulling { seq, id } => {
+                let Some(p) = self.paste.as_mut().filter(|p| p.seq == seq) else {
+                    return;
+                };
+                p.ids.push(id);
+                if let Some(x) = xfer.lock().unwrap().as_mut() {
+                    x.expect(id, p.dir.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The files the peer has in its clipboard, if any.
+    pub fn remote_files(&self) -> &[FsEntry] {
+        self.remote.as_ref().map(|r| r.1.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn has_files(&self) -> bool {
+        !self.remote_files().is_empty()
+    }
+
+    pub fn pasting(&self) -> bool {
+        self.paste.is_some()
+    }
+
+    /// Starts pulling the peer's files into `dir`, or into a fresh staging
+    /// folder when there is none. Returns the message for the peer.
+    pub fn start_paste(&mut self, dir: Option<PathBuf>) -> Option<Msg> {
+        let (seq, files) = self.remote.as_ref()?;
+        let dir = match dir {
+            Some(d) => d,
+            None => staging_dir(*seq),
+        };
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            crate::dbg_line(&format!("Einfuegeordner {}: {}", dir.display(), e));
             return None;
         }
-        self.last = text.clone();
-        Some(text)
+        self.paste = Some(Paste {
+            seq: *seq,
+            dir,
+            ids: Vec::new(),
+            want: files.len(),
+            started: Instant::now(),
+        });
+        Some(Msg::ClipPull { seq: *seq })
+    }
+
+    /// Where the running paste stands: `None` while files are still coming,
+    /// else the paths of everything that arrived or why it failed.
+    pub fn paste_ready(&mut self, xfers: &List) -> Option<Result<Vec<PathBuf>, String>> {
+        let p = self.paste.as_ref()?;
+        if p.ids.len() < p.want {
+            if p.started.elapsed() < PULL_TIMEOUT {
+                return None;
+            }
+            self.paste = None;
+            return Some(Err("Dateien aus der Zwischenablage kamen nicht an".to_string()));
+        }
+        let mut paths = Vec::new();
+        {
+            let list = xfers.lock().unwrap();
+            for id in &p.ids {
+                let x = list.iter().find(|x| x.id == *id && x.incoming)?;
+                if !x.finished {
+                    return None;
+                }
+                if !x.error.is_empty() {
+                    let err = format!("{}: {}", x.name, x.error);
+                    drop(list);
+                    self.paste = None;
+                    return Some(Err(err));
+                }
+                paths.push(p.dir.join(&x.name));
+            }
+        }
+        self.paste = None;
+        Some(Ok(paths))
+    }
+
+    /// Puts files into the local clipboard, as if they were copied here.
+    pub fn put_files(&mut self, paths: &[PathBuf]) -> bool {
+        let Some(cb) = self.cb.as_mut() else {
+            return false;
+        };
+        if let Err(e) = cb.set().file_list(paths) {
+            crate::dbg_line(&format!("Dateien in die Zwischenablage: {:?}", e));
+            return false;
+        }
+        // our own paste, not something to offer back
+        self.remember();
+        true
     }
 
     /// Writes text coming from the peer into the local clipboard. Returns
     /// false when nothing had to be done (same text) or when it failed.
     pub fn set(&mut self, text: &str) -> bool {
-        if text == self.last {
+        if text == self.last_text {
             return false;
         }
-        self.last = text.to_string();
-        match self.cb.as_mut() {
-            Some(cb) => match cb.set_text(text.to_string()) {
-                Ok(()) => true,
-                Err(e) => {
-                    crate::dbg_line(&format!("Zwischenablage schreiben fehlgeschlagen: {:?}", e));
-                    false
+        self.write(&[ClipItem {
+            kind: CLIP_TEXT,
+            size: text.len() as u32,
+            data: text.as_bytes().to_vec(),
+        }])
+    }
+
+    /// Puts the formats that are complete into the local clipboard - the
+    /// picture, else HTML with the text as alternate, else the text; RTF is
+    /// added where the system knows it, or goes in alone.
+    fn write(&mut self, items: &[ClipItem]) -> bool {
+        let get = |k: u8| {
+            items
+                .iter()
+                .find(|i| i.kind == k && !i.data.is_empty() && i.data.len() == i.size as usize)
+                .map(|i| i.data.as_slice())
+        };
+        let text = get(CLIP_TEXT).map(|t| String::from_utf8_lossy(t).into_owned());
+        let html = get(CLIP_HTML).map(|t| String::from_utf8_lossy(t).into_owned());
+        let png = get(CLIP_PNG).and_then(|b| {
+            image::load_from_memory_with_format(b, image::ImageFormat::Png).ok()
+        });
+        let rtf = get(CLIP_RTF);
+        let only_rtf = png.is_none() && html.is_none() && text.is_none();
+        let Some(cb) = self.cb.as_mut() else {
+            return false;
+        };
+        let res = if let Some(img) = png {
+            let img = img.to_rgba8();
+            cb.set_image(arboard::ImageData {
+                width: img.width() as usize,
+                height: img.height() as usize,
+                bytes: img.into_raw().into(),
+            })
+        } else if let Some(h) = html {
+            cb.set_html(h, text)
+        } else if let Some(t) = text {
+            cb.set_text(t)
+        } else if rtf.is_some() {
+            Ok(())
+        } else {
+            return false;
+        };
+        if let Err(e) = res {
+            crate::dbg_line(&format!("Zwischenablage schreiben fehlgeschlagen: {:?}", e));
+            return false;
+        }
+        if let Some(r) = rtf {
+            if !rtf::add(r, only_rtf) && only_rtf {
+                return false;
+            }
+        }
+        self.remember();
+        true
+    }
+}
+
+/// Name, kind, size and time of a copied path; gone files are left out.
+fn entry_of(p: &Path) -> Option<FsEntry> {
+    let meta = std::fs::metadata(p).ok()?;
+    Some(FsEntry {
+        name: p.file_name()?.to_string_lossy().into_owned(),
+        dir: meta.is_dir(),
+        size: if meta.is_dir() { 0 } else { meta.len() },
+        mtime: crate::xfer::mtime_of(&meta),
+    })
+}
+
+/// A new folder per paste under the temp dir; older ones are removed, the
+/// system's clipboard only ever points at the newest.
+fn staging_dir(seq: u32) -> PathBuf {
+    let base = std::env::temp_dir().join(crate::brand::DIR).join("clip");
+    if let Ok(rd) = std::fs::read_dir(&base) {
+        for e in rd.flatten() {
+            let _ = std::fs::remove_dir_all(e.path());
+        }
+    }
+    base.join(seq.to_string())
+}
+
+/// Watches the viewer's keys on the host and holds back the Ctrl+V (Cmd+V)
+/// that should paste the viewer's files: they have to be fetched first.
+#[derive(Default)]
+pub struct PasteKeys {
+    ctrl: bool,
+    /// a swallowed V is still down, its release goes too
+    swallowed: bool,
+}
+
+impl PasteKeys {
+    /// True when `m` must not reach the system.
+    pub fn feed(&mut self, m: &Msg, files_waiting: bool) -> bool {
+        let (is_ctrl, is_v, down) = match *m {
+            Msg::KeyVk { vk, down, .. } => (matches!(vk, 0x11 | 0xA2 | 0xA3), vk == 0x56, down),
+            Msg::Key { code, named, down } => (
+                named && (code == KEY_CTRL || code == KEY_META),
+                !named && (code == 'v' as u32 || code == 'V' as u32),
+                down,
+            ),
+            _ => return false,
+        };
+        if is_ctrl {
+            self.ctrl = down;
+            return false;
+        }
+        if !is_v {
+            return false;
+        }
+        if down && self.ctrl && files_waiting {
+            self.swallowed = true;
+            return true;
+        }
+        if !down && self.swallowed {
+            self.swallowed = false;
+            return true;
+        }
+        false
+    }
+
+    /// For keys going out: true for the press of Ctrl+V (Cmd+V) itself.
+    pub fn pasted(&mut self, m: &Msg) -> bool {
+        let held = self.swallowed;
+        self.feed(m, true) && !held
+    }
+
+    /// Whether the viewer still holds Ctrl (the replay then only adds V).
+    pub fn ctrl_held(&self) -> bool {
+        self.ctrl
+    }
+}
+
+/// The formats of `c` that fit their limits; the picture becomes PNG.
+fn items_of(c: Content) -> Vec<ClipItem> {
+    let mut items = Vec::new();
+    let mut add = |kind: u8, data: Vec<u8>| {
+        if !data.is_empty() && data.len() <= clip_limit(kind) {
+            items.push(ClipItem {
+                kind,
+                size: data.len() as u32,
+                data,
+            });
+        }
+    };
+    add(CLIP_TEXT, c.text.into_bytes());
+    add(CLIP_HTML, c.html.into_bytes());
+    add(CLIP_RTF, c.rtf);
+    if let Some((w, h, rgba)) = c.image {
+        if let Some(img) = image::RgbaImage::from_raw(w as u32, h as u32, rgba) {
+            let mut png = Vec::new();
+            let enc = image::codecs::png::PngEncoder::new(&mut png);
+            if img.write_with_encoder(enc).is_ok() {
+                add(CLIP_PNG, png);
+            }
+        }
+    }
+    items
+}
+
+/// What goes to the peer for `items`: plain text alone stays the old
+/// `Clipboard` message, so older builds keep working; richer content adds
+/// an offer (older builds just get the text out of it).
+fn offer(seq: u32, items: &[ClipItem]) -> Vec<Msg> {
+    let mut out = Vec::new();
+    if let Some(t) = items.iter().find(|i| i.kind == CLIP_TEXT) {
+        out.push(Msg::Clipboard {
+            text: String::from_utf8_lossy(&t.data).into_owned(),
+        });
+    }
+    if items.iter().any(|i| i.kind != CLIP_TEXT) {
+        out.push(Msg::ClipOffer {
+            seq,
+            items: items
+                .iter()
+                .map(|i| ClipItem {
+                    kind: i.kind,
+                    size: i.size,
+                    data: if i.data.len() <= CLIP_INLINE {
+                        i.data.clone()
+                    } else {
+                        Vec::new()
+                    },
+                })
+                .collect(),
+        });
+    }
+    out
+}
+
+/// The clipboard's change counter, where the system keeps one.
+#[cfg(windows)]
+fn change_count() -> Option<u32> {
+    Some(unsafe { windows::Win32::System::DataExchange::GetClipboardSequenceNumber() })
+}
+
+#[cfg(not(windows))]
+fn change_count() -> Option<u32> {
+    None
+}
+
+/// Rich Text Format, which arboard does not know. Only Windows gets it;
+/// elsewhere an RTF offer simply falls back to its HTML or text.
+#[cfg(windows)]
+mod rtf {
+    use windows::core::w;
+    use windows::Win32::Foundation::{HANDLE, HGLOBAL, HWND};
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard,
+        RegisterClipboardFormatW, SetClipboardData,
+    };
+    use windows::Win32::System::Memory::{
+        GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+    };
+
+    fn format() -> u32 {
+        unsafe { RegisterClipboardFormatW(w!("Rich Text Format")) }
+    }
+
+    /// Someone else may hold the clipboard for a moment.
+    fn open() -> bool {
+        for _ in 0..5 {
+            if unsafe { OpenClipboard(HWND::default()) }.is_ok() {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        false
+    }
+
+    pub fn read() -> Option<Vec<u8>> {
+        if !open() {
+            return None;
+        }
+        let out = unsafe {
+            match GetClipboardData(format()) {
+                Ok(h) if !h.is_invalid() => {
+                    let g = HGLOBAL(h.0);
+                    let p = GlobalLock(g) as *const u8;
+                    if p.is_null() {
+                        None
+                    } else {
+                        let n = GlobalSize(g);
+                        let mut v = std::slice::from_raw_parts(p, n).to_vec();
+                        let _ = GlobalUnlock(g);
+                        // the block may be longer than the text in it
+                        if let Some(end) = v.iter().position(|&b| b == 0) {
+                            v.truncate(end);
+                        }
+                        Some(v)
+                    }
                 }
-            },
-            None => false,
+                _ => None,
+            }
+        };
+        let _ = unsafe { CloseClipboard() };
+        out
+    }
+
+    /// Adds RTF to what is in the clipboard already (written just before),
+    /// or replaces everything with it when it is `alone`.
+    pub fn add(data: &[u8], alone: bool) -> bool {
+        if !open() {
+            return false;
         }
+        let mut ok = false;
+        unsafe {
+            if alone && EmptyClipboard().is_err() {
+                let _ = CloseClipboard();
+                return false;
+            }
+            if let Ok(g) = GlobalAlloc(GMEM_MOVEABLE, data.len() + 1) {
+                let p = GlobalLock(g) as *mut u8;
+                if p.is_null() {
+                    let _ = GlobalFree(g);
+                } else {
+                    std::ptr::copy_nonoverlapping(data.as_ptr(), p, data.len());
+                    *p.add(data.len()) = 0;
+                    let _ = GlobalUnlock(g);
+                    // on success the system owns the block
+                    ok = SetClipboardData(format(), HANDLE(g.0)).is_ok();
+                    if !ok {
+                        let _ = GlobalFree(g);
+                    }
+                }
+            }
+            let _ = CloseClipboard();
+        }
+        ok
+    }
+}
+
+#[cfg(not(windows))]
+mod rtf {
+    pub fn read() -> Option<Vec<u8>> {
+        None
+    }
+
+    pub fn add(_data: &[u8], _alone: bool) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(kind: u8, len: usize) -> ClipItem {
+        ClipItem {
+            kind,
+            size: len as u32,
+            data: vec![b'x'; len],
+        }
+    }
+
+    #[test]
+    fn plain_text_stays_the_old_message() {
+        let out = offer(1, &[item(CLIP_TEXT, 5)]);
+        assert!(matches!(out.as_slice(), [Msg::Clipboard { .. }]));
+    }
+
+    #[test]
+    fn big_formats_are_only_announced() {
+        let out = offer(
+            7,
+            &[
+                item(CLIP_TEXT, 10),
+                item(CLIP_HTML, 100),
+                item(CLIP_PNG, CLIP_INLINE + 1),
+            ],
+        );
+        match out.as_slice() {
+            [Msg::Clipboard { text }, Msg::ClipOffer { seq: 7, items }] => {
+                assert_eq!(text.len(), 10);
+                assert_eq!(items[1].data.len(), 100);
+                assert_eq!(items[2].size as usize, CLIP_INLINE + 1);
+                assert!(items[2].data.is_empty());
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn ctrl_v_is_held_back_only_with_files_waiting() {
+        let vk = |vk: u16, down: bool| Msg::KeyVk {
+            vk,
+            ext: false,
+            down,
+        };
+        let mut k = PasteKeys::default();
+        assert!(!k.feed(&vk(0xA2, true), true));
+        assert!(k.feed(&vk(0x56, true), true));
+        assert!(k.feed(&vk(0x56, false), true));
+        assert!(!k.feed(&vk(0x56, true), false));
+        assert!(!k.feed(&vk(0x56, false), false));
+        assert!(!k.feed(&vk(0xA2, false), true));
+        assert!(!k.feed(&vk(0x56, true), true));
+
+        let key = |code: u32, named: bool, down: bool| Msg::Key { code, named, down };
+        let mut k = PasteKeys::default();
+        assert!(!k.feed(&key(KEY_META, true, true), true));
+        assert!(k.feed(&key('v' as u32, false, true), true));
+        assert!(k.ctrl_held());
+        assert!(k.feed(&key('v' as u32, false, false), true));
+        assert!(k.pasted(&key('v' as u32, false, true)));
+        assert!(!k.pasted(&key('v' as u32, false, false)));
+    }
+
+    #[test]
+    fn files_are_only_pulled_right_after_our_paste_key() {
+        let mut c = Clip::new();
+        c.seq = 3;
+        c.held_files = vec![PathBuf::from("a.txt")];
+        assert!(c.may_serve(3), "the host serves the viewer's pulls");
+        c.guard_pulls();
+        assert!(!c.may_serve(3), "no paste key, no files");
+        c.allow_pull(Instant::now());
+        assert!(!c.may_serve(2), "an old offer gets nothing");
+        assert!(c.may_serve(3));
+        assert!(!c.may_serve(3), "one paste, one pull");
+        c.allow_pull(Instant::now() - PULL_GRANT);
+        assert!(!c.may_serve(3), "too late");
     }
 }
@@ -7,8 +7,36 @@
 //! those. A full frame ("keyframe") is only sent when the session starts, when
 //! the resolution changes or when so much of the screen changed that sending
 //! everything is cheaper than sending many rectangles.
+//!
+//! Scrolling and dragged windows would still dirty most tiles although the
+//! content only moved. Before the tiles are diffed we therefore look for one
+//! shift that explains the change; when there is one, the viewer gets a
+//! `CopyRect` to move that part of its canvas and only what is left over
+//! (usually the newly revealed strip) travels as JPEG tiles.
+//!
+//! Tiles that are still dirty are then looked up in the tile cache (see
+//! `tilecache`): content the viewer has already seen, e.g. the window that
+//! was in front a few seconds ago, is painted from its cache by reference.
+//!
+//! JPEG smears small fonts and coloured code. When the viewer asks for it,
+//! every tile is classified first: few colours become a palette plus PNG
+//! indices, other sharp content lossless PNG, and only photographic content
+//! stays JPEG.
+//!
+//! On a congested link changed tiles go out at a low JPEG quality. Every grid
+//! tile remembers the quality the viewer has of it and since when it has not
+//! changed; `refine` later re-sends tiles that stood still long enough at full
+//! quality, as far as the link has room for it.
+//!
+//! Scaling and the compression of the rectangles run on all cores (see
+//! `pool`); the output does not depend on the number of threads.
 
-use crate::proto::{Msg, Tile};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use crate::pool::Pool;
+use crate::proto::{CacheRef, Msg, Tile, TileFormat};
+use crate::tilecache::{cell_hash, CacheStats, Lru, CELLS};
 
 /// Edge length of one comparison tile in (downscaled) pixels.
 pub const TILE: u32 = 64;
@@ -20,6 +48,29 @@ pub const TILE_QUALITY: u8 = 72;
 pub const KEYFRAME_RATIO: f32 = 0.6;
 /// Safety valve: never send more rectangles than this, fall back to a keyframe.
 pub const MAX_RECTS: usize = 96;
+/// Width of the pixel run we look for in the previous frame when searching
+/// for a shift. Long enough to be distinctive, short enough to fit anywhere.
+const PROBE: u32 = 32;
+/// How many candidate shifts are verified at most.
+const MAX_CANDIDATES: usize = 24;
+/// A move is only used when it saves at least this fraction of the dirty area.
+const MOTION_GAIN: f32 = 0.3;
+/// More colours than a palette tile can hold.
+const PALETTE_MAX: usize = 256;
+/// Tiles where at least this share of pixels repeats its left neighbour are
+/// drawn, not photographed - they stay lossless even with many colours.
+const FLAT_RATIO: f32 = 0.55;
+/// JPEG quality for changes while the link is congested.
+pub const MOTION_QUALITY: u8 = 35;
+/// A tile has to stand still this long before it is refined.
+const REFINE_AFTER: Duration = Duration::from_millis(500);
+/// Refinement tiles per `refine` call, so refining never floods the link.
+const REFINE_CELLS: usize = 24;
+/// Quality mark of a tile the viewer has bit-exact (palette or PNG).
+const EXACT: u8 = u8::MAX;
+
+/// x, y, w, h in (downscaled) pixels - or x0, y0, x1, y1 for a bounding box.
+type Rect = (u32, u32, u32, u32);
 
 /// Box-filter downscale straight from RGBA into RGB.
 #[allow(dead_code)]
@@ -34,21 +85,23 @@ pub fn scale_to_rgb(src: &[u8], sw: u32, sh: u32, dw: u32, dh: u32) -> Vec<u8> {
 /// separate `rgba_to_rgb()`). `bgra = true` additionally swaps red and blue,
 /// which is the pixel order the DXGI duplication API hands us.
 pub fn scale_to_rgb_ex(src: &[u8], sw: u32, sh: u32, dw: u32, dh: u32, bgra: bool) -> Vec<u8> {
+    scale_to_rgb_par(src, sw, sh, dw, dh, bgra, &Pool::new(1))
+}
+
+/// `scale_to_rgb_ex` with the output rows split into bands over the pool.
+pub fn scale_to_rgb_par(
+    src: &[u8],
+    sw: u32,
+    sh: u32,
+    dw: u32,
+    dh: u32,
+    bgra: bool,
+    pool: &Pool,
+) -> Vec<u8> {
     let mut out = vec![0u8; dw as usize * dh as usize * 3];
     if sw == 0 || sh == 0 || dw == 0 || dh == 0 {
         return out;
     }
-    let (ri, bi) = if bgra { (2usize, 0usize) } else { (0usize, 2usize) };
-    if dw == sw && dh == sh {
-        for (o, px) in out.chunks_exact_mut(3).zip(src.chunks_exact(4)) {
-            o[0] = px[ri];
-            o[1] = px[1];
-            o[2] = px[bi];
-        }
-        return out;
-    }
-
-    let sstride = sw as usize * 4;
     let mut cols: Vec<(usize, usize)> = Vec::with_capacity(dw as usize);
     for dx in 0..dw as u64 {
         let x0 = (dx * sw as u64 / dw as u64) as usize;
@@ -61,8 +114,40 @@ pub fn scale_to_rgb_ex(src: &[u8], sw: u32, sh: u32, dw: u32, dh: u32, bgra: boo
         }
         cols.push((x0, x1));
     }
+    // a few bands per thread, so an uneven split does not leave cores idle
+    let rows = dh.div_ceil(pool.threads() as u32 * 3).max(16);
+    let band = rows as usize * dw as usize * 3;
+    pool.bands(&mut out, band, |i, o| {
+        scale_rows(src, (sw, sh), (dw, dh), bgra, &cols, i as u32 * rows, o)
+    });
+    out
+}
 
-    for dy in 0..dh as u64 {
+/// Fills `out` with the scaled rows starting at `dy0`.
+fn scale_rows(
+    src: &[u8],
+    (sw, sh): (u32, u32),
+    (dw, dh): (u32, u32),
+    bgra: bool,
+    cols: &[(usize, usize)],
+    dy0: u32,
+    out: &mut [u8],
+) {
+    let (ri, bi) = if bgra { (2usize, 0usize) } else { (0usize, 2usize) };
+    if dw == sw && dh == sh {
+        let s0 = dy0 as usize * sw as usize * 4;
+        for (o, px) in out.chunks_exact_mut(3).zip(src[s0.min(src.len())..].chunks_exact(4)) {
+            o[0] = px[ri];
+            o[1] = px[1];
+            o[2] = px[bi];
+        }
+        return;
+    }
+
+    let sstride = sw as usize * 4;
+    let band_rows = out.len() / (dw as usize * 3);
+    for row in 0..band_rows {
+        let dy = (dy0 as usize + row) as u64;
         let y0 = (dy * sh as u64 / dh as u64) as usize;
         let mut y1 = ((dy + 1) * sh as u64 / dh as u64) as usize;
         if y1 <= y0 {
@@ -71,7 +156,7 @@ pub fn scale_to_rgb_ex(src: &[u8], sw: u32, sh: u32, dw: u32, dh: u32, bgra: boo
         if y1 > sh as usize {
             y1 = sh as usize;
         }
-        let orow = dy as usize * dw as usize * 3;
+        let orow = row * dw as usize * 3;
         for (dx, &(x0, x1)) in cols.iter().enumerate() {
             let (mut r, mut g, mut b, mut n) = (0u32, 0u32, 0u32, 0u32);
             for y in y0..y1 {
@@ -96,7 +181,6 @@ pub fn scale_to_rgb_ex(src: &[u8], sw: u32, sh: u32, dw: u32, dh: u32, bgra: boo
             out[o + 2] = (b / n) as u8;
         }
     }
-    out
 }
 
 /// Tiles that differ between `cur` and `prev`, merged into pixel rectangles.
@@ -183,6 +267,231 @@ fn merge_grid(grid: &[bool], cols: u32, rows: u32, w: u32, h: u32) -> Vec<(u32,
         .collect()
 }
 
+/// Bounding box (x0, y0, x1, y1) of a set of rectangles.
+fn bounds(rects: &[Rect]) -> Option<Rect> {
+    let mut it = rects.iter();
+    let f = it.next()?;
+    let mut b = (f.0, f.1, f.0 + f.2, f.1 + f.3);
+    for r in it {
+        b.0 = b.0.min(r.0);
+        b.1 = b.1.min(r.1);
+        b.2 = b.2.max(r.0 + r.2);
+        b.3 = b.3.max(r.1 + r.3);
+    }
+    Some(b)
+}
+
+/// Polynomial hash of `PROBE` pixels, rolled one pixel at a time below.
+const HASH_MUL: u64 = 0x100_0000_01b3;
+
+fn px(buf: &[u8], i: usize) -> u64 {
+    (buf[i] as u64) | (buf[i + 1] as u64) << 8 | (buf[i + 2] as u64) << 16
+}
+
+/// Picks a run of `PROBE` pixels inside the dirty area of `cur` that is not
+/// flat (a plain background matches everywhere and says nothing).
+fn pick_probe(cur: &[u8], w: u32, b: Rect) -> Option<(u32, u32)> {
+    if b.2 - b.0 < PROBE {
+        return None;
+    }
+    let stride = w as usize * 3;
+    let (cx, cy) = ((b.0 + b.2) / 2, (b.1 + b.3) / 2);
+    let xs = [
+        cx.saturating_sub(PROBE / 2),
+        b.0 + (b.2 - b.0) / 4,
+        b.0 + (b.2 - b.0) * 3 / 4,
+    ];
+    let span = b.3 - b.1;
+    for step in 0..span.min(256) {
+        // walk outwards from the middle row: middle, +1, -1, +2, ...
+        let off = step.div_ceil(2) as i64 * if step % 2 == 0 { -1 } else { 1 };
+        let y = cy as i64 + off;
+        if y < b.1 as i64 || y >= b.3 as i64 {
+            continue;
+        }
+        for &x in &xs {
+            let x = x.clamp(b.0, b.2 - PROBE);
+            let a = y as usize * stride + x as usize * 3;
+            let run = &cur[a..a + PROBE as usize * 3];
+            let mut colours: Vec<&[u8]> = Vec::with_capacity(4);
+            for p in run.chunks_exact(3) {
+                if !colours.contains(&p) {
+                    colours.push(p);
+                    if colours.len() >= 4 {
+                        return Some((x, y as u32));
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Share of sampled rows of the overlap that are identical after shifting
+/// `prev` by (dx, dy) inside the box `b`.
+fn shift_score(cur: &[u8], prev: &[u8], w: u32, b: Rect, dx: i64, dy: i64) -> f32 {
+    let stride = w as usize * 3;
+    let x0 = (b.0 as i64).max(b.0 as i64 + dx);
+    let x1 = (b.2 as i64).min(b.2 as i64 + dx);
+    let y0 = (b.1 as i64).max(b.1 as i64 + dy);
+    let y1 = (b.3 as i64).min(b.3 as i64 + dy);
+    if x1 - x0 < PROBE as i64 || y1 - y0 < 2 {
+        return 0.0;
+    }
+    let rows = (y1 - y0) as usize;
+    let samples = rows.min(48);
+    let mut hits = 0usize;
+    for i in 0..samples {
+        let y = y0 + (i * rows / samples) as i64;
+        let a = y as usize * stride + x0 as usize * 3;
+        let s = (y - dy) as usize * stride + (x0 - dx) as usize * 3;
+        let n = (x1 - x0) as usize * 3;
+        if cur[a..a + n] == prev[s..s + n] {
+            hits += 1;
+        }
+    }
+    hits as f32 / samples as f32
+}
+
+/// Looks for one shift (dx, dy) that moved content of `prev` inside the
+/// dirty box `b` to where it is in `cur`. Scrolling (one axis) and dragged
+/// windows (both axes) look the same to this search.
+///
+/// A distinctive pixel run of `cur` is searched in `prev` with a rolling
+/// hash; every place it shows up is a candidate shift, and the candidate
+/// whose rows line up best wins.
+pub fn find_motion(cur: &[u8], prev: &[u8], w: u32, h: u32, b: Rect) -> Option<(i32, i32)> {
+    let stride = w as usize * 3;
+    if cur.len() != prev.len() || cur.len() < stride * h as usize {
+        return None;
+    }
+    if b.2 > w || b.3 > h || b.3 - b.1 < 2 {
+        return None;
+    }
+    let (qx, qy) = pick_probe(cur, w, b)?;
+    let qa = qy as usize * stride + qx as usize * 3;
+    let probe = &cur[qa..qa + PROBE as usize * 3];
+    let mut want = 0u64;
+    let mut top = 1u64;
+    for p in probe.chunks_exact(3) {
+        want = want.wrapping_mul(HASH_MUL).wrapping_add(px(p, 0));
+        top = top.wrapping_mul(HASH_MUL);
+    }
+
+    let mut cands: Vec<(i64, i64)> = Vec::new();
+    'rows: for y in b.1..b.3 {
+        let row = y as usize * stride;
+        let mut hsh = 0u64;
+        for x in b.0..b.2 {
+            let add = px(prev, row + x as usize * 3);
+            hsh = hsh.wrapping_mul(HASH_MUL).wrapping_add(add);
+            if x >= b.0 + PROBE {
+                let out = px(prev, row + (x - PROBE) as usize * 3);
+                hsh = hsh.wrapping_sub(out.wrapping_mul(top));
+            }
+            if x + 1 < b.0 + PROBE || hsh != want {
+                continue;
+            }
+            let sx = x + 1 - PROBE;
+            let s = row + sx as usize * 3;
+            if prev[s..s + PROBE as usize * 3] != *probe {
+                continue;
+            }
+            let d = (qx as i64 - sx as i64, qy as i64 - y as i64);
+            if d != (0, 0) {
+                cands.push(d);
+                if cands.len() >= MAX_CANDIDATES {
+                    break 'rows;
+                }
+            }
+        }
+    }
+
+    let mut best: Option<((i64, i64), f32)> = None;
+    for d in cands {
+        let score = shift_score(cur, prev, w, b, d.0, d.1);
+        if best.map(|(_, s)| score > s).unwrap_or(true) {
+            best = Some((d, score));
+        }
+    }
+    match best {
+        Some(((dx, dy), score)) if score >= 0.5 => Some((dx as i32, dy as i32)),
+        _ => None,
+    }
+}
+
+/// Moves the rectangle `src` (x, y, w, h) of a packed `bpp` bytes per pixel
+/// buffer to `dst` (x, y). Overlapping source and destination are fine, which
+/// is the normal case for scrolling. Returns false for rectangles that do not
+/// fit, so a hostile peer cannot make the viewer write out of bounds.
+pub fn copy_rect(
+    buf: &mut [u8],
+    w: u32,
+    h: u32,
+    bpp: usize,
+    src: Rect,
+    dst: (u32, u32),
+) -> bool {
+    let (sx, sy, cw, ch) = src;
+    let (dx, dy) = dst;
+    let fits = |x: u32, y: u32| {
+        x.checked_add(cw).is_some_and(|e| e <= w) && y.checked_add(ch).is_some_and(|e| e <= h)
+    };
+    if cw == 0 || ch == 0 || !fits(sx, sy) || !fits(dx, dy) {
+        return false;
+    }
+    if buf.len() < w as usize * h as usize * bpp {
+        return false;
+    }
+    let stride = w as usize * bpp;
+    let n = cw as usize * bpp;
+    let mut copy_row = |row: u32| {
+        let s = (sy + row) as usize * stride + sx as usize * bpp;
+        let d = (dy + row) as usize * stride + dx as usize * bpp;
+        buf.copy_within(s..s + n, d);
+    };
+    // moving down: start at the bottom so no row is overwritten before it
+    // has been copied
+    if dy > sy {
+        for row in (0..ch).rev() {
+            copy_row(row);
+        }
+    } else {
+        for row in 0..ch {
+            copy_row(row);
+        }
+    }
+    true
+}
+
+/// The part of the dirty box that a shift by (dx, dy) can fill from the
+/// previous frame, as (src, dst) for `copy_rect`.
+fn moved_area(b: Rect, dx: i32, dy: i32) -> Option<(Rect, (u32, u32))> {
+    let x0 = (b.0 as i64).max(b.0 as i64 + dx as i64);
+    let x1 = (b.2 as i64).min(b.2 as i64 + dx as i64);
+    let y0 = (b.1 as i64).max(b.1 as i64 + dy as i64);
+    let y1 = (b.3 as i64).min(b.3 as i64 + dy as i64);
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    let src = (
+        (x0 - dx as i64) as u32,
+        (y0 - dy as i64) as u32,
+        (x1 - x0) as u32,
+        (y1 - y0) as u32,
+    );
+    Some((src, (x0 as u32, y0 as u32)))
+}
+
+/// The grid tiles covered by a tile aligned rectangle, clipped to the frame.
+fn cells(r: Rect, w: u32, h: u32) -> impl Iterator<Item = Rect> {
+    (r.1..r.1 + r.3).step_by(TILE as usize).flat_map(move |y| {
+        (r.0..r.0 + r.2)
+            .step_by(TILE as usize)
+            .map(move |x| (x, y, TILE.min(w - x), TILE.min(h - y)))
+    })
+}
+
 /// Copies a rectangle out of a RGB buffer into its own contiguous buffer.
 pub fn crop_rgb(src: &[u8], w: u32, x: u32, y: u32, cw: u32, ch: u32) -> Vec<u8> {
     let stride = w as usize * 3;
@@ -198,6 +507,126 @@ pub fn crop_rgb(src: &[u8], w: u32, x: u32, y: u32, cw: u32, ch: u32) -> Vec<u8>
     out
 }
 
+/// Decides how a tile is best compressed: palette for a handful of colours
+/// (text, UI), PNG for drawn content with gradients or anti-aliasing, JPEG
+/// for everything that looks like a photo.
+pub fn classify(rgb: &[u8], w: u32, h: u32) -> TileFormat {
+    let n = w as usize * h as usize;
+    if n == 0 || rgb.len() < n * 3 {
+        return TileFormat::Jpeg;
+    }
+    let mut seen: HashSet<u32> = HashSet::with_capacity(64);
+    let mut flat = 0usize;
+    let mut prev = u32::MAX;
+    for (i, p) in rgb[..n * 3].chunks_exact(3).enumerate() {
+        let c = u32::from_le_bytes([p[0], p[1], p[2], 0]);
+        if c == prev && i % w as usize != 0 {
+            flat += 1;
+        }
+        prev = c;
+        if seen.len() <= PALETTE_MAX {
+            seen.insert(c);
+        }
+    }
+    if seen.len() <= PALETTE_MAX {
+        TileFormat::Palette
+    } else if flat as f32 >= n as f32 * FLAT_RATIO {
+        TileFormat::Png
+    } else {
+        TileFormat::Jpeg
+    }
+}
+
+fn png(buf: &[u8], w: u32, h: u32, ct: image::ExtendedColorType) -> Option<Vec<u8>> {
+    use image::ImageEncoder;
+    let mut out: Vec<u8> = Vec::with_capacity(buf.len() / 4 + 256);
+    image::codecs::png::PngEncoder::new_with_quality(
+        &mut out,
+        image::codecs::png::CompressionType::Fast,
+        image::codecs::png::FilterType::Sub,
+    )
+    .write_image(buf, w, h, ct)
+    .ok()?;
+    Some(out)
+}
+
+/// Palette tile: colour count - 1, the colours as RGB, then the palette
+/// indices as a grey PNG.
+fn palette_rgb(rgb: &[u8], w: u32, h: u32) -> Option<Vec<u8>> {
+    let mut index: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut colours: Vec<u8> = Vec::new();
+    let mut idx = Vec::with_capacity(rgb.len() / 3);
+    for p in rgb.chunks_exact(3) {
+        let c = [p[0], p[1], p[2]];
+        let i = match index.get(&c) {
+            Some(&i) => i,
+            None => {
+                if index.len() >= PALETTE_MAX {
+                    return None;
+                }
+                let i = index.len() as u8;
+                index.insert(c, i);
+                colours.extend_from_slice(&c);
+                i
+            }
+        };
+        idx.push(i);
+    }
+    let body = png(&idx, w, h, image::ExtendedColorType::L8)?;
+    let mut out = Vec::with_capacity(1 + colours.len() + body.len());
+    out.push((index.len().max(1) - 1) as u8);
+    out.extend_from_slice(&colours);
+    out.extend_from_slice(&body);
+    Some(out)
+}
+
+/// Compresses one tile in the given format.
+pub fn encode_tile(
+    rgb: &[u8],
+    w: u32,
+    h: u32,
+    format: TileFormat,
+    quality: u8,
+) -> Option<Vec<u8>> {
+    match format {
+        TileFormat::Jpeg => jpeg_rgb(rgb, w, h, quality),
+        TileFormat::Png => png(rgb, w, h, image::ExtendedColorType::Rgb8),
+        TileFormat::Palette => palette_rgb(rgb, w, h),
+    }
+}
+
+/// Unpacks a tile into RGB pixels plus their size, whatever its format.
+pub fn decode_tile(t: &Tile) -> Option<(Vec<u8>, u32, u32)> {
+    let rgb = match t.format {
+        TileFormat::Jpeg => {
+            image::load_from_memory_with_format(&t.data, image::ImageFormat::Jpeg)
+                .ok()?
+                .to_rgb8()
+        }
+        TileFormat::Png => {
+            image::load_from_memory_with_format(&t.data, image::ImageFormat::Png)
+                .ok()?
+                .to_rgb8()
+        }
+        TileFormat::Palette => {
+            let n = *t.data.first()? as usize + 1;
+            let colours = t.data.get(1..1 + n * 3)?;
+            let body = &t.data[1 + n * 3..];
+            let idx = image::load_from_memory_with_format(body, image::ImageFormat::Png)
+                .ok()?
+                .to_luma8();
+            let (w, h) = idx.dimensions();
+            let mut out = Vec::with_capacity(w as usize * h as usize * 3);
+            for &i in idx.as_raw() {
+                out.extend_from_slice(colours.get(i as usize * 3..i as usize * 3 + 3)?);
+            }
+            return Some((out, w, h));
+        }
+    };
+    let (w, h) = rgb.dimensions();
+    Some((rgb.into_raw(), w, h))
+}
+
 pub fn jpeg_rgb(rgb: &[u8], w: u32, h: u32, quality: u8) -> Option<Vec<u8>> {
     let mut buf: Vec<u8> = Vec::with_capacity(rgb.len() / 8 + 512);
     {
@@ -241,7 +670,14 @@ pub fn blit_rgb_to_rgba(
 }
 
 pub struct EncodeResult {
+    /// A `CopyRect` that has to reach the viewer before `msg`.
+    pub copy: Option<Msg>,
+    /// Tile cache messages (`CacheReset`, `CachePaint`) to send after `copy`
+    /// and before `msg`.
+    pub before: Vec<Msg>,
     pub msg: Option<Msg>,
+    /// `CacheStore` for the tiles in `msg`, sent right after it.
+    pub after: Vec<Msg>,
     pub keyframe: bool,
     #[allow(dead_code)]
     pub rects: usize,
@@ -252,7 +688,10 @@ pub struct EncodeResult {
 impl EncodeResult {
     fn nothing() -> Self {
         Self {
+            copy: None,
+            before: Vec::new(),
             msg: None,
+            after: Vec::new(),
             keyframe: false,
             rects: 0,
             dirty: 0.0,
@@ -268,6 +707,27 @@ pub struct Delta {
     h: u32,
     full_q: u8,
     tile_q: u8,
+    /// The viewer understands `CopyRect`, so moves may be detected.
+    motion: bool,
+    /// Scratch copy of `prev` with a detected move already applied.
+    moved: Vec<u8>,
+    /// Text and drawn content go out lossless instead of as JPEG.
+    lossless: bool,
+    /// The link is behind: send changes at `MOTION_QUALITY`, do not refine.
+    congested: bool,
+    /// Per grid tile: the quality the viewer has of it (`EXACT` = lossless).
+    cell_q: Vec<u8>,
+    /// Per grid tile: when it last changed.
+    since: Vec<Instant>,
+    refine_after: Duration,
+    /// Threads for compressing the rectangles of one frame.
+    pool: Pool,
+    /// Keys of the tiles the viewer has cached with their quality, None if
+    /// it has no cache.
+    cache: Option<Lru<u8>>,
+    /// The viewer has to drop its cache before the next tiles arrive.
+    cache_reset: bool,
+    cache_stats: CacheStats,
 }
 
 impl Default for Delta {
@@ -278,6 +738,17 @@ impl Default for Delta {
             h: 0,
             full_q: FULL_QUALITY,
             tile_q: TILE_QUALITY,
+            motion: false,
+            moved: Vec::new(),
+            lossless: false,
+            congested: false,
+            cell_q: Vec::new(),
+            since: Vec::new(),
+            refine_after: REFINE_AFTER,
+            pool: Pool::auto(),
+            cache: None,
+            cache_reset: false,
+            cache_stats: CacheStats::default(),
         }
     }
 }
@@ -297,15 +768,317 @@ impl Delta {
         }
     }
 
+    /// Only viewers that announced `CopyRect` support get moves.
+    pub fn set_motion(&mut self, on: bool) {
+        self.motion = on;
+    }
+
+    /// Sharp text for remote maintenance, when the viewer asked for it.
+    pub fn set_lossless(&mut self, on: bool) {
+        self.lossless = on;
+    }
+
+    /// How many threads compress the rectangles (1 = on the caller's).
+    pub fn set_threads(&mut self, n: usize) {
+        self.pool = Pool::new(n);
+    }
+
+    /// The host saw its send queue grow: changes get cheap until it drains.
+    pub fn set_congested(&mut self, on: bool) {
+        self.congested = on;
+    }
+
+    /// Share of the screen the viewer still has below the tile quality.
+    pub fn low_share(&self) -> f32 {
+        if self.cell_q.is_empty() {
+            return 0.0;
+        }
+        let low = self.cell_q.iter().filter(|&&q| q < self.tile_q).count();
+        low as f32 / self.cell_q.len() as f32
+    }
+
+    /// Index of the grid tile at pixel (x, y).
+    fn cell_at(&self, x: u32, y: u32) -> usize {
+        ((y / TILE) * self.w.div_ceil(TILE) + x / TILE) as usize
+    }
+
+    /// Records that the viewer now has the tiles under `r` at quality `q`.
+    fn mark(&mut self, r: Rect, q: u8) {
+        let now = Instant::now();
+        for (x, y, _, _) in cells(r, self.w, self.h) {
+            let i = self.cell_at(x, y);
+            if let (Some(cq), Some(t)) = (self.cell_q.get_mut(i), self.since.get_mut(i)) {
+                *cq = q;
+                *t = now;
+            }
+        }
+    }
+
+    /// A `CopyRect` moves pixels of unknown alignment: the tiles it lands on
+    /// get the worst quality of the tiles it came from.
+    fn mark_copy(&mut self, src: Rect, dst: (u32, u32)) {
+        let grid = |r: Rect| {
+            let x0 = r.0 / TILE * TILE;
+            let y0 = r.1 / TILE * TILE;
+            (x0, y0, r.0 + r.2 - x0, r.1 + r.3 - y0)
+        };
+        let q = cells(grid(src), self.w, self.h)
+            .map(|(x, y, _, _)| self.cell_q.get(self.cell_at(x, y)).copied().unwrap_or(0))
+            .min()
+            .unwrap_or(0);
+        self.mark(grid((dst.0, dst.1, src.2, src.3)), q);
+    }
+
+    /// Re-sends tiles that stood still for a while and are not at full
+    /// quality yet, a few per call. Call it whenever the link is idle; it
+    /// returns nothing while congested or when everything is sharp.
+    pub fn refine(&mut self) -> EncodeResult {
+        if self.congested || self.prev.is_empty() {
+            return EncodeResult::nothing();
+        }
+        let (w, h) = (self.w, self.h);
+        let cols = w.div_ceil(TILE);
+        let due: Vec<usize> = (0..self.cell_q.len())
+            .filter(|&i| self.cell_q[i] < self.tile_q)
+            .filter(|&i| self.since[i].elapsed() >= self.refine_after)
+            .take(REFINE_CELLS)
+            .collect();
+        let done = self.pool.map(&due, |&i| {
+            let x = (i as u32 % cols) * TILE;
+            let y = (i as u32 / cols) * TILE;
+            let (cw, ch) = (TILE.min(w - x), TILE.min(h - y));
+            let crop = crop_rgb(&self.prev, w, x, y, cw, ch);
+            (i, (x, y, cw, ch), self.tile_for(&crop, cw, ch, false))
+        });
+        let mut tiles = Vec::with_capacity(done.len());
+        let mut bytes = 0usize;
+        for (i, (x, y, cw, ch), (format, data, q)) in done {
+            let data = match data {
+                Some(d) => d,
+                None => continue,
+            };
+            bytes += data.len();
+            self.cell_q[i] = q;
+            tiles.push(Tile {
+                x,
+                y,
+                w: cw,
+                h: ch,
+                format,
+                data,
+            });
+        }
+        if tiles.is_empty() {
+            return EncodeResult::nothing();
+        }
+        let mut res = EncodeResult::nothing();
+        res.rects = tiles.len();
+        res.bytes = bytes;
+        res.msg = Some(Msg::Tiles {
+            width: w,
+            height: h,
+            tiles,
+        });
+        res
+    }
+
+    /// Picks format and quality for one tile and compresses it. Returns the
+    /// quality mark the viewer will have of it.
+    fn tile_for(
+        &self,
+        crop: &[u8],
+        cw: u32,
+        ch: u32,
+        changed: bool,
+    ) -> (TileFormat, Option<Vec<u8>>, u8) {
+        let cheap = changed && self.congested;
+        let q = if cheap {
+            self.tile_q.min(MOTION_QUALITY)
+        } else {
+            self.tile_q
+        };
+        let format = if self.lossless && !cheap {
+            classify(crop, cw, ch)
+        } else {
+            TileFormat::Jpeg
+        };
+        if format != TileFormat::Jpeg {
+            if let Some(data) = encode_tile(crop, cw, ch, format, q) {
+                return (format, Some(data), EXACT);
+            }
+        }
+        (TileFormat::Jpeg, jpeg_rgb(crop, cw, ch, q), q)
+    }
+
+    /// Only viewers that announced a tile cache get cache references.
+    pub fn set_cache(&mut self, on: bool) {
+        match (on, self.cache.is_some()) {
+            (true, false) => {
+                self.cache = Some(Lru::new(CELLS));
+                self.cache_reset = true;
+            }
+            (false, true) => self.cache = None,
+            _ => {}
+        }
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache_stats
+    }
+
+    /// Splits the dirty rectangles into the tiles the viewer can paint from
+    /// its cache and the rectangles that are left to encode. Only looks, the
+    /// cache itself is changed in `commit_cache` once the frame is sent.
+    fn cache_lookup(
+        &self,
+        rgb: &[u8],
+        w: u32,
+        h: u32,
+        rects: &[Rect],
+    ) -> Option<(Vec<CacheRef>, Vec<Rect>)> {
+        let cache = self.cache.as_ref()?;
+        let cols = w.div_ceil(TILE);
+        let rows = h.div_ceil(TILE);
+        let mut grid = vec![false; (cols * rows) as usize];
+        let mut refs = Vec::new();
+        for &r in rects {
+            for (x, y, cw, ch) in cells(r, w, h) {
+                let hash = cell_hash(rgb, w, x, y, cw, ch);
+                if cache.contains(hash) {
+                    refs.push(CacheRef { x, y, hash });
+                } else {
+                    grid[((y / TILE) * cols + x / TILE) as usize] = true;
+                }
+            }
+        }
+        if refs.is_empty() {
+            return None;
+        }
+        Some((refs, merge_grid(&grid, cols, rows, w, h)))
+    }
+
+    /// Applies what the viewer is about to do with its cache: touch every
+    /// painted reference, then store every freshly encoded tile, in exactly
+    /// the order of the messages. Returns the messages for before and after
+    /// the frame.
+    fn commit_cache(
+        &mut self,
+        rgb: &[u8],
+        w: u32,
+        h: u32,
+        paint: Vec<CacheRef>,
+        sent: &[Rect],
+    ) -> (Vec<Msg>, Vec<Msg>) {
+        let cache = match self.cache.as_mut() {
+            Some(c) => c,
+            None => return (Vec::new(), Vec::new()),
+        };
+        let mut before = Vec::new();
+        if std::mem::take(&mut self.cache_reset) {
+            cache.clear();
+            before.push(Msg::CacheReset);
+        }
+        let now = Instant::now();
+        for c in &paint {
+            let q = cache.touch(c.hash).copied().unwrap_or(0);
+            let i = ((c.y / TILE) * w.div_ceil(TILE) + c.x / TILE) as usize;
+            if let (Some(cq), Some(t)) = (self.cell_q.get_mut(i), self.since.get_mut(i)) {
+                *cq = q;
+                *t = now;
+            }
+        }
+        self.cache_stats.hits += paint.len() as u64;
+        if !paint.is_empty() {
+            before.push(Msg::CachePaint {
+                width: w,
+                height: h,
+                cells: paint,
+            });
+        }
+        let mut store = Vec::new();
+        for &r in sent {
+            for (x, y, cw, ch) in cells(r, w, h) {
+                let hash = cell_hash(rgb, w, x, y, cw, ch);
+                // the same content twice in one frame is stored once
+                if !cache.contains(hash) {
+                    let i = ((y / TILE) * w.div_ceil(TILE) + x / TILE) as usize;
+                    cache.insert(hash, self.cell_q.get(i).copied().unwrap_or(0));
+                    store.push(CacheRef { x, y, hash });
+                }
+            }
+        }
+        self.cache_stats.misses += store.len() as u64;
+        let mut after = Vec::new();
+        if !store.is_empty() {
+            after.push(Msg::CacheStore {
+                width: w,
+                height: h,
+                cells: store,
+            });
+        }
+        (before, after)
+    }
+
+    /// Tries to explain the change with a single move. Returns the
+    /// `CopyRect` plus the rectangles that are still dirty afterwards, or
+    /// None when moving would not save enough.
+    fn detect_move(
+        &mut self,
+        rgb: &[u8],
+        w: u32,
+        h: u32,
+        rects: &[Rect],
+        area: u64,
+    ) -> Option<(Msg, Vec<Rect>, u64)> {
+        let b = bounds(rects)?;
+        // a handful of tiles is cheap anyway, the search is not
+        if (b.2 - b.0) as u64 * (b.3 - b.1) as u64 <= 2 * (TILE * TILE) as u64 {
+            return None;
+        }
+        let (dx, dy) = find_motion(rgb, &self.prev, w, h, b)?;
+        let (src, dst) = moved_area(b, dx, dy)?;
+        self.moved.clear();
+        self.moved.extend_from_slice(&self.prev);
+        if !copy_rect(&mut self.moved, w, h, 3, src, dst) {
+            return None;
+        }
+        let rest = dirty_rects(rgb, &self.moved, w, h);
+        let rest_area: u64 = rest.iter().map(|r| r.2 as u64 * r.3 as u64).sum();
+        if rest_area as f32 > area as f32 * (1.0 - MOTION_GAIN) {
+            return None;
+        }
+        let copy = Msg::CopyRect {
+            width: w,
+            height: h,
+            src,
+            dst,
+        };
+        Some((copy, rest, rest_area))
+    }
+
+    /// Forgets the previous frame; the next one is a keyframe. A cache the
+    /// viewer has is dropped as well, so a fresh viewer never gets references
+    /// to tiles it has not seen.
     pub fn reset(&mut self) {
         self.prev.clear();
         self.w = 0;
         self.h = 0;
+        if self.cache.is_some() {
+            self.cache_reset = true;
+        }
     }
 
     /// Always emits a complete frame (used for the first frame and as fallback).
     pub fn encode_full(&mut self, rgb: &[u8], w: u32, h: u32) -> EncodeResult {
-        let jpeg = match jpeg_rgb(rgb, w, h, self.full_q) {
+        // the cheap keyframe quality only while the line is busy or `refine`
+        // makes the text lossless anyway; otherwise the tiles' quality right
+        // away, or `refine` would resend the whole screen after every keyframe
+        let q = if self.congested || self.lossless {
+            self.full_q
+        } else {
+            self.full_q.max(self.tile_q)
+        };
+        let jpeg = match jpeg_rgb(rgb, w, h, q) {
             Some(j) => j,
             None => return EncodeResult::nothing(),
         };
@@ -313,13 +1086,20 @@ impl Delta {
         self.prev.extend_from_slice(rgb);
         self.w = w;
         self.h = h;
+        let n = (w.div_ceil(TILE) * h.div_ceil(TILE)) as usize;
+        self.cell_q = vec![q; n];
+        self.since = vec![Instant::now(); n];
         let bytes = jpeg.len();
+        let (before, after) = self.commit_cache(rgb, w, h, Vec::new(), &[(0, 0, w, h)]);
         EncodeResult {
+            copy: None,
+            before,
             msg: Some(Msg::Frame {
                 width: w,
                 height: h,
                 jpeg,
             }),
+            after,
             keyframe: true,
             rects: 1,
             dirty: 1.0,
@@ -331,30 +1111,69 @@ impl Delta {
         if self.w != w || self.h != h || self.prev.len() != rgb.len() {
             return self.encode_full(rgb, w, h);
         }
-        let rects = dirty_rects(rgb, &self.prev, w, h);
+        let mut rects = dirty_rects(rgb, &self.prev, w, h);
         if rects.is_empty() {
             return EncodeResult::nothing();
         }
-        let area: u64 = rects.iter().map(|r| r.2 as u64 * r.3 as u64).sum();
+        let mut area: u64 = rects.iter().map(|r| r.2 as u64 * r.3 as u64).sum();
+        let mut copy = None;
+        if self.motion {
+            if let Some((c, rest, rest_area)) = self.detect_move(rgb, w, h, &rects, area) {
+                if let Msg::CopyRect { src, dst, .. } = c {
+                    self.mark_copy(src, dst);
+                }
+                copy = Some(c);
+                rects = rest;
+                area = rest_area;
+            }
+        }
+        let mut paint = Vec::new();
+        if let Some((refs, rest)) = self.cache_lookup(rgb, w, h, &rects) {
+            paint = refs;
+            rects = rest;
+            area = rects.iter().map(|r| r.2 as u64 * r.3 as u64).sum();
+        }
         let dirty = area as f32 / (w as f32 * h as f32).max(1.0);
+        if rects.is_empty() {
+            // the move and the cache alone explain everything
+            self.prev.copy_from_slice(rgb);
+            let (before, after) = self.commit_cache(rgb, w, h, paint, &[]);
+            return EncodeResult {
+                copy,
+                before,
+                msg: None,
+                after,
+                keyframe: false,
+                rects: 0,
+                dirty,
+                bytes: 0,
+            };
+        }
         if dirty > KEYFRAME_RATIO || rects.len() > MAX_RECTS {
             let mut res = self.encode_full(rgb, w, h);
             res.dirty = dirty;
             return res;
         }
 
+        let done = self.pool.map(&rects, |&(x, y, cw, ch)| {
+            let crop = crop_rgb(rgb, w, x, y, cw, ch);
+            self.tile_for(&crop, cw, ch, true)
+        });
         let mut tiles: Vec<Tile> = Vec::with_capacity(rects.len());
+        let mut sent: Vec<Rect> = Vec::with_capacity(rects.len());
         let mut bytes = 0usize;
-        for (x, y, cw, ch) in rects {
-            let crop = crop_rgb(rgb, w, x, y, cw, ch);
-            if let Some(jpeg) = jpeg_rgb(&crop, cw, ch, self.tile_q) {
-                bytes += jpeg.len();
+        for ((x, y, cw, ch), (format, data, q)) in rects.into_iter().zip(done) {
+            if let Some(data) = data {
+                bytes += data.len();
+                sent.push((x, y, cw, ch));
+                self.mark((x, y, cw, ch), q);
                 tiles.push(Tile {
                     x,
                     y,
                     w: cw,
                     h: ch,
-                    jpeg,
+                    format,
+                    data,
                 });
             }
         }
@@ -362,13 +1181,17 @@ impl Delta {
             return EncodeResult::nothing();
         }
         self.prev.copy_from_slice(rgb);
+        let (before, after) = self.commit_cache(rgb, w, h, paint, &sent);
         let n = tiles.len();
         EncodeResult {
+            copy,
+            before,
             msg: Some(Msg::Tiles {
                 width: w,
                 height: h,
                 tiles,
             }),
+            after,
             keyframe: false,
             rects: n,
             dirty,
@@ -527,6 +1350,105 @@ mod tests {
         assert!(r.keyframe);
     }
 
+    /// Deterministic "busy" picture: every pixel different from its
+    /// neighbours, like text or a photo.
+    fn noise_rgb(w: u32, h: u32, seed: u32) -> Vec<u8> {
+        let mut v = Vec::with_capacity((w * h * 3) as usize);
+        let mut x = seed.wrapping_mul(2_654_435_761).max(1);
+        for _ in 0..w * h * 3 {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            v.push((x >> 8) as u8);
+        }
+        v
+    }
+
+    #[test]
+    fn copy_rect_handles_overlap_both_ways() {
+        // 1 byte per pixel keeps the numbers readable: row n holds value n
+        let mut buf: Vec<u8> = (0..6u8).flat_map(|r| [r; 4]).collect();
+        assert!(copy_rect(&mut buf, 4, 6, 1, (0, 1, 4, 5), (0, 0)));
+        assert_eq!(&buf[..20], &[1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5]);
+        let mut buf: Vec<u8> = (0..6u8).flat_map(|r| [r; 4]).collect();
+        assert!(copy_rect(&mut buf, 4, 6, 1, (0, 0, 4, 5), (0, 1)));
+        assert_eq!(&buf[4..], &[0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4]);
+        // anything reaching outside the canvas is refused
+        assert!(!copy_rect(&mut buf, 4, 6, 1, (0, 2, 4, 5), (0, 0)));
+        assert!(!copy_rect(&mut buf, 4, 6, 1, (1, 0, u32::MAX, 1), (0, 0)));
+    }
+
+    #[test]
+    fn scrolling_sends_a_copy_and_only_the_new_strip() {
+        let (w, h) = (256u32, 256u32);
+        let a = noise_rgb(w, h, 1);
+        // content moves up by 24 rows, a fresh strip appears at the bottom
+        let mut b = a[(24 * w * 3) as usize..].to_vec();
+        b.extend_from_slice(&noise_rgb(w, 24, 2));
+        let mut d = Delta::new();
+        d.set_motion(true);
+        assert!(d.encode(&a, w, h).keyframe);
+        let r = d.encode(&b, w, h);
+        assert!(!r.keyframe, "a scroll must not turn into a keyframe");
+        match r.copy {
+            Some(Msg::CopyRect { src, dst, .. }) => {
+                assert_eq!(src, (0, 24, 256, 232));
+                assert_eq!(dst, (0, 0));
+            }
+            other => panic!("expected a copy, got {:?}", other),
+        }
+        match r.msg {
+            Some(Msg::Tiles { tiles, .. }) => {
+                assert_eq!(tiles.len(), 1);
+                assert_eq!((tiles[0].x, tiles[0].y, tiles[0].w, tiles[0].h), (0, 192, 256, 64));
+            }
+            other => panic!("expected the revealed strip, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dragged_window_is_found_on_both_axes() {
+        let (w, h) = (256u32, 256u32);
+        let win = noise_rgb(100, 80, 7);
+        let place = |x0: u32, y0: u32| {
+            let mut f = solid_rgb(w, h, 90);
+            for y in 0..80 {
+                let s = (y * 100 * 3) as usize;
+                let d = (((y0 + y) * w + x0) * 3) as usize;
+                f[d..d + 300].copy_from_slice(&win[s..s + 300]);
+            }
+            f
+        };
+        let a = place(40, 40);
+        let b = place(70, 90);
+        let bx = bounds(&dirty_rects(&b, &a, w, h)).unwrap();
+        assert_eq!(find_motion(&b, &a, w, h, bx), Some((30, 50)));
+
+        let mut d = Delta::new();
+        d.set_motion(true);
+        d.encode(&a, w, h);
+        let r = d.encode(&b, w, h);
+        assert!(r.copy.is_some());
+        // only the old window's top edge is left to paint
+        assert_eq!(r.rects, 1);
+    }
+
+    #[test]
+    fn no_copy_without_viewer_support_or_real_motion() {
+        let (w, h) = (256u32, 256u32);
+        let a = noise_rgb(w, h, 3);
+        let mut b = a[(16 * w * 3) as usize..].to_vec();
+        b.extend_from_slice(&noise_rgb(w, 16, 4));
+        let mut d = Delta::new();
+        d.encode(&a, w, h);
+        assert!(d.encode(&b, w, h).copy.is_none(), "motion is off by default");
+        // completely new content has nothing to move
+        let mut d = Delta::new();
+        d.set_motion(true);
+        d.encode(&a, w, h);
+        assert!(d.encode(&noise_rgb(w, h, 5), w, h).copy.is_none());
+    }
+
     #[test]
     fn quality_switch_forces_a_fresh_keyframe() {
         let mut d = Delta::new();
@@ -536,4 +1458,246 @@ mod tests {
         d.set_quality(40, 45);
         assert!(d.encode(&a, 128, 128).keyframe, "quality change must resend");
     }
+
+    #[test]
+    fn switching_back_paints_from_the_cache() {
+        let (w, h) = (256u32, 256u32);
+        let mut desk = solid_rgb(w, h, 90);
+        let win_a = noise_rgb(128, 128, 11);
+        let win_b = noise_rgb(128, 128, 12);
+        let show = |f: &mut Vec<u8>, win: &[u8]| {
+            for y in 0..128usize {
+                let d = ((64 + y) * w as usize + 64) * 3;
+                f[d..d + 384].copy_from_slice(&win[y * 384..y * 384 + 384]);
+            }
+        };
+        let mut d = Delta::new();
+        d.set_cache(true);
+        show(&mut desk, &win_a);
+        let r = d.encode(&desk, w, h);
+        assert!(matches!(r.before.first(), Some(Msg::CacheReset)), "fresh cache starts clean");
+        assert!(matches!(r.after.first(), Some(Msg::CacheStore { .. })));
+        let a = desk.clone();
+        show(&mut desk, &win_b);
+        let r = d.encode(&desk, w, h);
+        assert!(!r.keyframe && r.before.is_empty(), "window b was never seen");
+        match r.after.first() {
+            Some(Msg::CacheStore { cells, .. }) => assert_eq!(cells.len(), 4),
+            other => panic!("expected a store, got {:?}", other),
+        }
+        // back to window a: no pixels travel at all
+        let r = d.encode(&a, w, h);
+        assert!(r.msg.is_none() && r.after.is_empty());
+        match r.before.first() {
+            Some(Msg::CachePaint { cells, .. }) => assert_eq!(cells.len(), 4),
+            other => panic!("expected a paint, got {:?}", other),
+        }
+        assert_eq!(d.cache_stats().hits, 4);
+    }
+
+    #[test]
+    fn cache_is_off_by_default_and_reset_with_the_stream() {
+        let a = noise_rgb(128, 128, 1);
+        let b = noise_rgb(128, 128, 2);
+        let mut d = Delta::new();
+        d.encode(&a, 128, 128);
+        d.encode(&b, 128, 128);
+        let r = d.encode(&a, 128, 128);
+        assert!(r.before.is_empty() && r.after.is_empty());
+
+        d.set_cache(true);
+        d.encode(&a, 128, 128);
+        d.reset();
+        // a forced keyframe tells the viewer to forget and refills the cache
+        let r = d.encode(&b, 128, 128);
+        assert!(r.keyframe);
+        assert!(matches!(r.before.first(), Some(Msg::CacheReset)));
+        // the content from before the reset must not be referenced
+        let r = d.encode(&a, 128, 128);
+        assert!(!r.before.iter().any(|m| matches!(m, Msg::CachePaint { .. })));
+    }
+
+    /// Black "text" on white: two colours, hard edges.
+    fn text_rgb(w: u32, h: u32) -> Vec<u8> {
+        let mut v = solid_rgb(w, h, 255);
+        for y in 0..h {
+            for x in 0..w {
+                if (x / 3 + y / 5) % 4 == 0 && y % 12 < 9 {
+                    let i = ((y * w + x) * 3) as usize;
+                    v[i..i + 3].copy_from_slice(&[0, 0, 0]);
+                }
+            }
+        }
+        v
+    }
+
+    #[test]
+    fn tiles_are_classified_by_content() {
+        assert_eq!(classify(&text_rgb(64, 64), 64, 64), TileFormat::Palette);
+        assert_eq!(classify(&noise_rgb(64, 64, 9), 64, 64), TileFormat::Jpeg);
+        // a smooth horizontal gradient with flat rows: many colours, but drawn
+        let mut grad = Vec::new();
+        for y in 0..64u32 {
+            for x in 0..64u32 {
+                grad.extend_from_slice(&[(x / 8 * 30) as u8, y as u8 * 4, 7]);
+            }
+        }
+        assert_eq!(classify(&grad, 64, 64), TileFormat::Png);
+    }
+
+    #[test]
+    fn lossless_tiles_come_back_bit_exact() {
+        let text = text_rgb(100, 40);
+        for format in [TileFormat::Palette, TileFormat::Png] {
+            let data = encode_tile(&text, 100, 40, format, 70).unwrap();
+            let t = Tile {
+                x: 0,
+                y: 0,
+                w: 100,
+                h: 40,
+                format,
+                data,
+            };
+            assert_eq!(decode_tile(&t), Some((text.clone(), 100, 40)), "{:?}", format);
+        }
+        // more colours than a palette holds is refused, not truncated
+        assert!(encode_tile(&noise_rgb(64, 64, 1), 64, 64, TileFormat::Palette, 70).is_none());
+        // a palette index pointing past the colours must not panic
+        let mut bad = encode_tile(&text, 100, 40, TileFormat::Palette, 70).unwrap();
+        bad[0] = 0;
+        bad.drain(4..7);
+        let t = Tile {
+            x: 0,
+            y: 0,
+            w: 100,
+            h: 40,
+            format: TileFormat::Palette,
+            data: bad,
+        };
+        assert!(decode_tile(&t).is_none());
+    }
+
+    #[test]
+    fn lossless_mode_keeps_text_sharp_and_photos_jpeg() {
+        let (w, h) = (256u32, 256u32);
+        let a = solid_rgb(w, h, 255);
+        let mut b = a.clone();
+        let text = text_rgb(64, 64);
+        let photo = noise_rgb(64, 64, 4);
+        for y in 0..64usize {
+            let d = y * w as usize * 3;
+            b[d..d + 192].copy_from_slice(&text[y * 192..y * 192 + 192]);
+            let d = ((128 + y) * w as usize + 128) * 3;
+            b[d..d + 192].copy_from_slice(&photo[y * 192..y * 192 + 192]);
+        }
+        let mut d = Delta::new();
+        d.set_lossless(true);
+        d.encode(&a, w, h);
+        match d.encode(&b, w, h).msg {
+            Some(Msg::Tiles { tiles, .. }) => {
+                let fmt: Vec<TileFormat> = tiles.iter().map(|t| t.format).collect();
+                assert_eq!(fmt, vec![TileFormat::Palette, TileFormat::Jpeg]);
+                assert_eq!(decode_tile(&tiles[0]).unwrap().0, text);
+            }
+            other => panic!("expected tiles, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn congested_changes_are_refined_once_they_stand_still() {
+        let (w, h) = (256u32, 256u32);
+        let a = solid_rgb(w, h, 40);
+        let mut b = a.clone();
+        for y in 0..40usize {
+            for x in 0..40usize {
+                b[(y * 256 + x) * 3] = 200;
+            }
+        }
+        let mut d = Delta::new();
+        d.encode(&a, w, h);
+        // an idle keyframe already has tile quality
+        assert_eq!(d.low_share(), 0.0);
+        d.refine_after = Duration::ZERO;
+        assert!(d.refine().msg.is_none(), "nothing to refine after a keyframe");
+        d.set_congested(true);
+        assert!(!d.encode(&b, w, h).keyframe);
+        assert_eq!(d.low_share(), 1.0 / 16.0);
+        assert!(d.refine().msg.is_none(), "no refinement while the link is busy");
+
+        d.set_congested(false);
+        match d.refine().msg {
+            Some(Msg::Tiles { tiles, .. }) => {
+                // only the changed tile, not the whole screen
+                assert_eq!(tiles.len(), 1);
+                assert_eq!((tiles[0].x, tiles[0].y, tiles[0].w), (0, 0, TILE));
+            }
+            other => panic!("expected refinement tiles, got {:?}", other),
+        }
+        assert_eq!(d.low_share(), 0.0);
+        assert!(d.refine().msg.is_none(), "everything is sharp now");
+
+        // a keyframe while congested is cheap and gets refined later
+        d.set_congested(true);
+        d.reset();
+        d.encode(&a, w, h);
+        assert_eq!(d.low_share(), 1.0);
+    }
+
+    #[test]
+    fn lossless_refinement_sharpens_keyframe_text() {
+        let (w, h) = (128u32, 64u32);
+        let mut text = text_rgb(w, h);
+        let mut d = Delta::new();
+        d.set_lossless(true);
+        d.refine_after = Duration::ZERO;
+        assert!(d.encode(&text, w, h).keyframe);
+        let tiles = match d.refine().msg {
+            Some(Msg::Tiles { tiles, .. }) => tiles,
+            other => panic!("expected refinement tiles, got {:?}", other),
+        };
+        assert!(tiles.iter().all(|t| t.format == TileFormat::Palette));
+        assert_eq!(decode_tile(&tiles[1]).unwrap().0, crop_rgb(&text, w, 64, 0, 64, 64));
+        // a change at motion quality while congested drops the tile again
+        d.set_congested(true);
+        text[0] ^= 0xff;
+        d.encode(&text, w, h);
+        assert_eq!(d.low_share(), 0.5);
+    }
+
+    #[test]
+    fn threads_do_not_change_the_output() {
+        let rgba: Vec<u8> = noise_rgb(300, 200, 3)
+            .chunks(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect();
+        for (dw, dh) in [(300, 200), (157, 93)] {
+            let one = scale_to_rgb_ex(&rgba, 300, 200, dw, dh, true);
+            for threads in [2, 5] {
+                let many = scale_to_rgb_par(&rgba, 300, 200, dw, dh, true, &Pool::new(threads));
+                assert!(one == many, "{}x{} with {} threads", dw, dh, threads);
+            }
+        }
+
+        let (w, h) = (512u32, 256u32);
+        let a = solid_rgb(w, h, 200);
+        let mut b = a.clone();
+        // scattered changes -> several rectangles
+        for (x0, y0) in [(0usize, 0usize), (200, 70), (420, 190), (130, 200)] {
+            let p = noise_rgb(40, 40, x0 as u32);
+            for y in 0..40 {
+                let d = ((y0 + y) * w as usize + x0) * 3;
+                b[d..d + 120].copy_from_slice(&p[y * 120..y * 120 + 120]);
+            }
+        }
+        let mut out = Vec::new();
+        for threads in [1, 4] {
+            let mut d = Delta::new();
+            d.set_threads(threads);
+            d.set_lossless(true);
+            d.encode(&a, w, h);
+            out.push(format!("{:?}", d.encode(&b, w, h).msg));
+        }
+        assert!(out[0].contains("Tiles"));
+        assert_eq!(out[0], out[1]);
+    }
 }
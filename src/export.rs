@@ -0,0 +1,272 @@
+//! `--export in.fvrec out.avi`: turns a session recording into a video file
+//! any player opens (Motion-JPEG in AVI, see `avi`).
+//!
+//! The picture goes through the player's `Screen`, so it looks exactly like
+//! playing the recording: every output frame shows what was on screen at its
+//! time, with the remote cursor drawn in. The voice of both sides, if asked
+//! for, is mixed into one sound track. The video starts with the first
+//! picture; a resolution change on the host is scaled to the first size.
+
+use std::io;
+use std::path::Path;
+
+use crate::audio;
+use crate::avi::AviWriter;
+use crate::player::Screen;
+use crate::proto::Msg;
+use crate::record::{Recording, Side};
+
+/// Frames per second of exported videos.
+pub const FPS: u32 = 10;
+const QUALITY: u8 = 80;
+
+/// The classic arrow, 'B' black and 'W' white, hot spot top left.
+const ARROW: [&str; 17] = [
+    "B",
+    "BB",
+    "BWB",
+    "BWWB",
+    "BWWWB",
+    "BWWWWB",
+    "BWWWWWB",
+    "BWWWWWWB",
+    "BWWWWWWWB",
+    "BWWWWWWWWB",
+    "BWWWWWBBBBB",
+    "BWWBWWB",
+    "BWB BWWB",
+    "BB  BWWB",
+    "B    BWWB",
+    "     BWWB",
+    "      BB",
+];
+
+/// Draws the cursor arrow into an RGB picture with its tip at (x, y).
+fn draw_cursor(rgb: &mut [u8], w: u32, h: u32, x: i32, y: i32) {
+    for (dy, row) in ARROW.iter().enumerate() {
+        let py = y + dy as i32;
+        if py < 0 || py >= h as i32 {
+            continue;
+        }
+        for (dx, c) in row.bytes().enumerate() {
+            let px = x + dx as i32;
+            if px < 0 || px >= w as i32 {
+                continue;
+            }
+            let v = match c {
+                b'B' => 0,
+                b'W' => 255,
+                _ => continue,
+            };
+            let at = (py as usize * w as usize + px as usize) * 3;
+            rgb[at..at + 3].fill(v);
+        }
+    }
+}
+
+/// The voice of both sides, mixed: packets are laid down where they
+/// arrived, after whatever the same side said before.
+struct Mixer {
+    /// Sample number of `buf[0]`.
+    base: u64,
+    buf: Vec<i32>,
+    /// Where the last packet of each side ended.
+    end: [u64; 2],
+}
+
+impl Mixer {
+    fn add(&mut self, side: Side, t: u32, pcm: &[i16]) {
+        let s = side as usize;
+        let at = (t as u64 * audio::RATE as u64 / 1000).max(self.end[s]);
+        self.end[s] = at + pcm.len() as u64;
+        for (i, v) in pcm.iter().enumerate() {
+            let Some(k) = (at + i as u64).checked_sub(self.base) else {
+                continue;
+            };
+            let k = k as usize;
+            if self.buf.len() <= k {
+                self.buf.resize(k + 1, 0);
+            }
+            self.buf[k] += *v as i32;
+        }
+    }
+
+    /// Everything up to sample `upto`, silence where nobody spoke.
+    fn take(&mut self, upto: u64) -> Vec<i16> {
+        let n = upto.saturating_sub(self.base) as usize;
+        if self.buf.len() < n {
+            self.buf.resize(n, 0);
+        }
+        self.base += n as u64;
+        self.buf
+            .drain(..n)
+            .map(|v| v.clamp(i16::MIN as i32, i16::MAX as i32) as i16)
+            .collect()
+    }
+}
+
+/// Writes `src` as a video to `dst`. `progress` hears how far it got in ms
+/// of the recording. Returns the number of frames.
+pub fn export(
+    src: &Path,
+    dst: &Path,
+    fps: u32,
+    voice: bool,
+    progress: &mut dyn FnMut(u32),
+) -> io::Result<u32> {
+    let mut rec = Recording::open(src)?;
+    let fps = fps.clamp(1, 60);
+    let dur = rec.duration();
+    let mut screen = Screen::new();
+    let mut out: Option<AviWriter> = None;
+    let mut size = (0, 0);
+    let mut mix = Mixer {
+        base: 0,
+        buf: Vec::new(),
+        end: [0; 2],
+    };
+    let mut next = 0;
+    let mut frame: u64 = 0;
+    loop {
+        let t = (frame * 1000 / fps as u64) as u32;
+        if t > dur {
+            break;
+        }
+        while next < rec.entries.len() && rec.entries[next].t <= t {
+            let e = rec.entries[next];
+            next += 1;
+            match rec.msg(next - 1) {
+                Some(Msg::Audio { data, .. }) if voice && out.is_some() => {
+                    mix.add(e.from, e.t, &audio::decode_frame(&data))
+                }
+                Some(m) if e.from == Side::Host => screen.paint(m),
+                _ => {}
+            }
+        }
+        frame += 1;
+        if !screen.has_picture() {
+            continue;
+        }
+        let c = &screen.canvas;
+        if out.is_none() {
+            size = (c.w, c.h);
+            mix.base = t as u64 * audio::RATE as u64 / 1000;
+            mix.end = [mix.base; 2];
+            let rate = voice.then_some(audio::RATE);
+            out = Some(AviWriter::create(dst, c.w, c.h, fps, rate)?);
+        }
+        let Some(w) = out.as_mut() else {
+            continue;
+        };
+        let mut rgb: Vec<u8> = c
+            .rgba
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect();
+        if (c.w, c.h) != size {
+            let img = image::RgbImage::from_raw(c.w, c.h, rgb)
+                .ok_or_else(|| io::Error::other("Bildgroesse passt nicht"))?;
+            rgb =
+                image::imageops::resize(&img, size.0, size.1, image::imageops::Triangle).into_raw();
+        }
+        let (x, y, visible) = screen.cursor;
+        if visible {
+            draw_cursor(
+                &mut rgb,
+                size.0,
+                size.1,
+                (x as i64 * size.0 as i64 / 10000) as i32,
+                (y as i64 * size.1 as i64 / 10000) as i32,
+            );
+        }
+        let jpeg = crate::encoder::jpeg_rgb(&rgb, size.0, size.1, QUALITY)
+            .ok_or_else(|| io::Error::other("JPEG konnte nicht erzeugt werden"))?;
+        w.frame(&jpeg)?;
+        if voice {
+            // the sound up to the next frame
+            let upto = (frame * 1000 / fps as u64) * audio::RATE as u64 / 1000;
+            w.audio(&mix.take(upto))?;
+        }
+        progress(t);
+    }
+    let Some(w) = out else {
+        return Err(io::Error::other("die Aufnahme enthaelt kein Bild"));
+    };
+    let frames = w.frames();
+    w.finish()?;
+    Ok(frames)
+}
+
+/// `--export <in.fvrec> <out.avi> [--fps N] [--voice]`. Returns the exit code.
+pub fn cli(args: &[String]) -> i32 {
+    let mut pos = Vec::new();
+    let mut fps = FPS;
+    let mut it = args.iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--fps" => fps = it.next().and_then(|v| v.parse().ok()).unwrap_or(FPS),
+            "--voice" => {}
+            _ => pos.push(a),
+        }
+    }
+    let (Some(src), Some(dst)) = (pos.first(), pos.get(1)) else {
+        eprintln!("FAIL: freeviewer --export <aufnahme.fvrec> <video.avi> [--fps N] [--voice]");
+        return 2;
+    };
+    let voice = args.iter().any(|a| a == "--voice");
+    let mut shown = 0;
+    let r = export(Path::new(src), Path::new(dst), fps, voice, &mut |t| {
+        if t / 10_000 != shown {
+            shown = t / 10_000;
+            eprintln!("{}", crate::player::clock(t));
+        }
+    });
+    match r {
+        Ok(n) => {
+            println!("OK: {} Bilder -> {}", n, dst);
+            0
+        }
+        Err(e) => {
+            eprintln!("FAIL: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_is_clipped_at_the_edges() {
+        let (w, h) = (8u32, 6u32);
+        let mut rgb = vec![100u8; (w * h * 3) as usize];
+        draw_cursor(&mut rgb, w, h, 6, 4);
+        draw_cursor(&mut rgb, w, h, -3, -3);
+        // the tip and the edge below it are black, even in the corner
+        assert_eq!(&rgb[((4 * w + 6) * 3) as usize..][..3], &[0, 0, 0]);
+        assert_eq!(&rgb[((5 * w + 7) * 3) as usize..][..3], &[0, 0, 0]);
+        let mut big = vec![100u8; 20 * 20 * 3];
+        draw_cursor(&mut big, 20, 20, 0, 0);
+        assert_eq!(&big[((3 * 20 + 1) * 3)..][..3], &[255, 255, 255]);
+        assert_eq!(&big[5 * 3..][..3], &[100, 100, 100]);
+    }
+
+    #[test]
+    fn both_sides_are_mixed_and_kept_apart_in_time() {
+        let mut m = Mixer {
+            base: 0,
+            buf: Vec::new(),
+            end: [0; 2],
+        };
+        m.add(Side::Host, 0, &[100, 100]);
+        m.add(Side::Viewer, 0, &[10, 10]);
+        // a late packet of the host goes after its first one
+        m.add(Side::Host, 0, &[1]);
+        m.add(Side::Viewer, 0, &[i16::MAX]);
+        assert_eq!(m.take(4), vec![110, 110, i16::MAX, 0]);
+        assert_eq!(m.end, [3, 3]);
+        assert!(m.take(2).is_empty(), "nothing is taken twice");
+        assert_eq!(m.base, 4);
+    }
+}
@@ -0,0 +1,159 @@
+//! System information of the host (`Msg::SysInfoGet` / `Msg::SysInfo`).
+//!
+//! The host gathers a `SysReport` on request; the viewer shows it in a
+//! side panel and `--sysinfo` prints it. Gathering takes a moment (the CPU
+//! list, disks and interfaces are read fresh), so the host does it on a
+//! thread of its own.
+
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use sysinfo::{CpuRefreshKind, Disks, Networks, System};
+
+use crate::proto::{DiskInfo, Msg, NetInfo, SysReport, MAX_SYS_ITEMS};
+use crate::shared::Session;
+
+pub fn gather() -> SysReport {
+    let mut sys = System::new();
+    sys.refresh_cpu_list(CpuRefreshKind::nothing());
+    sys.refresh_memory();
+    let cpu = sys
+        .cpus()
+        .first()
+        .map(|c| c.brand().trim().to_string())
+        .unwrap_or_default();
+
+    let disks = Disks::new_with_refreshed_list()
+        .list()
+        .iter()
+        .take(MAX_SYS_ITEMS)
+        .map(|d| DiskInfo {
+            mount: d.mount_point().to_string_lossy().into_owned(),
+            fs: d.file_system().to_string_lossy().into_owned(),
+            total: d.total_space(),
+            free: d.available_space(),
+        })
+        .collect();
+
+    let mut nets: Vec<NetInfo> = Networks::new_with_refreshed_list()
+        .iter()
+        .map(|(name, data)| NetInfo {
+            name: name.clone(),
+            mac: data.mac_address().to_string(),
+            addrs: data
+                .ip_networks()
+                .iter()
+                .take(MAX_SYS_ITEMS)
+                .map(|n| format!("{}/{}", n.addr, n.prefix))
+                .collect(),
+        })
+        .collect();
+    nets.sort_by(|a, b| a.name.cmp(&b.name));
+    nets.truncate(MAX_SYS_ITEMS);
+
+    SysReport {
+        os: System::long_os_version().unwrap_or_else(|| std::env::consts::OS.to_string()),
+        kernel: System::kernel_version().unwrap_or_default(),
+        host: System::host_name().unwrap_or_default(),
+        user: std::env::var("USERNAME")
+            .or_else(|_| std::env::var("USER"))
+            .unwrap_or_default(),
+        version: crate::update::VERSION.to_string(),
+        cpu,
+        cores: sys.cpus().len() as u32,
+        mem_total: sys.total_memory(),
+        mem_used: sys.used_memory(),
+        uptime: System::uptime(),
+        disks,
+        nets,
+    }
+}
+
+/// "3 Tage 4:05 h" - how long the host has been running.
+pub fn uptime_text(secs: u64) -> String {
+    let days = secs / 86400;
+    let hm = format!("{}:{:02} h", secs % 86400 / 3600, secs % 3600 / 60);
+    match days {
+        0 => hm,
+        1 => format!("1 Tag {}", hm),
+        d => format!("{} Tage {}", d, hm),
+    }
+}
+
+/// The report as plain text, for `--sysinfo`.
+pub fn text(r: &SysReport) -> String {
+    let mut s = String::new();
+    let mut line = |k: &str, v: String| s.push_str(&format!("{:<14}{}\n", k, v));
+    line("Rechner", r.host.clone());
+    line("Benutzer", r.user.clone());
+    line("System", r.os.clone());
+    line("Kernel", r.kernel.clone());
+    line("Prozessor", format!("{} ({} Kerne)", r.cpu, r.cores));
+    line(
+        "Speicher",
+        format!(
+            "{} von {} belegt",
+            crate::human_size(r.mem_used),
+            crate::human_size(r.mem_total)
+        ),
+    );
+    line("Laufzeit", uptime_text(r.uptime));
+    line("FreeViewer", r.version.clone());
+    for d in &r.disks {
+        line(
+            "Laufwerk",
+            format!(
+                "{} ({}) {} frei von {}",
+                d.mount,
+                d.fs,
+                crate::human_size(d.free),
+                crate::human_size(d.total)
+            ),
+        );
+    }
+    for n in &r.nets {
+        line(
+            "Netzwerk",
+            format!("{} {} {}", n.name, n.mac, n.addrs.join(", ")),
+        );
+    }
+    s
+}
+
+/// `--sysinfo <id> <password>`: prints the host's report and exits.
+/// `run_viewer` must already be connecting. Returns the exit code.
+pub fn cli(sess: &Arc<Session>) -> i32 {
+    let start = Instant::now();
+    while !sess.connected.load(Ordering::Relaxed) {
+        if start.elapsed() > Duration::from_secs(30) {
+            eprintln!("FAIL: {}", sess.viewer_status.lock().unwrap());
+            return 1;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    sess.send_input(Msg::SysInfoGet);
+    let asked = Instant::now();
+    while asked.elapsed() < Duration::from_secs(20) {
+        if let Some(r) = sess.sysinfo.lock().unwrap().take() {
+            print!("{}", text(&r));
+            return 0;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    eprintln!("FAIL: keine Antwort vom Host");
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uptime_reads_like_a_clock() {
+        assert_eq!(uptime_text(59), "0:00 h");
+        assert_eq!(uptime_text(3 * 3600 + 5 * 60), "3:05 h");
+        assert_eq!(uptime_text(86400 + 60), "1 Tag 0:01 h");
+        assert_eq!(uptime_text(3 * 86400 + 4 * 3600 + 5 * 60), "3 Tage 4:05 h");
+    }
+}
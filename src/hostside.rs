@@ -11,9 +11,10 @@ use tokio::sync::mpsc;
 use tokio_tungstenite::tungstenite::Message as WsMsg;
 
 use crate::capture::{self, Next};
-use crate::clip::Clip;
+use crate::clip::{Clip, PasteKeys};
 use crate::crypto::{self, Cipher};
 use crate::encoder::{self, Delta};
+use crate::pool::Pool;
 use crate::input::{Injector, ScreenRect};
 use crate::net;
 use crate::proto::{self, decode, encode, Msg};
@@ -32,6 +33,8 @@ pub struct Profile {
     /// the encoder runs in variable bitrate mode, a still desktop costs
     /// almost nothing.
     pub bitrate: u32,
+    /// Text may go out lossless if the viewer asks for it.
+    pub lossless: bool,
 }
 
 pub const ADMIN: Profile = Profile {
@@ -42,6 +45,7 @@ pub const ADMIN: Profile = Profile {
     // alte 30er-Bremse war verschenkte Fluessigkeit.
     fps: 60,
     bitrate: 12_000_000,
+    lossless: true,
 };
 
 pub const GAME: Profile = Profile {
@@ -50,8 +54,18 @@ pub const GAME: Profile = Profile {
     tile_q: 55,
     fps: 60,
     bitrate: 15_000_000,
+    lossless: false,
 };
 
+/// Unsent bytes above which the link counts as congested: changes go out at
+/// low quality until it has drained.
+const SLOW_BACKLOG: u64 = 512 * 1024;
+/// Unsent bytes below which there is room for refinement tiles.
+const IDLE_BACKLOG: u64 = 64 * 1024;
+/// A congested link stays congested this long after the queue drained, so
+/// the quality does not flip with every frame.
+const SLOW_HOLD: Duration = Duration::from_secs(2);
+
 pub fn profile(mode: u8) -> Profile {
     if mode == proto::MODE_GAME {
         GAME
@@ -102,11 +116,20 @@ async fn host_once(shared: &Arc<Shared>, secret: &str) -> Result<()> {
     let (mut sink, mut stream) = ws.split();
     let (tx, mut rx) = mpsc::unbounded_channel::<WsMsg>();
 
+    let shared_w = shared.clone();
     let writer = tokio::spawn(async move {
         while let Some(m) = rx.recv().await {
+            let n = match &m {
+                WsMsg::Binary(b) => b.len() as u64,
+                _ => 0,
+            };
             if sink.send(m).await.is_err() {
                 break;
             }
+            // the capture loop watches this to notice a slow link
+            let _ = shared_w
+                .backlog
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |b| Some(b.saturating_sub(n)));
         }
     });
 
@@ -225,12 +248,30 @@ struct Session {
     mode: Arc<AtomicU8>,
     monitor: Arc<AtomicU8>,
     force_key: Arc<AtomicBool>,
-    /// The viewer told us it can decode H.264.
-    h264: Arc<AtomicBool>,
+    /// What the viewer announced with `Msg::Caps`.
+    caps: Arc<ViewerCaps>,
     /// Direct UDP path of this session (video only).
     p2p: Option<Arc<crate::p2p::P2p>>,
     /// Speech both ways while the session runs.
     voice: Option<crate::audio::Voice>,
+    /// Recording of this session (`ident::RECORD_HOST`).
+    rec: Option<crate::record::Recorder>,
+    /// What a knocking viewer calls itself; empty if it came with a
+    /// password.
+    peer: String,
+}
+
+/// Capabilities of the connected viewer, read by the capture loop.
+#[derive(Default)]
+struct ViewerCaps {
+    /// It can decode H.264.
+    h264: AtomicBool,
+    /// It can move parts of its canvas (`Msg::CopyRect`).
+    copy: AtomicBool,
+    /// It keeps a tile cache (`Msg::CachePaint` and friends).
+    cache: AtomicBool,
+    /// It wants text lossless (`Msg::SetQuality`).
+    lossless: AtomicBool,
 }
 
 /// The screens this machine could share, in protocol form.
@@ -261,9 +302,11 @@ impl Session {
             mode: Arc::new(AtomicU8::new(proto::MODE_ADMIN)),
             monitor: Arc::new(AtomicU8::new(0)),
             force_key: Arc::new(AtomicBool::new(false)),
-            h264: Arc::new(AtomicBool::new(false)),
+            caps: Arc::new(ViewerCaps::default()),
             p2p: None,
             voice: None,
+            rec: None,
+            peer: String::new(),
         }
     }
 
@@ -365,12 +408,13 @@ impl Session {
                 let key = crypto::session_key(secret, client_pub, salt);
                 let code = crypto::session_code(&key);
                 shared.knock_answer.store(0, Ordering::Relaxed);
+                self.peer = if from.trim().is_empty() {
+                    "Unbekanntes Geraet".to_string()
+                } else {
+                    from.trim().to_string()
+                };
                 *shared.knock.lock().unwrap() = Some(crate::shared::Knock {
-                    from: if from.trim().is_empty() {
-                        "Unbekanntes Geraet".to_string()
-                    } else {
-                        from.trim().to_string()
-                    },
+                    from: self.peer.clone(),
                     code,
                     at: Instant::now(),
                 });
@@ -398,6 +442,9 @@ impl Session {
                     c.open(data)
                         .ok_or_else(|| anyhow!("Entschluesselung fehlgeschlagen"))?
                 };
+                if let Some(r) = self.rec.as_ref() {
+                    r.tap(crate::record::Side::Viewer, &plain);
+                }
                 if let Some(m) = decode(&plain) {
                     match m {
                         Msg::Ping { ts } => {
@@ -430,12 +477,21 @@ impl Session {
                                 p.set_remote(&addrs);
                             }
                         }
-                        Msg::Caps { h264 } => {
-                            let before = self.h264.swap(h264, Ordering::Relaxed);
+                        Msg::Caps { h264, copy, cache } => {
+                            self.caps.copy.store(copy, Ordering::Relaxed);
+                            self.caps.cache.store(cache, Ordering::Relaxed);
+                            let before = self.caps.h264.swap(h264, Ordering::Relaxed);
                             if before != h264 {
                                 self.force_key.store(true, Ordering::Relaxed);
                             }
                         }
+                        Msg::SetQuality { lossless } => {
+                            // a keyframe lets the refinement redo the text
+                            // in the new quality
+                            if self.caps.lossless.swap(lossless, Ordering::Relaxed) != lossless {
+                                self.force_key.store(true, Ordering::Relaxed);
+                            }
+                        }
                         Msg::SetMode { mode } => {
                             self.mode.store(mode, Ordering::Relaxed);
                             *shared.host_peer.lock().unwrap() = if mode == proto::MODE_GAME {
@@ -471,6 +527,13 @@ impl Session {
         shared: &Arc<Shared>,
     ) -> Result<()> {
         *shared.session_code.lock().unwrap() = crypto::session_code(&key);
+        let (peer, how) = if self.peer.is_empty() {
+            ("Zugreifender", "Passwort")
+        } else {
+            (self.peer.as_str(), "Anfrage")
+        };
+        shared.chat.lock().unwrap().start(peer);
+        crate::audit::log(peer, &format!("Sitzung beginnt ({})", how));
                 let cipher = Arc::new(Mutex::new(Cipher::new(&key, true)));
                 tx.send(WsMsg::Binary(vec![crypto::TAG_OK].into()))?;
 
@@ -483,14 +546,33 @@ impl Session {
                     }
                 };
 
+                self.rec = None;
+                if crate::ident::record_sessions() & crate::ident::RECORD_HOST != 0 {
+                    let peer = format!("Sitzung {}", crypto::session_code(&key));
+                    let dir = crate::record::default_dir();
+                    match crate::record::Recorder::start(&dir, &peer, crate::record::Side::Host) {
+                        Ok(r) => {
+                            shared.set_host_status("Diese Sitzung wird aufgezeichnet");
+                            self.rec = Some(r);
+                        }
+                        Err(e) => capture::log_line(&format!("Aufzeichnung: {}", e)),
+                    }
+                }
+
                 // outgoing pipeline: plain proto bytes -> sealed -> websocket.
                 // Video frames take the direct path whenever one is up.
                 let (out_tx, mut out_rx) = mpsc::unbounded_channel::<Vec<u8>>();
                 let c2 = cipher.clone();
                 let tx2 = tx.clone();
                 let p2p_send = p2p.clone();
+                let sh_out = shared.clone();
+                let rec = self.rec.clone();
+                sh_out.backlog.store(0, Ordering::Relaxed);
                 tokio::spawn(async move {
                     while let Some(plain) = out_rx.recv().await {
+                        if let Some(r) = rec.as_ref() {
+                            r.tap(crate::record::Side::Host, &plain);
+                        }
                         if proto::is_video(&plain) {
                             if let Some(p) = p2p_send.as_ref() {
                                 if p.send_msg(&plain).await {
@@ -499,6 +581,7 @@ impl Session {
                             }
                         }
                         let sealed = { c2.lock().unwrap().seal(&plain) };
+                        sh_out.backlog.fetch_add(sealed.len() as u64, Ordering::Relaxed);
                         if tx2.send(WsMsg::Binary(sealed.into())).is_err() {
                             break;
                         }
@@ -563,9 +646,9 @@ impl Session {
                 let mode = self.mode.clone();
                 let mon = self.monitor.clone();
                 let fkey = self.force_key.clone();
-                let h264 = self.h264.clone();
+                let caps = self.caps.clone();
                 std::thread::spawn(move || {
-                    capture_loop(stop, out_tx, screen_cap, shared2, mode, mon, fkey, h264)
+                    capture_loop(stop, out_tx, screen_cap, shared2, mode, mon, fkey, caps)
                 });
 
                 self.cipher = Some(cipher);
@@ -616,6 +699,53 @@ impl Session {
     }
 }
 
+/// Looks the shared and redacted windows up again (they may move, others
+/// may cover them) and puts the result into `mask` and `shared.mask`, where
+/// the input side reads it. If it changed, returns what was hidden before.
+fn refresh_mask(
+    shared: &Shared,
+    names: &mut crate::share::Names,
+    mask: &mut crate::share::Mask,
+) -> Option<Vec<ScreenRect>> {
+    let share = shared.share.lock().unwrap().clone();
+    let rules = shared.redact.lock().unwrap().clone();
+    let m = if share == crate::share::Share::Screen && rules.is_empty() {
+        crate::share::Mask::default()
+    } else {
+        let wins = crate::share::windows_named(names);
+        let mut m = crate::share::mask(&share, &wins, crate::share::focus());
+        m.hide.extend(crate::redact::hidden(&rules, &wins));
+        m
+    };
+    if m == *mask {
+        return None;
+    }
+    *shared.mask.lock().unwrap() = m.clone();
+    Some(std::mem::replace(mask, m).hide)
+}
+
+/// Paints `mask` into the picture, and what `was` hid before the mask
+/// changed as well: a window that moved between the grab and the lookup
+/// is covered where it was and where it is, the frame may show either.
+fn black_out(
+    mask: &crate::share::Mask,
+    was: Option<&[ScreenRect]>,
+    screen: ScreenRect,
+    buf: &mut [u8],
+    w: u32,
+    h: u32,
+    nv12: bool,
+) {
+    match was {
+        Some(was) if !was.is_empty() => {
+            let mut m = mask.clone();
+            m.hide.extend_from_slice(was);
+            m.apply(screen, buf, w, h, nv12);
+        }
+        _ => mask.apply(screen, buf, w, h, nv12),
+    }
+}
+
 /// Streaming resolution for a captured screen (downscale wide screens).
 fn target_size(w: u32, h: u32, max_w: u32) -> (u32, u32) {
     let (tw, th) = if w > max_w {
@@ -630,26 +760,32 @@ fn target_size(w: u32, h: u32, max_w: u32) -> (u32, u32) {
 }
 
 /// Scaled RGB of the current frame: hardware scaler if the backend has one,
-/// otherwise the CPU box filter.
-fn frame_rgb(cap: &mut Box<dyn capture::Backend>, dw: u32, dh: u32) -> Vec<u8> {
+/// otherwise the CPU box filter on all cores.
+fn frame_rgb(cap: &mut Box<dyn capture::Backend>, dw: u32, dh: u32, pool: &Pool) -> Vec<u8> {
     if let Some(b) = cap.scaled(dw, dh, false) {
         return b.to_vec();
     }
     let (buf, w, h, bgra) = cap.frame();
-    encoder::scale_to_rgb_ex(buf, w, h, dw, dh, bgra)
+    encoder::scale_to_rgb_par(buf, w, h, dw, dh, bgra, pool)
 }
 
 /// NV12 of the current frame for the video encoder. The GPU does scaling and
 /// colour conversion in one step; without a hardware scaler we scale on the
 /// CPU and convert afterwards.
-fn frame_nv12(cap: &mut Box<dyn capture::Backend>, dw: u32, dh: u32, out: &mut Vec<u8>) -> bool {
+fn frame_nv12(
+    cap: &mut Box<dyn capture::Backend>,
+    dw: u32,
+    dh: u32,
+    out: &mut Vec<u8>,
+    pool: &Pool,
+) -> bool {
     if let Some(b) = cap.scaled(dw, dh, true) {
         out.clear();
         out.extend_from_slice(b);
         return true;
     }
     let (buf, w, h, bgra) = cap.frame();
-    let rgb = encoder::scale_to_rgb_ex(buf, w, h, dw, dh, bgra);
+    let rgb = encoder::scale_to_rgb_par(buf, w, h, dw, dh, bgra, pool);
     crate::h264::rgb_to_nv12(&rgb, dw, dh, out);
     false
 }
@@ -754,6 +890,7 @@ pub fn capture_selftest(rounds: u32) -> String {
 /// screen: capture, scale, delta encode, once per configured profile.
 pub fn delta_selftest(rounds: u32) -> String {
     let mut out = String::new();
+    let pool = Pool::auto();
     for (name, prof) in [("Fernwartung", ADMIN), ("Spiel", GAME)] {
         let mut cap = match capture::open(true) {
             Some(c) => c,
@@ -763,6 +900,9 @@ pub fn delta_selftest(rounds: u32) -> String {
         let (dw, dh) = target_size(sw, sh, prof.max_w);
         let mut delta = Delta::new();
         delta.set_quality(prof.full_q, prof.tile_q);
+        delta.set_motion(true);
+        delta.set_cache(true);
+        delta.set_lossless(prof.lossless);
 
         let (mut n_cap, mut n_scale, mut n_enc) = (0u128, 0u128, 0u128);
         let mut frames = 0u32;
@@ -770,6 +910,8 @@ pub fn delta_selftest(rounds: u32) -> String {
         let mut bytes = 0usize;
         let mut sent = 0u32;
         let mut keys = 0u32;
+        let mut moves = 0u32;
+        let mut refined = 0usize;
         let t0 = Instant::now();
         for _ in 0..rounds {
             let t = Instant::now();
@@ -783,16 +925,22 @@ pub fn delta_selftest(rounds: u32) -> String {
             }
             n_cap += t.elapsed().as_micros();
             let t1 = Instant::now();
-            let rgb = frame_rgb(&mut cap, dw, dh);
+            let rgb = frame_rgb(&mut cap, dw, dh, &pool);
             n_scale += t1.elapsed().as_micros();
             let t2 = Instant::now();
             let res = delta.encode(&rgb, dw, dh);
             n_enc += t2.elapsed().as_micros();
             frames += 1;
             bytes += res.bytes;
-            if res.msg.is_some() {
+            if res.msg.is_some() || res.copy.is_some() || !res.before.is_empty() {
                 sent += 1;
             }
+            if res.copy.is_some() {
+                moves += 1;
+            }
+            let refine = delta.refine();
+            refined += refine.rects;
+            bytes += refine.bytes;
             if res.keyframe {
                 keys += 1;
             }
@@ -812,15 +960,99 @@ pub fn delta_selftest(rounds: u32) -> String {
             secs
         ));
         out.push_str(&format!(
-            "             capture {:.1} ms | scale {:.1} ms | encode {:.1} ms => {:.1} fps moeglich, {} gesendet ({} Keyframes), {:.1} KB/Frame\n",
+            "             capture {:.1} ms | scale {:.1} ms | encode {:.1} ms => {:.1} fps moeglich, {} gesendet ({} Keyframes, {} verschoben), {:.1} KB/Frame\n",
             n_cap as f32 / f / 1000.0,
             n_scale as f32 / f / 1000.0,
             n_enc as f32 / f / 1000.0,
             1000.0 / ((n_cap + n_scale + n_enc) as f32 / f / 1000.0).max(0.001),
             sent,
             keys,
+            moves,
             bytes as f32 / sent.max(1) as f32 / 1024.0
         ));
+        let cs = delta.cache_stats();
+        out.push_str(&format!(
+            "             Tile-Cache: {} Treffer, {} neu => {:.0} % aus dem Cache | {} Kacheln nachgeschaerft, {:.0} % noch unscharf\n",
+            cs.hits,
+            cs.misses,
+            cs.ratio() * 100.0,
+            refined,
+            delta.low_share() * 100.0
+        ));
+    }
+    out.push_str(&cores_report(rounds));
+    out
+}
+
+/// Raw frames kept for `cores_report`; a 4K frame is 33 MB.
+const CORE_FRAMES: usize = 10;
+
+/// Scale + encode of the very same captured frames with 1, 2, 4 ... threads,
+/// so the speed-up of the worker pool is visible per core count.
+fn cores_report(rounds: u32) -> String {
+    let mut cap = match capture::open(true) {
+        Some(c) => c,
+        None => return String::new(),
+    };
+    let (sw, sh) = cap.size();
+    let (dw, dh) = target_size(sw, sh, ADMIN.max_w);
+    let mut raw: Vec<(Vec<u8>, u32, u32, bool)> = Vec::new();
+    for _ in 0..rounds {
+        if raw.len() >= CORE_FRAMES {
+            break;
+        }
+        match cap.next(200) {
+            Next::Frame => {}
+            Next::Unchanged => continue,
+            Next::Lost => break,
+        }
+        let (buf, w, h, bgra) = cap.frame();
+        raw.push((buf.to_vec(), w, h, bgra));
+    }
+    if raw.len() < 2 {
+        return "Kerne        zu wenige Bilder - bitte waehrenddessen etwas bewegen\n".to_string();
+    }
+    let mut out = format!(
+        "Kerne        {} Bilder {}x{} -> {}x{}, skalieren + kodieren\n",
+        raw.len(),
+        sw,
+        sh,
+        dw,
+        dh
+    );
+    let max = crate::pool::auto_threads();
+    let mut counts = vec![1];
+    while counts[counts.len() - 1] * 2 < max {
+        counts.push(counts[counts.len() - 1] * 2);
+    }
+    if max > 1 {
+        counts.push(max);
+    }
+    let mut base = 0f32;
+    for threads in counts {
+        let pool = Pool::new(threads);
+        let mut delta = Delta::new();
+        delta.set_quality(ADMIN.full_q, ADMIN.tile_q);
+        delta.set_lossless(ADMIN.lossless);
+        delta.set_threads(threads);
+        let t = Instant::now();
+        // three passes: the later ones are deltas against the previous frame
+        for _ in 0..3 {
+            for (buf, w, h, bgra) in &raw {
+                let rgb = encoder::scale_to_rgb_par(buf, *w, *h, dw, dh, *bgra, &pool);
+                delta.encode(&rgb, dw, dh);
+            }
+        }
+        let ms = t.elapsed().as_secs_f32() * 1000.0 / (raw.len() * 3) as f32;
+        if threads == 1 {
+            base = ms;
+        }
+        out.push_str(&format!(
+            "             {} Threads: {:.1} ms/Bild, {:.2}x schneller\n",
+            threads,
+            ms,
+            base / ms.max(0.001)
+        ));
     }
     out
 }
@@ -830,6 +1062,7 @@ pub fn delta_selftest(rounds: u32) -> String {
 /// hardware H.264, side by side.
 pub fn video_selftest(rounds: u32) -> String {
     let mut out = String::new();
+    let pool = Pool::auto();
     for (name, prof) in [("Fernwartung", ADMIN), ("Spiel", GAME)] {
         let mut cap = match capture::open(true) {
             Some(c) => c,
@@ -882,12 +1115,12 @@ pub fn video_selftest(rounds: u32) -> String {
             }
             t_cap += t.elapsed().as_micros();
             let t = Instant::now();
-            let rgb = frame_rgb(&mut cap, dw, dh);
+            let rgb = frame_rgb(&mut cap, dw, dh, &pool);
             t_scale += t.elapsed().as_micros();
             frames += 1;
 
             let t = Instant::now();
-            let gpu_nv12 = frame_nv12(&mut cap, dw, dh, &mut nv12);
+            let gpu_nv12 = frame_nv12(&mut cap, dw, dh, &mut nv12, &pool);
             t_nv += t.elapsed().as_micros();
             if frames == 1 {
                 // verify the colour conversion the GPU did against our own
@@ -999,7 +1232,7 @@ fn capture_loop(
     mode: Arc<AtomicU8>,
     monitor: Arc<AtomicU8>,
     force_key: Arc<AtomicBool>,
-    want_h264: Arc<AtomicBool>,
+    caps: Arc<ViewerCaps>,
 ) {
     // FV_NODELTA / FV_NOSKIP force a full frame every time (benchmarks)
     let force_full = std::env::var("FV_NODELTA").is_ok() || std::env::var("FV_NOSKIP").is_ok();
@@ -1014,9 +1247,10 @@ fn capture_loop(
     let screen_grab = screen.clone();
     let mon_grab = monitor.clone();
     let key_grab = force_key.clone();
-    let h264_grab = want_h264.clone();
+    let caps_grab = caps.clone();
 
     let grabber = std::thread::spawn(move || {
+        let pool = Pool::auto();
         let mut cur_mon = mon_grab.load(Ordering::Relaxed) as usize;
         let mut cap = match capture::open_index(!no_dxgi, cur_mon) {
             Some(c) => c,
@@ -1066,6 +1300,9 @@ fn capture_loop(
 
         let mut last_cursor = (i32::MIN, i32::MIN, false);
         let mut fails = 0u32;
+        // only a window or a region shared, or windows to redact: black
+        let mut mask = crate::share::Mask::default();
+        let mut names = crate::share::Names::new();
         // A picture has to arrive even when nothing moves. Desktop
         // Duplication only reports *changes*, and the lock screen is
         // perfectly still - without this the viewer would stare at a black
@@ -1128,6 +1365,12 @@ fn capture_loop(
                     height: sh,
                 }));
             }
+            let shown = ScreenRect {
+                x: ox,
+                y: oy,
+                w: sw,
+                h: sh,
+            };
             let prof = profile(mode_grab.load(Ordering::Relaxed));
             let budget = Duration::from_millis(1000 / prof.fps.max(1));
             let t0 = Instant::now();
@@ -1141,17 +1384,31 @@ fn capture_loop(
                     let (dw, dh) = target_size(cw, ch, prof.max_w);
                     // With H.264 the GPU scales AND converts to NV12 in one
                     // pass, so no RGB frame is ever built on the CPU.
-                    let (buf, is_nv12) = if h264_grab.load(Ordering::Relaxed) {
+                    let (mut buf, is_nv12) = if caps_grab.h264.load(Ordering::Relaxed) {
                         let mut b = Vec::new();
-                        frame_nv12(&mut cap, dw, dh, &mut b);
+                        frame_nv12(&mut cap, dw, dh, &mut b, &pool);
                         (b, true)
                     } else {
-                        (frame_rgb(&mut cap, dw, dh), false)
+                        (frame_rgb(&mut cap, dw, dh, &pool), false)
                     };
+                    // looked up after the grab, not before: a window that
+                    // moved while we waited is already where the frame has it
+                    let was = refresh_mask(&shared_grab, &mut names, &mut mask);
+                    if was.is_some() {
+                        key_grab.store(true, Ordering::Relaxed);
+                    }
+                    black_out(&mask, was.as_deref(), shown, &mut buf, dw, dh, is_nv12);
                     // channel full = encoder still busy, drop this frame
                     pushed += raw_tx.try_send((buf, dw, dh, is_nv12)).is_ok() as u64;
                 }
                 Next::Unchanged => {
+                    // a still picture, but the focus may have moved or a
+                    // covered window come up; the viewer has to see the new
+                    // area right away
+                    let was = refresh_mask(&shared_grab, &mut names, &mut mask);
+                    if was.is_some() {
+                        key_grab.store(true, Ordering::Relaxed);
+                    }
                     let quiet = last_push.elapsed();
                     let have_pixels = !cap.frame().0.is_empty();
                     // A viewer that asked for a keyframe is staring at a
@@ -1171,13 +1428,14 @@ fn capture_loop(
                         key_grab.store(true, Ordering::Relaxed);
                         let (cw, ch) = cap.size();
                         let (dw, dh) = target_size(cw, ch, prof.max_w);
-                        let (buf, is_nv12) = if h264_grab.load(Ordering::Relaxed) {
+                        let (mut buf, is_nv12) = if caps_grab.h264.load(Ordering::Relaxed) {
                             let mut b = Vec::new();
-                            frame_nv12(&mut cap, dw, dh, &mut b);
+                            frame_nv12(&mut cap, dw, dh, &mut b, &pool);
                             (b, true)
                         } else {
-                            (frame_rgb(&mut cap, dw, dh), false)
+                            (frame_rgb(&mut cap, dw, dh, &pool), false)
                         };
+                        black_out(&mask, was.as_deref(), shown, &mut buf, dw, dh, is_nv12);
                         pushed += raw_tx.try_send((buf, dw, dh, is_nv12)).is_ok() as u64;
                     } else if !have_pixels
                         && !tried_fallback
@@ -1241,6 +1499,8 @@ fn capture_loop(
             // the duplication API does not paint the cursor into the frame,
             // so the viewer gets its position and draws it itself
             let (cx, cy, vis) = cap.cursor();
+            // a pointer outside the shared area gives away where it is
+            let vis = vis && mask.shows(cx, cy);
             if (cx, cy, vis) != last_cursor && (sw > 0 && sh > 0) {
                 last_cursor = (cx, cy, vis);
                 let nx = ((cx - ox) as i64 * 10000 / sw.max(1) as i64) as i32;
@@ -1277,16 +1537,39 @@ fn capture_loop(
     let mut frames = 0u32;
     let mut bytes = 0usize;
     let mut window = Instant::now();
+    let mut slow_until = Instant::now();
 
     while !stop.load(Ordering::Relaxed) {
-        let (pixels, dw, dh, is_nv12) = match raw_rx.recv_timeout(Duration::from_millis(300)) {
+        let backlog = shared.backlog.load(Ordering::Relaxed);
+        if backlog > SLOW_BACKLOG {
+            slow_until = Instant::now() + SLOW_HOLD;
+        }
+        if let Codec::Jpeg(delta) = &mut codec {
+            delta.set_congested(Instant::now() < slow_until);
+        }
+        let (pixels, dw, dh, is_nv12) = match raw_rx.recv_timeout(Duration::from_millis(100)) {
             Ok(v) => v,
-            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Timeout) => {
+                // nothing changed: use the quiet moment to sharpen what is
+                // still blurry from a keyframe or a congested stretch
+                if let Codec::Jpeg(delta) = &mut codec {
+                    if backlog < IDLE_BACKLOG {
+                        let res = delta.refine();
+                        if let Some(msg) = res.msg {
+                            bytes += res.bytes;
+                            if out.send(encode(&msg)).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
             Err(RecvTimeoutError::Disconnected) => break,
         };
         let cur_mode = mode.load(Ordering::Relaxed);
         let prof = profile(cur_mode);
-        let want = want_h264.load(Ordering::Relaxed) && !no_h264 && !force_full;
+        let want = caps.h264.load(Ordering::Relaxed) && !no_h264 && !force_full;
 
         // (re)build the video encoder when the viewer, the resolution or the
         // profile changed; any failure silently falls back to JPEG tiles
@@ -1312,7 +1595,7 @@ fn capture_loop(
                 }
                 Err(e) => {
                     capture::log_line(&format!("h264 aus, bleibe bei JPEG: {}", e));
-                    want_h264.store(false, Ordering::Relaxed);
+                    caps.h264.store(false, Ordering::Relaxed);
                     codec = Codec::Jpeg(Delta::new());
                 }
             }
@@ -1331,6 +1614,9 @@ fn capture_loop(
                 }
                 let rgb = &pixels;
                 delta.set_quality(prof.full_q, prof.tile_q);
+                delta.set_motion(caps.copy.load(Ordering::Relaxed));
+                delta.set_cache(caps.cache.load(Ordering::Relaxed));
+                delta.set_lossless(prof.lossless && caps.lossless.load(Ordering::Relaxed));
                 if key_now {
                     delta.reset();
                 }
@@ -1339,13 +1625,34 @@ fn capture_loop(
                 } else {
                     delta.encode(rgb, dw, dh)
                 };
+                // the move and the cached tiles have to land before the
+                // tiles that fill in the rest, the cache store right after
+                let quiet = res.msg.is_none();
+                if quiet && (res.copy.is_some() || !res.before.is_empty()) {
+                    frames += 1;
+                }
+                let mut sent = true;
+                for m in res.copy.iter().chain(&res.before) {
+                    sent &= out.send(encode(m)).is_ok();
+                }
                 if let Some(msg) = res.msg {
                     frames += 1;
                     bytes += res.bytes;
-                    if out.send(encode(&msg)).is_err() {
-                        break;
+                    sent &= out.send(encode(&msg)).is_ok();
+                }
+                for m in &res.after {
+                    sent &= out.send(encode(m)).is_ok();
+                }
+                if backlog < IDLE_BACKLOG {
+                    let refine = delta.refine();
+                    if let Some(msg) = refine.msg {
+                        bytes += refine.bytes;
+                        sent &= out.send(encode(&msg)).is_ok();
                     }
                 }
+                if !sent {
+                    break;
+                }
             }
             Codec::H264 { enc, nv12, .. } => {
                 if key_now {
@@ -1378,7 +1685,7 @@ fn capture_loop(
                     }
                     Err(e) => {
                         capture::log_line(&format!("h264 encode: {} - zurueck auf JPEG", e));
-                        want_h264.store(false, Ordering::Relaxed);
+                        caps.h264.store(false, Ordering::Relaxed);
                         codec = Codec::Jpeg(Delta::new());
                     }
                 }
@@ -1405,6 +1712,10 @@ fn capture_loop(
                 let mut st = shared.stats.lock().unwrap();
                 st.fps = frames as f32 / secs;
                 st.kbps = (bytes as f32 * 8.0 / 1000.0) / secs;
+                if let Codec::Jpeg(d) = &codec {
+                    st.cache_hits = d.cache_stats().ratio();
+                    st.low_quality = d.low_share();
+                }
             }
             frames = 0;
             bytes = 0;
@@ -1425,6 +1736,7 @@ fn input_loop(
 ) {
     let mut inj = Injector::new();
     let mut clip = Clip::new();
+    let mut keys = PasteKeys::default();
 
     // file transfers of this session use the same encrypted channel
     let send_msg: Arc<dyn Fn(Msg) + Send + Sync> = {
@@ -1434,20 +1746,59 @@ fn input_loop(
         })
     };
     shared.xfers.lock().unwrap().clear();
-    *shared.xfer.lock().unwrap() = Some(crate::xfer::Xfer::new(shared.clone(), send_msg));
+    *shared.xfer.lock().unwrap() = Some(crate::xfer::Xfer::new(
+        shared.clone(),
+        shared.xfers.clone(),
+        send_msg.clone(),
+    ));
+    *shared.chat_out.lock().unwrap() = Some(send_msg.clone());
+    // shells of this session; they end with it
+    let mut terms = crate::term::Host::new(shared.clone(), send_msg.clone());
+    let tunnels = crate::tunnel::Tunnels::new(send_msg.clone());
+    let procs = crate::procs::Host::new(shared.clone(), send_msg.clone());
+    // the viewer's drawings, on top of the shared screen
+    let mut overlay = crate::annot::Overlay::default();
+    // black screen and locked input, while the viewer asks for it
+    let mut curtain = crate::privacy::Curtain::default();
 
     loop {
         if stop.load(Ordering::Relaxed) {
             break;
         }
         match rx.recv_timeout(Duration::from_millis(40)) {
+            Ok(m) if keys.feed(&m, clip.has_files()) => {
+                // Ctrl+V with the viewer's files in its clipboard: fetch them
+                // first, the paste is replayed once they are here
+                if !clip.pasting() {
+                    if let Some(pull) = clip.start_paste(None) {
+                        let _ = out.send(encode(&pull));
+                    }
+                }
+            }
             Ok(m) => {
                 let rect = *screen.lock().unwrap();
                 match m {
-                    Msg::MouseMove { x, y } => inj.mouse_abs(x, y, rect),
+                    Msg::MouseMove { x, y } => {
+                        let clamped = shared.mask.lock().unwrap().clamp(x, y, rect);
+                        if let Some((x, y)) = clamped {
+                            inj.mouse_abs(x, y, rect);
+                        }
+                    }
+                    // relative motion cannot be kept inside an area
+                    Msg::MouseDelta { .. } if shared.mask.lock().unwrap().keep.is_some() => {}
                     Msg::MouseDelta { dx, dy } => inj.mouse_delta(dx, dy),
+                    // no clicks on what the viewer cannot see; letting go
+                    // always goes through, nothing may stick
+                    Msg::MouseButton { down: true, .. } | Msg::Wheel { .. }
+                        if !shared.mask.lock().unwrap().takes_click(inj.cursor()) => {}
                     Msg::MouseButton { button, down } => inj.button(button, down),
                     Msg::Wheel { lines } => inj.wheel(lines),
+                    // keys for other programs than the shared one stay out,
+                    // the special keys as well
+                    Msg::KeyVk { down: true, .. }
+                    | Msg::Key { down: true, .. }
+                    | Msg::Special { .. }
+                        if !shared.mask.lock().unwrap().keys => {}
                     Msg::KeyVk { vk, ext, down } => inj.key_vk(vk, ext, down),
                     Msg::Key { code, named, down } => {
                         match if named {
@@ -1463,14 +1814,65 @@ fn input_loop(
                         let what = inj.special(code);
                         shared.set_host_status(format!("Sondertaste: {}", what));
                     }
-                    Msg::Clipboard { text } => {
-                        if shared.clip_on.load(Ordering::Relaxed) { clip.set(&text); }
+                    m if crate::clip::is_clip_msg(&m) => {
+                        let may_read = shared.rights.load(Ordering::Relaxed)
+                            & crate::ident::RIGHT_FILES_READ
+                            != 0;
+                        let pull = matches!(m, Msg::ClipPull { .. });
+                        if shared.clip_on.load(Ordering::Relaxed) && (may_read || !pull) {
+                            clip.on_msg(m, &shared.xfer, &|reply| {
+                                let _ = out.send(encode(&reply));
+                            });
+                        }
                     }
                     other if crate::xfer::is_file_msg(&other) => {
                         if let Some(x) = shared.xfer.lock().unwrap().as_mut() {
                             x.on_msg(other);
                         }
                     }
+                    other if crate::remotefs::is_request(&other) => {
+                        crate::remotefs::serve(&shared, other, &send_msg);
+                    }
+                    other if crate::term::is_term_msg(&other) => terms.on_msg(other),
+                    other if crate::tunnel::is_tun_msg(&other) => {
+                        if let Msg::TunOpen { host, port, .. } = &other {
+                            shared.set_host_status(format!("Weiterleitung nach {}:{}", host, port));
+                        }
+                        let allow = shared.tunnel_allow.lock().unwrap().clone();
+                        tunnels.on_msg(other, &allow);
+                    }
+                    other if crate::procs::is_proc_msg(&other) => procs.on_msg(other),
+                    other if crate::annot::is_annot_msg(&other) => overlay.on_msg(&other, rect),
+                    Msg::Privacy { on } => {
+                        let was = curtain.is_on();
+                        let now = curtain.set(on);
+                        if now != was {
+                            let peer = shared.chat.lock().unwrap().peer.clone();
+                            let what = if now { "Privatmodus an" } else { "Privatmodus aus" };
+                            crate::audit::log(&peer, what);
+                            shared.set_host_status(what.to_string());
+                        }
+                        send_msg(Msg::Privacy { on: now });
+                    }
+                    other if crate::chat::is_chat_msg(&other) => {
+                        let mut chat = shared.chat.lock().unwrap();
+                        if let Some(ack) = chat.on_msg(other) {
+                            send_msg(ack);
+                            // a line came in: up with the chat window, and a
+                            // balloon for when the screen is busy elsewhere
+                            shared.chat_open.store(true, Ordering::Relaxed);
+                            let text = chat.last_in().unwrap_or_default().to_string();
+                            crate::tray::balloon(&format!("Nachricht von {}", chat.peer), &text);
+                        }
+                    }
+                    Msg::SysInfoGet => {
+                        let send_msg = send_msg.clone();
+                        std::thread::spawn(move || {
+                            send_msg(Msg::SysInfo {
+                                report: crate::hostinfo::gather(),
+                            })
+                        });
+                    }
                     _ => {}
                 }
             }
@@ -1478,16 +1880,42 @@ fn input_loop(
             Err(RecvTimeoutError::Disconnected) => break,
         }
 
-        if let Some(text) = clip.poll().filter(|_| shared.clip_on.load(Ordering::Relaxed)) {
-            if out.send(encode(&Msg::Clipboard { text })).is_err() {
-                break;
+        terms.tick();
+
+        match clip.paste_ready(&shared.xfers) {
+            Some(Ok(paths)) => {
+                if clip.put_files(&paths) {
+                    inj.paste(keys.ctrl_held());
+                }
             }
+            Some(Err(e)) => shared.set_host_status(format!("Einfuegen: {}", e)),
+            None => {}
+        }
+
+        let on = shared.clip_on.load(Ordering::Relaxed);
+        // without read access the viewer does not even learn of copied files
+        let may_read =
+            shared.rights.load(Ordering::Relaxed) & crate::ident::RIGHT_FILES_READ != 0;
+        if clip
+            .poll()
+            .into_iter()
+            .filter(|m| on && (may_read || !matches!(m, Msg::ClipFiles { .. })))
+            .any(|m| out.send(encode(&m)).is_err())
+        {
+            break;
         }
     }
 
     if let Some(mut x) = shared.xfer.lock().unwrap().take() {
         x.shutdown();
     }
+    tunnels.shutdown();
+    // screen and input back before anything else can go wrong
+    curtain.set(false);
+    *shared.chat_out.lock().unwrap() = None;
+    shared.chat_open.store(false, Ordering::Relaxed);
+    let peer = shared.chat.lock().unwrap().peer.clone();
+    crate::audit::log(&peer, "Sitzung endet");
     // never leave keys stuck on the host when a session dies
     inj.release_all();
 }
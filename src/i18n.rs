@@ -198,6 +198,22 @@ const TABLE: &[(&str, &str, &str)] = &[
     ("pwask.pw", "Passwort", "Password"),
     ("pwask.ask", "Anfrage senden", "Send request"),
     ("set.access", "Zugriff", "Access"),
+    ("set.files_read", "Dateien ansehen und herunterladen erlauben", "Allow browsing and downloading files"),
+    ("set.files_read_tip", "Wer verbunden ist, darf die Ordner dieses Computers durchsuchen und Dateien holen.", "Whoever is connected may browse this computer's folders and fetch files."),
+    ("set.files_write", "Dateien ändern erlauben", "Allow changing files"),
+    ("set.files_write_tip", "Hochladen, Umbenennen, Löschen und neue Ordner auf diesem Computer.", "Upload, rename, delete and create folders on this computer."),
+    ("set.shell", "Terminal erlauben", "Allow terminal"),
+    ("set.tunnels", "Weiterleitungen erlauben zu:", "Allow port forwarding to:"),
+    ("set.tunnels_tip", "Ein Ziel pro Zeile als Host:Port, * für jeden Port. Leer: keine Weiterleitung.", "One target per line as host:port, * for any port. Empty: no forwarding."),
+    ("set.redact", "Im Bild immer schwärzen:", "Always black out in the picture:"),
+    ("set.redact_tip", "Fenster, die der Zugreifende nie sieht: ein Muster pro Zeile für Programmname oder Fenstertitel, * und ? als Platzhalter, Groß-/Kleinschreibung egal.", "Windows the person connecting never sees: one pattern per line for the program name or window title, * and ? as wildcards, case does not matter."),
+    ("set.record_viewer", "Eigene Fernwartungen aufzeichnen", "Record my remote sessions"),
+    ("set.record_viewer_tip", "Jede Sitzung zu einem anderen Computer landet als .fvrec unter Videos\\FreeViewer. Abspielen mit --play.", "Every session to another computer is saved as .fvrec in Videos\\FreeViewer. Play it with --play."),
+    ("set.record_host", "Zugriffe auf diesen Computer aufzeichnen", "Record access to this computer"),
+    ("set.record_host_tip", "Bild, Eingaben und Sprache jeder Sitzung auf diesem Computer werden gespeichert.", "Picture, input and voice of every session on this computer are saved."),
+    ("set.procs", "Prozesse beenden erlauben", "Allow ending processes"),
+    ("set.procs_tip", "Verbundene dürfen in der Prozessliste Programme beenden. Ansehen geht immer; Programme starten gehört zum Terminal.", "Connected users may end programs from the process list. Looking is always possible; starting programs comes with the terminal."),
+    ("set.shell_tip", "Verbundene dürfen eine Shell (cmd unter Windows) mit den Rechten dieses Kontos öffnen und aus der Prozessliste Programme starten.", "Connected users may open a shell (cmd on Windows) with the rights of this account and start programs from the process list."),
     ("set.audio", "Ton", "Sound"),
     ("set.look", "Darstellung", "Appearance"),
     ("set.about", "Info", "About"),
@@ -425,6 +441,7 @@ const TABLE: &[(&str, &str, &str)] = &[
     ("dev.last", "Zuletzt", "Last"),
     ("dev.nosel", "Links ein Gerät anklicken.", "Pick a device on the left."),
     // Sitzung
+    ("sess.low_quality", "{} % noch unscharf", "{} % still blurry"),
     ("sess.mic_on", "Mikrofon an", "Microphone on"),
     ("sess.mic_off", "Mikrofon aus", "Microphone off"),
     ("sess.snd_on", "Ton an", "Sound on"),
@@ -447,18 +464,134 @@ const TABLE: &[(&str, &str, &str)] = &[
         "Relative Maus für Ingame-Kameras, komplette Tastatur (Win, Alt+Tab), mehr fps",
         "Relative mouse for in-game cameras, whole keyboard (Win, Alt+Tab), more fps",
     ),
+    ("sess.sharp", "Scharfe Schrift", "Sharp text"),
+    (
+        "sess.sharp_tip",
+        "Text und Oberflächen verlustfrei statt als JPEG - gestochen scharf, braucht etwas mehr Bandbreite",
+        "Text and UI lossless instead of JPEG - crisp, needs a little more bandwidth",
+    ),
     ("sess.screen", "Bildschirm", "Screen"),
     ("sess.files", "Dateien", "Files"),
     ("sess.send_file", "Datei senden …", "Send a file …"),
+    ("sess.send_dir", "Ordner senden …", "Send a folder …"),
+    ("sess.browse", "Dateimanager", "File manager"),
+    ("sess.terminal", "Terminal", "Terminal"),
+    ("sess.tunnels", "Tunnel", "Tunnels"),
+    ("tun.conns", "Verb.", "conn."),
+    ("tun.stop", "Weiterleitung beenden (offene Verbindungen laufen weiter)", "Stop forwarding (open connections keep running)"),
+    ("tun.add", "Weiterleiten", "Forward"),
+    ("tun.tip", "lokaler Port : Ziel : Port, vom anderen Computer aus gesehen. Der andere Computer muss das Ziel freigeben.", "local port : target : port, as seen from the other computer. The other computer has to allow the target."),
+    ("sess.terminal_tip", "Eine Shell auf dem anderen Computer, ohne die Oberfläche zu bedienen.", "A shell on the other computer without driving its desktop."),
+    ("term.title", "Terminal", "Terminal"),
+    ("sess.sysinfo", "Systeminfo", "System info"),
+    ("sess.sysinfo_tip", "Betriebssystem, Hardware, Laufwerke und Netzwerk des anderen Computers.", "Operating system, hardware, drives and network of the other computer."),
+    ("sess.view", "Ansicht", "View"),
+    ("sess.view_fit", "Ins Fenster einpassen", "Fit to window"),
+    ("sess.view_actual", "Originalgröße (1:1)", "Actual size (1:1)"),
+    ("sess.view_letterbox", "Vollbild mit Rändern", "Fullscreen, letterboxed"),
+    ("sess.view_zoom", "Zoom:", "Zoom:"),
+    ("sess.view_pan", "Beim Zoom verschiebt die mittlere Maustaste das Bild.", "When zoomed, drag with the middle mouse button to pan."),
+    ("sess.view_only", "Nur ansehen", "View only"),
+    ("sess.view_only_tip", "Maus und Tastatur gehen nicht an den anderen Rechner. Wird für dieses Gerät gemerkt.", "Mouse and keyboard are not sent to the other computer. Remembered for this device."),
+    ("sess.view_only_on", "Nur ansehen", "View only"),
+    ("sess.draw", "Zeichnen", "Draw"),
+    ("draw.off", "Aus (fernsteuern)", "Off (remote control)"),
+    ("draw.pen", "Stift", "Pen"),
+    ("draw.arrow", "Pfeil", "Arrow"),
+    ("draw.mark", "Textmarker", "Highlighter"),
+    ("draw.laser", "Laserpointer", "Laser pointer"),
+    ("draw.clear", "Alles löschen", "Clear all"),
+    ("share.label", "Freigeben", "Share"),
+    ("share.screen", "Ganzer Bildschirm", "Whole screen"),
+    ("share.region", "Bereich", "Region"),
+    ("share.windows", "Nur ein Fenster:", "Just one window:"),
+    ("share.size", "Größe", "Size"),
+    ("share.tip", "Was der Zugreifende sieht. Alles andere wird schwarz, die Maus bleibt im freigegebenen Teil. Gilt sofort, auch für eine laufende Sitzung.", "What the person connecting sees. Everything else goes black and the mouse stays inside the shared part. Applies at once, also to a running session."),
+    ("sess.privacy", "Privatmodus", "Privacy mode"),
+    ("sess.privacy_tip", "Der Bildschirm des anderen Rechners wird schwarz, seine Tastatur und Maus sind gesperrt. Du siehst und steuerst weiter. Endet spätestens mit der Sitzung.", "The other computer's screen goes black and its keyboard and mouse are locked. You keep seeing and controlling it. Ends with the session at the latest."),
+    ("sess.privacy_on", "Privatmodus aktiv - der Host-Bildschirm ist schwarz", "Privacy mode on - the host's screen is black"),
+    ("privacy.banner_title", "Wartungsarbeiten", "Maintenance in progress"),
+    ("privacy.banner", "Dieser Rechner wird gerade aus der Ferne betreut. Bildschirm, Tastatur und Maus sind bis zum Ende der Sitzung gesperrt.", "This computer is being serviced remotely. Screen, keyboard and mouse are locked until the session ends."),
+    ("draw.tip", "Zeichnungen verblassen nach ein paar Sekunden. Esc schaltet zurück aufs Fernsteuern.", "Drawings fade after a few seconds. Esc switches back to remote control."),
+    ("sess.tab_close", "Diese Sitzung beenden", "End this session"),
+    ("sess.chat", "Chat", "Chat"),
+    ("sess.chat_tip", "Schreib dem Menschen am anderen Computer – er sieht die Nachricht in einem kleinen Fenster.", "Write to the person at the other computer – they see the message in a small window."),
+    ("chat.title", "Chat", "Chat"),
+    ("chat.host", "Host", "Host"),
+    ("chat.host_tip", "Nachrichten mit dem, der gerade auf diesen Computer zugreift.", "Messages with whoever is accessing this computer."),
+    ("chat.me", "Ich", "Me"),
+    ("chat.send", "Senden", "Send"),
+    ("chat.hint", "Nachricht …", "Message …"),
+    ("chat.empty", "Noch keine Nachrichten.", "No messages yet."),
+    ("chat.sent", "gesendet", "sent"),
+    ("chat.delivered", "zugestellt", "delivered"),
+    ("sess.record", "Aufnahme", "Record"),
+    ("sess.record_tip", "Bild, Eingaben und Sprache dieser Sitzung aufzeichnen. Nochmal klicken beendet die Aufnahme.", "Record picture, input and voice of this session. Click again to stop."),
+    ("sess.record_saved", "Aufnahme gespeichert:", "Recording saved:"),
+    ("play.nopic", "Noch kein Bild", "No picture yet"),
+    ("play.inputs", "Eingaben", "Input"),
+    ("play.export", "Als Video speichern", "Save as video"),
+    ("play.export_tip", "Speichert die Aufnahme als AVI-Datei (Motion-JPEG) mit Mauszeiger und Sprache, die jeder Videoplayer abspielt", "Saves the recording as an AVI file (Motion-JPEG) with mouse pointer and voice that any video player plays"),
+    ("play.exported", "Gespeichert: {}", "Saved: {}"),
+    ("sess.procs", "Prozesse", "Processes"),
+    ("sess.procs_tip", "Laufende Programme des anderen Computers, auch wenn sein Bildschirm hängt.", "Running programs of the other computer, even when its screen hangs."),
+    ("proc.title", "Prozesse", "Processes"),
+    ("proc.filter", "Filter", "Filter"),
+    ("proc.cmd", "Befehl, z. B. notepad", "Command, e.g. notepad"),
+    ("proc.start", "Starten", "Start"),
+    ("proc.kill", "Beenden", "End"),
+    ("proc.really", "wirklich beenden? Ungespeichertes geht verloren.", "really end? Unsaved work is lost."),
+    ("proc.cancel", "Abbrechen", "Cancel"),
+    ("proc.wait", "Wird abgefragt …", "Asking …"),
+    ("proc.name", "Name", "Name"),
+    ("proc.pid", "PID", "PID"),
+    ("proc.user", "Benutzer", "User"),
+    ("proc.cpu", "CPU", "CPU"),
+    ("proc.mem", "Speicher", "Memory"),
+    ("info.title", "Systeminfo", "System info"),
+    ("info.refresh", "Aktualisieren", "Refresh"),
+    ("info.wait", "Wird abgefragt …", "Asking …"),
+    ("info.host", "Rechner", "Computer"),
+    ("info.user", "Benutzer", "User"),
+    ("info.os", "System", "System"),
+    ("info.kernel", "Kernel", "Kernel"),
+    ("info.cpu", "Prozessor", "Processor"),
+    ("info.mem", "Speicher", "Memory"),
+    ("info.uptime", "Laufzeit", "Uptime"),
+    ("info.version", "FreeViewer", "FreeViewer"),
+    ("info.disk", "Laufwerk", "Drive"),
+    ("info.free", "frei", "free"),
+    ("info.net", "Netzwerk", "Network"),
+    ("term.start", "Starten", "Start"),
+    ("term.hint", "Klick in das Terminal, dann gehen die Tasten an die Shell des anderen Computers.", "Click into the terminal and the keys go to the shell of the other computer."),
+    ("fm.title", "Dateimanager", "File manager"),
+    ("fm.local", "Dieser Computer", "This computer"),
+    ("fm.remote", "Entfernter Computer", "Remote computer"),
+    ("fm.up", "Eine Ebene höher", "One level up"),
+    ("fm.reload", "Neu laden", "Reload"),
+    ("fm.upload", "Hochladen →", "Upload →"),
+    ("fm.upload_tip", "Datei wählen und einen beschreibbaren Ordner auf dem anderen Computer öffnen.", "Pick a file and open a writable folder on the other computer."),
+    ("fm.download", "← Herunterladen", "← Download"),
+    ("fm.mkdir", "Neuer Ordner", "New folder"),
+    ("fm.rename", "Umbenennen", "Rename"),
+    ("fm.delete", "Löschen", "Delete"),
+    ("fm.delete_sure", "Wirklich löschen?", "Really delete?"),
+    ("fm.paste", "Einfügen", "Paste"),
+    ("fm.paste_tip", "Holt die Dateien aus der Zwischenablage des anderen Computers in diesen Ordner.", "Fetches the files in the other computer's clipboard into this folder."),
+    ("sess.conflict", "Wenn die Datei schon da ist:", "If the file already exists:"),
+    ("sess.keep_both", "beide behalten", "keep both"),
+    ("sess.skip", "überspringen", "skip"),
+    ("sess.overwrite", "überschreiben", "overwrite"),
     ("sess.open_dir", "Empfangsordner öffnen", "Open the download folder"),
     (
         "sess.drop_tip",
-        "Tipp: Dateien einfach ins Fenster ziehen",
-        "Tip: just drag files onto the window",
+        "Tipp: Dateien und Ordner einfach ins Fenster ziehen",
+        "Tip: just drag files or folders onto the window",
     ),
     ("sess.keys", "Tasten senden", "Send keys"),
     ("sess.direct", "direkt", "direct"),
     ("sess.via_relay", "über Relay", "via relay"),
+    ("sess.cache_hits", "Cache {} %", "cache {} %"),
     ("sess.escape", "rechte Strg = raus", "right Ctrl = out"),
     (
         "sess.escape_tip",
@@ -277,6 +277,95 @@ pub fn set_clipboard(on: bool) {
     }
 }
 
+// What a connected viewer may do on this machine besides watching and
+// steering it. Bits, stored as a number in <config dir>/rights.
+
+/// Browse the disks and fetch files and folders.
+pub const RIGHT_FILES_READ: u32 = 1 << 0;
+/// Rename, delete, create folders and upload into any folder.
+pub const RIGHT_FILES_WRITE: u32 = 1 << 1;
+/// Open a shell (remote terminal) under the account the host runs as.
+pub const RIGHT_SHELL: u32 = 1 << 2;
+/// End processes from the process manager (starting them needs `RIGHT_SHELL`).
+pub const RIGHT_PROCS: u32 = 1 << 3;
+/// Without a rights file: looking around is fine, changing is not.
+pub const RIGHTS_DEFAULT: u32 = RIGHT_FILES_READ;
+
+pub fn host_rights() -> u32 {
+    fs::read_to_string(config_dir().join("rights"))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(RIGHTS_DEFAULT)
+}
+
+pub fn set_host_rights(bits: u32) {
+    let _ = fs::create_dir_all(config_dir());
+    let _ = fs::write(config_dir().join("rights"), bits.to_string());
+}
+
+/// Where a viewer's forwarded ports may lead from this machine: one
+/// `host:port` or `host:*` per line in <config dir>/tunnels. No file, no
+/// forwarding.
+pub fn tunnel_allow() -> Vec<String> {
+    fs::read_to_string(config_dir().join("tunnels"))
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+}
+
+pub fn set_tunnel_allow(list: &[String]) {
+    let _ = fs::create_dir_all(config_dir());
+    let _ = fs::write(config_dir().join("tunnels"), list.join("\n"));
+}
+
+/// Windows the host always blacks out, one pattern per line in
+/// <config dir>/redact (see `redact`).
+pub fn redact_rules() -> Vec<String> {
+    fs::read_to_string(config_dir().join("redact"))
+        .unwrap_or_default()
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect()
+}
+
+pub fn set_redact_rules(list: &[String]) {
+    let _ = fs::create_dir_all(config_dir());
+    let _ = fs::write(config_dir().join("redact"), list.join("\n"));
+}
+
+/// Record every session as viewer and/or as host (bits), see `record`.
+/// Stored as a number in <config dir>/record; no file, no recording.
+pub const RECORD_VIEWER: u32 = 1 << 0;
+pub const RECORD_HOST: u32 = 1 << 1;
+
+pub fn record_sessions() -> u32 {
+    fs::read_to_string(config_dir().join("record"))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub fn set_record_sessions(bits: u32) {
+    let _ = fs::create_dir_all(config_dir());
+    let _ = fs::write(config_dir().join("record"), bits.to_string());
+}
+
+/// Rate limit for sending files in bytes per second, 0 = none.
+pub fn xfer_limit() -> u64 {
+    fs::read_to_string(config_dir().join("xfer_limit"))
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+pub fn set_xfer_limit(bytes: u64) {
+    let _ = fs::create_dir_all(config_dir());
+    let _ = fs::write(config_dir().join("xfer_limit"), bytes.to_string());
+}
+
 #[cfg(test)]
 mod config_dir_tests {
     use super::*;
@@ -9,7 +9,7 @@
 use crate::proto;
 
 /// Rectangle of the shared screen inside the virtual desktop (physical px).
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct ScreenRect {
     pub x: i32,
     pub y: i32,
@@ -60,9 +60,10 @@ mod imp {
     use windows::Win32::Foundation::HMODULE;
     use windows::Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW};
     use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    use windows::Win32::Foundation::POINT;
     use windows::Win32::UI::WindowsAndMessaging::{
-        GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
-        SM_YVIRTUALSCREEN,
+        GetCursorPos, GetSystemMetrics, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN,
+        SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
     };
 
     fn send(inputs: &[INPUT]) {
@@ -185,6 +186,13 @@ mod imp {
             mouse(MOUSEEVENTF_WHEEL, 0, 0, lines * 120);
         }
 
+        /// Where the pointer is now, in desktop pixels.
+        pub fn cursor(&self) -> Option<(i32, i32)> {
+            let mut p = POINT::default();
+            unsafe { GetCursorPos(&mut p) }.ok()?;
+            Some((p.x, p.y))
+        }
+
         pub fn key_vk(&mut self, vk: u16, ext: bool, down: bool) {
             if down {
                 self.held.insert(vk);
@@ -343,6 +351,10 @@ impl Injector {
             let _ = e.scroll(-lines, Axis::Vertical);
         }
     }
+    pub fn cursor(&self) -> Option<(i32, i32)> {
+        use enigo::Mouse;
+        self.enigo.as_ref()?.location().ok()
+    }
     pub fn key_vk(&mut self, _vk: u16, _ext: bool, _down: bool) {}
     pub fn release_all(&mut self) {}
     pub fn special(&mut self, _code: u8) -> &'static str {
@@ -373,6 +385,38 @@ impl Injector {
             let _ = e.key(k, dir);
         }
     }
+
+    /// Replays a paste shortcut that was held back; Ctrl (Cmd on the Mac)
+    /// is only pressed when the viewer let go of it in the meantime.
+    pub fn paste(&mut self, ctrl_held: bool) {
+        #[cfg(windows)]
+        {
+            if !ctrl_held {
+                self.key_vk(0x11, false, true);
+            }
+            self.key_vk(0x56, false, true);
+            self.key_vk(0x56, false, false);
+            if !ctrl_held {
+                self.key_vk(0x11, false, false);
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            let modifier = if cfg!(target_os = "macos") {
+                proto::KEY_META
+            } else {
+                proto::KEY_CTRL
+            };
+            if !ctrl_held {
+                self.key_portable(modifier, true, true);
+            }
+            self.key_portable('v' as u32, false, true);
+            self.key_portable('v' as u32, false, false);
+            if !ctrl_held {
+                self.key_portable(modifier, true, false);
+            }
+        }
+    }
 }
 
 fn named_key(code: u32) -> Option<enigo::Key> {
@@ -9,18 +9,24 @@
 //! the session password with FV_PASSWORD.
 
 mod audio;
+mod audit;
 mod brand;
 mod autostart;
+mod avi;
 mod capture;
+mod chat;
 mod chrome;
 mod clip;
 mod crypto;
 mod encoder;
+mod export;
 mod feedback;
 mod h264;
+mod hostinfo;
 mod hostside;
 mod i18n;
 mod account;
+mod annot;
 mod icons;
 mod ident;
 #[cfg(feature = "license")]
@@ -38,16 +44,27 @@ mod meetschirm;
 mod input;
 mod net;
 mod p2p;
+mod player;
+mod pool;
+mod privacy;
+mod procs;
 mod res;
 mod partners;
 mod presence;
 mod pwlist;
 mod proto;
+mod record;
+mod redact;
+mod remotefs;
 mod selftest;
 mod service;
 mod setup;
+mod share;
 mod shared;
 mod theme;
+mod term;
+mod tilecache;
+mod tunnel;
 mod tray;
 mod update;
 mod viewer;
@@ -59,7 +76,7 @@ use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
 use proto::Msg;
-use shared::Shared;
+use shared::{Session, Shared};
 
 pub const DEFAULT_RELAY: &str = "wss://freeviewer.fleitec.com/fv/ws";
 
@@ -1274,8 +1291,8 @@ fn main() -> eframe::Result<()> {
         }
         // Die Sitzungsansicht braucht ein Bild und einmal beide Leisten:
         // im Fenster und schwebend im Vollbild.
-        shared.connected.store(true, Ordering::Relaxed);
-        *shared.remote_size.lock().unwrap() = (1920, 1080);
+        app.tab().sess.connected.store(true, Ordering::Relaxed);
+        *app.tab().sess.remote_size.lock().unwrap() = (1920, 1080);
         for (name, full) in [("Sitzung", false), ("Sitzung Vollbild", true)] {
             app.full = full;
             let input = egui::RawInput {
@@ -1293,7 +1310,7 @@ fn main() -> eframe::Result<()> {
                 ok = false;
             }
         }
-        shared.connected.store(false, Ordering::Relaxed);
+        app.tab().sess.connected.store(false, Ordering::Relaxed);
 
         // ---- Das Meetingfenster. Beitritts-Schirm einmal ueber den echten
         // Weg (mit Geraeteabfrage), das laufende Meeting ueber eine
@@ -1643,10 +1660,43 @@ fn main() -> eframe::Result<()> {
         println!("{}", report);
         return Ok(());
     }
+    //   freeviewer --export <aufnahme> <video.avi> [--fps N] [--voice]:
+    //   Aufnahme als Video speichern
+    if let Some(i) = std::env::args().position(|a| a == "--export") {
+        let args: Vec<String> = std::env::args().skip(i + 1).collect();
+        std::process::exit(export::cli(&args));
+    }
+    //   freeviewer --play <datei>: Aufnahme einer Sitzung abspielen
+    if let Some(i) = std::env::args().position(|a| a == "--play") {
+        let path = std::path::PathBuf::from(std::env::args().nth(i + 1).unwrap_or_default());
+        let rec = match record::Recording::open(&path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("FAIL: {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        };
+        let player = player::Player::new(rec, &path);
+        let options = eframe::NativeOptions {
+            viewport: egui::ViewportBuilder::default()
+                .with_inner_size([1280.0, 800.0])
+                .with_icon(app_icon())
+                .with_title(player.title()),
+            ..Default::default()
+        };
+        return eframe::run_native(
+            crate::brand::NAME,
+            options,
+            Box::new(move |cc| {
+                install_theme(&cc.egui_ctx);
+                Ok(Box::new(player))
+            }),
+        );
+    }
     // in --connect test mode we only act as a viewer (otherwise this process
     // would register the same machine identity and kick the real host offline)
     let viewer_only = std::env::args()
-        .any(|a| a == "--connect" || a == "--inputtest" || a == "--ask");
+        .any(|a| a == "--connect" || a == "--inputtest" || a == "--ask" || a == "--shell" || a == "--sysinfo");
     // Started by the service? Then we ARE the host of this machine.
     let is_agent = std::env::args().any(|a| a == "--agent");
     // Two processes with the same identity would kick each other off the
@@ -1719,7 +1769,27 @@ fn main() -> eframe::Result<()> {
 
     // headless viewer mode for testing:
     //   freeviewer --connect <id> <password> [frames] [--game]
+    //   freeviewer --connect <id> <password> --sendfile a b c [--limit 2M]
+    //   freeviewer --connect <id> <password> -L 13389:127.0.0.1:3389 [-L ...]
     let argv: Vec<String> = std::env::args().collect();
+    //   freeviewer --shell <id> <password>: Terminal des Hosts hier im Terminal
+    if let Some(pos) = argv.iter().position(|a| a == "--shell") {
+        let id = argv.get(pos + 1).cloned().unwrap_or_default();
+        let pw = argv.get(pos + 2).cloned().unwrap_or_default();
+        let sess = Arc::new(Session::new());
+        let (sh, se) = (shared.clone(), sess.clone());
+        rt().spawn(async move { viewer::run_viewer(sh, se, id, pw).await });
+        std::process::exit(term::cli(&sess));
+    }
+    //   freeviewer --sysinfo <id> <password>: Systeminfo des Hosts ausgeben
+    if let Some(pos) = argv.iter().position(|a| a == "--sysinfo") {
+        let id = argv.get(pos + 1).cloned().unwrap_or_default();
+        let pw = argv.get(pos + 2).cloned().unwrap_or_default();
+        let sess = Arc::new(Session::new());
+        let (sh, se) = (shared.clone(), sess.clone());
+        rt().spawn(async move { viewer::run_viewer(sh, se, id, pw).await });
+        std::process::exit(hostinfo::cli(&sess));
+    }
     if let Some(pos) = argv.iter().position(|a| a == "--connect") {
         let id = argv.get(pos + 1).cloned().unwrap_or_default();
         let pw = argv.get(pos + 2).cloned().unwrap_or_default();
@@ -1734,14 +1804,37 @@ fn main() -> eframe::Result<()> {
             .position(|a| a == "--monitor")
             .and_then(|i| argv.get(i + 1))
             .and_then(|s| s.parse().ok());
-        let file_arg: Option<String> = argv
+        //   --sendfile a b c [--limit 2M]: alles in die Warteschlange
+        let files: Vec<String> = argv
             .iter()
             .position(|a| a == "--sendfile")
-            .and_then(|i| argv.get(i + 1))
-            .cloned();
-        let sh = shared.clone();
+            .map(|i| {
+                argv[i + 1..]
+                    .iter()
+                    .take_while(|a| !a.starts_with("--"))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        //   -L lokal:ziel:port, beliebig oft: bleibt verbunden bis zum Abbruch
+        let tunnels: Vec<String> = argv
+            .windows(2)
+            .filter(|w| w[0] == "-L")
+            .map(|w| w[1].clone())
+            .collect();
+        if let Some(l) = argv.iter().position(|a| a == "--limit") {
+            match argv.get(l + 1).and_then(|s| xfer::parse_rate(s)) {
+                Some(r) => shared.xfer_limit.store(r, Ordering::Relaxed),
+                None => {
+                    println!("FAIL: --limit erwartet z. B. 2M oder 500k");
+                    std::process::exit(1);
+                }
+            }
+        }
+        let sess = Arc::new(Session::new());
+        let (sh, se) = (shared.clone(), sess.clone());
         let idc = id.clone();
-        rt().spawn(async move { viewer::run_viewer(sh, idc, pw).await });
+        rt().spawn(async move { viewer::run_viewer(sh, se, idc, pw).await });
         let start = std::time::Instant::now();
         let mut last = 0u64;
         let mut mode_sent = false;
@@ -1751,7 +1844,7 @@ fn main() -> eframe::Result<()> {
         loop {
             std::thread::sleep(Duration::from_millis(200));
             if !mons_printed {
-                let mons = shared.monitors.lock().unwrap().clone();
+                let mons = sess.monitors.lock().unwrap().clone();
                 if !mons.is_empty() {
                     mons_printed = true;
                     for (i, m) in mons.iter().enumerate() {
@@ -1759,62 +1852,91 @@ fn main() -> eframe::Result<()> {
                     }
                 }
             }
-            if !extras_done && shared.connected.load(Ordering::Relaxed) {
+            if !extras_done && sess.connected.load(Ordering::Relaxed) {
                 extras_done = true;
                 if let Some(idx) = mon_arg {
                     println!("switching to monitor {}", idx);
-                    shared.send_input(Msg::SetMonitor { index: idx });
+                    sess.send_input(Msg::SetMonitor { index: idx });
+                }
+                if !tunnels.is_empty() {
+                    let t = sess.tunnels.lock().unwrap().clone();
+                    for spec in &tunnels {
+                        match t.as_ref().map(|t| t.forward(spec)) {
+                            Some(Ok(port)) => println!("forwarding 127.0.0.1:{} ({})", port, spec),
+                            Some(Err(e)) => {
+                                println!("FAIL: {}", e);
+                                std::process::exit(1);
+                            }
+                            None => println!("FAIL: keine Weiterleitung"),
+                        }
+                    }
                 }
-                if let Some(f) = file_arg.clone() {
-                    let path = std::path::PathBuf::from(&f);
-                    match shared.xfer.lock().unwrap().as_mut() {
+                if !files.is_empty() {
+                    match sess.xfer.lock().unwrap().as_mut() {
                         Some(x) => {
-                            println!("sending file {}", f);
-                            x.send_path(path);
+                            for f in &files {
+                                println!("queueing file {}", f);
+                                x.send_path(std::path::PathBuf::from(f));
+                            }
                         }
                         None => println!("FAIL: kein Transfer-Modul"),
                     }
                 }
             }
-            if mon_arg.is_some() && !mon_switched && shared.connected.load(Ordering::Relaxed) {
-                let act = shared.active_monitor.load(Ordering::Relaxed);
+            if mon_arg.is_some() && !mon_switched && sess.connected.load(Ordering::Relaxed) {
+                let act = sess.active_monitor.load(Ordering::Relaxed);
                 if Some(act) == mon_arg {
-                    let (rw, rh) = *shared.remote_size.lock().unwrap();
+                    let (rw, rh) = *sess.remote_size.lock().unwrap();
                     println!("monitor active {} -> {}x{}", act, rw, rh);
                     mon_switched = true;
                 }
             }
-            if file_arg.is_some() {
-                let done = shared
+            if !tunnels.is_empty() {
+                if extras_done && !sess.connected.load(Ordering::Relaxed) {
+                    println!("Verbindung getrennt");
+                    std::process::exit(1);
+                }
+                continue;
+            }
+            if !files.is_empty() {
+                let out: Vec<xfer::Progress> = sess
                     .xfers
                     .lock()
                     .unwrap()
                     .iter()
-                    .find(|x| !x.incoming && x.finished)
-                    .cloned();
-                if let Some(p) = done {
-                    if p.error.is_empty() {
-                        println!("FILE OK: {} ({} Bytes)", p.name, p.done);
-                        std::process::exit(0);
+                    .filter(|x| !x.incoming)
+                    .cloned()
+                    .collect();
+                if out.len() >= files.len() && out.iter().all(|p| p.finished) {
+                    let mut ok = true;
+                    for p in &out {
+                        if p.error.is_empty() {
+                            println!("FILE OK: {} ({} Bytes)", p.name, p.done);
+                        } else {
+                            println!("FILE FAIL: {} - {}", p.name, p.error);
+                            ok = false;
+                        }
                     }
-                    println!("FILE FAIL: {} - {}", p.name, p.error);
-                    std::process::exit(1);
+                    std::process::exit(if ok { 0 } else { 1 });
                 }
-                if start.elapsed() > Duration::from_secs(120) {
+                // mit Limit darf es dauern; steht eine Datei still, gibt der Sender auf
+                if start.elapsed() > Duration::from_secs(120)
+                    && shared.xfer_limit.load(Ordering::Relaxed) == 0
+                {
                     println!("FILE FAIL: Zeitueberschreitung");
                     std::process::exit(1);
                 }
                 continue;
             }
-            if game && !mode_sent && shared.connected.load(Ordering::Relaxed) {
-                shared.send_input(Msg::SetMode {
+            if game && !mode_sent && sess.connected.load(Ordering::Relaxed) {
+                sess.send_input(Msg::SetMode {
                     mode: proto::MODE_GAME,
                 });
-                shared.mode.store(proto::MODE_GAME, Ordering::Relaxed);
+                sess.mode.store(proto::MODE_GAME, Ordering::Relaxed);
                 mode_sent = true;
                 println!("mode -> game");
             }
-            let seq = shared
+            let seq = sess
                 .frame
                 .lock()
                 .unwrap()
@@ -1826,9 +1948,9 @@ fn main() -> eframe::Result<()> {
                 println!("frame {} {}x{}", seq.0, seq.1, seq.2);
             }
             if last >= want {
-                let st = *shared.stats.lock().unwrap();
-                let cur = *shared.remote_cursor.lock().unwrap();
-                let udp = shared.udp_frames.load(Ordering::Relaxed);
+                let st = *sess.stats.lock().unwrap();
+                let cur = *sess.remote_cursor.lock().unwrap();
+                let udp = sess.udp_frames.load(Ordering::Relaxed);
                 println!(
                     "OK: {} Frames in {:.1}s, {:.0} fps, {:.0} kbit/s, {:.0} ms rtt, Cursor {:?}",
                     last,
@@ -1840,7 +1962,7 @@ fn main() -> eframe::Result<()> {
                 );
                 println!(
                     "Transport: {} ({} Bilder direkt per UDP, Rest ueber den Relay)",
-                    if shared.direct.load(Ordering::Relaxed) {
+                    if sess.direct.load(Ordering::Relaxed) {
                         "direkt (P2P)"
                     } else {
                         "Relay"
@@ -1849,13 +1971,13 @@ fn main() -> eframe::Result<()> {
                 );
                 std::process::exit(0);
             }
-            let status = shared.viewer_status.lock().unwrap().clone();
+            let status = sess.viewer_status.lock().unwrap().clone();
             if status.starts_with("Fehler") {
                 println!("FAIL: {}", status);
                 std::process::exit(1);
             }
             if start.elapsed() > Duration::from_secs(30) {
-                println!("FAIL: {}", shared.viewer_status.lock().unwrap());
+                println!("FAIL: {}", sess.viewer_status.lock().unwrap());
                 std::process::exit(1);
             }
         }
@@ -1868,14 +1990,15 @@ fn main() -> eframe::Result<()> {
             .get(pos + 2)
             .and_then(|s| s.parse().ok())
             .unwrap_or(10);
-        let sh = shared.clone();
+        let sess = Arc::new(Session::new());
+        let (sh, se) = (shared.clone(), sess.clone());
         let idc = id.clone();
-        rt().spawn(async move { viewer::run_viewer_ask(sh, idc).await });
+        rt().spawn(async move { viewer::run_viewer_ask(sh, se, idc).await });
         let start = std::time::Instant::now();
         let mut last = 0u64;
         loop {
             std::thread::sleep(Duration::from_millis(200));
-            let seq = shared
+            let seq = sess
                 .frame
                 .lock()
                 .unwrap()
@@ -1891,11 +2014,11 @@ fn main() -> eframe::Result<()> {
                     "OK: {} Frames in {:.1}s, Code {}",
                     last,
                     start.elapsed().as_secs_f32(),
-                    shared.session_code.lock().unwrap()
+                    sess.session_code.lock().unwrap()
                 );
                 std::process::exit(0);
             }
-            let status = shared.viewer_status.lock().unwrap().clone();
+            let status = sess.viewer_status.lock().unwrap().clone();
             if status.starts_with("Fehler") {
                 println!("FAIL: {}", status);
                 std::process::exit(1);
@@ -1912,7 +2035,7 @@ fn main() -> eframe::Result<()> {
         selftest::input_selftest(shared.clone(), &argv, pos);
     }
 
-    vinput::init(shared.clone());
+    vinput::init();
     // Ist der Dienst installiert, macht ER das Update (als SYSTEM, ohne
     // Nachfrage). Oberflaeche und Agent sehen dann nur nach und melden es -
     // sonst fragten beide staendig nach Administrator-Rechten.
@@ -2102,12 +2225,12 @@ fn main() -> eframe::Result<()> {
 
 struct App {
     shared: Arc<Shared>,
+    /// Alle Reiter des Sitzungsfensters, in ihrer Reihenfolge; nie leer.
+    /// Der vordere (`front`) bekommt Eingaben und wird gezeigt.
+    tabs: Vec<Tab>,
+    front: usize,
     partner_id: String,
     partner_pw: String,
-    tex: Option<egui::TextureHandle>,
-    last_seq: u64,
-    last_mods: egui::Modifiers,
-    viewer_task: Option<tokio::task::JoinHandle<()>>,
     hint: String,
     /// Is a permanent password stored for this machine?
     pw_fixed: bool,
@@ -2117,8 +2240,6 @@ struct App {
     remember_pw: bool,
     /// Entry currently being renamed: (id, buffer).
     renaming: Option<(String, String)>,
-    /// Running session: partner id + when it started.
-    session: Option<(String, std::time::Instant)>,
     /// Until when the "how do I get out" hint stays on screen.
     hint_until: Option<std::time::Instant>,
     /// Started into the tray, so the window has to disappear once winit has
@@ -2167,6 +2288,10 @@ struct App {
     folder: String,
     /// Offenes "Geraet hinzufuegen"-Fenster.
     add_dev: Option<AddDev>,
+    /// Freigegebene Ziele fuer Weiterleitungen, eins pro Zeile (Einstellungen).
+    tun_allow: String,
+    /// Die Regeln zum Schwaerzen, wie sie im Textfeld stehen.
+    redact: String,
     edit_dev: Option<DevEdit>,
     /// Wann die Titelleiste zuletzt eingefaerbt wurde.
     caption_tick: std::time::Instant,
@@ -2235,8 +2360,6 @@ struct App {
     bar_pinned: bool,
     /// Wann die Leiste zuletzt beachtet wurde (fuer das Zurueckziehen).
     bar_seen: std::time::Instant,
-    /// Wann das letzte Bild ankam - fuer den Hinweis "kein Bild".
-    frame_at: std::time::Instant,
     /// Konto: angemeldete Sitzung, Eingabefelder, Zustand des Abgleichs.
     acc: Option<account::Session>,
     acc_user: String,
@@ -2288,20 +2411,19 @@ impl App {
     fn new(shared: Arc<Shared>, start_hidden: bool, auto_setup: Option<(String, String)>) -> Self {
         let watch = presence::Watch::new(shared.relay_url.clone());
         watch.start();
+        let tab = Tab::new();
+        vinput::set_session(Some(tab.sess.clone()));
         Self {
             shared,
+            tabs: vec![tab],
+            front: 0,
             partner_id: String::new(),
             partner_pw: String::new(),
-            tex: None,
-            last_seq: 0,
-            last_mods: egui::Modifiers::default(),
-            viewer_task: None,
             hint: String::new(),
             pw_fixed: ident::has_fixed_password(),
             book: partners::Book::load(),
             remember_pw: false,
             renaming: None,
-            session: None,
             hint_until: None,
             start_hidden,
             first_frame: true,
@@ -2327,6 +2449,8 @@ impl App {
             fb_contact: String::new(),
             folder: String::new(),
             add_dev: None,
+            tun_allow: ident::tunnel_allow().join("\n"),
+            redact: ident::redact_rules().join("\n"),
             edit_dev: None,
             caption_tick: std::time::Instant::now() - Duration::from_secs(9),
             shot_n: 0,
@@ -2363,7 +2487,6 @@ impl App {
             bar_w: 700.0,
             bar_pinned: true,
             bar_seen: std::time::Instant::now(),
-            frame_at: std::time::Instant::now(),
             acc: account::load(),
             acc_user: String::new(),
             acc_pass: String::new(),
@@ -2464,20 +2587,25 @@ impl App {
             .filter(|c| c.is_ascii_digit())
             .collect();
         if id.len() < 9 {
-            self.shared
+            self.tab().sess
                 .set_viewer_status(i18n::t("start.bad_id"));
             return;
         }
-        let sh = self.shared.clone();
+        if !self.make_room(&id) {
+            return;
+        }
+        let (sh, se) = (self.shared.clone(), self.tab().sess.clone());
         let pw = self.partner_pw.clone();
-        self.tex = None;
-        self.last_seq = 0;
+        self.tab_mut().tex = None;
+        self.tab_mut().last_seq = 0;
         self.book.started(&id, &pw, self.remember_pw);
-        self.session = Some((id.clone(), std::time::Instant::now()));
+        self.tab_mut().session = Some((id.clone(), std::time::Instant::now()));
+        self.load_view(&id);
         self.hint_until = Some(std::time::Instant::now() + Duration::from_secs(8));
-        self.shared.mode.store(proto::MODE_ADMIN, Ordering::Relaxed);
-        self.viewer_task = Some(rt().spawn(async move {
-            viewer::run_viewer(sh, id, pw).await;
+        self.tab().sess.mode.store(proto::MODE_ADMIN, Ordering::Relaxed);
+        self.tab().sess.connecting.store(true, Ordering::Relaxed);
+        self.tab_mut().viewer_task = Some(rt().spawn(async move {
+            viewer::run_viewer(sh, se, id, pw).await;
         }));
     }
 
@@ -2494,60 +2622,195 @@ impl App {
             .filter(|c| c.is_ascii_digit())
             .collect();
         if id.len() < 9 {
-            self.shared
+            self.tab().sess
                 .set_viewer_status(i18n::t("start.bad_id"));
             return;
         }
-        let sh = self.shared.clone();
-        self.tex = None;
-        self.last_seq = 0;
+        if !self.make_room(&id) {
+            return;
+        }
+        let (sh, se) = (self.shared.clone(), self.tab().sess.clone());
+        self.tab_mut().tex = None;
+        self.tab_mut().last_seq = 0;
         self.book.started(&id, "", false);
-        self.session = Some((id.clone(), std::time::Instant::now()));
+        self.tab_mut().session = Some((id.clone(), std::time::Instant::now()));
+        self.load_view(&id);
         self.hint_until = Some(std::time::Instant::now() + Duration::from_secs(8));
-        self.shared.mode.store(proto::MODE_ADMIN, Ordering::Relaxed);
-        self.viewer_task = Some(rt().spawn(async move {
-            viewer::run_viewer_ask(sh, id).await;
+        self.tab().sess.mode.store(proto::MODE_ADMIN, Ordering::Relaxed);
+        self.tab().sess.connecting.store(true, Ordering::Relaxed);
+        self.tab_mut().viewer_task = Some(rt().spawn(async move {
+            viewer::run_viewer_ask(sh, se, id).await;
         }));
     }
 
+    /// Ansicht und "Nur ansehen" so, wie sie beim letzten Mal fuer dieses
+    /// Geraet eingestellt waren.
+    fn load_view(&mut self, id: &str) {
+        let p = self.book.get(id).cloned().unwrap_or_default();
+        self.tab_mut().scale = p.scale;
+        self.tab_mut().view_only = p.view_only;
+        self.tab_mut().pan = egui::Vec2::ZERO;
+    }
+
     /// Books the time of a finished session into the address book.
     fn close_session(&mut self) {
         self.full = false;
-        if let Some((id, started)) = self.session.take() {
+        if let Some((id, started)) = self.tab_mut().session.take() {
             self.book.ended(&id, started.elapsed().as_secs());
         }
         self.hint_until = None;
     }
 
+    /// Ends the session in front; another tab, if there is one, takes its
+    /// place.
     fn stop_session(&mut self) {
         self.close_session();
+        self.release_input();
+        if let Some(t) = self.tab_mut().viewer_task.take() {
+            t.abort();
+        }
+        self.tab().sess.connected.store(false, Ordering::Relaxed);
+        self.tab().sess.connecting.store(false, Ordering::Relaxed);
+        *self.tab().sess.input_tx.lock().unwrap() = None;
+        *self.tab().sess.frame.lock().unwrap() = None;
+        self.tab().sess.set_viewer_status("Getrennt");
+        self.tab_mut().tex = None;
+        self.tab_mut().last_seq = 0;
+        self.drop_tab();
+    }
+
+    /// Lets go of the remote keyboard: no grab any more, and the modifiers
+    /// that might still be held down on the remote side come up. In view
+    /// only mode nothing was sent, so nothing has to come up either.
+    fn release_input(&mut self) {
         vinput::set_active(false);
-        // release modifiers that might still be held down on the remote side
+        self.tab_mut().last_mods = egui::Modifiers::default();
+        if self.tab().view_only {
+            return;
+        }
         for code in [proto::KEY_SHIFT, proto::KEY_CTRL, proto::KEY_ALT] {
-            self.shared.send_input(Msg::Key {
+            self.tab().sess.send_input(Msg::Key {
                 code,
                 named: true,
                 down: false,
             });
         }
-        if let Some(t) = self.viewer_task.take() {
-            t.abort();
+    }
+
+    /// Der Reiter vorn.
+    fn tab(&self) -> &Tab {
+        &self.tabs[self.front]
+    }
+
+    fn tab_mut(&mut self) -> &mut Tab {
+        &mut self.tabs[self.front]
+    }
+
+    /// Mit wem die Sitzung im Reiter `i` verbunden ist.
+    fn tab_id(&self, i: usize) -> Option<&str> {
+        self.tabs[i].session.as_ref().map(|(id, _)| id.as_str())
+    }
+
+    fn tab_of(&self, id: &str) -> Option<usize> {
+        (0..self.tabs.len()).find(|&i| self.tab_id(i) == Some(id))
+    }
+
+    /// Holt den Reiter `i` nach vorn. Die Sitzung, die bisher vorn war,
+    /// laeuft im Hintergrund weiter, bekommt aber keine Eingaben mehr.
+    fn show_tab(&mut self, i: usize) {
+        if i == self.front || i >= self.tabs.len() {
+            return;
         }
-        self.shared.connected.store(false, Ordering::Relaxed);
-        self.shared.connecting.store(false, Ordering::Relaxed);
-        *self.shared.input_tx.lock().unwrap() = None;
-        *self.shared.frame.lock().unwrap() = None;
-        self.shared.set_viewer_status("Getrennt");
-        self.tex = None;
-        self.last_seq = 0;
-        self.last_mods = egui::Modifiers::default();
+        self.release_input();
+        self.front = i;
+        vinput::set_session(Some(self.tab().sess.clone()));
+    }
+
+    /// Die Sitzung vorn ist vorbei: ihr Reiter geht zu, der Nachbar kommt
+    /// nach vorn. Der letzte Reiter bleibt stehen.
+    fn drop_tab(&mut self) {
+        if self.tabs.len() < 2 {
+            return;
+        }
+        let old = self.front;
+        self.show_tab(if old + 1 < self.tabs.len() { old + 1 } else { old - 1 });
+        self.tabs.remove(old);
+        if self.front > old {
+            self.front -= 1;
+        }
+    }
+
+    /// Beendet die Sitzung im Reiter `i`; vorn bleibt, was vorn war.
+    fn close_tab(&mut self, i: usize) {
+        let back = self.front;
+        self.show_tab(i);
+        self.stop_session();
+        if back != i {
+            self.show_tab(if back > i { back - 1 } else { back });
+        }
+    }
+
+    /// Name einer Gegenstelle fuer Reiter und Fenstertitel.
+    fn tab_label(&self, id: &str) -> String {
+        match self.book.get(id) {
+            Some(e) => self.dev_label(e),
+            None => partners::pretty_id(id),
+        }
+    }
+
+    /// Reiterleiste ueber der Sitzung, sobald mehr als eine laeuft: ein
+    /// Reiter pro Gegenstelle mit Punkt fuer "verbunden" und x zum Beenden.
+    fn tab_strip(&mut self, ui: &mut egui::Ui, a: &mut BarActs) {
+        for i in 0..self.tabs.len() {
+            let Some(id) = self.tab_id(i).map(str::to_string) else {
+                continue;
+            };
+            let sess = &self.tabs[i].sess;
+            dot(ui, sess.connected.load(Ordering::Relaxed));
+            let status = sess.viewer_status.lock().unwrap().clone();
+            if ui
+                .selectable_label(i == self.front, self.tab_label(&id))
+                .on_hover_text(status)
+                .clicked()
+            {
+                a.tab = Some(i);
+            }
+            if ui
+                .small_button("\u{d7}")
+                .on_hover_text(i18n::t("sess.tab_close"))
+                .clicked()
+            {
+                a.close_tab = Some(i);
+            }
+            ui.separator();
+        }
+    }
+
+    /// Macht vorn Platz fuer eine Sitzung mit `id`: ein neuer Reiter, wenn
+    /// schon eine laeuft, sonst eine frische `Session`. Ist `id` schon in
+    /// einem Reiter verbunden, kommt nur der nach vorn (false).
+    fn make_room(&mut self, id: &str) -> bool {
+        if let Some(i) = self.tab_of(id) {
+            self.show_tab(i);
+            return false;
+        }
+        if self.tab().session.is_some() {
+            self.tabs.push(Tab::new());
+            self.show_tab(self.tabs.len() - 1);
+        } else {
+            self.tab_mut().sess = Arc::new(Session::new());
+            vinput::set_session(Some(self.tab().sess.clone()));
+        }
+        true
     }
 
     fn set_mode(&mut self, mode: u8) {
-        self.shared.mode.store(mode, Ordering::Relaxed);
-        self.shared.send_input(Msg::SetMode { mode });
+        self.tab().sess.mode.store(mode, Ordering::Relaxed);
+        self.tab().sess.send_input(Msg::SetMode { mode });
         if mode == proto::MODE_GAME {
-            vinput::set_active(true);
+            // "Nur ansehen": kein Griff nach Maus und Tastatur, auch nicht
+            // bis zum naechsten Bild
+            vinput::set_active(!self.tab().view_only);
             self.hint =
                 "Spielmodus: Maus + Tastatur werden komplett uebertragen. Rechte Strg = freigeben."
                     .to_string();
@@ -2603,7 +2866,16 @@ impl App {
     }
 
     /// Files dropped onto the window go to the other side of the session.
-    fn handle_drops(&mut self, ctx: &egui::Context) {
+    /// Abgelegte Dateien gehen an die Sitzung des Fensters: im
+    /// Sitzungsfenster an den Reiter vorn, im Hauptfenster an die eingehende
+    /// Sitzung, sonst ebenfalls an den Reiter vorn.
+    fn handle_drops(&mut self, ctx: &egui::Context, session_window: bool) {
+        let (sh, se) = (self.shared.clone(), self.tab().sess.clone());
+        let engine = if !session_window && sh.xfer.lock().unwrap().is_some() {
+            &sh.xfer
+        } else {
+            &se.xfer
+        };
         // Solange etwas ueber dem Fenster haengt: zeigen, dass hier abgelegt
         // werden darf. Ohne das raet man, ob Ziehen und Ablegen geht.
         if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
@@ -2620,7 +2892,7 @@ impl App {
                 egui::Stroke::new(2.0, p.accent),
                 egui::StrokeKind::Inside,
             );
-            let live = self.shared.xfer.lock().unwrap().is_some();
+            let live = engine.lock().unwrap().is_some();
             pt.text(
                 screen.center(),
                 egui::Align2::CENTER_CENTER,
@@ -2643,7 +2915,7 @@ impl App {
         if files.is_empty() {
             return;
         }
-        let mut guard = self.shared.xfer.lock().unwrap();
+        let mut guard = engine.lock().unwrap();
         match guard.as_mut() {
             Some(x) => {
                 let n = files.len();
@@ -2663,7 +2935,8 @@ impl App {
             .set_title("Datei(en) an die Gegenstelle senden")
             .pick_files();
         if let Some(files) = files {
-            let mut guard = self.shared.xfer.lock().unwrap();
+            let sess = self.tab().sess.clone();
+            let mut guard = sess.xfer.lock().unwrap();
             if let Some(x) = guard.as_mut() {
                 for p in files {
                     x.send_path(p);
@@ -2674,6 +2947,385 @@ impl App {
         }
     }
 
+    fn pick_dir_and_send(&mut self) {
+        let dir = rfd::FileDialog::new()
+            .set_title("Ordner an die Gegenstelle senden")
+            .pick_folder();
+        if let Some(dir) = dir {
+            let sess = self.tab().sess.clone();
+            let mut guard = sess.xfer.lock().unwrap();
+            if let Some(x) = guard.as_mut() {
+                x.send_path(dir);
+            } else {
+                self.hint = "Keine aktive Sitzung".to_string();
+            }
+        }
+    }
+
+    /// Dateimanager geht auf: links der Empfangsordner, rechts die oberste
+    /// Ebene des Hosts (oder wo man zuletzt war).
+    fn fm_open(&mut self) {
+        if self.tab().fm.local.as_os_str().is_empty() {
+            let dir = self.shared.drop_dir.lock().unwrap().clone();
+            self.tab_mut().fm.local = dir;
+        }
+        self.fm_local_reload();
+        let m = self.tab().sess.fs.lock().unwrap().refresh();
+        self.tab().sess.send_input(m);
+    }
+
+    fn fm_local_reload(&mut self) {
+        let fm = &mut self.tab_mut().fm;
+        match remotefs::read_dir_sorted(&fm.local) {
+            Ok(l) => {
+                fm.local_list = l;
+                fm.local_err.clear();
+            }
+            Err(e) => {
+                fm.local_list.clear();
+                fm.local_err = format!("{}", e);
+            }
+        }
+        self.tab_mut().fm.sel_local = None;
+    }
+
+    /// Zwei Spalten: dieser Rechner | Host. Die Bytes laufen ueber die
+    /// normale Dateiuebertragung, sie erscheinen unten in der Liste.
+    fn files_ui(&mut self, ui: &mut egui::Ui) {
+        let remote = {
+            let b = self.tab().sess.fs.lock().unwrap();
+            (
+                b.path.clone(),
+                b.parent.clone(),
+                b.entries.clone(),
+                b.write,
+                b.status.clone(),
+                b.busy,
+                b.info.clone(),
+            )
+        };
+        let (rpath, rparent, rentries, write, status, busy, info) = remote;
+        let mut send: Vec<Msg> = Vec::new();
+        let mut upload: Option<std::path::PathBuf> = None;
+        // eingefuegte Dateien sind da: links neu einlesen
+        let pasted = self.tab().sess.clip_pasted.load(Ordering::Relaxed);
+        if pasted != self.tab().fm.pasted {
+            self.tab_mut().fm.pasted = pasted;
+            self.fm_local_reload();
+        }
+        let clip_files = self.tab().sess.clip_files.lock().unwrap().len();
+
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(i18n::t("fm.title")).strong());
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("x").clicked() {
+                    self.tab_mut().fm.open = false;
+                }
+            });
+        });
+        ui.columns(2, |cols| {
+            // ---- links: dieser Rechner
+            let ui = &mut cols[0];
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(i18n::t("fm.local")).strong());
+                if ui.small_button("..").on_hover_text(i18n::t("fm.up")).clicked() {
+                    if let Some(p) = self.tab().fm.local.parent() {
+                        self.tab_mut().fm.local = p.to_path_buf();
+                        self.fm_local_reload();
+                    }
+                }
+                ui.label(egui::RichText::new(self.tab().fm.local.to_string_lossy()).weak());
+            });
+            if !self.tab().fm.local_err.is_empty() {
+                ui.colored_label(egui::Color32::from_rgb(230, 120, 120), &self.tab().fm.local_err);
+            }
+            let mut go: Option<std::path::PathBuf> = None;
+            egui::ScrollArea::vertical()
+                .id_salt("fm_local")
+                .max_height(ui.available_height() - 30.0)
+                .show(ui, |ui| {
+                    let fm = &mut self.tab_mut().fm;
+                    for e in &fm.local_list {
+                        let sel = fm.sel_local.as_deref() == Some(e.name.as_str());
+                        let r = fm_row(ui, e, sel);
+                        if r.clicked() {
+                            fm.sel_local = Some(e.name.clone());
+                        }
+                        if r.double_clicked() && e.dir {
+                            go = Some(fm.local.join(&e.name));
+                        }
+                    }
+                });
+            if let Some(p) = go {
+                self.tab_mut().fm.local = p;
+                self.fm_local_reload();
+            }
+            ui.horizontal(|ui| {
+                let can = self.tab().fm.sel_local.is_some() && write && !rpath.is_empty();
+                if ui
+                    .add_enabled(can, egui::Button::new(i18n::t("fm.upload")))
+                    .on_disabled_hover_text(i18n::t("fm.upload_tip"))
+                    .clicked()
+                {
+                    if let Some(n) = &self.tab().fm.sel_local {
+                        upload = Some(self.tab().fm.local.join(n));
+                    }
+                }
+                // was auf dem Host kopiert wurde, landet im Ordner links
+                if clip_files > 0
+                    && ui
+                        .button(i18n::t("fm.paste"))
+                        .on_hover_text(format!("{} ({})", i18n::t("fm.paste_tip"), clip_files))
+                        .clicked()
+                {
+                    *self.tab().sess.clip_paste_to.lock().unwrap() = Some(self.tab().fm.local.clone());
+                }
+            });
+
+            // ---- rechts: der Host
+            let ui = &mut cols[1];
+            ui.horizontal(|ui| {
+                ui.label(egui::RichText::new(i18n::t("fm.remote")).strong());
+                if let Some(parent) = &rparent {
+                    if ui.small_button("..").on_hover_text(i18n::t("fm.up")).clicked() {
+                        send.push(self.tab().sess.fs.lock().unwrap().list(parent));
+                        self.tab_mut().fm.sel_remote = None;
+                    }
+                }
+                if ui.small_button(i18n::t("fm.reload")).clicked() {
+                    send.push(self.tab().sess.fs.lock().unwrap().refresh());
+                }
+                if busy {
+                    ui.spinner();
+                }
+                ui.label(egui::RichText::new(&rpath).weak());
+            });
+            egui::ScrollArea::vertical()
+                .id_salt("fm_remote")
+                .max_height(ui.available_height() - 58.0)
+                .show(ui, |ui| {
+                    for e in &rentries {
+                        let sel = self.tab().fm.sel_remote.as_deref() == Some(e.name.as_str());
+                        let r = fm_row(ui, e, sel);
+                        let full = remotefs::join(&rpath, &e.name);
+                        if r.clicked() {
+                            self.tab_mut().fm.sel_remote = Some(e.name.clone());
+                            self.tab_mut().fm.name = e.name.clone();
+                            self.tab_mut().fm.del_armed = false;
+                            send.push(self.tab().sess.fs.lock().unwrap().stat(&full));
+                        }
+                        if r.double_clicked() && e.dir {
+                            send.push(self.tab().sess.fs.lock().unwrap().list(&full));
+                            self.tab_mut().fm.sel_remote = None;
+                        }
+                    }
+                });
+            let sel = self
+                .tab()
+                .fm
+                .sel_remote
+                .clone()
+                .map(|n| remotefs::join(&rpath, &n));
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(sel.is_some(), egui::Button::new(i18n::t("fm.download")))
+                    .clicked()
+                {
+                    if let Some(s) = &sel {
+                        let into = self.tab().fm.local.clone();
+                        send.push(self.tab().sess.fs.lock().unwrap().get(s, into));
+                    }
+                }
+                ui.add_enabled_ui(write && !rpath.is_empty(), |ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.tab_mut().fm.name).desired_width(120.0));
+                    if ui.button(i18n::t("fm.mkdir")).clicked() && !self.tab().fm.name.trim().is_empty() {
+                        let p = remotefs::join(&rpath, self.tab().fm.name.trim());
+                        send.push(self.tab().sess.fs.lock().unwrap().mkdir(&p));
+                    }
+                    if let Some(s) = &sel {
+                        if ui.button(i18n::t("fm.rename")).clicked()
+                            && !self.tab().fm.name.trim().is_empty()
+                        {
+                            let to = remotefs::join(&rpath, self.tab().fm.name.trim());
+                            send.push(self.tab().sess.fs.lock().unwrap().rename(s, &to));
+                            self.tab_mut().fm.sel_remote = None;
+                        }
+                        let label = if self.tab().fm.del_armed {
+                            i18n::t("fm.delete_sure")
+                        } else {
+                            i18n::t("fm.delete")
+                        };
+                        if ui.button(label).clicked() {
+                            if self.tab().fm.del_armed {
+                                send.push(self.tab().sess.fs.lock().unwrap().delete(s));
+                                self.tab_mut().fm.sel_remote = None;
+                            }
+                            self.tab_mut().fm.del_armed = !self.tab().fm.del_armed;
+                        }
+                    }
+                });
+            });
+            if !status.is_empty() {
+                ui.colored_label(egui::Color32::from_rgb(230, 120, 120), &status);
+            } else if let Some(i) = &info {
+                if self.tab().fm.sel_remote.as_deref() == Some(i.name.as_str()) && !i.dir {
+                    ui.label(egui::RichText::new(human_size(i.size)).weak());
+                }
+            }
+        });
+
+        for m in send {
+            self.tab().sess.send_input(m);
+        }
+        if let Some(p) = upload {
+            let mut guard = self.tab().sess.xfer.lock().unwrap();
+            if let Some(x) = guard.as_mut() {
+                // erst das Ziel ansagen, dann senden - beides auf demselben Weg
+                let id = x.reserve_id();
+                self.tab().sess.send_input(Msg::FsPut { id, dir: rpath.clone() });
+                x.send_path_as(id, p);
+            }
+        }
+    }
+
+    /// Prozesse des Hosts: Kopfzeile, darunter Filter, Starten und die
+    /// Tabelle. Solange sie offen ist, fragt sie alle paar Sekunden neu.
+    fn procs_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.strong(i18n::t("proc.title"));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("x").clicked() {
+                    self.tab_mut().procs_open = false;
+                }
+            });
+        });
+        let send = procs::widget(ui, &mut self.tab_mut().sess.procs.lock().unwrap());
+        for m in send {
+            self.tab().sess.send_input(m);
+        }
+    }
+
+    /// Chat mit dem Menschen am Host: Kopfzeile, Verlauf, Eingabezeile.
+    fn chat_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.strong(i18n::t("chat.title"));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("x").clicked() {
+                    self.tab_mut().chat_open = false;
+                }
+            });
+        });
+        ui.separator();
+        let other = i18n::t("chat.host");
+        let send = chat::widget(ui, &mut self.tab_mut().sess.chat.lock().unwrap(), other);
+        if let Some(m) = send {
+            self.tab().sess.send_input(m);
+        }
+    }
+
+    /// Systeminfo des Hosts als Tabelle; kommt auf Anfrage, daher der
+    /// Knopf zum Auffrischen.
+    fn sysinfo_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.strong(i18n::t("info.title"));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("x").clicked() {
+                    self.tab_mut().info_open = false;
+                }
+                if ui.small_button(i18n::t("info.refresh")).clicked() {
+                    self.tab().sess.send_input(Msg::SysInfoGet);
+                }
+            });
+        });
+        ui.separator();
+        let report = self.tab().sess.sysinfo.lock().unwrap().clone();
+        let Some(r) = report else {
+            ui.label(egui::RichText::new(i18n::t("info.wait")).weak());
+            return;
+        };
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("fv_sysinfo_grid")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    let row = |ui: &mut egui::Ui, k: &str, v: String| {
+                        ui.label(egui::RichText::new(i18n::t(k)).weak());
+                        ui.label(v);
+                        ui.end_row();
+                    };
+                    row(ui, "info.host", r.host.clone());
+                    row(ui, "info.user", r.user.clone());
+                    row(ui, "info.os", r.os.clone());
+                    row(ui, "info.kernel", r.kernel.clone());
+                    row(ui, "info.cpu", format!("{} ({} \u{00d7})", r.cpu, r.cores));
+                    row(
+                        ui,
+                        "info.mem",
+                        format!("{} / {}", human_size(r.mem_used), human_size(r.mem_total)),
+                    );
+                    row(ui, "info.uptime", hostinfo::uptime_text(r.uptime));
+                    row(ui, "info.version", r.version.clone());
+                    for d in &r.disks {
+                        row(
+                            ui,
+                            "info.disk",
+                            format!(
+                                "{} ({})\n{} / {} {}",
+                                d.mount,
+                                d.fs,
+                                human_size(d.free),
+                                human_size(d.total),
+                                i18n::t("info.free")
+                            ),
+                        );
+                    }
+                    for n in r.nets.iter().filter(|n| !n.addrs.is_empty()) {
+                        row(
+                            ui,
+                            "info.net",
+                            format!("{}  {}\n{}", n.name, n.mac, n.addrs.join("\n")),
+                        );
+                    }
+                });
+        });
+    }
+
+    /// Shell des Hosts: Kopfzeile mit Neustart/Schliessen, darunter der
+    /// Bildschirm. Tastatur geht an die Shell, solange er den Fokus hat.
+    fn term_ui(&mut self, ui: &mut egui::Ui) {
+        let (running, ended) = {
+            let t = self.tab().sess.term.lock().unwrap();
+            (t.running, t.ended.clone())
+        };
+        let mut send: Vec<Msg> = Vec::new();
+        ui.horizontal(|ui| {
+            ui.label(egui::RichText::new(i18n::t("term.title")).strong());
+            if let Some(e) = &ended {
+                ui.label(egui::RichText::new(e).weak());
+            } else if running {
+                ui.label(egui::RichText::new(i18n::t("term.hint")).weak().size(11.0));
+            }
+            if !running && ui.small_button(i18n::t("term.start")).clicked() {
+                send.push(self.tab().sess.term.lock().unwrap().open(24, 80));
+            }
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("x").clicked() {
+                    self.tab_mut().term_open = false;
+                    send.extend(self.tab().sess.term.lock().unwrap().close());
+                }
+            });
+        });
+        // nach dem Ende bleibt die letzte Ausgabe stehen
+        if running || ended.is_some() {
+            send.extend(term::widget(ui, &mut self.tab_mut().sess.term.lock().unwrap()));
+            // Ausgabe kommt ohne Eingabe, also regelmaessig neu zeichnen
+            ui.ctx().request_repaint_after(std::time::Duration::from_millis(50));
+        }
+        for m in send {
+            self.tab().sess.send_input(m);
+        }
+    }
+
     fn open_drop_dir(&self) {
         let dir = self.shared.drop_dir.lock().unwrap().clone();
         let _ = std::fs::create_dir_all(&dir);
@@ -2683,14 +3335,23 @@ impl App {
         }
     }
 
-    /// Bottom bar with every running/finished transfer of this session.
-    fn transfer_ui(&mut self, ctx: &egui::Context) {
-        let list = self.shared.xfers.lock().unwrap().clone();
+    /// Bottom bar with every running/finished transfer of one session: the
+    /// tab in front in the session window, the incoming one in the main window.
+    fn transfer_ui(&mut self, ctx: &egui::Context, session_window: bool) {
+        let (sh, se) = (self.shared.clone(), self.tab().sess.clone());
+        let (xfers, engine) = if session_window {
+            (&se.xfers, &se.xfer)
+        } else {
+            (&sh.xfers, &sh.xfer)
+        };
+        let list = xfers.lock().unwrap().clone();
         if list.is_empty() {
             return;
         }
         egui::TopBottomPanel::bottom("xfer_bar").show(ctx, |ui| {
             let mut clear = false;
+            // (id, eingehend, was): 0 = anhalten, 1 = weiter, 2 = abbrechen
+            let mut act: Option<(u32, bool, u8)> = None;
             ui.horizontal(|ui| {
                 ui.label(egui::RichText::new("Dateien").strong());
                 if ui.small_button("Ordner oeffnen").clicked() {
@@ -2699,6 +3360,21 @@ impl App {
                 if ui.small_button("Liste leeren").clicked() {
                     clear = true;
                 }
+                let limit = self.shared.xfer_limit.load(Ordering::Relaxed);
+                let mut pick = limit;
+                egui::ComboBox::from_id_salt("xfer_limit")
+                    .selected_text(rate_label(limit))
+                    .show_ui(ui, |ui| {
+                        for r in XFER_LIMITS {
+                            ui.selectable_value(&mut pick, r, rate_label(r));
+                        }
+                    })
+                    .response
+                    .on_hover_text("Hoechstens so viel pro Sekunde fuer alle gesendeten Dateien");
+                if pick != limit {
+                    self.shared.xfer_limit.store(pick, Ordering::Relaxed);
+                    ident::set_xfer_limit(pick);
+                }
             });
             egui::ScrollArea::vertical()
                 .max_height(96.0)
@@ -2718,27 +3394,64 @@ impl App {
                                     "fertig",
                                 );
                             } else {
-                                ui.add(
-                                    egui::ProgressBar::new(p.percent())
-                                        .desired_width(160.0)
-                                        .show_percentage(),
-                                );
+                                if p.queued && !p.paused {
+                                    ui.weak("wartet");
+                                } else if p.hashing {
+                                    ui.add(
+                                        egui::ProgressBar::new(p.percent())
+                                            .desired_width(160.0)
+                                            .text("Prüfsumme …"),
+                                    );
+                                } else {
+                                    ui.add(
+                                        egui::ProgressBar::new(p.percent())
+                                            .desired_width(160.0)
+                                            .show_percentage(),
+                                    );
+                                }
+                                if p.files > 0 {
+                                    ui.label(format!("{}/{} Dateien", p.files_done, p.files));
+                                }
                                 ui.label(format!(
                                     "{:.1}/{:.1} MB",
                                     p.done as f32 / 1_048_576.0,
                                     p.size as f32 / 1_048_576.0
                                 ));
+                                if !p.incoming {
+                                    if p.paused {
+                                        ui.weak("angehalten");
+                                        if ui.small_button("Weiter").clicked() {
+                                            act = Some((p.id, false, 1));
+                                        }
+                                    } else if ui.small_button("Anhalten").clicked() {
+                                        act = Some((p.id, false, 0));
+                                    }
+                                }
+                                // eingehende Ordner laufen beim Sender, dort abbrechen
+                                if (!p.incoming || p.files == 0)
+                                    && ui.small_button("Abbrechen").clicked()
+                                {
+                                    act = Some((p.id, p.incoming, 2));
+                                }
                             }
                         });
                     }
                 });
             if clear {
-                self.shared
-                    .xfers
+                xfers
                     .lock()
                     .unwrap()
                     .retain(|p| !p.finished && p.error.is_empty());
             }
+            if let Some((id, incoming, what)) = act {
+                if let Some(x) = engine.lock().unwrap().as_mut() {
+                    match what {
+                        0 => x.pause(id),
+                        1 => x.resume(id),
+                        _ => x.cancel(id, incoming),
+                    }
+                }
+            }
         });
     }
 
@@ -2803,11 +3516,12 @@ impl App {
         }
     }
     fn pull_frame(&mut self, ctx: &egui::Context) {
+        let t = self.tab_mut();
         let img = {
-            let guard = self.shared.frame.lock().unwrap();
+            let guard = t.sess.frame.lock().unwrap();
             match guard.as_ref() {
-                Some(f) if f.seq != self.last_seq => {
-                    self.last_seq = f.seq;
+                Some(f) if f.seq != t.last_seq => {
+                    t.last_seq = f.seq;
                     Some(egui::ColorImage::from_rgba_unmultiplied(
                         [f.width as usize, f.height as usize],
                         &f.rgba,
@@ -2817,12 +3531,10 @@ impl App {
             }
         };
         if let Some(ci) = img {
-            self.frame_at = std::time::Instant::now();
-            match self.tex.as_mut() {
-                Some(t) => t.set(ci, egui::TextureOptions::LINEAR),
-                None => {
-                    self.tex = Some(ctx.load_texture("remote", ci, egui::TextureOptions::LINEAR))
-                }
+            t.frame_at = std::time::Instant::now();
+            match t.tex.as_mut() {
+                Some(tex) => tex.set(ci, egui::TextureOptions::LINEAR),
+                None => t.tex = Some(ctx.load_texture("remote", ci, egui::TextureOptions::LINEAR)),
             }
         }
     }
@@ -2885,11 +3597,86 @@ impl App {
     /// Startseite: links dieser PC, rechts der Weg nach draußen.
     /// Startseite: links dieser PC, rechts der Weg nach draußen. Bewusst
     /// wenig Text - was erklärt werden muss, steht im Tooltip.
+    /// Was der Host zeigt: den ganzen Bildschirm, ein Fenster oder einen
+    /// Bereich. Gilt sofort, auch fuer die laufende Sitzung.
+    fn share_ui(&mut self, ui: &mut egui::Ui) {
+        use share::Share;
+        label_small(ui, i18n::t("share.label"));
+        let cur = self.shared.share.lock().unwrap().clone();
+        let text = match &cur {
+            Share::Screen => i18n::t("share.screen").to_string(),
+            Share::Window { title, .. } => title.chars().take(40).collect(),
+            Share::Region(_) => i18n::t("share.region").to_string(),
+        };
+        let mut pick = None;
+        egui::ComboBox::from_id_salt("fv_share")
+            .selected_text(text)
+            .width(240.0)
+            .show_ui(ui, |ui| {
+                if ui
+                    .selectable_label(cur == Share::Screen, i18n::t("share.screen"))
+                    .clicked()
+                {
+                    pick = Some(Share::Screen);
+                }
+                if ui
+                    .selectable_label(matches!(cur, Share::Region(_)), i18n::t("share.region"))
+                    .clicked()
+                    && !matches!(cur, Share::Region(_))
+                {
+                    // die Mitte eines Full-HD-Bildschirms als Anfang
+                    pick = Some(Share::Region(input::ScreenRect {
+                        x: 480,
+                        y: 270,
+                        w: 960,
+                        h: 540,
+                    }));
+                }
+                ui.separator();
+                label_small(ui, i18n::t("share.windows"));
+                let me = std::process::id();
+                for w in share::windows().into_iter().filter(|w| w.app && w.pid != me) {
+                    let on = matches!(&cur, Share::Window { id, .. } if *id == w.id);
+                    let title: String = w.title.chars().take(60).collect();
+                    if ui.selectable_label(on, title).clicked() {
+                        pick = Some(Share::Window {
+                            id: w.id,
+                            title: w.title,
+                        });
+                    }
+                }
+            })
+            .response
+            .on_hover_text(i18n::t("share.tip"));
+        if let Share::Region(r) = cur {
+            let mut r2 = r;
+            let (mut w, mut h) = (r.w as i32, r.h as i32);
+            ui.horizontal(|ui| {
+                ui.label("x");
+                ui.add(egui::DragValue::new(&mut r2.x).speed(4));
+                ui.label("y");
+                ui.add(egui::DragValue::new(&mut r2.y).speed(4));
+                ui.label(i18n::t("share.size"));
+                ui.add(egui::DragValue::new(&mut w).speed(4).range(16..=16384));
+                ui.label("x");
+                ui.add(egui::DragValue::new(&mut h).speed(4).range(16..=16384));
+            });
+            r2.w = w as u32;
+            r2.h = h as u32;
+            if r2 != r {
+                pick = Some(Share::Region(r2));
+            }
+        }
+        if let Some(p) = pick {
+            *self.shared.share.lock().unwrap() = p;
+        }
+    }
+
     fn start_view(&mut self, ui: &mut egui::Ui) {
         let my_id = self.shared.my_id.lock().unwrap().clone();
         let host_status = self.shared.host_status.lock().unwrap().clone();
         let host_peer = self.shared.host_peer.lock().unwrap().clone();
-        let connecting = self.shared.connecting.load(Ordering::Relaxed);
+        let connecting = self.tab().sess.connecting.load(Ordering::Relaxed);
         let p = theme::palette();
 
         ui.columns(2, |cols| {
@@ -2962,8 +3749,35 @@ impl App {
                     ui.label(egui::RichText::new(host_status).size(12.0).color(p.muted));
                 });
                 if host_peer != i18n::t("start.nosession") && !host_peer.is_empty() {
-                    ui.label(egui::RichText::new(host_peer).size(12.0).color(p.muted));
+                    let st = *self.shared.stats.lock().unwrap();
+                    ui.label(egui::RichText::new(host_peer).size(12.0).color(p.muted))
+                        .on_hover_text(format!(
+                            "{:.0} fps, {:.0} kbit/s, {}",
+                            st.fps,
+                            st.kbps,
+                            i18n::tf(
+                                "sess.low_quality",
+                                &format!("{:.0}", st.low_quality * 100.0)
+                            )
+                        ));
                 }
+                if self.shared.chat_out.lock().unwrap().is_some() {
+                    let unread = self.shared.chat.lock().unwrap().unread;
+                    let text = if unread > 0 {
+                        format!("{} ({})", i18n::t("sess.chat"), unread)
+                    } else {
+                        i18n::t("sess.chat").to_string()
+                    };
+                    if ui
+                        .add(ghost(egui::vec2(110.0, 28.0), &text))
+                        .on_hover_text(i18n::t("chat.host_tip"))
+                        .clicked()
+                    {
+                        self.shared.chat_open.store(true, Ordering::Relaxed);
+                    }
+                }
+                ui.add_space(6.0);
+                self.share_ui(ui);
             });
 
 
@@ -3026,7 +3840,7 @@ impl App {
                     if check(ui, &mut remember, i18n::t("start.remember")).changed() {
                         self.remember_pw = remember;
                     }
-                    let mut game = self.shared.game_mode();
+                    let mut game = self.tab().sess.game_mode();
                     if check(ui, &mut game, i18n::t("start.game"))
                         .on_hover_text(i18n::t("start.game_tip"))
                         .changed()
@@ -3087,8 +3901,7 @@ impl App {
 
     /// Mikrofon und Ton als zwei Symbolschalter - im Dashboard und in der
     /// Sitzungsleiste dieselbe Bedienung.
-    fn voice_buttons(&mut self, ui: &mut egui::Ui, size: f32) {
-        let v = self.shared.voice.clone();
+    fn voice_buttons(&mut self, ui: &mut egui::Ui, size: f32, v: Arc<audio::VoiceState>) {
         let mic = v.mic.load(Ordering::Relaxed);
         let snd = v.speaker.load(Ordering::Relaxed);
         if icon_toggle(
@@ -3123,6 +3936,16 @@ impl App {
         }
     }
 
+    /// Die Sprechverbindung, um die es gerade geht: die Sitzung vorn, sonst
+    /// die eingehende.
+    fn voice_now(&self) -> Arc<audio::VoiceState> {
+        if self.tab().session.is_some() {
+            self.tab().sess.voice.clone()
+        } else {
+            self.shared.voice.clone()
+        }
+    }
+
     /// Verbindet mit einem Eintrag aus der Liste.
     /// Ist das die eigene ID? Dann bringt eine Verbindung nichts - der
     /// Bildschirm wuerde sich selbst zeigen, bis nichts mehr geht.
@@ -5213,6 +6036,116 @@ if let Some(path) = self.shot.clone() {
                 ident::set_clipboard(clip);
             }
             ui.add_space(4.0);
+            let rights = self.shared.rights.load(Ordering::Relaxed);
+            let mut read = rights & ident::RIGHT_FILES_READ != 0;
+            let mut write = rights & ident::RIGHT_FILES_WRITE != 0;
+            let r1 = check(ui, &mut read, i18n::t("set.files_read"))
+                .on_hover_text(i18n::t("set.files_read_tip"));
+            ui.add_space(4.0);
+            let r2 = check(ui, &mut write, i18n::t("set.files_write"))
+                .on_hover_text(i18n::t("set.files_write_tip"));
+            if r1.changed() || r2.changed() {
+                // aendern ohne ansehen ergibt keinen Sinn
+                if r2.changed() && write {
+                    read = true;
+                }
+                if r1.changed() && !read {
+                    write = false;
+                }
+                let mut bits = rights & !(ident::RIGHT_FILES_READ | ident::RIGHT_FILES_WRITE);
+                if read {
+                    bits |= ident::RIGHT_FILES_READ;
+                }
+                if write {
+                    bits |= ident::RIGHT_FILES_WRITE;
+                }
+                self.shared.rights.store(bits, Ordering::Relaxed);
+                ident::set_host_rights(bits);
+            }
+            ui.add_space(4.0);
+            let mut shell = rights & ident::RIGHT_SHELL != 0;
+            if check(ui, &mut shell, i18n::t("set.shell"))
+                .on_hover_text(i18n::t("set.shell_tip"))
+                .changed()
+            {
+                let bits = self.shared.rights.load(Ordering::Relaxed);
+                let bits = if shell {
+                    bits | ident::RIGHT_SHELL
+                } else {
+                    bits & !ident::RIGHT_SHELL
+                };
+                self.shared.rights.store(bits, Ordering::Relaxed);
+                ident::set_host_rights(bits);
+            }
+            let mut kill = rights & ident::RIGHT_PROCS != 0;
+            if check(ui, &mut kill, i18n::t("set.procs"))
+                .on_hover_text(i18n::t("set.procs_tip"))
+                .changed()
+            {
+                let bits = self.shared.rights.load(Ordering::Relaxed);
+                let bits = if kill {
+                    bits | ident::RIGHT_PROCS
+                } else {
+                    bits & !ident::RIGHT_PROCS
+                };
+                self.shared.rights.store(bits, Ordering::Relaxed);
+                ident::set_host_rights(bits);
+            }
+            ui.add_space(4.0);
+            ui.label(i18n::t("set.tunnels")).on_hover_text(i18n::t("set.tunnels_tip"));
+            if ui
+                .add(
+                    egui::TextEdit::multiline(&mut self.tun_allow)
+                        .hint_text("127.0.0.1:3389\n192.168.1.10:*")
+                        .desired_rows(2)
+                        .desired_width(260.0),
+                )
+                .changed()
+            {
+                let list: Vec<String> = self
+                    .tun_allow
+                    .lines()
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect();
+                ident::set_tunnel_allow(&list);
+                *self.shared.tunnel_allow.lock().unwrap() = list;
+            }
+            ui.add_space(4.0);
+            ui.label(i18n::t("set.redact")).on_hover_text(i18n::t("set.redact_tip"));
+            if ui
+                .add(
+                    egui::TextEdit::multiline(&mut self.redact)
+                        .hint_text("keepass*.exe\n*Online-Banking*")
+                        .desired_rows(2)
+                        .desired_width(260.0),
+                )
+                .changed()
+            {
+                let list: Vec<String> = self
+                    .redact
+                    .lines()
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect();
+                ident::set_redact_rules(&list);
+                *self.shared.redact.lock().unwrap() = list;
+            }
+            ui.add_space(4.0);
+            let rec = ident::record_sessions();
+            for (bit, key, tip) in [
+                (ident::RECORD_VIEWER, "set.record_viewer", "set.record_viewer_tip"),
+                (ident::RECORD_HOST, "set.record_host", "set.record_host_tip"),
+            ] {
+                let mut on = rec & bit != 0;
+                if check(ui, &mut on, i18n::t(key))
+                    .on_hover_text(i18n::t(tip))
+                    .changed()
+                {
+                    ident::set_record_sessions(if on { rec | bit } else { rec & !bit });
+                }
+            }
+            ui.add_space(4.0);
             let mut keep = self.pw_fixed;
             if check(ui, &mut keep, i18n::t("start.keep_pw"))
                 .on_hover_text(i18n::t("start.keep_pw_tip"))
@@ -6216,9 +7149,9 @@ if let Some(path) = self.shot.clone() {
             ui.add_space(5.0);
             label_small(ui, i18n::t("set.audio_now"));
             ui.horizontal(|ui| {
-                self.voice_buttons(ui, 18.0);
+                let v = self.voice_now();
+                self.voice_buttons(ui, 18.0, v.clone());
                 ui.add_space(8.0);
-                let v = self.shared.voice.clone();
                 level_bar(
                     ui,
                     v.level_out.load(Ordering::Relaxed),
@@ -6606,8 +7539,8 @@ if let Some(path) = self.shot.clone() {
     /// (oben im Fenster und schwebend im Vollbild), den Inhalt aber nur
     /// einmal - deshalb sammelt sie ihre Wuensche hier ein.
     fn session_bar(&mut self, ui: &mut egui::Ui, a: &mut BarActs, kompakt: bool) {
-        let stats = *self.shared.stats.lock().unwrap();
-        let game = self.shared.game_mode();
+        let stats = *self.tab().sess.stats.lock().unwrap();
+        let game = self.tab().sess.game_mode();
         let p = theme::palette();
 
         if kompakt {
@@ -6650,11 +7583,22 @@ if let Some(path) = self.shot.clone() {
         {
             a.mode = Some(proto::MODE_GAME);
         }
+        if !game {
+            let sharp = self.tab().sess.sharp_text.load(Ordering::Relaxed);
+            if zeigefinger(
+                ui.selectable_label(sharp, i18n::t("sess.sharp"))
+                    .on_hover_text(i18n::t("sess.sharp_tip")),
+            )
+            .clicked()
+            {
+                a.sharp = Some(!sharp);
+            }
+        }
 
-        let mons = self.shared.monitors.lock().unwrap().clone();
+        let mons = self.tab().sess.monitors.lock().unwrap().clone();
         if mons.len() > 1 {
             ui.separator();
-            let act = self.shared.active_monitor.load(Ordering::Relaxed) as usize;
+            let act = self.tab().sess.active_monitor.load(Ordering::Relaxed) as usize;
             let label = |i: usize, m: &proto::MonitorInfo| {
                 if kompakt {
                     format!("{}. {}", i + 1, m.name)
@@ -6681,11 +7625,11 @@ if let Some(path) = self.shot.clone() {
         // am Sitzungsende die vorherige wieder.
         ui.separator();
         {
-            let (rw, rh) = *self.shared.remote_size.lock().unwrap();
+            let (rw, rh) = *self.tab().sess.remote_size.lock().unwrap();
             // Was der ferne Bildschirm wirklich kann (sagt der Host); solange
             // nichts da ist, die gaengigen Stufen.
             let modi = {
-                let l = self.shared.remote_resolutions.lock().unwrap().clone();
+                let l = self.tab().sess.remote_resolutions.lock().unwrap().clone();
                 if l.is_empty() {
                     vec![
                         (1280u32, 720u32),
@@ -6714,20 +7658,95 @@ if let Some(path) = self.shot.clone() {
         }
 
         ui.separator();
-        self.voice_buttons(ui, 16.0);
+        self.voice_buttons(ui, 16.0, self.tab().sess.voice.clone());
+        let unread = self.tab().sess.chat.lock().unwrap().unread;
+        let label = if unread > 0 {
+            egui::RichText::new(format!("{} ({})", i18n::t("sess.chat"), unread))
+                .color(theme::accent())
+        } else {
+            egui::RichText::new(i18n::t("sess.chat"))
+        };
+        if ui
+            .selectable_label(self.tab().chat_open, label)
+            .on_hover_text(i18n::t("sess.chat_tip"))
+            .clicked()
+        {
+            a.chat = true;
+        }
+        let rec = self.tab().sess.recorder.lock().unwrap().clone();
+        let label = match &rec {
+            Some(r) => egui::RichText::new(format!(
+                "\u{25cf} {}",
+                player::clock(r.progress().0.as_millis() as u32)
+            ))
+            .color(egui::Color32::from_rgb(230, 80, 80)),
+            None => egui::RichText::new(i18n::t("sess.record")),
+        };
+        if ui
+            .button(label)
+            .on_hover_text(i18n::t("sess.record_tip"))
+            .clicked()
+        {
+            a.record = true;
+        }
         ui.separator();
 
         let mut want_pick = false;
+        let mut want_dir = false;
         let mut want_open = false;
         ui.menu_button(i18n::t("sess.files"), |ui| {
             if ui.button(i18n::t("sess.send_file")).clicked() {
                 want_pick = true;
                 ui.close();
             }
+            if ui.button(i18n::t("sess.send_dir")).clicked() {
+                want_dir = true;
+                ui.close();
+            }
+            if ui.button(i18n::t("sess.browse")).clicked() {
+                a.browse = true;
+                ui.close();
+            }
+            if ui
+                .button(i18n::t("sess.terminal"))
+                .on_hover_text(i18n::t("sess.terminal_tip"))
+                .clicked()
+            {
+                a.terminal = true;
+                ui.close();
+            }
+            if ui
+                .button(i18n::t("sess.sysinfo"))
+                .on_hover_text(i18n::t("sess.sysinfo_tip"))
+                .clicked()
+            {
+                a.sysinfo = true;
+                ui.close();
+            }
+            if ui
+                .button(i18n::t("sess.procs"))
+                .on_hover_text(i18n::t("sess.procs_tip"))
+                .clicked()
+            {
+                a.procs = true;
+                ui.close();
+            }
             if ui.button(i18n::t("sess.open_dir")).clicked() {
                 want_open = true;
                 ui.close();
             }
+            ui.separator();
+            ui.label(i18n::t("sess.conflict"));
+            let rule = self.shared.on_conflict.load(Ordering::Relaxed);
+            for (val, key) in [
+                (xfer::KEEP_BOTH, "sess.keep_both"),
+                (xfer::SKIP, "sess.skip"),
+                (xfer::OVERWRITE, "sess.overwrite"),
+            ] {
+                if ui.radio(rule == val, i18n::t(key)).clicked() {
+                    self.shared.on_conflict.store(val, Ordering::Relaxed);
+                }
+            }
             ui.label(
                 egui::RichText::new(i18n::t("sess.drop_tip"))
                     .weak()
@@ -6737,26 +7756,174 @@ if let Some(path) = self.shot.clone() {
         if want_pick {
             a.pick = true;
         }
+        if want_dir {
+            a.pick_dir = true;
+        }
         if want_open {
             a.open_dir = true;
         }
 
+        let tunnels = self.tab().sess.tunnels.lock().unwrap().clone();
+        if let Some(t) = tunnels {
+            let list = t.forwards();
+            let label = if list.is_empty() {
+                i18n::t("sess.tunnels").to_string()
+            } else {
+                format!("{} ({})", i18n::t("sess.tunnels"), list.len())
+            };
+            ui.menu_button(label, |ui| {
+                for f in &list {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "127.0.0.1:{} \u{2192} {}:{}  \u{00b7}  {} {}  \u{00b7}  {}",
+                            f.local,
+                            f.host,
+                            f.port,
+                            f.conns,
+                            i18n::t("tun.conns"),
+                            human_size(f.bytes)
+                        ));
+                        if ui.small_button("x").on_hover_text(i18n::t("tun.stop")).clicked() {
+                            t.stop_forward(f.local);
+                        }
+                    });
+                }
+                if !list.is_empty() {
+                    ui.separator();
+                }
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.tab_mut().tun_spec)
+                            .hint_text("13389:127.0.0.1:3389")
+                            .desired_width(170.0),
+                    );
+                    if ui.button(i18n::t("tun.add")).clicked() {
+                        match t.forward(&self.tab().tun_spec) {
+                            Ok(_) => {
+                                self.tab_mut().tun_spec.clear();
+                                self.tab_mut().tun_err.clear();
+                            }
+                            Err(e) => self.tab_mut().tun_err = e,
+                        }
+                    }
+                });
+                if !self.tab().tun_err.is_empty() {
+                    ui.colored_label(egui::Color32::from_rgb(230, 120, 120), &self.tab().tun_err);
+                }
+                ui.label(
+                    egui::RichText::new(i18n::t("tun.tip"))
+                        .weak()
+                        .size(11.0),
+                );
+            });
+        }
+
+        ui.separator();
+        // nur zusehen heisst auch: keine Sondertasten
+        ui.add_enabled_ui(!self.tab().view_only, |ui| {
+            ui.menu_button(i18n::t("sess.keys"), |ui| {
+                for (text, code) in [
+                    ("Strg+Alt+Entf", proto::SPECIAL_CAD),
+                    ("Task-Manager (Strg+Shift+Esc)", proto::SPECIAL_TASKMGR),
+                    ("Windows-Taste", proto::SPECIAL_WIN),
+                    ("Alt+Tab", proto::SPECIAL_ALTTAB),
+                    ("Sperren (Win+L)", proto::SPECIAL_LOCK),
+                ] {
+                    if ui.button(text).clicked() {
+                        a.special = Some(code);
+                        ui.close();
+                    }
+                }
+            });
+        });
+
         ui.separator();
-        ui.menu_button(i18n::t("sess.keys"), |ui| {
-            for (text, code) in [
-                ("Strg+Alt+Entf", proto::SPECIAL_CAD),
-                ("Task-Manager (Strg+Shift+Esc)", proto::SPECIAL_TASKMGR),
-                ("Windows-Taste", proto::SPECIAL_WIN),
-                ("Alt+Tab", proto::SPECIAL_ALTTAB),
-                ("Sperren (Win+L)", proto::SPECIAL_LOCK),
+        ui.menu_button(i18n::t("sess.view"), |ui| {
+            use partners::Scale;
+            for (scale, key) in [
+                (Scale::Fit, "sess.view_fit"),
+                (Scale::Actual, "sess.view_actual"),
+                (Scale::Letterbox, "sess.view_letterbox"),
             ] {
-                if ui.button(text).clicked() {
-                    a.special = Some(code);
+                if ui.radio(self.tab().scale == scale, i18n::t(key)).clicked() {
+                    a.scale = Some(scale);
+                    ui.close();
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.label(i18n::t("sess.view_zoom"));
+                for z in ZOOMS {
+                    if ui
+                        .selectable_label(self.tab().scale == Scale::Zoom(z), format!("{}%", z))
+                        .clicked()
+                    {
+                        a.scale = Some(Scale::Zoom(z));
+                        ui.close();
+                    }
+                }
+            });
+            ui.label(
+                egui::RichText::new(i18n::t("sess.view_pan"))
+                    .weak()
+                    .size(11.0),
+            );
+            ui.separator();
+            let mut only = self.tab().view_only;
+            if ui
+                .checkbox(&mut only, i18n::t("sess.view_only"))
+                .on_hover_text(i18n::t("sess.view_only_tip"))
+                .changed()
+            {
+                a.view_only = Some(only);
+            }
+        });
+        if self.tab().view_only {
+            ui.colored_label(
+                egui::Color32::from_rgb(230, 190, 90),
+                i18n::t("sess.view_only_on"),
+            );
+        }
+
+        let drawing = self.tab().pad.tool.is_some();
+        let label = if drawing {
+            egui::RichText::new(i18n::t("sess.draw")).color(theme::accent())
+        } else {
+            egui::RichText::new(i18n::t("sess.draw"))
+        };
+        ui.menu_button(label, |ui| {
+            if ui.radio(!drawing, i18n::t("draw.off")).clicked() {
+                a.draw = Some(None);
+                ui.close();
+            }
+            for (tool, key) in annot::TOOLS {
+                if ui.radio(self.tab().pad.tool == Some(tool), i18n::t(key)).clicked() {
+                    a.draw = Some(Some(tool));
                     ui.close();
                 }
             }
+            ui.separator();
+            if ui.button(i18n::t("draw.clear")).clicked() {
+                a.draw_clear = true;
+                ui.close();
+            }
+            ui.label(egui::RichText::new(i18n::t("draw.tip")).weak().size(11.0));
         });
 
+        let want = self.tab().sess.privacy_want.load(Ordering::Relaxed);
+        if ui
+            .selectable_label(want, i18n::t("sess.privacy"))
+            .on_hover_text(i18n::t("sess.privacy_tip"))
+            .clicked()
+        {
+            a.privacy = Some(!want);
+        }
+        if self.tab().sess.privacy.load(Ordering::Relaxed) {
+            ui.colored_label(
+                egui::Color32::from_rgb(230, 190, 90),
+                i18n::t("sess.privacy_on"),
+            );
+        }
+
         ui.separator();
         // Vollbild an/aus - im Vollbild zusaetzlich das Anheften
         let (icon, tip) = if self.full {
@@ -6775,8 +7942,8 @@ if let Some(path) = self.shot.clone() {
         }
 
         ui.separator();
-        let (rw, rh) = *self.shared.remote_size.lock().unwrap();
-        let direct = self.shared.direct.load(Ordering::Relaxed);
+        let (rw, rh) = *self.tab().sess.remote_size.lock().unwrap();
+        let direct = self.tab().sess.direct.load(Ordering::Relaxed);
         let text = if kompakt {
             format!("{:.0} fps  {:.0} ms", stats.fps, stats.latency_ms)
         } else {
@@ -6795,7 +7962,7 @@ if let Some(path) = self.shot.clone() {
             )
         };
         ui.label(text).on_hover_text(format!(
-            "{}x{}, {:.0} kbit/s, {}",
+            "{}x{}, {:.0} kbit/s, {}, {}",
             rw,
             rh,
             stats.kbps,
@@ -6803,7 +7970,8 @@ if let Some(path) = self.shot.clone() {
                 i18n::t("sess.direct")
             } else {
                 i18n::t("sess.via_relay")
-            }
+            },
+            i18n::tf("sess.cache_hits", &format!("{:.0}", stats.cache_hits * 100.0))
         ));
 
         if !kompakt {
@@ -6903,6 +8071,11 @@ if let Some(path) = self.shot.clone() {
                 self.bar_seen = std::time::Instant::now();
             }
         } else {
+            if self.tabs.len() > 1 {
+                egui::TopBottomPanel::top("fv_tabs").show(ctx, |ui| {
+                    ui.horizontal(|ui| self.tab_strip(ui, &mut a));
+                });
+            }
             egui::TopBottomPanel::top("session_bar").show(ctx, |ui| {
                 ui.horizontal(|ui| {
                     self.session_bar(ui, &mut a, false);
@@ -6933,35 +8106,190 @@ if let Some(path) = self.shot.clone() {
             }
         }
 
-        let mut image_rect: Option<egui::Rect> = None;
+        if a.browse {
+            self.tab_mut().fm.open = !self.tab().fm.open;
+            if self.tab().fm.open {
+                self.fm_open();
+            }
+        }
+        if self.tab().fm.open {
+            egui::TopBottomPanel::bottom("fv_files")
+                .resizable(true)
+                .default_height(280.0)
+                .show(ctx, |ui| self.files_ui(ui));
+        }
+        if a.terminal {
+            self.tab_mut().term_open = !self.tab().term_open;
+            let m = {
+                let mut t = self.tab().sess.term.lock().unwrap();
+                if self.tab().term_open {
+                    // gleich starten; die Groesse passt das Widget an
+                    (!t.running).then(|| t.open(24, 80))
+                } else {
+                    t.close()
+                }
+            };
+            if let Some(m) = m {
+                self.tab().sess.send_input(m);
+            }
+        }
+        if self.tab().term_open {
+            egui::TopBottomPanel::bottom("fv_term")
+                .resizable(true)
+                .default_height(320.0)
+                .show(ctx, |ui| self.term_ui(ui));
+        }
+        if a.procs {
+            self.tab_mut().procs_open = !self.tab().procs_open;
+        }
+        if a.record {
+            let running = self.tab().sess.recorder.lock().unwrap().take();
+            match running {
+                Some(r) => {
+                    r.finish();
+                    self.hint = format!("{} {}", i18n::t("sess.record_saved"), r.path().display());
+                    self.hint_until = Some(std::time::Instant::now() + Duration::from_secs(8));
+                }
+                None => {
+                    let peer = self.tab_id(self.front).unwrap_or_default().to_string();
+                    viewer::start_recording(&self.tab().sess, &peer)
+                }
+            }
+        }
+        if self.tab().procs_open {
+            egui::TopBottomPanel::bottom("fv_procs")
+                .resizable(true)
+                .default_height(300.0)
+                .show(ctx, |ui| self.procs_ui(ui));
+        }
+        if a.sysinfo {
+            self.tab_mut().info_open = !self.tab().info_open;
+            if self.tab().info_open {
+                self.tab().sess.send_input(Msg::SysInfoGet);
+            }
+        }
+        if self.tab().info_open {
+            egui::SidePanel::right("fv_sysinfo")
+                .resizable(true)
+                .default_width(320.0)
+                .show(ctx, |ui| self.sysinfo_ui(ui));
+        }
+        // eine neue Zeile vom Host klappt den Chat von selbst auf
+        if a.chat {
+            self.tab_mut().chat_open = !self.tab().chat_open;
+        } else if self.tab().sess.chat.lock().unwrap().unread > 0 {
+            self.tab_mut().chat_open = true;
+        }
+        if self.tab().chat_open {
+            egui::SidePanel::right("fv_chat")
+                .resizable(true)
+                .default_width(300.0)
+                .show(ctx, |ui| self.chat_ui(ui));
+        }
+
+        // Vollbild mit Raendern heisst Vollbild; wer es mit F11 verlaesst,
+        // bekommt wieder das eingepasste Bild im Fenster.
+        if self.tab().scale == partners::Scale::Letterbox && !self.full && !a.toggle_full {
+            self.set_full(ctx, true);
+        }
+
+        // Bild und der Teil davon, der zu sehen ist (1:1 und Zoom zeigen
+        // nur einen Ausschnitt - nur dort geht die Maus an den Host).
+        let mut image_rect: Option<(egui::Rect, egui::Rect)> = None;
         let mut clicked_image = false;
+        let scale = self.tab().scale;
+        let tex = self.tab().tex.clone();
         egui::CentralPanel::default()
             .frame(egui::Frame::NONE.fill(egui::Color32::from_rgb(18, 18, 18)))
             .show(ctx, |ui| {
-                if let Some(tex) = &self.tex {
-                    let size_px = tex.size();
-                    let (iw, ih) = (size_px[0] as f32, size_px[1] as f32);
-                    let avail = ui.available_size();
-                    let scale = (avail.x / iw).min(avail.y / ih).max(0.05);
-                    let size = egui::vec2(iw * scale, ih * scale);
-                    ui.vertical_centered(|ui| {
-                        let resp = ui.add(
-                            egui::Image::new(egui::load::SizedTexture::new(tex.id(), size))
-                                .sense(egui::Sense::click_and_drag()),
-                        );
-                        clicked_image = resp.clicked();
-                        image_rect = Some(resp.rect);
-                    });
-                } else {
+                let Some(tex) = &tex else {
                     ui.centered_and_justified(|ui| {
                         ui.label(i18n::t("sess.waiting"));
                     });
+                    return;
+                };
+                let size_px = tex.size();
+                let (iw, ih) = (size_px[0] as f32, size_px[1] as f32);
+                let ppp = ui.ctx().pixels_per_point();
+                match scale {
+                    partners::Scale::Fit | partners::Scale::Letterbox => {
+                        let avail = ui.available_size();
+                        let f = (avail.x / iw).min(avail.y / ih).max(0.05);
+                        let size = egui::vec2(iw * f, ih * f);
+                        if scale == partners::Scale::Letterbox {
+                            ui.add_space(((avail.y - size.y) / 2.0).max(0.0));
+                        }
+                        ui.vertical_centered(|ui| {
+                            let resp = ui.add(
+                                egui::Image::new(egui::load::SizedTexture::new(tex.id(), size))
+                                    .sense(egui::Sense::click_and_drag()),
+                            );
+                            clicked_image = resp.clicked();
+                            image_rect = Some((resp.rect, resp.rect));
+                        });
+                    }
+                    partners::Scale::Actual => {
+                        let size = egui::vec2(iw / ppp, ih / ppp);
+                        egui::ScrollArea::both()
+                            .auto_shrink([false, false])
+                            .show(ui, |ui| {
+                                let resp = ui.add(
+                                    egui::Image::new(egui::load::SizedTexture::new(
+                                        tex.id(),
+                                        size,
+                                    ))
+                                    .sense(egui::Sense::click_and_drag()),
+                                );
+                                clicked_image = resp.clicked();
+                                image_rect = Some((resp.rect, ui.clip_rect()));
+                            });
+                    }
+                    partners::Scale::Zoom(z) => {
+                        let panel = ui.max_rect();
+                        let size = egui::vec2(iw, ih) * (z as f32 / 100.0) / ppp;
+                        let resp = ui.interact(
+                            panel,
+                            egui::Id::new("fv_zoom"),
+                            egui::Sense::click_and_drag(),
+                        );
+                        let pan = &mut self.tab_mut().pan;
+                        if resp.dragged_by(egui::PointerButton::Middle) {
+                            *pan += resp.drag_delta();
+                        }
+                        // kleiner als das Fenster: mittig, sonst nicht ueber
+                        // den Rand hinaus schieben
+                        let axis = |room: f32, len: f32, pan: &mut f32| {
+                            if len <= room {
+                                *pan = 0.0;
+                                (room - len) / 2.0
+                            } else {
+                                *pan = pan.clamp(room - len, 0.0);
+                                *pan
+                            }
+                        };
+                        let off = egui::vec2(
+                            axis(panel.width(), size.x, &mut pan.x),
+                            axis(panel.height(), size.y, &mut pan.y),
+                        );
+                        let rect = egui::Rect::from_min_size(panel.min + off, size);
+                        ui.painter_at(panel).image(
+                            tex.id(),
+                            rect,
+                            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                            egui::Color32::WHITE,
+                        );
+                        if resp.dragged_by(egui::PointerButton::Middle) {
+                            ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+                        }
+                        clicked_image = resp.clicked();
+                        image_rect = Some((rect, panel.intersect(rect)));
+                    }
                 }
             });
 
         // Kein neues Bild seit ein paar Sekunden? Dann sagen wir, woran es
         // fast immer liegt, statt nur "0 fps" anzuzeigen.
-        if self.frame_at.elapsed() > Duration::from_secs(4) {
+        if self.tab().frame_at.elapsed() > Duration::from_secs(4) {
             let p = theme::palette();
             egui::Area::new(egui::Id::new("fv_stall"))
                 .order(egui::Order::Foreground)
@@ -6990,16 +8318,64 @@ if let Some(path) = self.shot.clone() {
                 });
         }
 
-        if let Some(rect) = image_rect {
-            self.draw_remote_cursor(ctx, rect, game_mode(&self.shared));
-            self.update_grab(ctx, rect, game_mode(&self.shared), clicked_image);
-            self.forward_input(ctx, rect, game_mode(&self.shared));
+        if let Some(tool) = a.draw {
+            if tool.is_some() {
+                // beim Zeichnen gehen Maus und Tastatur nicht an den Host
+                self.release_input();
+            }
+            if let Some(m) = self.tab_mut().pad.release() {
+                self.tab().sess.send_input(m);
+            }
+            self.tab_mut().pad.tool = tool;
+        }
+        if a.draw_clear {
+            let m = self.tab_mut().pad.clear();
+            self.tab().sess.send_input(m);
+        }
+        if let Some(on) = a.privacy {
+            self.tab().sess.privacy_want.store(on, Ordering::Relaxed);
+            self.tab().sess.send_input(Msg::Privacy { on });
+        }
+        if let Some((rect, seen)) = image_rect {
+            self.draw_remote_cursor(ctx, rect, game_mode(&self.tab().sess));
+            self.paint_annot(ctx, rect, seen);
+            if self.tab().pad.tool.is_some() {
+                self.annotate(ctx, rect, seen);
+            } else {
+                self.update_grab(ctx, seen, game_mode(&self.tab().sess), clicked_image);
+                if !self.tab().view_only {
+                    self.forward_input(ctx, rect, seen, game_mode(&self.tab().sess));
+                }
+            }
         }
 
         if a.toggle_full {
             let on = !self.full;
+            if !on && self.tab().scale == partners::Scale::Letterbox {
+                a.scale = Some(partners::Scale::Fit);
+            }
             self.set_full(ctx, on);
         }
+        if a.scale.is_some() || a.view_only.is_some() {
+            if let Some(scale) = a.scale {
+                if scale == partners::Scale::Letterbox {
+                    self.set_full(ctx, true);
+                }
+                self.tab_mut().scale = scale;
+                self.tab_mut().pan = egui::Vec2::ZERO;
+            }
+            if let Some(on) = a.view_only {
+                if on {
+                    // nichts bleibt drueben gedrueckt haengen
+                    self.release_input();
+                }
+                self.tab_mut().view_only = on;
+            }
+            let t = &self.tabs[self.front];
+            if let Some((id, _)) = &t.session {
+                self.book.set_view(id, t.scale, t.view_only);
+            }
+        }
         if a.toggle_pin {
             self.bar_pinned = !self.bar_pinned;
             self.bar_seen = std::time::Instant::now();
@@ -7007,21 +8383,28 @@ if let Some(path) = self.shot.clone() {
         if let Some(m) = a.mode {
             self.set_mode(m);
         }
+        if let Some(on) = a.sharp {
+            self.tab().sess.sharp_text.store(on, Ordering::Relaxed);
+            self.tab().sess.send_input(Msg::SetQuality { lossless: on });
+        }
         if let Some(i) = a.monitor {
-            self.shared.active_monitor.store(i, Ordering::Relaxed);
-            self.shared.send_input(Msg::SetMonitor { index: i });
-            self.tex = None;
-            self.last_seq = 0;
+            self.tab().sess.active_monitor.store(i, Ordering::Relaxed);
+            self.tab().sess.send_input(Msg::SetMonitor { index: i });
+            self.tab_mut().tex = None;
+            self.tab_mut().last_seq = 0;
         }
         if let Some((w, h)) = a.res {
-            self.shared.send_input(Msg::SetResolution { width: w, height: h });
+            self.tab().sess.send_input(Msg::SetResolution { width: w, height: h });
         }
-        if let Some(code) = a.special {
-            self.shared.send_input(Msg::Special { code });
+        if let Some(code) = a.special.filter(|_| !self.tab().view_only) {
+            self.tab().sess.send_input(Msg::Special { code });
         }
         if a.pick {
             self.pick_and_send();
         }
+        if a.pick_dir {
+            self.pick_dir_and_send();
+        }
         if a.open_dir {
             self.open_drop_dir();
         }
@@ -7029,6 +8412,12 @@ if let Some(path) = self.shot.clone() {
             self.set_full(ctx, false);
             self.stop_session();
         }
+        if let Some(i) = a.tab {
+            self.show_tab(i);
+        }
+        if let Some(i) = a.close_tab {
+            self.close_tab(i);
+        }
     }
 
     /// The duplication API does not paint the cursor into the frame. In game
@@ -7039,7 +8428,7 @@ if let Some(path) = self.shot.clone() {
         if !game {
             return;
         }
-        let (x, y, visible) = *self.shared.remote_cursor.lock().unwrap();
+        let (x, y, visible) = *self.tab().sess.remote_cursor.lock().unwrap();
         if !visible {
             return;
         }
@@ -7064,6 +8453,87 @@ if let Some(path) = self.shot.clone() {
         ));
     }
 
+    /// Drawing instead of remote control: the left mouse button paints on
+    /// the host's screen. `rect` is the whole picture, `seen` the part of
+    /// it on screen.
+    fn annotate(&mut self, ctx: &egui::Context, rect: egui::Rect, seen: egui::Rect) {
+        let (down, pressed, pos, esc) = ctx.input(|i| {
+            (
+                i.pointer.primary_down(),
+                i.pointer.primary_pressed(),
+                i.pointer.latest_pos(),
+                i.key_pressed(egui::Key::Escape),
+            )
+        });
+        if esc {
+            self.tab_mut().pad.tool = None;
+        }
+        let inside = pos.filter(|p| seen.contains(*p));
+        if inside.is_some() {
+            ctx.set_cursor_icon(egui::CursorIcon::Crosshair);
+        }
+        let m = match inside {
+            // a stroke starts in the picture, not on the bar above it
+            Some(p) if down && (pressed || self.tab().pad.drawing()) => {
+                let norm = |v: f32, from: f32, len: f32| {
+                    ((v - from) / len.max(1.0) * 10000.0).clamp(0.0, 10000.0) as u16
+                };
+                self.tab_mut().pad.point((
+                    norm(p.x, rect.left(), rect.width()),
+                    norm(p.y, rect.top(), rect.height()),
+                ))
+            }
+            _ if !down && self.tab().pad.drawing() => self.tab_mut().pad.release(),
+            _ => None,
+        };
+        if let Some(m) = m {
+            self.tab().sess.send_input(m);
+        }
+    }
+
+    /// What was drawn, over the picture - the host keeps its overlay out
+    /// of the stream, so the viewer paints its own copy.
+    fn paint_annot(&mut self, ctx: &egui::Context, rect: egui::Rect, seen: egui::Rect) {
+        let now = std::time::Instant::now();
+        self.tab_mut().pad.board.expire(now);
+        if self.tab().pad.board.is_empty() {
+            return;
+        }
+        // keep fading while nothing else moves
+        ctx.request_repaint_after(std::time::Duration::from_millis(40));
+        let painter = ctx
+            .layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("fv_annot"),
+            ))
+            .with_clip_rect(seen);
+        for s in &self.tab().pad.board.strokes {
+            let (rgb, width, _) = annot::style(s.tool);
+            let col = egui::Color32::from_rgba_unmultiplied(
+                rgb[0],
+                rgb[1],
+                rgb[2],
+                (s.alpha(now) * 255.0) as u8,
+            );
+            let width = (width * rect.height() / 1080.0).max(1.5);
+            for path in annot::paths(s, rect.width(), rect.height()) {
+                let pts: Vec<egui::Pos2> = path
+                    .iter()
+                    .map(|&(x, y)| rect.min + egui::vec2(x, y))
+                    .collect();
+                match pts.len() {
+                    0 => {}
+                    1 => {
+                        painter.circle_filled(pts[0], width / 2.0, col);
+                    }
+                    _ => {
+                        painter.add(egui::Shape::line(pts, egui::Stroke::new(width, col)));
+                    }
+                }
+            }
+        }
+    }
+
     /// Keeps the pointer lock in sync with focus and clicks.
     fn update_grab(&mut self, ctx: &egui::Context, rect: egui::Rect, game: bool, clicked: bool) {
         // Die Tastatur wird in BEIDEN Betriebsarten komplett uebernommen -
@@ -7071,7 +8541,8 @@ if let Some(path) = self.shot.clone() {
         // und nicht mehr an das eigene Windows. Rechte Strg holt sie zurueck.
         vinput::set_relative(game);
         let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
-        if !focused {
+        // "Nur ansehen": auch der Tastatur-Hook bleibt aus
+        if !focused || self.tab().view_only {
             vinput::set_active(false);
             return;
         }
@@ -7094,39 +8565,48 @@ if let Some(path) = self.shot.clone() {
         }
     }
 
-    fn forward_input(&mut self, ctx: &egui::Context, rect: egui::Rect, game: bool) {
+    /// `rect` is the whole picture, `seen` the part of it on screen.
+    fn forward_input(
+        &mut self,
+        ctx: &egui::Context,
+        rect: egui::Rect,
+        seen: egui::Rect,
+        game: bool,
+    ) {
         let grabbed = game && vinput::is_active();
+        // beim Zoom verschiebt die mittlere Maustaste das Bild
+        let pan = matches!(self.tab().scale, partners::Scale::Zoom(_));
 
         // modifier keys are not part of egui's Key enum, so we diff the state.
         // In game mode the low level hook already forwards everything.
         if !grabbed {
             let mods = ctx.input(|i| i.modifiers);
-            if mods.shift != self.last_mods.shift {
-                self.shared.send_input(Msg::Key {
+            if mods.shift != self.tab().last_mods.shift {
+                self.tab().sess.send_input(Msg::Key {
                     code: proto::KEY_SHIFT,
                     named: true,
                     down: mods.shift,
                 });
             }
-            if mods.ctrl != self.last_mods.ctrl {
-                self.shared.send_input(Msg::Key {
+            if mods.ctrl != self.tab().last_mods.ctrl {
+                self.tab().sess.send_input(Msg::Key {
                     code: proto::KEY_CTRL,
                     named: true,
                     down: mods.ctrl,
                 });
             }
-            if mods.alt != self.last_mods.alt {
-                self.shared.send_input(Msg::Key {
+            if mods.alt != self.tab().last_mods.alt {
+                self.tab().sess.send_input(Msg::Key {
                     code: proto::KEY_ALT,
                     named: true,
                     down: mods.alt,
                 });
             }
-            self.last_mods = mods;
+            self.tab_mut().last_mods = mods;
         }
 
         let norm = |pos: egui::Pos2| -> Option<(i32, i32)> {
-            if rect.width() < 1.0 || rect.height() < 1.0 || !rect.contains(pos) {
+            if rect.width() < 1.0 || rect.height() < 1.0 || !seen.contains(pos) {
                 return None;
             }
             let x = ((pos.x - rect.left()) / rect.width() * 10000.0).clamp(0.0, 10000.0) as i32;
@@ -7141,7 +8621,7 @@ if let Some(path) = self.shot.clone() {
                     // in game mode the raw delta path owns the mouse
                     if !grabbed {
                         if let Some((x, y)) = norm(pos) {
-                            self.shared.send_input(Msg::MouseMove { x, y });
+                            self.tab().sess.send_input(Msg::MouseMove { x, y });
                         }
                     }
                 }
@@ -7158,14 +8638,17 @@ if let Some(path) = self.shot.clone() {
                         egui::PointerButton::Extra2 => 4u8,
                         _ => 0u8,
                     };
+                    if pan && b == 2 && !grabbed {
+                        continue;
+                    }
                     if grabbed {
-                        self.shared.send_input(Msg::MouseButton {
+                        self.tab().sess.send_input(Msg::MouseButton {
                             button: b,
                             down: pressed,
                         });
                     } else if let Some((x, y)) = norm(pos) {
-                        self.shared.send_input(Msg::MouseMove { x, y });
-                        self.shared.send_input(Msg::MouseButton {
+                        self.tab().sess.send_input(Msg::MouseMove { x, y });
+                        self.tab().sess.send_input(Msg::MouseButton {
                             button: b,
                             down: pressed,
                         });
@@ -7179,7 +8662,7 @@ if let Some(path) = self.shot.clone() {
                     };
                     let l = lines.round() as i32;
                     if l != 0 {
-                        self.shared.send_input(Msg::Wheel { lines: l });
+                        self.tab().sess.send_input(Msg::Wheel { lines: l });
                     }
                 }
                 egui::Event::Key {
@@ -7188,9 +8671,11 @@ if let Some(path) = self.shot.clone() {
                     repeat,
                     ..
                 } => {
-                    if !repeat && !grabbed {
+                    // tippt jemand gerade in ein eigenes Feld (Dateimanager),
+                    // gehoeren die Tasten nicht dem anderen Rechner
+                    if !repeat && !grabbed && !ctx.wants_keyboard_input() {
                         if let Some((code, named)) = map_key(key) {
-                            self.shared.send_input(Msg::Key {
+                            self.tab().sess.send_input(Msg::Key {
                                 code,
                                 named,
                                 down: pressed,
@@ -7324,7 +8809,7 @@ impl eframe::App for App {
             let look = self.look.clone();
             theme::apply(ctx, &look);
         }
-        self.handle_drops(ctx);
+        self.handle_drops(ctx, false);
         self.handle_link(ctx);
         // Konto: Ergebnisse abholen und alle zwei Minuten leise abgleichen
         self.take_sync_result();
@@ -7334,10 +8819,10 @@ impl eframe::App for App {
         {
             self.start_sync(false);
         }
-        self.transfer_ui(ctx);
+        self.transfer_ui(ctx, false);
 
         // right Ctrl: 1x hands the input back, 3x leaves the session
-        match self.shared.escape.swap(0, Ordering::Relaxed) {
+        match self.tab().sess.escape.swap(0, Ordering::Relaxed) {
             1 => {
                 self.hint = "Eingabe freigegeben - ins Bild klicken uebernimmt wieder".to_string();
                 self.hint_until = Some(std::time::Instant::now() + Duration::from_secs(4));
@@ -7349,11 +8834,39 @@ impl eframe::App for App {
             _ => {}
         }
 
-        let connected = self.shared.connected.load(Ordering::Relaxed);
+        // sessions in other tabs that ended on their own: book the time,
+        // close the tab and say why
+        let mut i = 0;
+        while i < self.tabs.len() {
+            let t = &self.tabs[i];
+            if i == self.front || t.session.is_none() || t.sess.alive() {
+                i += 1;
+                continue;
+            }
+            let t = self.tabs.remove(i);
+            if i < self.front {
+                self.front -= 1;
+            }
+            if let Some((id, started)) = t.session {
+                self.book.ended(&id, started.elapsed().as_secs());
+                let why = t.sess.viewer_status.lock().unwrap().clone();
+                self.hint = format!("{}: {}", partners::pretty_id(&id), why);
+                self.hint_until = Some(std::time::Instant::now() + Duration::from_secs(8));
+            }
+        }
+
+        let mut connected = self.tab().sess.connected.load(Ordering::Relaxed);
         if !connected {
-            if self.session.is_some() && !self.shared.connecting.load(Ordering::Relaxed) {
+            if self.tab().session.is_some() && !self.tab().sess.connecting.load(Ordering::Relaxed) {
                 // the session died on its own - still book the time
+                let id = self.tab().session.as_ref().map(|s| s.0.clone()).unwrap_or_default();
                 self.close_session();
+                if self.tabs.len() > 1 {
+                    let why = self.tab().sess.viewer_status.lock().unwrap().clone();
+                    self.drop_tab();
+                    self.hint = format!("{}: {}", partners::pretty_id(&id), why);
+                    self.hint_until = Some(std::time::Instant::now() + Duration::from_secs(8));
+                }
             }
             if vinput::is_active() {
                 vinput::set_active(false);
@@ -7363,6 +8876,9 @@ impl eframe::App for App {
             // im Vollbild.
             self.full = false;
         }
+        // Reiter, die noch verbinden, stehen im Sitzungsfenster neben denen,
+        // die schon laufen
+        connected |= self.tabs.iter().any(|t| t.sess.connected.load(Ordering::Relaxed));
 
         // Das Hauptfenster bleibt immer das Hauptfenster: Geraete, Meet und
         // Einstellungen sind auch waehrend einer laufenden Sitzung bedienbar.
@@ -7395,16 +8911,9 @@ impl eframe::App for App {
         // bleibt das Hauptfenster benutzbar (zweite Sitzung aufmachen, Datei
         // suchen), und Vollbild betrifft nur den entfernten Bildschirm.
         if connected {
-            let title = match self.shared.host_peer.lock().unwrap().clone() {
-                s if s.is_empty() => i18n::t("sess.window").to_string(),
-                _ => {
-                    let id = self.partner_id.clone();
-                    if id.is_empty() {
-                        i18n::t("sess.window").to_string()
-                    } else {
-                        format!("{} - {}", i18n::t("sess.window"), partners::pretty_id(&id))
-                    }
-                }
+            let title = match self.tab_id(self.front) {
+                Some(id) => format!("{} - {}", i18n::t("sess.window"), self.tab_label(id)),
+                None => i18n::t("sess.window").to_string(),
             };
             let mut closed = false;
             ctx.show_viewport_immediate(
@@ -7417,7 +8926,7 @@ impl eframe::App for App {
                     // Ablegen muss HIER gelesen werden - das Sitzungsfenster
                     // hat einen eigenen Eingang, das Hauptfenster bekam die
                     // Dateien nie zu sehen.
-                    self.handle_drops(vctx);
+                    self.handle_drops(vctx, true);
                     self.session_ui(vctx);
                     if vctx.input(|i| i.viewport().close_requested()) {
                         closed = true;
@@ -7426,7 +8935,43 @@ impl eframe::App for App {
                 },
             );
             if closed {
-                self.stop_session();
+                // das Fenster zu beendet alle Reiter
+                for _ in 0..self.tabs.len() {
+                    self.stop_session();
+                }
+            }
+        }
+
+        // Chat der eingehenden Sitzung: ein kleines Fenster, das ueber allem
+        // anderen liegt - wer am Host arbeitet, soll die Nachricht sehen,
+        // ohne erst FreeViewer zu suchen.
+        if self.shared.chat_open.load(Ordering::Relaxed) {
+            let peer = self.shared.chat.lock().unwrap().peer.clone();
+            let mut closed = false;
+            ctx.show_viewport_immediate(
+                egui::ViewportId::from_hash_of("fv_host_chat"),
+                egui::ViewportBuilder::default()
+                    .with_title(format!("{} - {}", i18n::t("chat.title"), peer))
+                    .with_inner_size([360.0, 420.0])
+                    .with_min_inner_size([260.0, 220.0])
+                    .with_window_level(egui::WindowLevel::AlwaysOnTop),
+                |vctx, _class| {
+                    egui::CentralPanel::default().show(vctx, |ui| {
+                        let send = chat::widget(ui, &mut self.shared.chat.lock().unwrap(), &peer);
+                        if let Some(m) = send {
+                            if let Some(out) = self.shared.chat_out.lock().unwrap().as_ref() {
+                                out(m);
+                            }
+                        }
+                    });
+                    if vctx.input(|i| i.viewport().close_requested()) {
+                        closed = true;
+                    }
+                    vctx.request_repaint_after(Duration::from_millis(250));
+                },
+            );
+            if closed {
+                self.shared.chat_open.store(false, Ordering::Relaxed);
             }
         }
 
@@ -7558,15 +9103,151 @@ struct BarActs {
     res: Option<(u32, u32)>,
     special: Option<u8>,
     pick: bool,
+    pick_dir: bool,
+    /// Dateimanager auf- bzw. zuklappen.
+    browse: bool,
+    /// Terminal des Hosts auf- bzw. zuklappen.
+    terminal: bool,
+    /// Systeminfo des Hosts auf- bzw. zuklappen.
+    sysinfo: bool,
+    /// Prozessliste des Hosts auf- bzw. zuklappen.
+    procs: bool,
+    /// Chat auf- bzw. zuklappen.
+    chat: bool,
+    /// Aufnahme der Sitzung starten bzw. beenden.
+    record: bool,
     open_dir: bool,
     toggle_full: bool,
     toggle_pin: bool,
+    /// Schrift verlustfrei (an) oder alles als JPEG (aus).
+    sharp: Option<bool>,
     /// Wie weit der Griff der schwebenden Leiste gezogen wurde.
     drag: f32,
+    /// Andere Ansicht (einpassen, 1:1, Zoom, Vollbild mit Raendern).
+    scale: Option<partners::Scale>,
+    /// "Nur ansehen" ein- oder ausschalten.
+    view_only: Option<bool>,
+    /// Zeichenwerkzeug waehlen (`None` darin = wieder fernsteuern).
+    draw: Option<Option<u8>>,
+    /// Alle Zeichnungen wegwischen.
+    draw_clear: bool,
+    /// Privatmodus des Hosts ein- oder ausschalten.
+    privacy: Option<bool>,
+    /// Reiter nach vorn holen bzw. seine Sitzung beenden.
+    tab: Option<usize>,
+    close_tab: Option<usize>,
+}
+
+/// Vergroesserungen im Menue "Ansicht", in Prozent.
+const ZOOMS: [u16; 6] = [50, 75, 125, 150, 200, 300];
+
+fn game_mode(sess: &Arc<Session>) -> bool {
+    sess.game_mode()
+}
+
+/// Eine ausgehende Sitzung im Sitzungsfenster, mit allem, was die
+/// Oberflaeche zu ihr weiss.
+struct Tab {
+    sess: Arc<Session>,
+    viewer_task: Option<tokio::task::JoinHandle<()>>,
+    /// Running session: partner id + when it started.
+    session: Option<(String, std::time::Instant)>,
+    tex: Option<egui::TextureHandle>,
+    last_seq: u64,
+    last_mods: egui::Modifiers,
+    /// Dateimanager der Sitzung.
+    fm: FileMgr,
+    /// Terminal-Leiste der Sitzung ist offen.
+    term_open: bool,
+    /// Systeminfo des Hosts wird rechts angezeigt.
+    info_open: bool,
+    /// Prozessliste des Hosts ist offen.
+    procs_open: bool,
+    /// Chat mit dem Host wird rechts angezeigt.
+    chat_open: bool,
+    /// Zeichenwerkzeug und was damit auf den Host-Bildschirm gemalt wurde.
+    pad: annot::Pad,
+    /// Eingabe fuer eine neue Weiterleitung (lokal:ziel:port) und ihr Fehler.
+    tun_spec: String,
+    tun_err: String,
+    /// Wie das Bild gezeigt wird und ob nur zugeschaut wird - pro Geraet im
+    /// Adressbuch gemerkt.
+    scale: partners::Scale,
+    view_only: bool,
+    /// Verschiebung des vergroesserten Bildes (Zoom), in Punkten.
+    pan: egui::Vec2,
+    /// Wann das letzte Bild ankam - fuer den Hinweis "kein Bild".
+    frame_at: std::time::Instant,
+}
+
+impl Tab {
+    fn new() -> Self {
+        Self {
+            sess: Arc::new(Session::new()),
+            viewer_task: None,
+            session: None,
+            tex: None,
+            last_seq: 0,
+            last_mods: egui::Modifiers::default(),
+            fm: FileMgr::default(),
+            term_open: false,
+            info_open: false,
+            procs_open: false,
+            chat_open: false,
+            pad: annot::Pad::default(),
+            tun_spec: String::new(),
+            tun_err: String::new(),
+            scale: partners::Scale::Fit,
+            view_only: false,
+            pan: egui::Vec2::ZERO,
+            frame_at: std::time::Instant::now(),
+        }
+    }
+}
+
+/// Dateimanager der Sitzung: links dieser Rechner, rechts der Host.
+#[derive(Default)]
+struct FileMgr {
+    open: bool,
+    /// Ordner links und was darin liegt.
+    local: std::path::PathBuf,
+    local_list: Vec<proto::FsEntry>,
+    local_err: String,
+    /// Gewaehlte Zeile links bzw. rechts (Name).
+    sel_local: Option<String>,
+    sel_remote: Option<String>,
+    /// Eingabe fuer Umbenennen und neuen Ordner auf dem Host.
+    name: String,
+    /// Loeschen wurde einmal geklickt und wartet auf die Bestaetigung.
+    del_armed: bool,
+    /// Zuletzt gesehener Stand von `Session::clip_pasted`.
+    pasted: u32,
+}
+
+/// Auswahl fuer das Sendelimit der Dateiuebertragung (Bytes/s, 0 = frei).
+const XFER_LIMITS: [u64; 6] = [0, 256 << 10, 1 << 20, 2 << 20, 5 << 20, 10 << 20];
+
+fn rate_label(r: u64) -> String {
+    if r == 0 {
+        "ohne Limit".to_string()
+    } else {
+        format!("max. {}/s", human_size(r))
+    }
 }
 
-fn game_mode(shared: &Arc<Shared>) -> bool {
-    shared.game_mode()
+/// "1.4 MB" fuer die Dateilisten.
+fn human_size(n: u64) -> String {
+    const K: f64 = 1024.0;
+    let f = n as f64;
+    if f < K {
+        format!("{} B", n)
+    } else if f < K * K {
+        format!("{:.1} kB", f / K)
+    } else if f < K * K * K {
+        format!("{:.1} MB", f / (K * K))
+    } else {
+        format!("{:.1} GB", f / (K * K * K))
+    }
 }
 
 /// Was im "Geraet hinzufuegen"-Fenster steht, solange es offen ist.
@@ -7991,6 +9672,16 @@ fn save_shot(img: &egui::ColorImage, path: &std::path::Path) {
 
 // ------------------------------------------------------------ kleine Helfer
 
+/// Eine Zeile im Dateimanager: Ordner mit Schraegstrich, Dateien mit Groesse.
+fn fm_row(ui: &mut egui::Ui, e: &proto::FsEntry, sel: bool) -> egui::Response {
+    let text = if e.dir {
+        format!("{}/", e.name)
+    } else {
+        format!("{}    {}", e.name, human_size(e.size))
+    };
+    ui.selectable_label(sel, text)
+}
+
 /// Symbolknopf ohne Rahmen - Hover und Druck kommen aus der Palette.
 fn zeigefinger(r: egui::Response) -> egui::Response {
     r.on_hover_cursor(egui::CursorIcon::PointingHand)
@@ -55,6 +55,27 @@ pub struct Partner {
     /// entferntes Geraet nicht beim naechsten Abgleich wieder auftaucht.
     #[serde(default)]
     pub deleted: bool,
+    /// Wie das Bild dieses Geraets gezeigt wird.
+    #[serde(default)]
+    pub scale: Scale,
+    /// Nur zuschauen: Maus und Tastatur gehen nicht an das Geraet.
+    #[serde(default)]
+    pub view_only: bool,
+}
+
+/// Wie der Viewer das Bild des anderen Rechners zeigt.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Scale {
+    /// Ins Fenster einpassen.
+    #[default]
+    Fit,
+    /// Ein Pixel dort ist ein Pixel hier, mit Bildlaufleisten.
+    Actual,
+    /// Feste Vergroesserung in Prozent; verschoben wird mit der mittleren
+    /// Maustaste.
+    Zoom(u16),
+    /// Vollbild, eingepasst, mit schwarzen Raendern.
+    Letterbox,
 }
 
 impl Partner {
@@ -311,6 +332,21 @@ impl Book {
         self.save();
     }
 
+    /// Ansicht merken. Die bleibt auf diesem Rechner und aendert `at` nicht -
+    /// sonst wuerde ein Klick auf "1:1" beim Abgleich einen neueren Namen
+    /// vom Konto ueberstimmen.
+    pub fn set_view(&mut self, id: &str, scale: Scale, view_only: bool) {
+        let Some(e) = self.entries.iter_mut().find(|p| p.id == id) else {
+            return;
+        };
+        if e.scale == scale && e.view_only == view_only {
+            return;
+        }
+        e.scale = scale;
+        e.view_only = view_only;
+        self.save();
+    }
+
     /// Alle vorhandenen Ordner, alphabetisch.
     pub fn groups(&self) -> Vec<String> {
         let mut v: Vec<String> = self
@@ -412,6 +448,7 @@ impl Book {
                         at: r.at,
                         deleted: r.deleted,
                         secret: None,
+                        ..Default::default()
                     });
                     changed = true;
                 }
@@ -548,6 +585,24 @@ mod tests {
         assert_eq!(wieder.sorted()[0].id, "222222222");
     }
 
+    #[test]
+    fn die_ansicht_bleibt_pro_geraet_und_ohne_zeitstempel() {
+        eigener_ordner("ansicht");
+        let mut b = Book::default();
+        b.entries = vec![Partner {
+            id: "444444444".into(),
+            at: 7,
+            ..Default::default()
+        }];
+        b.set_view("444444444", Scale::Zoom(150), true);
+        let wieder = Book::load();
+        let e = wieder.get("444444444").unwrap();
+        assert_eq!((e.scale, e.view_only, e.at), (Scale::Zoom(150), true, 7));
+        // aeltere Dateien kennen die Felder nicht
+        let alt: Partner = serde_json::from_str(r#"{"id":"1"}"#).unwrap();
+        assert_eq!((alt.scale, alt.view_only), (Scale::Fit, false));
+    }
+
     fn dev(id: &str, name: &str, at: u64) -> SyncDevice {
         SyncDevice {
             id: id.into(),
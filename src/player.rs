@@ -0,0 +1,602 @@
+//! `--play file`: plays a session recording in a window of its own.
+//!
+//! The picture is painted by the viewer's `Canvas` (JPEG frames and tiles)
+//! or the H.264 decoder, exactly as it arrived. Seeking starts over from
+//! the last keyframe before the new position. The input of the viewer is
+//! listed beside the picture and marked on the timeline. "Als Video
+//! speichern" hands the file to `export` on a thread of its own.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::proto::{self, Msg};
+use crate::record::{Recording, Side};
+use crate::viewer::{is_screen_msg, Canvas};
+
+const SPEEDS: [f32; 5] = [0.5, 1.0, 2.0, 4.0, 8.0];
+/// Typing closer together than this becomes one line in the list.
+const TYPING_GAP: u32 = 1500;
+
+/// Something the viewer did, for the list and the timeline.
+pub struct Event {
+    pub t: u32,
+    pub text: String,
+}
+
+/// "3:07" or "1:02:03" for `ms`.
+pub fn clock(ms: u32) -> String {
+    let s = ms / 1000;
+    if s >= 3600 {
+        format!("{}:{:02}:{:02}", s / 3600, s % 3600 / 60, s % 60)
+    } else {
+        format!("{}:{:02}", s / 60, s % 60)
+    }
+}
+
+/// The printable character a key press types, if any.
+fn typed(m: &Msg) -> Option<char> {
+    match *m {
+        Msg::Key {
+            code,
+            named: false,
+            down: true,
+        } => char::from_u32(code).filter(|c| !c.is_control()),
+        Msg::Key {
+            code: proto::KEY_SPACE,
+            named: true,
+            down: true,
+        } => Some(' '),
+        // letters and digits have their ASCII code as virtual key
+        Msg::KeyVk { vk, down: true, .. } if (0x30..=0x5A).contains(&vk) => {
+            char::from_u32(vk as u32).map(|c| c.to_ascii_lowercase())
+        }
+        Msg::KeyVk {
+            vk: 0x20,
+            down: true,
+            ..
+        } => Some(' '),
+        _ => None,
+    }
+}
+
+/// One line for everything but typing; moves and releases say nothing.
+fn describe(m: &Msg) -> Option<String> {
+    Some(match *m {
+        Msg::MouseButton { button, down: true } => match button {
+            0 => "Klick links".to_string(),
+            1 => "Klick rechts".to_string(),
+            2 => "Klick Mitte".to_string(),
+            b => format!("Maustaste {}", b + 1),
+        },
+        Msg::Wheel { lines } => format!("Mausrad {:+}", lines),
+        Msg::Key {
+            code,
+            named: true,
+            down: true,
+        } => match code {
+            proto::KEY_ENTER => "Eingabetaste".to_string(),
+            proto::KEY_BACKSPACE => "Rueckschritt".to_string(),
+            proto::KEY_TAB => "Tab".to_string(),
+            proto::KEY_ESCAPE => "Esc".to_string(),
+            proto::KEY_DELETE => "Entf".to_string(),
+            proto::KEY_SHIFT | proto::KEY_CTRL | proto::KEY_ALT | proto::KEY_META => return None,
+            c if (proto::KEY_F1..proto::KEY_F1 + 12).contains(&c) => {
+                format!("F{}", c - proto::KEY_F1 + 1)
+            }
+            c => format!("Taste {}", c),
+        },
+        Msg::KeyVk { vk, down: true, .. } => match vk {
+            0x0D => "Eingabetaste".to_string(),
+            0x08 => "Rueckschritt".to_string(),
+            0x09 => "Tab".to_string(),
+            0x1B => "Esc".to_string(),
+            0x2E => "Entf".to_string(),
+            // Shift, Strg, Alt and the Windows keys only count with others
+            0x10..=0x12 | 0xA0..=0xA5 | 0x5B | 0x5C => return None,
+            0x70..=0x7B => format!("F{}", vk - 0x6F),
+            v => format!("Taste 0x{:02X}", v),
+        },
+        Msg::Special { code } => match code {
+            proto::SPECIAL_CAD => "Strg+Alt+Entf".to_string(),
+            proto::SPECIAL_TASKMGR => "Task-Manager".to_string(),
+            proto::SPECIAL_WIN => "Windows-Taste".to_string(),
+            proto::SPECIAL_ALTTAB => "Alt+Tab".to_string(),
+            proto::SPECIAL_LOCK => "Sperren".to_string(),
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
+
+/// The viewer's input of a recording as a list, typing merged into words.
+pub fn events(rec: &mut Recording) -> Vec<Event> {
+    let mut out: Vec<Event> = Vec::new();
+    let mut typing: Option<(u32, u32)> = None; // index in out, time of last key
+    for i in 0..rec.entries.len() {
+        let e = rec.entries[i];
+        if e.from != Side::Viewer {
+            continue;
+        }
+        let Some(m) = rec.msg(i) else {
+            continue;
+        };
+        if let Some(c) = typed(&m) {
+            match typing {
+                Some((at, last)) if e.t - last < TYPING_GAP => {
+                    out[at as usize].text.push(c);
+                    typing = Some((at, e.t));
+                }
+                _ => {
+                    typing = Some((out.len() as u32, e.t));
+                    out.push(Event {
+                        t: e.t,
+                        text: format!("Eingabe: {}", c),
+                    });
+                }
+            }
+        } else if let Some(text) = describe(&m) {
+            typing = None;
+            out.push(Event { t: e.t, text });
+        }
+    }
+    out
+}
+
+/// The picture of a recording at some point: what the host sent, painted
+/// by the viewer's `Canvas` or the H.264 decoder. Shared by the player and
+/// the video export.
+pub(crate) struct Screen {
+    pub(crate) canvas: Canvas,
+    video: Option<crate::h264::Decoder>,
+    rgba: Vec<u8>,
+    /// Remote cursor in 1/10000 of the picture, and whether it shows.
+    pub(crate) cursor: (i32, i32, bool),
+    /// The canvas changed since this was last cleared.
+    pub(crate) dirty: bool,
+    /// Why part of the recording could not be shown.
+    pub(crate) err: String,
+}
+
+impl Screen {
+    pub(crate) fn new() -> Self {
+        Self {
+            canvas: Canvas::new(),
+            video: None,
+            rgba: Vec::new(),
+            cursor: (0, 0, false),
+            dirty: false,
+            err: String::new(),
+        }
+    }
+
+    /// Forgets the picture, to paint again from a keyframe.
+    fn restart(&mut self) {
+        self.canvas = Canvas::new();
+        self.video = None;
+        self.dirty = true;
+    }
+
+    /// Paints one message of the host.
+    pub(crate) fn paint(&mut self, m: Msg) {
+        match m {
+            Msg::Cursor { x, y, visible } => self.cursor = (x, y, visible),
+            Msg::Video {
+                width,
+                height,
+                key,
+                data,
+            } => {
+                let stale = self.video.as_ref().map(|d| d.size() != (width, height));
+                if stale.unwrap_or(true) {
+                    if !key {
+                        return;
+                    }
+                    match crate::h264::Decoder::new(width, height) {
+                        Ok(d) => self.video = Some(d),
+                        Err(e) => {
+                            self.err = format!("H.264 kann hier nicht abgespielt werden: {}", e);
+                            return;
+                        }
+                    }
+                }
+                let Some(d) = self.video.as_mut() else {
+                    return;
+                };
+                match d.decode(&data, &mut self.rgba) {
+                    Ok(Some((w, h))) => {
+                        self.canvas.set_full(w, h, std::mem::take(&mut self.rgba));
+                        self.dirty = true;
+                    }
+                    Ok(None) => {}
+                    Err(_) => self.video = None,
+                }
+            }
+            m if is_screen_msg(&m) => self.dirty |= self.canvas.apply(&m),
+            _ => {}
+        }
+    }
+
+    /// Whether there is a whole picture to show.
+    pub(crate) fn has_picture(&self) -> bool {
+        let c = &self.canvas;
+        c.w > 0 && c.rgba.len() == c.w as usize * c.h as usize * 4
+    }
+}
+
+/// A running "save as video" of the player.
+struct Export {
+    dst: std::path::PathBuf,
+    /// How far it got, in ms of the recording.
+    done: Arc<AtomicU32>,
+    result: Arc<Mutex<Option<Result<u32, String>>>>,
+}
+
+pub struct Player {
+    rec: Recording,
+    path: PathBuf,
+    title: String,
+    events: Vec<Event>,
+    screen: Screen,
+    /// Entries painted so far.
+    next: usize,
+    /// Position in ms.
+    pos: f64,
+    playing: bool,
+    speed: f32,
+    tex: Option<egui::TextureHandle>,
+    tick: Instant,
+    export: Option<Export>,
+    /// What the last export came to.
+    note: String,
+}
+
+impl Player {
+    pub fn new(mut rec: Recording, path: &Path) -> Self {
+        let title = format!(
+            "{} - {} ({})",
+            path.file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            rec.peer,
+            crate::record::stamp(rec.started / 1000).replace('_', " ")
+        );
+        let mut screen = Screen::new();
+        if rec.cut {
+            screen.err = "Die Aufnahme endet mitten in einer Nachricht - gespielt wird bis dahin."
+                .to_string();
+        }
+        Self {
+            events: events(&mut rec),
+            rec,
+            path: path.to_path_buf(),
+            title,
+            screen,
+            next: 0,
+            pos: 0.0,
+            playing: true,
+            speed: 1.0,
+            tex: None,
+            tick: Instant::now(),
+            export: None,
+            note: String::new(),
+        }
+    }
+
+    /// File, partner and date, for the window title.
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Paints everything up to `t`. A jump starts over from the keyframe
+    /// before it; playing on just paints what came since.
+    fn play_to(&mut self, t: u32, jump: bool) {
+        let want = self.rec.count_at(t);
+        let key = self.rec.key_before(want);
+        if want < self.next || (jump && key > self.next) {
+            self.next = key;
+            self.screen.restart();
+        }
+        while self.next < want {
+            let i = self.next;
+            self.next += 1;
+            if self.rec.entries[i].from != Side::Host {
+                continue;
+            }
+            if let Some(m) = self.rec.msg(i) {
+                self.screen.paint(m);
+            }
+        }
+    }
+
+    fn seek(&mut self, t: f64) {
+        self.pos = t.clamp(0.0, self.rec.duration() as f64);
+        self.play_to(self.pos as u32, true);
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui) {
+        let dur = self.rec.duration();
+        ui.horizontal(|ui| {
+            let label = if self.playing { "\u{23f8}" } else { "\u{25b6}" };
+            if ui.button(label).clicked() {
+                if !self.playing && self.pos as u32 >= dur {
+                    self.seek(0.0);
+                }
+                self.playing = !self.playing;
+            }
+            ui.label(format!("{} / {}", clock(self.pos as u32), clock(dur)));
+            ui.separator();
+            for s in SPEEDS {
+                if ui
+                    .selectable_label(self.speed == s, format!("{}x", s))
+                    .clicked()
+                {
+                    self.speed = s;
+                }
+            }
+            ui.separator();
+            self.export_button(ui, dur);
+            if !self.screen.err.is_empty() {
+                ui.separator();
+                ui.colored_label(egui::Color32::from_rgb(230, 170, 90), &self.screen.err);
+            }
+        });
+
+        // timeline: the position, and a tick per input event
+        let (rect, resp) = ui.allocate_exact_size(
+            egui::vec2(ui.available_width(), 26.0),
+            egui::Sense::click_and_drag(),
+        );
+        let painter = ui.painter_at(rect);
+        let p = crate::theme::palette();
+        painter.rect_filled(rect, 3.0, p.card);
+        let x_of = |t: u32| rect.left() + rect.width() * (t as f32 / dur.max(1) as f32);
+        for e in &self.events {
+            let x = x_of(e.t);
+            painter.line_segment(
+                [
+                    egui::pos2(x, rect.top() + 4.0),
+                    egui::pos2(x, rect.bottom() - 4.0),
+                ],
+                egui::Stroke::new(1.0, egui::Color32::from_rgb(240, 200, 90)),
+            );
+        }
+        let x = x_of(self.pos as u32);
+        painter.rect_filled(
+            egui::Rect::from_min_max(rect.min, egui::pos2(x, rect.bottom())),
+            3.0,
+            p.accent.gamma_multiply(0.35),
+        );
+        painter.line_segment(
+            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+            egui::Stroke::new(2.0, p.accent),
+        );
+        if let Some(at) = resp.interact_pointer_pos() {
+            let f = ((at.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+            self.seek(f as f64 * dur as f64);
+        }
+    }
+
+    /// "Als Video speichern", or how far the export is.
+    fn export_button(&mut self, ui: &mut egui::Ui, dur: u32) {
+        if let Some(x) = &self.export {
+            let done = x.result.lock().unwrap().take();
+            match done {
+                None => {
+                    let f = x.done.load(Ordering::Relaxed) as f32 / dur.max(1) as f32;
+                    ui.add(
+                        egui::ProgressBar::new(f.min(1.0))
+                            .desired_width(140.0)
+                            .show_percentage(),
+                    );
+                    ui.ctx()
+                        .request_repaint_after(std::time::Duration::from_millis(200));
+                    return;
+                }
+                Some(Ok(frames)) => {
+                    self.note = crate::i18n::tf("play.exported", &x.dst.display().to_string());
+                    self.note.push_str(&format!(" ({} Bilder)", frames));
+                }
+                Some(Err(e)) => self.note = format!("Export fehlgeschlagen: {}", e),
+            }
+            self.export = None;
+        }
+        if ui
+            .button(crate::i18n::t("play.export"))
+            .on_hover_text(crate::i18n::t("play.export_tip"))
+            .clicked()
+        {
+            let name = self
+                .path
+                .with_extension("avi")
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if let Some(dst) = rfd::FileDialog::new()
+                .add_filter("AVI", &["avi"])
+                .set_file_name(name)
+                .save_file()
+            {
+                let x = Export {
+                    dst: dst.clone(),
+                    done: Arc::new(AtomicU32::new(0)),
+                    result: Arc::new(Mutex::new(None)),
+                };
+                let (src, done, result) = (self.path.clone(), x.done.clone(), x.result.clone());
+                std::thread::spawn(move || {
+                    let r = crate::export::export(&src, &dst, crate::export::FPS, true, &mut |t| {
+                        done.store(t, Ordering::Relaxed)
+                    });
+                    *result.lock().unwrap() = Some(r.map_err(|e| e.to_string()));
+                });
+                self.note.clear();
+                self.export = Some(x);
+            }
+        }
+        if !self.note.is_empty() {
+            ui.label(&self.note);
+        }
+    }
+
+    fn event_list(&mut self, ui: &mut egui::Ui) {
+        ui.strong(format!(
+            "{} ({})",
+            crate::i18n::t("play.inputs"),
+            self.events.len()
+        ));
+        ui.separator();
+        let now = self.pos as u32;
+        let mut go = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                for e in &self.events {
+                    let past = e.t <= now;
+                    let text = egui::RichText::new(format!("{}  {}", clock(e.t), e.text));
+                    let text = if past { text } else { text.weak() };
+                    if ui.selectable_label(false, text).clicked() {
+                        go = Some(e.t);
+                    }
+                }
+            });
+        if let Some(t) = go {
+            self.seek(t as f64);
+        }
+    }
+
+    fn picture(&mut self, ui: &mut egui::Ui) {
+        if !self.screen.has_picture() {
+            ui.centered_and_justified(|ui| {
+                ui.label(crate::i18n::t("play.nopic"));
+            });
+            return;
+        }
+        let c = &self.screen.canvas;
+        if self.screen.dirty || self.tex.is_none() {
+            self.screen.dirty = false;
+            let img =
+                egui::ColorImage::from_rgba_unmultiplied([c.w as usize, c.h as usize], &c.rgba);
+            match self.tex.as_mut() {
+                Some(t) => t.set(img, egui::TextureOptions::LINEAR),
+                None => {
+                    self.tex = Some(ui.ctx().load_texture(
+                        "fv_play",
+                        img,
+                        egui::TextureOptions::LINEAR,
+                    ))
+                }
+            }
+        }
+        let Some(tex) = &self.tex else {
+            return;
+        };
+        let [iw, ih] = tex.size();
+        let avail = ui.available_size();
+        let scale = (avail.x / iw as f32).min(avail.y / ih as f32).max(0.05);
+        let size = egui::vec2(iw as f32 * scale, ih as f32 * scale);
+        let rect = ui
+            .vertical_centered(|ui| {
+                ui.add(egui::Image::new(egui::load::SizedTexture::new(
+                    tex.id(),
+                    size,
+                )))
+                .rect
+            })
+            .inner;
+        let (x, y, visible) = self.screen.cursor;
+        if visible {
+            let p = egui::pos2(
+                rect.left() + rect.width() * (x as f32 / 10000.0).clamp(0.0, 1.0),
+                rect.top() + rect.height() * (y as f32 / 10000.0).clamp(0.0, 1.0),
+            );
+            let pts = vec![
+                p,
+                egui::pos2(p.x, p.y + 17.0),
+                egui::pos2(p.x + 4.5, p.y + 12.5),
+                egui::pos2(p.x + 10.5, p.y + 12.0),
+            ];
+            ui.painter().add(egui::Shape::convex_polygon(
+                pts,
+                egui::Color32::WHITE,
+                egui::Stroke::new(1.2, egui::Color32::BLACK),
+            ));
+        }
+    }
+}
+
+impl eframe::App for Player {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let dt = self.tick.elapsed().as_secs_f64() * 1000.0;
+        self.tick = Instant::now();
+        if ctx.input(|i| i.key_pressed(egui::Key::Space)) {
+            self.playing = !self.playing;
+        }
+        if self.playing {
+            let dur = self.rec.duration() as f64;
+            self.pos = (self.pos + dt * self.speed as f64).min(dur);
+            self.play_to(self.pos as u32, false);
+            if self.pos >= dur {
+                self.playing = false;
+            }
+            ctx.request_repaint();
+        }
+
+        egui::TopBottomPanel::bottom("fv_play_bar").show(ctx, |ui| {
+            ui.add_space(4.0);
+            self.controls(ui);
+            ui.add_space(4.0);
+        });
+        egui::SidePanel::right("fv_play_events")
+            .resizable(true)
+            .default_width(240.0)
+            .show(ctx, |ui| self.event_list(ui));
+        egui::CentralPanel::default()
+            .frame(egui::Frame::NONE.fill(egui::Color32::from_rgb(18, 18, 18)))
+            .show(ctx, |ui| self.picture(ui));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_grows_hours_only_when_needed() {
+        assert_eq!(clock(0), "0:00");
+        assert_eq!(clock(187_000), "3:07");
+        assert_eq!(clock(3_723_000), "1:02:03");
+    }
+
+    #[test]
+    fn typing_becomes_words() {
+        let key = |c: char| Msg::Key {
+            code: c as u32,
+            named: false,
+            down: true,
+        };
+        assert_eq!(typed(&key('a')), Some('a'));
+        assert_eq!(
+            typed(&Msg::KeyVk {
+                vk: 0x41,
+                ext: false,
+                down: true
+            }),
+            Some('a')
+        );
+        assert_eq!(typed(&Msg::MouseMove { x: 1, y: 1 }), None);
+        assert_eq!(
+            describe(&Msg::MouseButton {
+                button: 1,
+                down: true
+            })
+            .as_deref(),
+            Some("Klick rechts")
+        );
+        assert!(describe(&Msg::MouseButton {
+            button: 0,
+            down: false
+        })
+        .is_none());
+    }
+}
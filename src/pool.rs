@@ -0,0 +1,159 @@
+//! Fork-join helper for the per-frame work of the host: the CPU downscale
+//! and the compression of the dirty rectangles.
+//!
+//! The jobs borrow the frame, so instead of long-lived threads fed through
+//! channels (which would need owned copies of every crop) each call runs on
+//! scoped threads. Starting a handful of threads costs microseconds, encoding
+//! a 4K frame costs milliseconds, and a `Pool` is only a thread count, cheap
+//! to make for every encoder. Results always come back in input order, so
+//! the bytes on the wire do not depend on how many cores did the work.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Upper bound for `Pool::auto`; beyond this memory bandwidth is the limit.
+pub const MAX_THREADS: usize = 8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Pool {
+    threads: usize,
+}
+
+impl Pool {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+        }
+    }
+
+    /// One thread per core, at most `MAX_THREADS`. `FV_THREADS=n` overrides
+    /// it (benchmarks, or a host that should leave cores to something else).
+    pub fn auto() -> Self {
+        Self::new(auto_threads())
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// `f` applied to every item, results in the order of `items`. Items are
+    /// handed out one at a time, so one big rectangle does not hold up the
+    /// threads that got the small ones.
+    pub fn map<T: Sync, R: Send>(&self, items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+        let n = self.threads.min(items.len());
+        if n <= 1 {
+            return items.iter().map(f).collect();
+        }
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(items.len()));
+        self.run(n, &|| {
+            let mut out = Vec::new();
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                match items.get(i) {
+                    Some(it) => out.push((i, f(it))),
+                    None => break,
+                }
+            }
+            results.lock().unwrap().append(&mut out);
+        });
+        let mut done: Vec<(usize, R)> = results.into_inner().unwrap();
+        done.sort_unstable_by_key(|(i, _)| *i);
+        done.into_iter().map(|(_, r)| r).collect()
+    }
+
+    /// Cuts `out` into bands of `band` bytes (the last one may be shorter)
+    /// and runs `f(band index, band)` for each, spread over the threads.
+    pub fn bands(&self, out: &mut [u8], band: usize, f: impl Fn(usize, &mut [u8]) + Sync) {
+        let parts: Vec<(usize, &mut [u8])> = out.chunks_mut(band.max(1)).enumerate().collect();
+        let n = self.threads.min(parts.len());
+        if n <= 1 {
+            for (i, p) in parts {
+                f(i, p);
+            }
+            return;
+        }
+        let parts = Mutex::new(parts.into_iter());
+        self.run(n, &|| loop {
+            let part = parts.lock().unwrap().next();
+            match part {
+                Some((i, p)) => f(i, p),
+                None => break,
+            }
+        });
+    }
+
+    /// Runs `work` on the caller's thread and on `n - 1` more, and returns
+    /// once all of them are through. `work` has to share out the items
+    /// itself. A panic in one of the threads comes back out of this call.
+    fn run(&self, n: usize, work: &(dyn Fn() + Sync)) {
+        std::thread::scope(|s| {
+            for _ in 1..n {
+                s.spawn(work);
+            }
+            work();
+        });
+    }
+}
+
+/// What `Pool::auto` picks, without starting any threads.
+pub fn auto_threads() -> usize {
+    std::env::var("FV_THREADS")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .map(|n| n.clamp(1, MAX_THREADS))
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(NonZeroUsize::get)
+                .unwrap_or(1)
+                .min(MAX_THREADS)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_keeps_the_input_order() {
+        let items: Vec<u64> = (0..100).collect();
+        for threads in [1, 2, 3, 8] {
+            let out = Pool::new(threads).map(&items, |x| {
+                // uneven work so the threads finish out of order
+                std::thread::sleep(std::time::Duration::from_micros(100 - x));
+                x * 2
+            });
+            assert_eq!(out, items.iter().map(|x| x * 2).collect::<Vec<_>>());
+        }
+        assert!(Pool::new(4).map(&[] as &[u8], |x| *x).is_empty());
+    }
+
+    #[test]
+    fn bands_cover_everything_once() {
+        let mut buf = vec![0u8; 1000];
+        Pool::new(3).bands(&mut buf, 64, |i, b| {
+            for x in b.iter_mut() {
+                *x += i as u8 + 1;
+            }
+        });
+        for (j, x) in buf.iter().enumerate() {
+            assert_eq!(*x as usize, j / 64 + 1);
+        }
+    }
+
+    #[test]
+    fn one_pool_serves_many_and_nested_calls() {
+        let pool = Pool::new(3);
+        let items: Vec<u32> = (0..50).collect();
+        for _ in 0..200 {
+            assert_eq!(pool.map(&items, |x| x + 1)[49], 50);
+        }
+        // a job that uses the same pool again must not wait for itself
+        let sums = pool.map(&[10u32, 20, 30], |&n| {
+            let inner: Vec<u32> = (0..n).collect();
+            pool.map(&inner, |x| *x).iter().sum::<u32>()
+        });
+        assert_eq!(sums, vec![45, 190, 435]);
+    }
+}
@@ -0,0 +1,305 @@
+//! Privacy mode: the host's monitors go black and its local keyboard and
+//! mouse stop working while the viewer works on, for maintenance nobody
+//! should watch or interfere with.
+//!
+//! The "black" is a window over the whole desktop that is kept out of the
+//! capture (`WDA_EXCLUDEFROMCAPTURE`, like the drawing overlay), so the
+//! viewer still sees everything below it. It is click-through, so the
+//! viewer's clicks land on the desktop, and it says in big letters what is
+//! going on. Local input is swallowed by low level hooks; what the session
+//! injects carries the "injected" flag and goes through.
+//!
+//! Everything belongs to one thread and goes away with it: when the session
+//! ends, when its input loop dies, and when the process dies the screen and
+//! the input come back. Ctrl+Alt+Del cannot be blocked and stays the way
+//! out for the person at the machine.
+//!
+//! Without `WDA_EXCLUDEFROMCAPTURE` (Windows before 10 2004) the viewer
+//! would see the black as well, so the mode is refused there.
+
+/// The black screen of one session on the host.
+#[derive(Default)]
+pub struct Curtain {
+    #[cfg(windows)]
+    inner: Option<win::Handle>,
+}
+
+impl Curtain {
+    /// Switches the mode; returns the state the host is really in now.
+    pub fn set(&mut self, on: bool) -> bool {
+        #[cfg(windows)]
+        {
+            if !on {
+                self.inner = None;
+            } else if self.inner.is_none() {
+                match win::start() {
+                    Ok(h) => self.inner = Some(h),
+                    Err(e) => crate::capture::log_line(&format!("Privatmodus: {}", e)),
+                }
+            }
+            self.inner.is_some()
+        }
+        #[cfg(not(windows))]
+        {
+            let _ = on;
+            false
+        }
+    }
+
+    pub fn is_on(&self) -> bool {
+        #[cfg(windows)]
+        {
+            self.inner.is_some()
+        }
+        #[cfg(not(windows))]
+        {
+            false
+        }
+    }
+}
+
+#[cfg(windows)]
+mod win {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+
+    use windows::core::{w, PCWSTR};
+    use windows::Win32::Foundation::{COLORREF, HINSTANCE, HWND, LPARAM, LRESULT, RECT, WPARAM};
+    use windows::Win32::Graphics::Gdi::{
+        BeginPaint, CreateFontIndirectW, DeleteObject, DrawTextW, EndPaint, FillRect,
+        GetStockObject, InvalidateRect, SelectObject, SetBkMode, SetTextColor, BLACK_BRUSH,
+        DT_CENTER, DT_NOPREFIX, DT_WORDBREAK, HBRUSH, HGDIOBJ, LOGFONTW, PAINTSTRUCT, TRANSPARENT,
+    };
+    use windows::Win32::System::LibraryLoader::GetModuleHandleW;
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    const LLKHF_INJECTED: u32 = 0x10;
+    const LLMHF_INJECTED: u32 = 0x01;
+
+    /// Lives as long as the mode is on; dropping it brings everything back
+    /// and waits until it has.
+    pub struct Handle {
+        stop: Arc<AtomicBool>,
+        thread: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            self.stop.store(true, Ordering::Relaxed);
+            if let Some(t) = self.thread.take() {
+                let _ = t.join();
+            }
+        }
+    }
+
+    /// Puts up the curtain. Returns once it is up, or why it is not.
+    pub fn start() -> Result<Handle, String> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let s2 = stop.clone();
+        let thread = std::thread::spawn(move || unsafe { run(&s2, tx) });
+        match rx.recv() {
+            Ok(Ok(())) => Ok(Handle {
+                stop,
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err("Fenster-Thread beendet".into()),
+        }
+    }
+
+    /// Swallows the keys of the local keyboard.
+    unsafe extern "system" fn key_hook(code: i32, wp: WPARAM, lp: LPARAM) -> LRESULT {
+        if code >= 0 {
+            let info = &*(lp.0 as *const KBDLLHOOKSTRUCT);
+            if info.flags.0 & LLKHF_INJECTED == 0 {
+                return LRESULT(1);
+            }
+        }
+        CallNextHookEx(None, code, wp, lp)
+    }
+
+    /// Swallows the local mouse.
+    unsafe extern "system" fn mouse_hook(code: i32, wp: WPARAM, lp: LPARAM) -> LRESULT {
+        if code >= 0 {
+            let info = &*(lp.0 as *const MSLLHOOKSTRUCT);
+            if info.flags & LLMHF_INJECTED == 0 {
+                return LRESULT(1);
+            }
+        }
+        CallNextHookEx(None, code, wp, lp)
+    }
+
+    unsafe extern "system" fn wndproc(hwnd: HWND, msg: u32, wp: WPARAM, lp: LPARAM) -> LRESULT {
+        if msg == WM_PAINT {
+            paint(hwnd);
+            return LRESULT(0);
+        }
+        DefWindowProcW(hwnd, msg, wp, lp)
+    }
+
+    /// Black, and in the middle what is going on.
+    unsafe fn paint(hwnd: HWND) {
+        let mut ps = PAINTSTRUCT::default();
+        let hdc = BeginPaint(hwnd, &mut ps);
+        let mut rc = RECT::default();
+        let _ = GetClientRect(hwnd, &mut rc);
+        FillRect(hdc, &rc, HBRUSH(GetStockObject(BLACK_BRUSH).0));
+        SetBkMode(hdc, TRANSPARENT);
+        SetTextColor(hdc, COLORREF(0x00E0E0E0));
+
+        // the primary monitor sits at 0,0 of the desktop; the text goes in
+        // its middle, where someone in front of the machine looks
+        let (pw, ph) = (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN));
+        let (ox, oy) = (
+            -GetSystemMetrics(SM_XVIRTUALSCREEN),
+            -GetSystemMetrics(SM_YVIRTUALSCREEN),
+        );
+        let lines = [
+            (crate::i18n::t("privacy.banner_title"), ph / 14, 700),
+            (crate::i18n::t("privacy.banner"), ph / 36, 400),
+        ];
+        let mut y = oy + ph * 2 / 5;
+        for (text, size, weight) in lines {
+            let mut lf = LOGFONTW {
+                lfHeight: -size.max(12),
+                lfWeight: weight,
+                ..Default::default()
+            };
+            for (d, c) in lf.lfFaceName.iter_mut().zip("Segoe UI".encode_utf16()) {
+                *d = c;
+            }
+            let font = CreateFontIndirectW(&lf);
+            let old = SelectObject(hdc, HGDIOBJ(font.0));
+            let mut buf: Vec<u16> = text.encode_utf16().collect();
+            let mut r = RECT {
+                left: ox + pw / 10,
+                top: y,
+                right: ox + pw * 9 / 10,
+                bottom: oy + ph,
+            };
+            DrawTextW(
+                hdc,
+                &mut buf,
+                &mut r,
+                DT_CENTER | DT_WORDBREAK | DT_NOPREFIX,
+            );
+            y += size * 2;
+            SelectObject(hdc, old);
+            let _ = DeleteObject(HGDIOBJ(font.0));
+        }
+        let _ = EndPaint(hwnd, &ps);
+    }
+
+    /// The whole desktop, all monitors.
+    unsafe fn desktop() -> (i32, i32, i32, i32) {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN).max(1),
+            GetSystemMetrics(SM_CYVIRTUALSCREEN).max(1),
+        )
+    }
+
+    unsafe fn run(stop: &AtomicBool, ready: mpsc::Sender<Result<(), String>>) {
+        let Ok(module) = GetModuleHandleW(None) else {
+            let _ = ready.send(Err("kein Modul".into()));
+            return;
+        };
+        let hinst = HINSTANCE(module.0);
+        let class = w!("FreeViewerPrivacy");
+        let wc = WNDCLASSW {
+            lpfnWndProc: Some(wndproc),
+            hInstance: hinst,
+            lpszClassName: class,
+            hbrBackground: HBRUSH(GetStockObject(BLACK_BRUSH).0),
+            ..Default::default()
+        };
+        RegisterClassW(&wc);
+        // layered + transparent = the viewer's clicks go to the desktop
+        let hwnd = match CreateWindowExW(
+            WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE,
+            class,
+            PCWSTR::null(),
+            WS_POPUP,
+            0,
+            0,
+            1,
+            1,
+            HWND::default(),
+            HMENU::default(),
+            hinst,
+            None,
+        ) {
+            Ok(h) => h,
+            Err(e) => {
+                let _ = ready.send(Err(e.to_string()));
+                return;
+            }
+        };
+        // without this the viewer would only see black: then rather not
+        if let Err(e) = SetWindowDisplayAffinity(hwnd, WDA_EXCLUDEFROMCAPTURE) {
+            let _ = DestroyWindow(hwnd);
+            let _ = ready.send(Err(format!("nicht vom Aufnehmen ausnehmbar: {}", e)));
+            return;
+        }
+        let _ = SetLayeredWindowAttributes(hwnd, COLORREF(0), 255, LWA_ALPHA);
+        let keys = SetWindowsHookExW(WH_KEYBOARD_LL, Some(key_hook), None, 0);
+        let mouse = SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_hook), None, 0);
+        let (Ok(keys), Ok(mouse)) = (keys, mouse) else {
+            if let Ok(h) = keys {
+                let _ = UnhookWindowsHookEx(h);
+            }
+            if let Ok(h) = mouse {
+                let _ = UnhookWindowsHookEx(h);
+            }
+            let _ = DestroyWindow(hwnd);
+            let _ = ready.send(Err("Eingabe nicht sperrbar".into()));
+            return;
+        };
+
+        let mut area = (0, 0, 0, 0);
+        let mut last_top = std::time::Instant::now();
+        let _ = ready.send(Ok(()));
+        while !stop.load(Ordering::Relaxed) {
+            // a monitor added or rearranged: cover it, too
+            let now = desktop();
+            if now != area {
+                area = now;
+                let _ = SetWindowPos(
+                    hwnd,
+                    HWND_TOPMOST,
+                    area.0,
+                    area.1,
+                    area.2,
+                    area.3,
+                    SWP_NOACTIVATE | SWP_SHOWWINDOW,
+                );
+                let _ = InvalidateRect(hwnd, None, true);
+            } else if last_top.elapsed() >= std::time::Duration::from_secs(1) {
+                // other always-on-top windows must not get in front
+                let _ = SetWindowPos(
+                    hwnd,
+                    HWND_TOPMOST,
+                    0,
+                    0,
+                    0,
+                    0,
+                    SWP_NOACTIVATE | SWP_NOMOVE | SWP_NOSIZE,
+                );
+                last_top = std::time::Instant::now();
+            }
+            // wake on every input event: the hooks sit in this thread, and
+            // the session's own input goes through them as well
+            MsgWaitForMultipleObjects(None, false, 100, QS_ALLINPUT);
+            let mut msg = MSG::default();
+            while PeekMessageW(&mut msg, HWND::default(), 0, 0, PM_REMOVE).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+        let _ = UnhookWindowsHookEx(keys);
+        let _ = UnhookWindowsHookEx(mouse);
+        let _ = DestroyWindow(hwnd);
+    }
+}
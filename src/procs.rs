@@ -0,0 +1,446 @@
+//! Process manager: the host's running programs as a table in the viewer.
+//!
+//! `ProcListGet` asks for the list, the host answers with `ProcList`
+//! (pid, name, user, CPU, memory). `ProcKill` ends a process and needs
+//! `ident::RIGHT_PROCS` on the host; `ProcStart` runs a command line like
+//! the "Run" box would, which is as much as a shell, so it needs
+//! `ident::RIGHT_SHELL`. Both answer with `ProcResult`. Looking is free -
+//! the desktop shows the same in Task Manager anyway.
+//!
+//! CPU usage is the difference between two looks at the process table,
+//! so the host keeps one `System` per session and the first list after
+//! connecting waits a moment for its second look.
+
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use sysinfo::{
+    Pid, ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind, Users,
+    MINIMUM_CPU_UPDATE_INTERVAL,
+};
+
+use crate::ident::{RIGHT_PROCS, RIGHT_SHELL};
+use crate::proto::{Msg, ProcInfo, MAX_PROCS};
+use crate::shared::Shared;
+
+/// How often an open table asks for a fresh list.
+const REFRESH: Duration = Duration::from_secs(2);
+
+/// True for every message of the process channel.
+pub fn is_proc_msg(m: &Msg) -> bool {
+    matches!(
+        m,
+        Msg::ProcListGet
+            | Msg::ProcList { .. }
+            | Msg::ProcKill { .. }
+            | Msg::ProcStart { .. }
+            | Msg::ProcResult { .. }
+    )
+}
+
+// ------------------------------------------------------------------ host --
+
+struct Table {
+    sys: System,
+    users: Users,
+    /// CPU numbers need an earlier look to compare with.
+    primed: bool,
+}
+
+/// The process side of one session on the host. Cheap to clone; the work
+/// happens on threads so a slow process table never stalls the input loop.
+#[derive(Clone)]
+pub struct Host {
+    shared: Arc<Shared>,
+    send: Arc<dyn Fn(Msg) + Send + Sync>,
+    table: Arc<Mutex<Table>>,
+}
+
+impl Host {
+    pub fn new(shared: Arc<Shared>, send: Arc<dyn Fn(Msg) + Send + Sync>) -> Self {
+        Self {
+            shared,
+            send,
+            table: Arc::new(Mutex::new(Table {
+                sys: System::new(),
+                users: Users::new(),
+                primed: false,
+            })),
+        }
+    }
+
+    pub fn on_msg(&self, m: Msg) {
+        let rights = self.shared.rights.load(Ordering::Relaxed);
+        match m {
+            Msg::ProcListGet => {
+                let h = self.clone();
+                std::thread::spawn(move || {
+                    let procs = h.list();
+                    (h.send)(Msg::ProcList { procs });
+                });
+            }
+            Msg::ProcKill { .. } if rights & RIGHT_PROCS == 0 => {
+                (self.send)(Msg::ProcResult {
+                    ok: false,
+                    msg: "Dieser Computer erlaubt keine Prozesse zu beenden".to_string(),
+                });
+            }
+            Msg::ProcStart { .. } if rights & RIGHT_SHELL == 0 => {
+                (self.send)(Msg::ProcResult {
+                    ok: false,
+                    msg: "Dieser Computer erlaubt keine Programme zu starten".to_string(),
+                });
+            }
+            Msg::ProcKill { pid } => {
+                let h = self.clone();
+                std::thread::spawn(move || {
+                    let (ok, msg) = match h.kill(pid) {
+                        Ok(name) => {
+                            h.shared
+                                .set_host_status(format!("Prozess beendet: {}", name));
+                            (true, format!("{} ({}) beendet", name, pid))
+                        }
+                        Err(e) => (false, e),
+                    };
+                    (h.send)(Msg::ProcResult { ok, msg });
+                });
+            }
+            Msg::ProcStart { cmd } => {
+                let (ok, msg) = match start(&cmd) {
+                    Ok(()) => {
+                        self.shared
+                            .set_host_status(format!("Programm gestartet: {}", cmd));
+                        (true, format!("{} gestartet", cmd))
+                    }
+                    Err(e) => (false, format!("{}: {}", cmd, e)),
+                };
+                (self.send)(Msg::ProcResult { ok, msg });
+            }
+            _ => {}
+        }
+    }
+
+    fn list(&self) -> Vec<ProcInfo> {
+        let mut t = self.table.lock().unwrap();
+        let kind = ProcessRefreshKind::nothing()
+            .with_cpu()
+            .with_memory()
+            .with_user(UpdateKind::OnlyIfNotSet);
+        t.sys
+            .refresh_processes_specifics(ProcessesToUpdate::All, true, kind);
+        if !t.primed {
+            t.primed = true;
+            t.users.refresh();
+            std::thread::sleep(MINIMUM_CPU_UPDATE_INTERVAL);
+            t.sys
+                .refresh_processes_specifics(ProcessesToUpdate::All, true, kind);
+        }
+        let t = &*t;
+        let mut procs: Vec<ProcInfo> = t
+            .sys
+            .processes()
+            .values()
+            // threads show up as processes on Linux; the table wants programs
+            .filter(|p| p.thread_kind().is_none())
+            .map(|p| ProcInfo {
+                pid: p.pid().as_u32(),
+                name: p.name().to_string_lossy().into_owned(),
+                user: p
+                    .user_id()
+                    .and_then(|u| t.users.get_user_by_id(u))
+                    .map(|u| u.name().to_string())
+                    .unwrap_or_default(),
+                cpu: (p.cpu_usage() * 10.0).round() as u32,
+                mem: p.memory(),
+            })
+            .collect();
+        // the busy ones first if there are ever too many
+        procs.sort_by(|a, b| b.cpu.cmp(&a.cpu));
+        procs.truncate(MAX_PROCS);
+        procs
+    }
+
+    /// Ends `pid` and says which program that was.
+    fn kill(&self, pid: u32) -> Result<String, String> {
+        if pid == std::process::id() {
+            return Err("FreeViewer selbst laesst sich so nicht beenden".to_string());
+        }
+        let mut t = self.table.lock().unwrap();
+        let pid = Pid::from_u32(pid);
+        t.sys
+            .refresh_processes(ProcessesToUpdate::Some(&[pid]), true);
+        let Some(p) = t.sys.process(pid) else {
+            return Err(format!("Prozess {} laeuft nicht mehr", pid));
+        };
+        let name = p.name().to_string_lossy().into_owned();
+        if !p.kill() {
+            return Err(format!("{} ({}) laesst sich nicht beenden", name, pid));
+        }
+        Ok(name)
+    }
+}
+
+/// Runs `cmd` through the shell, detached from the host: it keeps running
+/// when the session ends.
+fn start(cmd: &str) -> std::io::Result<()> {
+    use std::process::{Command, Stdio};
+    let cmd = cmd.trim();
+    if cmd.is_empty() {
+        return Err(std::io::Error::other("kein Befehl"));
+    }
+    #[cfg(windows)]
+    let mut c = {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        // "start" gives GUI programs their own window and returns at once
+        let mut c = Command::new("cmd");
+        c.raw_arg(format!("/C start \"\" {}", cmd))
+            .creation_flags(CREATE_NO_WINDOW);
+        c
+    };
+    #[cfg(not(windows))]
+    let mut c = {
+        let mut c = Command::new("sh");
+        c.arg("-c").arg(cmd);
+        c
+    };
+    let mut child = c
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // reap it whenever it ends, no zombies left behind
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+// ---------------------------------------------------------------- viewer --
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Sort {
+    Pid,
+    Name,
+    User,
+    #[default]
+    Cpu,
+    Mem,
+}
+
+/// The table on the viewer side.
+#[derive(Default)]
+pub struct View {
+    pub procs: Vec<ProcInfo>,
+    pub sort: Sort,
+    /// Smallest first; numbers start biggest first, names from A.
+    pub asc: bool,
+    /// Only lines whose name or user contains this.
+    pub filter: String,
+    /// Command line for "start".
+    pub cmd: String,
+    /// Last `ProcResult`: ok and what the host said.
+    pub result: Option<(bool, String)>,
+    /// Kill asked for, waiting for "really?".
+    pub confirm: Option<ProcInfo>,
+    asked: Option<Instant>,
+    loaded: bool,
+}
+
+impl View {
+    pub fn on_msg(&mut self, m: Msg) {
+        match m {
+            Msg::ProcList { procs } => {
+                self.procs = procs;
+                self.loaded = true;
+                self.sort_now();
+            }
+            Msg::ProcResult { ok, msg } => {
+                self.result = Some((ok, msg));
+                // show the change right away
+                self.asked = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// `ProcListGet` when the table is due for a refresh.
+    pub fn poll(&mut self) -> Option<Msg> {
+        if self.asked.is_some_and(|t| t.elapsed() < REFRESH) {
+            return None;
+        }
+        self.asked = Some(Instant::now());
+        Some(Msg::ProcListGet)
+    }
+
+    /// Click on a column head: sort by it, a second click turns it round.
+    pub fn sort_by(&mut self, s: Sort) {
+        if self.sort == s {
+            self.asc = !self.asc;
+        } else {
+            self.sort = s;
+            self.asc = !matches!(s, Sort::Cpu | Sort::Mem);
+        }
+        self.sort_now();
+    }
+
+    fn sort_now(&mut self) {
+        let (s, asc) = (self.sort, self.asc);
+        self.procs.sort_by(|a, b| {
+            let o = match s {
+                Sort::Pid => a.pid.cmp(&b.pid),
+                Sort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                Sort::User => a.user.to_lowercase().cmp(&b.user.to_lowercase()),
+                Sort::Cpu => a.cpu.cmp(&b.cpu),
+                Sort::Mem => a.mem.cmp(&b.mem),
+            };
+            let o = if asc { o } else { o.reverse() };
+            // equal lines stay in a stable order while the numbers move
+            o.then(a.pid.cmp(&b.pid))
+        });
+    }
+
+    fn visible(&self) -> impl Iterator<Item = &ProcInfo> {
+        let f = self.filter.to_lowercase();
+        self.procs.iter().filter(move |p| {
+            f.is_empty() || p.name.to_lowercase().contains(&f) || p.user.to_lowercase().contains(&f)
+        })
+    }
+}
+
+/// Start box, filter and the sortable table. Returns what goes to the host.
+pub fn widget(ui: &mut egui::Ui, view: &mut View) -> Vec<Msg> {
+    use crate::i18n::t;
+    let mut out = Vec::new();
+    out.extend(view.poll());
+    // the refresh has to happen without the mouse moving
+    ui.ctx().request_repaint_after(REFRESH);
+
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut view.filter)
+                .hint_text(t("proc.filter"))
+                .desired_width(160.0),
+        );
+        ui.separator();
+        let r = ui.add(
+            egui::TextEdit::singleline(&mut view.cmd)
+                .hint_text(t("proc.cmd"))
+                .desired_width(220.0),
+        );
+        let enter = r.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        if (ui.button(t("proc.start")).clicked() || enter) && !view.cmd.trim().is_empty() {
+            out.push(Msg::ProcStart {
+                cmd: std::mem::take(&mut view.cmd).trim().to_string(),
+            });
+        }
+        if let Some((ok, msg)) = &view.result {
+            let c = if *ok {
+                egui::Color32::from_rgb(120, 200, 120)
+            } else {
+                egui::Color32::from_rgb(230, 120, 120)
+            };
+            ui.colored_label(c, msg);
+        }
+    });
+
+    if let Some(p) = view.confirm.clone() {
+        ui.horizontal(|ui| {
+            ui.label(format!("{} ({}) {}", p.name, p.pid, t("proc.really")));
+            if ui.button(t("proc.kill")).clicked() {
+                out.push(Msg::ProcKill { pid: p.pid });
+                view.confirm = None;
+            }
+            if ui.button(t("proc.cancel")).clicked() {
+                view.confirm = None;
+            }
+        });
+    }
+    ui.separator();
+
+    if !view.loaded {
+        ui.label(egui::RichText::new(t("proc.wait")).weak());
+        return out;
+    }
+
+    let mut clicked = None;
+    let mut kill = None;
+    egui::ScrollArea::both()
+        .auto_shrink([false, false])
+        .show(ui, |ui| {
+            egui::Grid::new("fv_procs_grid")
+                .num_columns(6)
+                .striped(true)
+                .min_col_width(40.0)
+                .show(ui, |ui| {
+                    for (s, key) in [
+                        (Sort::Name, "proc.name"),
+                        (Sort::Pid, "proc.pid"),
+                        (Sort::User, "proc.user"),
+                        (Sort::Cpu, "proc.cpu"),
+                        (Sort::Mem, "proc.mem"),
+                    ] {
+                        let mut text = t(key).to_string();
+                        if view.sort == s {
+                            text.push_str(if view.asc { " \u{25b2}" } else { " \u{25bc}" });
+                        }
+                        if ui.selectable_label(view.sort == s, text).clicked() {
+                            clicked = Some(s);
+                        }
+                    }
+                    ui.label("");
+                    ui.end_row();
+                    for p in view.visible() {
+                        ui.label(&p.name);
+                        ui.label(p.pid.to_string());
+                        ui.label(&p.user);
+                        ui.label(format!("{:.1} %", p.cpu as f32 / 10.0));
+                        ui.label(crate::human_size(p.mem));
+                        if ui.small_button("x").on_hover_text(t("proc.kill")).clicked() {
+                            kill = Some(p.clone());
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    if let Some(s) = clicked {
+        view.sort_by(s);
+    }
+    if kill.is_some() {
+        view.confirm = kill;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(pid: u32, name: &str, cpu: u32, mem: u64) -> ProcInfo {
+        ProcInfo {
+            pid,
+            name: name.to_string(),
+            user: String::new(),
+            cpu,
+            mem,
+        }
+    }
+
+    #[test]
+    fn columns_sort_and_turn_round() {
+        let mut v = View::default();
+        v.on_msg(Msg::ProcList {
+            procs: vec![p(3, "b", 10, 5), p(1, "C", 500, 1), p(2, "a", 10, 9)],
+        });
+        let pids = |v: &View| v.procs.iter().map(|p| p.pid).collect::<Vec<_>>();
+        // busiest first, ties by pid
+        assert_eq!(pids(&v), vec![1, 2, 3]);
+        v.sort_by(Sort::Mem);
+        assert_eq!(pids(&v), vec![2, 3, 1]);
+        v.sort_by(Sort::Mem);
+        assert_eq!(pids(&v), vec![1, 3, 2]);
+        v.sort_by(Sort::Name);
+        assert_eq!(pids(&v), vec![2, 3, 1]);
+        v.filter = "c".to_string();
+        assert_eq!(v.visible().map(|p| p.pid).collect::<Vec<_>>(), vec![1]);
+    }
+}
@@ -18,6 +18,16 @@ pub struct MonitorInfo {
     pub primary: bool,
 }
 
+/// One line of a directory listing of the remote file browser.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FsEntry {
+    pub name: String,
+    pub dir: bool,
+    pub size: u64,
+    /// seconds since 1970, 0 = unknown
+    pub mtime: u64,
+}
+
 /// One changed rectangle of the current frame (delta update).
 #[derive(Debug, Clone)]
 pub struct Tile {
@@ -25,7 +35,136 @@ pub struct Tile {
     pub y: u32,
     pub w: u32,
     pub h: u32,
-    pub jpeg: Vec<u8>,
+    pub format: TileFormat,
+    pub data: Vec<u8>,
+}
+
+/// How the pixels of a `Tile` are compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileFormat {
+    /// Photos and video, lossy.
+    Jpeg,
+    /// Lossless RGB PNG for sharp content with many colours.
+    Png,
+    /// Lossless: a palette of at most 256 colours followed by a grey PNG
+    /// of palette indices. Text and UI usually end up here.
+    Palette,
+}
+
+impl TileFormat {
+    fn byte(self) -> u8 {
+        match self {
+            TileFormat::Jpeg => 0,
+            TileFormat::Png => 1,
+            TileFormat::Palette => 2,
+        }
+    }
+
+    fn from_byte(b: u8) -> Option<Self> {
+        match b {
+            0 => Some(TileFormat::Jpeg),
+            1 => Some(TileFormat::Png),
+            2 => Some(TileFormat::Palette),
+            _ => None,
+        }
+    }
+}
+
+/// What a `DirEntry` creates on the receiving side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// A directory (also the empty ones, and to carry its time).
+    Dir,
+    /// A symbolic link to a file.
+    Link,
+    /// A symbolic link to a directory (Windows needs to know beforehand).
+    DirLink,
+}
+
+impl EntryKind {
+    fn byte(self) -> u8 {
+        match self {
+            EntryKind::Dir => 0,
+            EntryKind::Link => 1,
+            EntryKind::DirLink => 2,
+        }
+    }
+
+    fn from_byte(b: u8) -> Option<Self> {
+        match b {
+            0 => Some(EntryKind::Dir),
+            1 => Some(EntryKind::Link),
+            2 => Some(EntryKind::DirLink),
+            _ => None,
+        }
+    }
+}
+
+/// One grid tile of the tile cache: the tile at (`x`, `y`) of the canvas and
+/// the hash of its content.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheRef {
+    pub x: u32,
+    pub y: u32,
+    pub hash: u64,
+}
+
+/// One format of a clipboard offer (`CLIP_*`).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ClipItem {
+    pub kind: u8,
+    pub size: u32,
+    pub data: Vec<u8>,
+}
+
+/// What the host tells about itself (`Msg::SysInfo`).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SysReport {
+    /// "Windows 11 Pro 23H2", "Ubuntu 24.04 LTS", ...
+    pub os: String,
+    pub kernel: String,
+    pub host: String,
+    /// the account the host runs under
+    pub user: String,
+    /// FreeViewer version of the host
+    pub version: String,
+    pub cpu: String,
+    /// logical processors
+    pub cores: u32,
+    pub mem_total: u64,
+    pub mem_used: u64,
+    /// seconds since boot
+    pub uptime: u64,
+    pub disks: Vec<DiskInfo>,
+    pub nets: Vec<NetInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DiskInfo {
+    pub mount: String,
+    pub fs: String,
+    pub total: u64,
+    pub free: u64,
+}
+
+/// A network interface with its addresses ("192.168.1.24/24").
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NetInfo {
+    pub name: String,
+    pub mac: String,
+    pub addrs: Vec<String>,
+}
+
+/// One line of the host's process list (`Msg::ProcList`).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProcInfo {
+    pub pid: u32,
+    pub name: String,
+    pub user: String,
+    /// Tenths of a percent of one core (a busy thread on 2 cores is 2000).
+    pub cpu: u32,
+    /// Resident memory in bytes.
+    pub mem: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -48,16 +187,44 @@ pub enum Msg {
     },
     /// Viewer tells the host what it can decode. Sent right after the
     /// handshake. A viewer that never sends this only gets JPEG, which keeps
-    /// older builds working.
-    Caps { h264: bool },
+    /// older builds working. Fields after `h264` were added later and read as
+    /// false when an older viewer leaves them out.
+    Caps { h264: bool, copy: bool, cache: bool },
     /// Only the parts of the frame that changed since the previous one.
     /// `width`/`height` describe the full frame the tiles belong to, so the
     /// viewer can detect a stale canvas and wait for the next keyframe.
+    /// As long as every tile is JPEG this goes out in the original layout,
+    /// so only viewers that asked for lossless tiles ever see another one.
     Tiles {
         width: u32,
         height: u32,
         tiles: Vec<Tile>,
     },
+    /// Part of the canvas moved (scrolling, a dragged window): copy the
+    /// `src` rectangle (x, y, w, h) of the current canvas to `dst` (x, y).
+    /// Comes right before the `Tiles` that fill in the newly revealed strip.
+    CopyRect {
+        width: u32,
+        height: u32,
+        src: (u32, u32, u32, u32),
+        dst: (u32, u32),
+    },
+    /// Paint these tiles from the viewer's tile cache (see `tilecache`).
+    CachePaint {
+        width: u32,
+        height: u32,
+        cells: Vec<CacheRef>,
+    },
+    /// The tiles just painted hold this content - keep a copy under the hash.
+    /// Follows the `Frame`/`Tiles` it refers to.
+    CacheStore {
+        width: u32,
+        height: u32,
+        cells: Vec<CacheRef>,
+    },
+    /// Forget every cached tile. Sent with the keyframe after the host had to
+    /// start over, so both caches are empty at the same moment.
+    CacheReset,
     /// Where the remote mouse pointer is (normalized), so the viewer can draw
     /// it - the duplication API never renders the cursor into the frame.
     Cursor { x: i32, y: i32, visible: bool },
@@ -81,8 +248,27 @@ pub enum Msg {
     Special { code: u8 },
     /// Clipboard text of the sender changed.
     Clipboard { text: String },
+    /// Clipboard of the sender changed and holds more than text. Every
+    /// format comes with its size; `data` is empty when it is bigger than
+    /// `CLIP_INLINE` and has to be fetched with `ClipFetch`.
+    ClipOffer { seq: u32, items: Vec<ClipItem> },
+    /// Asks for format `kind` of offer `seq`.
+    ClipFetch { seq: u32, kind: u8 },
+    /// Answer to `ClipFetch`.
+    ClipData { seq: u32, kind: u8, data: Vec<u8> },
+    /// The sender copied files and folders (names only, the bytes follow
+    /// when the other side pastes).
+    ClipFiles { seq: u32, files: Vec<FsEntry> },
+    /// Paste: send the files of offer `seq`.
+    ClipPull { seq: u32 },
+    /// File transfer `id` belongs to the paste of offer `seq`; sent right
+    /// before its offer.
+    ClipPulling { seq: u32, id: u32 },
     /// Viewer asks the host to switch its capture/input profile.
     SetMode { mode: u8 },
+    /// Viewer wants text and other sharp content lossless (maintenance mode
+    /// only). Older hosts ignore it and keep sending JPEG.
+    SetQuality { lossless: bool },
     /// Host announces every screen it could share plus the active one.
     Monitors { active: u8, list: Vec<MonitorInfo> },
     /// Viewer picks the screen to capture (index into the list above).
@@ -92,10 +278,53 @@ pub enum Msg {
     SetResolution { width: u32, height: u32 },
     /// Host lists the resolutions the captured screen really supports.
     Resolutions { list: Vec<(u32, u32)> },
-    /// Start of a file transfer. Works in both directions.
-    FileOffer { id: u32, name: String, size: u64 },
+    /// Start of a file transfer. Works in both directions. `sha256` of the
+    /// whole file lets the receiver verify it and resume a broken transfer;
+    /// older builds leave it out. Inside a folder (`folder` = id of its
+    /// `DirOffer`, otherwise 0) `name` is the path below the folder with `/`
+    /// between the parts. `mtime`: seconds since 1970, 0 = unknown.
+    /// `codecs`: the `CODEC_*` bits the sender could pack the chunks with
+    /// (0 = raw only, also what older builds mean).
+    FileOffer {
+        id: u32,
+        name: String,
+        size: u64,
+        sha256: Option<[u8; 32]>,
+        folder: u32,
+        mtime: u64,
+        codecs: u8,
+    },
+    /// Start of a folder transfer: `files` files with `size` bytes in all
+    /// follow as `DirEntry`/`FileOffer`, a `FileEnd` with this `id` closes it.
+    DirOffer {
+        id: u32,
+        name: String,
+        files: u32,
+        size: u64,
+    },
+    /// A directory or symbolic link inside folder `dir`. `path` as in
+    /// `FileOffer`, `target` is the link target (empty for directories).
+    DirEntry {
+        dir: u32,
+        path: String,
+        kind: EntryKind,
+        target: String,
+        mtime: u64,
+    },
+    /// Receiver already holds the first `off` bytes from an earlier attempt:
+    /// the sender continues from there (0 = from the start). `codec` is the
+    /// one `CODEC_*` out of the offer the receiver wants, 0 = raw.
+    FileResume { id: u32, off: u64, codec: u8 },
     /// One piece of the file at byte offset `off`.
     FileChunk { id: u32, off: u64, data: Vec<u8> },
+    /// `len` bytes at offset `off`, packed with the codec agreed on in
+    /// `FileResume`. Offsets and `FileAck` always count unpacked bytes.
+    FileChunkZ {
+        id: u32,
+        off: u64,
+        len: u32,
+        data: Vec<u8>,
+    },
     /// Transfer finished (`ok`) or was aborted (reason in `msg`).
     FileEnd { id: u32, ok: bool, msg: String },
     /// Receiver confirms how many bytes it has written (flow control).
@@ -104,6 +333,85 @@ pub enum Msg {
     P2pOffer { token: u64, addrs: Vec<String> },
     /// The direct path is up (or down again) - purely informational.
     P2pState { direct: bool, rtt_ms: u32 },
+    /// Remote file browser, viewer -> host: content of a directory
+    /// ("" = the drives / top level). Every request carries a number `req`
+    /// that comes back in the answer.
+    FsList { req: u32, path: String },
+    /// Host -> viewer: the directory, its parent ("" = top level) and
+    /// whether the viewer may change things.
+    FsListing {
+        req: u32,
+        path: String,
+        parent: String,
+        write: bool,
+        entries: Vec<FsEntry>,
+    },
+    /// Details of one file or directory ...
+    FsStat { req: u32, path: String },
+    /// ... and the answer.
+    FsInfo { req: u32, entry: FsEntry },
+    /// Viewer wants a file or folder of the host. The host answers with
+    /// `FsGetting` and then sends it as a normal transfer with that id.
+    FsGet { req: u32, path: String },
+    FsGetting { req: u32, id: u32 },
+    FsRename { req: u32, from: String, to: String },
+    /// Deletes a file or a whole directory tree.
+    FsDelete { req: u32, path: String },
+    FsMkdir { req: u32, path: String },
+    /// Viewer -> host: the transfer `id` that follows goes into `dir`
+    /// instead of the host's download folder.
+    FsPut { id: u32, dir: String },
+    /// Result of a request that has no answer of its own (or its error).
+    FsDone { req: u32, ok: bool, msg: String },
+    /// Viewer -> host: start a shell on a pseudo terminal of this size.
+    TermOpen { id: u32, rows: u16, cols: u16 },
+    /// Keys (viewer -> host) or screen output (host -> viewer), raw bytes.
+    TermData { id: u32, data: Vec<u8> },
+    TermResize { id: u32, rows: u16, cols: u16 },
+    /// Either side ends the terminal; from the host with the reason (exit
+    /// code, refused, ...).
+    TermClose { id: u32, msg: String },
+    /// Viewer -> host: open a TCP connection to `host:port` (as seen from
+    /// the host) for the forwarded stream `id`.
+    TunOpen { id: u32, host: String, port: u16 },
+    /// ... and whether that worked.
+    TunOpened { id: u32, ok: bool, msg: String },
+    /// Bytes of stream `id`, either direction.
+    TunData { id: u32, data: Vec<u8> },
+    /// The receiver wrote `n` bytes of stream `id` out; the sender may
+    /// have that much more in flight.
+    TunAck { id: u32, n: u32 },
+    /// Stream `id` is closed (either side).
+    TunClose { id: u32 },
+    /// Viewer -> host: tell me about your system.
+    SysInfoGet,
+    SysInfo { report: SysReport },
+    /// Viewer -> host: the running processes, please.
+    ProcListGet,
+    ProcList { procs: Vec<ProcInfo> },
+    /// Viewer -> host: end process `pid`. Needs `ident::RIGHT_PROCS`.
+    ProcKill { pid: u32 },
+    /// Viewer -> host: start a program (a command line as typed into
+    /// "Run"). Needs `ident::RIGHT_SHELL`, it is as much as a shell.
+    ProcStart { cmd: String },
+    /// Host -> viewer: how a kill or start went.
+    ProcResult { ok: bool, msg: String },
+    /// A chat line, either direction. `id` counts the sender's lines, `ts`
+    /// is the sender's clock (unix ms).
+    Chat { id: u32, ts: u64, text: String },
+    /// Chat line `id` of the other side arrived (the delivery receipt).
+    ChatAck { id: u32 },
+    /// Viewer -> host: points of a drawing on the host screen, 0..10000
+    /// over the shared screen like the cursor. The first message with a
+    /// new `id` starts the stroke, later ones add to it. `tool` is one of
+    /// `ANNOT_*`.
+    Annot { id: u32, tool: u8, points: Vec<(u16, u16)> },
+    /// Viewer -> host: wipe all drawings.
+    AnnotClear,
+    /// Viewer -> host: black out the host's monitors and lock its local
+    /// keyboard and mouse (or stop doing so). The host answers with the
+    /// state it really is in; `on: false` to a request means it could not.
+    Privacy { on: bool },
     /// One 20 ms packet of speech: mono, 24 kHz, IMA-ADPCM. Travels in both
     /// directions inside the same encrypted channel as everything else.
     Audio { seq: u32, data: Vec<u8> },    /// The video path lost data, please send a full frame.
@@ -145,6 +453,12 @@ pub const SPECIAL_ALTTAB: u8 = 4; // Alt+Tab
 pub const SPECIAL_LOCK: u8 = 5; // Win+L
 pub const SPECIAL_RELEASE: u8 = 6; // let go of everything the viewer still holds
 
+/// Drawing tools of `Msg::Annot`.
+pub const ANNOT_PEN: u8 = 0;
+pub const ANNOT_ARROW: u8 = 1;
+pub const ANNOT_MARK: u8 = 2;
+pub const ANNOT_LASER: u8 = 3;
+
 const T_SCREEN: u8 = 0x20;
 const T_FRAME: u8 = 0x21;
 const T_TILES: u8 = 0x22;
@@ -161,13 +475,61 @@ const T_MODE: u8 = 0x38;
 const T_MONS: u8 = 0x24;
 const T_VIDEO: u8 = 0x25;
 const T_CAPS: u8 = 0x26;
+const T_COPY: u8 = 0x27;
+const T_CPAINT: u8 = 0x28;
+const T_CSTORE: u8 = 0x29;
+const T_CRESET: u8 = 0x2A;
+const T_TILES2: u8 = 0x2B;
 const T_SETMON: u8 = 0x39;
 const T_SETRES: u8 = 0x3A;
 const T_RESLIST: u8 = 0x3B;
+const T_QUALITY: u8 = 0x3C;
+const T_CLIPOFFER: u8 = 0x3D;
+const T_CLIPFETCH: u8 = 0x3E;
+const T_CLIPDATA: u8 = 0x3F;
+const T_CLIPFILES: u8 = 0x42;
+const T_CLIPPULL: u8 = 0x43;
+const T_CLIPPULLING: u8 = 0x44;
 const T_FOFFER: u8 = 0x50;
 const T_FCHUNK: u8 = 0x51;
 const T_FEND: u8 = 0x52;
 const T_FACK: u8 = 0x53;
+const T_FRESUME: u8 = 0x54;
+const T_DOFFER: u8 = 0x55;
+const T_DENTRY: u8 = 0x56;
+const T_FCHUNKZ: u8 = 0x57;
+const T_FSLIST: u8 = 0x80;
+const T_FSLISTING: u8 = 0x81;
+const T_FSSTAT: u8 = 0x82;
+const T_FSINFO: u8 = 0x83;
+const T_FSGET: u8 = 0x84;
+const T_FSGETTING: u8 = 0x85;
+const T_FSRENAME: u8 = 0x86;
+const T_FSDELETE: u8 = 0x87;
+const T_FSMKDIR: u8 = 0x88;
+const T_FSPUT: u8 = 0x89;
+const T_FSDONE: u8 = 0x8A;
+const T_TERMOPEN: u8 = 0x90;
+const T_TERMDATA: u8 = 0x91;
+const T_TERMRESIZE: u8 = 0x92;
+const T_TERMCLOSE: u8 = 0x93;
+const T_TUNOPEN: u8 = 0xA0;
+const T_TUNOPENED: u8 = 0xA1;
+const T_TUNDATA: u8 = 0xA2;
+const T_TUNACK: u8 = 0xA3;
+const T_TUNCLOSE: u8 = 0xA4;
+const T_SYSINFOGET: u8 = 0xB0;
+const T_SYSINFO: u8 = 0xB1;
+const T_PROCLISTGET: u8 = 0xB2;
+const T_PROCLIST: u8 = 0xB3;
+const T_PROCKILL: u8 = 0xB4;
+const T_PROCSTART: u8 = 0xB5;
+const T_PROCRESULT: u8 = 0xB6;
+const T_CHAT: u8 = 0xC0;
+const T_CHATACK: u8 = 0xC1;
+const T_ANNOT: u8 = 0xC2;
+const T_ANNOTCLEAR: u8 = 0xC3;
+const T_PRIVACY: u8 = 0xC4;
 const T_P2P: u8 = 0x60;
 const T_P2PST: u8 = 0x61;
 const T_NEEDKEY: u8 = 0x62;
@@ -181,16 +543,54 @@ const MAX_TILES: usize = 4096;
 pub const MAX_VIDEO: usize = 8 * 1024 * 1024;
 /// Clipboard transfers are capped so a peer cannot exhaust our memory.
 pub const MAX_CLIP: usize = 256 * 1024;
+/// Clipboard formats of `ClipOffer`.
+pub const CLIP_TEXT: u8 = 0;
+pub const CLIP_HTML: u8 = 1;
+pub const CLIP_RTF: u8 = 2;
+pub const CLIP_PNG: u8 = 3;
+/// Formats up to this size travel inside the offer, bigger ones on demand.
+pub const CLIP_INLINE: usize = 64 * 1024;
+/// Files and folders in one copied selection.
+pub const MAX_CLIP_FILES: usize = 1000;
+
+/// Largest payload of a clipboard format; 0 = unknown format.
+pub fn clip_limit(kind: u8) -> usize {
+    match kind {
+        CLIP_TEXT => MAX_CLIP,
+        CLIP_HTML => 2 * 1024 * 1024,
+        CLIP_RTF => 4 * 1024 * 1024,
+        CLIP_PNG => MAX_VIDEO,
+        _ => 0,
+    }
+}
 /// Upper bound for one file chunk on the wire.
 pub const MAX_CHUNK: usize = 512 * 1024;
 /// Bytes per chunk we actually send.
 pub const CHUNK: usize = 64 * 1024;
+/// File chunks packed with raw deflate (`FileOffer::codecs`, `FileResume`).
+pub const CODEC_DEFLATE: u8 = 1;
 /// A file name never needs more than this.
 pub const MAX_NAME: usize = 512;
+/// A full path on the host.
+pub const MAX_PATH: usize = 4096;
+/// Lines of one directory listing; larger directories are cut off.
+pub const MAX_FS_ENTRIES: usize = 5000;
 const MAX_MONITORS: usize = 32;
 const MAX_ADDRS: usize = 8;
 /// One speech packet is 243 bytes; anything much larger is not ours.
 pub const MAX_AUDIO: usize = 4096;
+/// Terminal output is sent in pieces of at most this size.
+pub const MAX_TERM_DATA: usize = 64 * 1024;
+/// Forwarded TCP data is sent in pieces of at most this size.
+pub const MAX_TUN_DATA: usize = 32 * 1024;
+/// Disks, interfaces and addresses per interface in a `SysReport`.
+pub const MAX_SYS_ITEMS: usize = 64;
+/// Lines of a `ProcList`; more than any desktop runs.
+pub const MAX_PROCS: usize = 8192;
+/// One chat line, in bytes.
+pub const MAX_CHAT: usize = 4096;
+/// Points of one `Annot` message; a stroke is sent in pieces.
+pub const MAX_ANNOT_POINTS: usize = 512;
 
 /// Is this encoded message a video frame? The direct UDP path only carries
 /// those; everything else stays on the reliable relay channel.
@@ -198,6 +598,26 @@ pub fn is_video(encoded: &[u8]) -> bool {
     encoded.first() == Some(&T_VIDEO)
 }
 
+/// A picture that paints the whole screen on its own: a JPEG frame or an
+/// H.264 keyframe. Recordings can be played from there.
+pub fn is_keyframe(encoded: &[u8]) -> bool {
+    match encoded.first() {
+        Some(&T_FRAME) => true,
+        // tag, width, height, key flag
+        Some(&T_VIDEO) => encoded.get(9) == Some(&1),
+        _ => false,
+    }
+}
+
+/// What a session recording keeps: the picture, the cursor, the input
+/// and the voice. Files, shells, clipboard contents and the like stay out.
+pub fn is_recorded(encoded: &[u8]) -> bool {
+    matches!(
+        encoded.first(),
+        Some(&(T_SCREEN..=T_TILES2)) | Some(&(T_MOVE..=T_SPECIAL)) | Some(&T_AUDIO)
+    )
+}
+
 fn pu32(v: &mut Vec<u8>, x: u32) {
     v.extend_from_slice(&x.to_le_bytes());
 }
@@ -207,6 +627,48 @@ fn pi32(v: &mut Vec<u8>, x: i32) {
 fn pu64(v: &mut Vec<u8>, x: u64) {
     v.extend_from_slice(&x.to_le_bytes());
 }
+/// Length prefixed string, cut at `max` bytes.
+fn pstr(v: &mut Vec<u8>, s: &str, max: usize) {
+    let b = s.as_bytes();
+    let n = b.len().min(max);
+    pu32(v, n as u32);
+    v.extend_from_slice(&b[..n]);
+}
+fn pentry(v: &mut Vec<u8>, e: &FsEntry) {
+    pstr(v, &e.name, MAX_NAME);
+    v.push(e.dir as u8);
+    pu64(v, e.size);
+    pu64(v, e.mtime);
+}
+
+fn preport(v: &mut Vec<u8>, r: &SysReport) {
+    for s in [&r.os, &r.kernel, &r.host, &r.user, &r.version, &r.cpu] {
+        pstr(v, s, MAX_NAME);
+    }
+    pu32(v, r.cores);
+    pu64(v, r.mem_total);
+    pu64(v, r.mem_used);
+    pu64(v, r.uptime);
+    let disks = &r.disks[..r.disks.len().min(MAX_SYS_ITEMS)];
+    pu32(v, disks.len() as u32);
+    for d in disks {
+        pstr(v, &d.mount, MAX_NAME);
+        pstr(v, &d.fs, MAX_NAME);
+        pu64(v, d.total);
+        pu64(v, d.free);
+    }
+    let nets = &r.nets[..r.nets.len().min(MAX_SYS_ITEMS)];
+    pu32(v, nets.len() as u32);
+    for n in nets {
+        pstr(v, &n.name, MAX_NAME);
+        pstr(v, &n.mac, MAX_NAME);
+        let addrs = &n.addrs[..n.addrs.len().min(MAX_SYS_ITEMS)];
+        pu32(v, addrs.len() as u32);
+        for a in addrs {
+            pstr(v, a, MAX_NAME);
+        }
+    }
+}
 
 pub fn encode(m: &Msg) -> Vec<u8> {
     let mut v: Vec<u8> = Vec::with_capacity(32);
@@ -233,9 +695,10 @@ pub fn encode(m: &Msg) -> Vec<u8> {
             height,
             tiles,
         } => {
-            let total: usize = tiles.iter().map(|t| t.jpeg.len() + 20).sum();
+            let total: usize = tiles.iter().map(|t| t.data.len() + 21).sum();
             v.reserve(total + 16);
-            v.push(T_TILES);
+            let plain = tiles.iter().all(|t| t.format == TileFormat::Jpeg);
+            v.push(if plain { T_TILES } else { T_TILES2 });
             pu32(&mut v, *width);
             pu32(&mut v, *height);
             pu32(&mut v, tiles.len() as u32);
@@ -244,8 +707,11 @@ pub fn encode(m: &Msg) -> Vec<u8> {
                 pu32(&mut v, t.y);
                 pu32(&mut v, t.w);
                 pu32(&mut v, t.h);
-                pu32(&mut v, t.jpeg.len() as u32);
-                v.extend_from_slice(&t.jpeg);
+                if !plain {
+                    v.push(t.format.byte());
+                }
+                pu32(&mut v, t.data.len() as u32);
+                v.extend_from_slice(&t.data);
             }
         }
         Msg::Video {
@@ -262,9 +728,56 @@ pub fn encode(m: &Msg) -> Vec<u8> {
             pu32(&mut v, data.len() as u32);
             v.extend_from_slice(data);
         }
-        Msg::Caps { h264 } => {
+        Msg::Caps { h264, copy, cache } => {
             v.push(T_CAPS);
             v.push(if *h264 { 1 } else { 0 });
+            v.push(if *copy { 1 } else { 0 });
+            v.push(if *cache { 1 } else { 0 });
+        }
+        Msg::CachePaint {
+            width,
+            height,
+            cells,
+        }
+        | Msg::CacheStore {
+            width,
+            height,
+            cells,
+        } => {
+            let n = cells.len().min(MAX_TILES);
+            v.reserve(n * 16 + 16);
+            v.push(if matches!(m, Msg::CachePaint { .. }) {
+                T_CPAINT
+            } else {
+                T_CSTORE
+            });
+            pu32(&mut v, *width);
+            pu32(&mut v, *height);
+            pu32(&mut v, n as u32);
+            for c in cells.iter().take(n) {
+                pu32(&mut v, c.x);
+                pu32(&mut v, c.y);
+                pu64(&mut v, c.hash);
+            }
+        }
+        Msg::CacheReset => {
+            v.push(T_CRESET);
+        }
+        Msg::CopyRect {
+            width,
+            height,
+            src,
+            dst,
+        } => {
+            v.push(T_COPY);
+            pu32(&mut v, *width);
+            pu32(&mut v, *height);
+            pu32(&mut v, src.0);
+            pu32(&mut v, src.1);
+            pu32(&mut v, src.2);
+            pu32(&mut v, src.3);
+            pu32(&mut v, dst.0);
+            pu32(&mut v, dst.1);
         }
         Msg::Cursor { x, y, visible } => {
             v.push(T_CURSOR);
@@ -315,6 +828,59 @@ pub fn encode(m: &Msg) -> Vec<u8> {
             pu32(&mut v, n as u32);
             v.extend_from_slice(&b[..n]);
         }
+        Msg::ClipOffer { seq, items } => {
+            v.push(T_CLIPOFFER);
+            pu32(&mut v, *seq);
+            let items: Vec<&ClipItem> = items
+                .iter()
+                .filter(|i| i.size as usize <= clip_limit(i.kind))
+                .take(4)
+                .collect();
+            v.push(items.len() as u8);
+            for i in items {
+                let n = if i.data.len() <= CLIP_INLINE {
+                    i.data.len()
+                } else {
+                    0
+                };
+                v.push(i.kind);
+                pu32(&mut v, i.size);
+                pu32(&mut v, n as u32);
+                v.extend_from_slice(&i.data[..n]);
+            }
+        }
+        Msg::ClipFetch { seq, kind } => {
+            v.push(T_CLIPFETCH);
+            pu32(&mut v, *seq);
+            v.push(*kind);
+        }
+        Msg::ClipData { seq, kind, data } => {
+            let n = data.len().min(clip_limit(*kind));
+            v.reserve(n + 12);
+            v.push(T_CLIPDATA);
+            pu32(&mut v, *seq);
+            v.push(*kind);
+            pu32(&mut v, n as u32);
+            v.extend_from_slice(&data[..n]);
+        }
+        Msg::ClipFiles { seq, files } => {
+            v.push(T_CLIPFILES);
+            pu32(&mut v, *seq);
+            let n = files.len().min(MAX_CLIP_FILES);
+            pu32(&mut v, n as u32);
+            for e in &files[..n] {
+                pentry(&mut v, e);
+            }
+        }
+        Msg::ClipPull { seq } => {
+            v.push(T_CLIPPULL);
+            pu32(&mut v, *seq);
+        }
+        Msg::ClipPulling { seq, id } => {
+            v.push(T_CLIPPULLING);
+            pu32(&mut v, *seq);
+            pu32(&mut v, *id);
+        }
         Msg::SetMode { mode } => {
             v.push(T_MODE);
             v.push(*mode);
@@ -342,6 +908,10 @@ pub fn encode(m: &Msg) -> Vec<u8> {
             pu32(&mut v, *width);
             pu32(&mut v, *height);
         }
+        Msg::SetQuality { lossless } => {
+            v.push(T_QUALITY);
+            v.push(if *lossless { 1 } else { 0 });
+        }
         Msg::Resolutions { list } => {
             v.push(T_RESLIST);
             pu32(&mut v, list.len().min(64) as u32);
@@ -350,7 +920,15 @@ pub fn encode(m: &Msg) -> Vec<u8> {
                 pu32(&mut v, *h);
             }
         }
-        Msg::FileOffer { id, name, size } => {
+        Msg::FileOffer {
+            id,
+            name,
+            size,
+            sha256,
+            folder,
+            mtime,
+            codecs,
+        } => {
             let nb = name.as_bytes();
             let n = nb.len().min(MAX_NAME);
             v.push(T_FOFFER);
@@ -358,6 +936,57 @@ pub fn encode(m: &Msg) -> Vec<u8> {
             pu64(&mut v, *size);
             pu32(&mut v, n as u32);
             v.extend_from_slice(&nb[..n]);
+            let more = *folder != 0 || *mtime != 0 || *codecs != 0;
+            match sha256 {
+                Some(h) => v.extend_from_slice(h),
+                // all zero = "no hash", only needed to reach the fields after it
+                None if more => v.extend_from_slice(&[0u8; 32]),
+                None => {}
+            }
+            if more {
+                pu32(&mut v, *folder);
+                pu64(&mut v, *mtime);
+                v.push(*codecs);
+            }
+        }
+        Msg::DirOffer {
+            id,
+            name,
+            files,
+            size,
+        } => {
+            let nb = name.as_bytes();
+            let n = nb.len().min(MAX_NAME);
+            v.push(T_DOFFER);
+            pu32(&mut v, *id);
+            pu32(&mut v, *files);
+            pu64(&mut v, *size);
+            pu32(&mut v, n as u32);
+            v.extend_from_slice(&nb[..n]);
+        }
+        Msg::DirEntry {
+            dir,
+            path,
+            kind,
+            target,
+            mtime,
+        } => {
+            v.push(T_DENTRY);
+            pu32(&mut v, *dir);
+            v.push(kind.byte());
+            pu64(&mut v, *mtime);
+            for s in [path, target] {
+                let b = s.as_bytes();
+                let n = b.len().min(MAX_NAME);
+                pu32(&mut v, n as u32);
+                v.extend_from_slice(&b[..n]);
+            }
+        }
+        Msg::FileResume { id, off, codec } => {
+            v.push(T_FRESUME);
+            pu32(&mut v, *id);
+            pu64(&mut v, *off);
+            v.push(*codec);
         }
         Msg::FileChunk { id, off, data } => {
             let n = data.len().min(MAX_CHUNK);
@@ -368,6 +997,16 @@ pub fn encode(m: &Msg) -> Vec<u8> {
             pu32(&mut v, n as u32);
             v.extend_from_slice(&data[..n]);
         }
+        Msg::FileChunkZ { id, off, len, data } => {
+            let n = data.len().min(MAX_CHUNK);
+            v.reserve(n + 24);
+            v.push(T_FCHUNKZ);
+            pu32(&mut v, *id);
+            pu64(&mut v, *off);
+            pu32(&mut v, *len);
+            pu32(&mut v, n as u32);
+            v.extend_from_slice(&data[..n]);
+        }
         Msg::FileEnd { id, ok, msg } => {
             let mb = msg.as_bytes();
             let n = mb.len().min(MAX_NAME);
@@ -382,6 +1021,183 @@ pub fn encode(m: &Msg) -> Vec<u8> {
             pu32(&mut v, *id);
             pu64(&mut v, *got);
         }
+        Msg::FsList { req, path } => {
+            v.push(T_FSLIST);
+            pu32(&mut v, *req);
+            pstr(&mut v, path, MAX_PATH);
+        }
+        Msg::FsListing {
+            req,
+            path,
+            parent,
+            write,
+            entries,
+        } => {
+            v.push(T_FSLISTING);
+            pu32(&mut v, *req);
+            pstr(&mut v, path, MAX_PATH);
+            pstr(&mut v, parent, MAX_PATH);
+            v.push(*write as u8);
+            let n = entries.len().min(MAX_FS_ENTRIES);
+            pu32(&mut v, n as u32);
+            for e in &entries[..n] {
+                pentry(&mut v, e);
+            }
+        }
+        Msg::FsStat { req, path } => {
+            v.push(T_FSSTAT);
+            pu32(&mut v, *req);
+            pstr(&mut v, path, MAX_PATH);
+        }
+        Msg::FsInfo { req, entry } => {
+            v.push(T_FSINFO);
+            pu32(&mut v, *req);
+            pentry(&mut v, entry);
+        }
+        Msg::FsGet { req, path } => {
+            v.push(T_FSGET);
+            pu32(&mut v, *req);
+            pstr(&mut v, path, MAX_PATH);
+        }
+        Msg::FsGetting { req, id } => {
+            v.push(T_FSGETTING);
+            pu32(&mut v, *req);
+            pu32(&mut v, *id);
+        }
+        Msg::FsRename { req, from, to } => {
+            v.push(T_FSRENAME);
+            pu32(&mut v, *req);
+            pstr(&mut v, from, MAX_PATH);
+            pstr(&mut v, to, MAX_PATH);
+        }
+        Msg::FsDelete { req, path } => {
+            v.push(T_FSDELETE);
+            pu32(&mut v, *req);
+            pstr(&mut v, path, MAX_PATH);
+        }
+        Msg::FsMkdir { req, path } => {
+            v.push(T_FSMKDIR);
+            pu32(&mut v, *req);
+            pstr(&mut v, path, MAX_PATH);
+        }
+        Msg::FsPut { id, dir } => {
+            v.push(T_FSPUT);
+            pu32(&mut v, *id);
+            pstr(&mut v, dir, MAX_PATH);
+        }
+        Msg::FsDone { req, ok, msg } => {
+            v.push(T_FSDONE);
+            pu32(&mut v, *req);
+            v.push(*ok as u8);
+            pstr(&mut v, msg, MAX_NAME);
+        }
+        Msg::TermOpen { id, rows, cols } | Msg::TermResize { id, rows, cols } => {
+            v.push(if matches!(m, Msg::TermOpen { .. }) {
+                T_TERMOPEN
+            } else {
+                T_TERMRESIZE
+            });
+            pu32(&mut v, *id);
+            v.extend_from_slice(&rows.to_le_bytes());
+            v.extend_from_slice(&cols.to_le_bytes());
+        }
+        Msg::TermData { id, data } => {
+            v.push(T_TERMDATA);
+            pu32(&mut v, *id);
+            let n = data.len().min(MAX_TERM_DATA);
+            pu32(&mut v, n as u32);
+            v.extend_from_slice(&data[..n]);
+        }
+        Msg::TermClose { id, msg } => {
+            v.push(T_TERMCLOSE);
+            pu32(&mut v, *id);
+            pstr(&mut v, msg, MAX_NAME);
+        }
+        Msg::TunOpen { id, host, port } => {
+            v.push(T_TUNOPEN);
+            pu32(&mut v, *id);
+            pstr(&mut v, host, MAX_NAME);
+            v.extend_from_slice(&port.to_le_bytes());
+        }
+        Msg::TunOpened { id, ok, msg } => {
+            v.push(T_TUNOPENED);
+            pu32(&mut v, *id);
+            v.push(*ok as u8);
+            pstr(&mut v, msg, MAX_NAME);
+        }
+        Msg::TunData { id, data } => {
+            v.push(T_TUNDATA);
+            pu32(&mut v, *id);
+            let n = data.len().min(MAX_TUN_DATA);
+            pu32(&mut v, n as u32);
+            v.extend_from_slice(&data[..n]);
+        }
+        Msg::TunAck { id, n } => {
+            v.push(T_TUNACK);
+            pu32(&mut v, *id);
+            pu32(&mut v, *n);
+        }
+        Msg::TunClose { id } => {
+            v.push(T_TUNCLOSE);
+            pu32(&mut v, *id);
+        }
+        Msg::SysInfoGet => v.push(T_SYSINFOGET),
+        Msg::SysInfo { report } => {
+            v.push(T_SYSINFO);
+            preport(&mut v, report);
+        }
+        Msg::ProcListGet => v.push(T_PROCLISTGET),
+        Msg::ProcList { procs } => {
+            v.push(T_PROCLIST);
+            let procs = &procs[..procs.len().min(MAX_PROCS)];
+            pu32(&mut v, procs.len() as u32);
+            for p in procs {
+                pu32(&mut v, p.pid);
+                pstr(&mut v, &p.name, MAX_NAME);
+                pstr(&mut v, &p.user, MAX_NAME);
+                pu32(&mut v, p.cpu);
+                pu64(&mut v, p.mem);
+            }
+        }
+        Msg::ProcKill { pid } => {
+            v.push(T_PROCKILL);
+            pu32(&mut v, *pid);
+        }
+        Msg::ProcStart { cmd } => {
+            v.push(T_PROCSTART);
+            pstr(&mut v, cmd, MAX_PATH);
+        }
+        Msg::ProcResult { ok, msg } => {
+            v.push(T_PROCRESULT);
+            v.push(*ok as u8);
+            pstr(&mut v, msg, MAX_NAME);
+        }
+        Msg::Chat { id, ts, text } => {
+            v.push(T_CHAT);
+            pu32(&mut v, *id);
+            pu64(&mut v, *ts);
+            pstr(&mut v, text, MAX_CHAT);
+        }
+        Msg::ChatAck { id } => {
+            v.push(T_CHATACK);
+            pu32(&mut v, *id);
+        }
+        Msg::Annot { id, tool, points } => {
+            v.push(T_ANNOT);
+            pu32(&mut v, *id);
+            v.push(*tool);
+            let points = &points[..points.len().min(MAX_ANNOT_POINTS)];
+            pu32(&mut v, points.len() as u32);
+            for (x, y) in points {
+                v.extend_from_slice(&x.to_le_bytes());
+                v.extend_from_slice(&y.to_le_bytes());
+            }
+        }
+        Msg::AnnotClear => v.push(T_ANNOTCLEAR),
+        Msg::Privacy { on } => {
+            v.push(T_PRIVACY);
+            v.push(*on as u8);
+        }
         Msg::P2pOffer { token, addrs } => {
             v.push(T_P2P);
             pu64(&mut v, *token);
@@ -456,6 +1272,61 @@ impl<'a> Rd<'a> {
         self.p += n;
         Some(s)
     }
+    fn str(&mut self, max: usize) -> Option<String> {
+        let n = self.u32()? as usize;
+        if n > max {
+            return None;
+        }
+        Some(String::from_utf8_lossy(self.take(n)?).into_owned())
+    }
+    /// `count` with the `MAX_SYS_ITEMS` cap of the system report.
+    fn items(&mut self) -> Option<usize> {
+        let n = self.u32()? as usize;
+        (n <= MAX_SYS_ITEMS).then_some(n)
+    }
+    fn report(&mut self) -> Option<SysReport> {
+        let mut r = SysReport {
+            os: self.str(MAX_NAME)?,
+            kernel: self.str(MAX_NAME)?,
+            host: self.str(MAX_NAME)?,
+            user: self.str(MAX_NAME)?,
+            version: self.str(MAX_NAME)?,
+            cpu: self.str(MAX_NAME)?,
+            cores: self.u32()?,
+            mem_total: self.u64()?,
+            mem_used: self.u64()?,
+            uptime: self.u64()?,
+            ..Default::default()
+        };
+        for _ in 0..self.items()? {
+            r.disks.push(DiskInfo {
+                mount: self.str(MAX_NAME)?,
+                fs: self.str(MAX_NAME)?,
+                total: self.u64()?,
+                free: self.u64()?,
+            });
+        }
+        for _ in 0..self.items()? {
+            let mut n = NetInfo {
+                name: self.str(MAX_NAME)?,
+                mac: self.str(MAX_NAME)?,
+                addrs: Vec::new(),
+            };
+            for _ in 0..self.items()? {
+                n.addrs.push(self.str(MAX_NAME)?);
+            }
+            r.nets.push(n);
+        }
+        Some(r)
+    }
+    fn entry(&mut self) -> Option<FsEntry> {
+        Some(FsEntry {
+            name: self.str(MAX_NAME)?,
+            dir: self.u8()? != 0,
+            size: self.u64()?,
+            mtime: self.u64()?,
+        })
+    }
 }
 
 pub fn decode(b: &[u8]) -> Option<Msg> {
@@ -477,7 +1348,7 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
                 jpeg: data.to_vec(),
             })
         }
-        T_TILES => {
+        T_TILES | T_TILES2 => {
             let width = r.u32()?;
             let height = r.u32()?;
             let count = r.u32()? as usize;
@@ -490,6 +1361,11 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
                 let y = r.u32()?;
                 let w = r.u32()?;
                 let h = r.u32()?;
+                let format = if tag == T_TILES2 {
+                    TileFormat::from_byte(r.u8()?)?
+                } else {
+                    TileFormat::Jpeg
+                };
                 let n = r.u32()? as usize;
                 let data = r.take(n)?;
                 tiles.push(Tile {
@@ -497,7 +1373,8 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
                     y,
                     w,
                     h,
-                    jpeg: data.to_vec(),
+                    format,
+                    data: data.to_vec(),
                 });
             }
             Some(Msg::Tiles {
@@ -522,7 +1399,48 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
                 data,
             })
         }
-        T_CAPS => Some(Msg::Caps { h264: r.u8()? != 0 }),
+        T_CAPS => Some(Msg::Caps {
+            h264: r.u8()? != 0,
+            // older viewers send only the first byte(s)
+            copy: r.u8().map(|x| x != 0).unwrap_or(false),
+            cache: r.u8().map(|x| x != 0).unwrap_or(false),
+        }),
+        T_CPAINT | T_CSTORE => {
+            let width = r.u32()?;
+            let height = r.u32()?;
+            let count = r.u32()? as usize;
+            if count > MAX_TILES {
+                return None;
+            }
+            let mut cells = Vec::with_capacity(count.min(256));
+            for _ in 0..count {
+                cells.push(CacheRef {
+                    x: r.u32()?,
+                    y: r.u32()?,
+                    hash: r.u64()?,
+                });
+            }
+            Some(if tag == T_CPAINT {
+                Msg::CachePaint {
+                    width,
+                    height,
+                    cells,
+                }
+            } else {
+                Msg::CacheStore {
+                    width,
+                    height,
+                    cells,
+                }
+            })
+        }
+        T_CRESET => Some(Msg::CacheReset),
+        T_COPY => Some(Msg::CopyRect {
+            width: r.u32()?,
+            height: r.u32()?,
+            src: (r.u32()?, r.u32()?, r.u32()?, r.u32()?),
+            dst: (r.u32()?, r.u32()?),
+        }),
         T_CURSOR => Some(Msg::Cursor {
             x: r.i32()?,
             y: r.i32()?,
@@ -562,6 +1480,56 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
                 text: String::from_utf8_lossy(data).into_owned(),
             })
         }
+        T_CLIPOFFER => {
+            let seq = r.u32()?;
+            let count = r.u8()? as usize;
+            if count > 4 {
+                return None;
+            }
+            let mut items = Vec::with_capacity(count);
+            for _ in 0..count {
+                let kind = r.u8()?;
+                let size = r.u32()?;
+                let n = r.u32()? as usize;
+                if size as usize > clip_limit(kind) || n > CLIP_INLINE || n > size as usize {
+                    return None;
+                }
+                let data = r.take(n)?.to_vec();
+                items.push(ClipItem { kind, size, data });
+            }
+            Some(Msg::ClipOffer { seq, items })
+        }
+        T_CLIPFETCH => Some(Msg::ClipFetch {
+            seq: r.u32()?,
+            kind: r.u8()?,
+        }),
+        T_CLIPDATA => {
+            let seq = r.u32()?;
+            let kind = r.u8()?;
+            let n = r.u32()? as usize;
+            if n > clip_limit(kind) {
+                return None;
+            }
+            let data = r.take(n)?.to_vec();
+            Some(Msg::ClipData { seq, kind, data })
+        }
+        T_CLIPFILES => {
+            let seq = r.u32()?;
+            let n = r.u32()? as usize;
+            if n > MAX_CLIP_FILES {
+                return None;
+            }
+            let mut files = Vec::with_capacity(n);
+            for _ in 0..n {
+                files.push(r.entry()?);
+            }
+            Some(Msg::ClipFiles { seq, files })
+        }
+        T_CLIPPULL => Some(Msg::ClipPull { seq: r.u32()? }),
+        T_CLIPPULLING => Some(Msg::ClipPulling {
+            seq: r.u32()?,
+            id: r.u32()?,
+        }),
         T_MODE => Some(Msg::SetMode { mode: r.u8()? }),
         T_MONS => {
             let active = r.u8()?;
@@ -588,6 +1556,9 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
             width: r.u32()?,
             height: r.u32()?,
         }),
+        T_QUALITY => Some(Msg::SetQuality {
+            lossless: r.u8()? != 0,
+        }),
         T_RESLIST => {
             let n = r.u32()? as usize;
             if n > 64 {
@@ -607,8 +1578,68 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
                 return None;
             }
             let name = String::from_utf8_lossy(r.take(n)?).into_owned();
-            Some(Msg::FileOffer { id, name, size })
+            // older senders end here
+            let sha256 = r.take(32).and_then(|h| {
+                let mut a = [0u8; 32];
+                a.copy_from_slice(h);
+                (a != [0u8; 32]).then_some(a)
+            });
+            let folder = r.u32().unwrap_or(0);
+            let mtime = r.u64().unwrap_or(0);
+            let codecs = r.u8().unwrap_or(0);
+            Some(Msg::FileOffer {
+                id,
+                name,
+                size,
+                sha256,
+                folder,
+                mtime,
+                codecs,
+            })
+        }
+        T_DOFFER => {
+            let id = r.u32()?;
+            let files = r.u32()?;
+            let size = r.u64()?;
+            let n = r.u32()? as usize;
+            if n > MAX_NAME {
+                return None;
+            }
+            let name = String::from_utf8_lossy(r.take(n)?).into_owned();
+            Some(Msg::DirOffer {
+                id,
+                name,
+                files,
+                size,
+            })
         }
+        T_DENTRY => {
+            let dir = r.u32()?;
+            let kind = EntryKind::from_byte(r.u8()?)?;
+            let mtime = r.u64()?;
+            let mut text = [String::new(), String::new()];
+            for t in text.iter_mut() {
+                let n = r.u32()? as usize;
+                if n > MAX_NAME {
+                    return None;
+                }
+                *t = String::from_utf8_lossy(r.take(n)?).into_owned();
+            }
+            let [path, target] = text;
+            Some(Msg::DirEntry {
+                dir,
+                path,
+                kind,
+                target,
+                mtime,
+            })
+        }
+        T_FRESUME => Some(Msg::FileResume {
+            id: r.u32()?,
+            off: r.u64()?,
+            // older receivers never pack
+            codec: r.u8().unwrap_or(0),
+        }),
         T_FCHUNK => {
             let id = r.u32()?;
             let off = r.u64()?;
@@ -619,6 +1650,17 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
             let data = r.take(n)?.to_vec();
             Some(Msg::FileChunk { id, off, data })
         }
+        T_FCHUNKZ => {
+            let id = r.u32()?;
+            let off = r.u64()?;
+            let len = r.u32()?;
+            let n = r.u32()? as usize;
+            if len as usize > MAX_CHUNK || n > MAX_CHUNK {
+                return None;
+            }
+            let data = r.take(n)?.to_vec();
+            Some(Msg::FileChunkZ { id, off, len, data })
+        }
         T_FEND => {
             let id = r.u32()?;
             let ok = r.u8()? != 0;
@@ -633,6 +1675,171 @@ pub fn decode(b: &[u8]) -> Option<Msg> {
             id: r.u32()?,
             got: r.u64()?,
         }),
+        T_FSLIST => Some(Msg::FsList {
+            req: r.u32()?,
+            path: r.str(MAX_PATH)?,
+        }),
+        T_FSLISTING => {
+            let req = r.u32()?;
+            let path = r.str(MAX_PATH)?;
+            let parent = r.str(MAX_PATH)?;
+            let write = r.u8()? != 0;
+            let n = r.u32()? as usize;
+            if n > MAX_FS_ENTRIES {
+                return None;
+            }
+            let mut entries = Vec::with_capacity(n);
+            for _ in 0..n {
+                entries.push(r.entry()?);
+            }
+            Some(Msg::FsListing {
+                req,
+                path,
+                parent,
+                write,
+                entries,
+            })
+        }
+        T_FSSTAT => Some(Msg::FsStat {
+            req: r.u32()?,
+            path: r.str(MAX_PATH)?,
+        }),
+        T_FSINFO => Some(Msg::FsInfo {
+            req: r.u32()?,
+            entry: r.entry()?,
+        }),
+        T_FSGET => Some(Msg::FsGet {
+            req: r.u32()?,
+            path: r.str(MAX_PATH)?,
+        }),
+        T_FSGETTING => Some(Msg::FsGetting {
+            req: r.u32()?,
+            id: r.u32()?,
+        }),
+        T_FSRENAME => Some(Msg::FsRename {
+            req: r.u32()?,
+            from: r.str(MAX_PATH)?,
+            to: r.str(MAX_PATH)?,
+        }),
+        T_FSDELETE => Some(Msg::FsDelete {
+            req: r.u32()?,
+            path: r.str(MAX_PATH)?,
+        }),
+        T_FSMKDIR => Some(Msg::FsMkdir {
+            req: r.u32()?,
+            path: r.str(MAX_PATH)?,
+        }),
+        T_FSPUT => Some(Msg::FsPut {
+            id: r.u32()?,
+            dir: r.str(MAX_PATH)?,
+        }),
+        T_FSDONE => Some(Msg::FsDone {
+            req: r.u32()?,
+            ok: r.u8()? != 0,
+            msg: r.str(MAX_NAME)?,
+        }),
+        T_TERMOPEN => Some(Msg::TermOpen {
+            id: r.u32()?,
+            rows: r.u16()?,
+            cols: r.u16()?,
+        }),
+        T_TERMRESIZE => Some(Msg::TermResize {
+            id: r.u32()?,
+            rows: r.u16()?,
+            cols: r.u16()?,
+        }),
+        T_TERMDATA => {
+            let id = r.u32()?;
+            let n = r.u32()? as usize;
+            if n > MAX_TERM_DATA {
+                return None;
+            }
+            Some(Msg::TermData {
+                id,
+                data: r.take(n)?.to_vec(),
+            })
+        }
+        T_TERMCLOSE => Some(Msg::TermClose {
+            id: r.u32()?,
+            msg: r.str(MAX_NAME)?,
+        }),
+        T_TUNOPEN => Some(Msg::TunOpen {
+            id: r.u32()?,
+            host: r.str(MAX_NAME)?,
+            port: r.u16()?,
+        }),
+        T_TUNOPENED => Some(Msg::TunOpened {
+            id: r.u32()?,
+            ok: r.u8()? != 0,
+            msg: r.str(MAX_NAME)?,
+        }),
+        T_TUNDATA => {
+            let id = r.u32()?;
+            let n = r.u32()? as usize;
+            if n > MAX_TUN_DATA {
+                return None;
+            }
+            Some(Msg::TunData {
+                id,
+                data: r.take(n)?.to_vec(),
+            })
+        }
+        T_TUNACK => Some(Msg::TunAck {
+            id: r.u32()?,
+            n: r.u32()?,
+        }),
+        T_TUNCLOSE => Some(Msg::TunClose { id: r.u32()? }),
+        T_SYSINFOGET => Some(Msg::SysInfoGet),
+        T_SYSINFO => Some(Msg::SysInfo {
+            report: r.report()?,
+        }),
+        T_PROCLISTGET => Some(Msg::ProcListGet),
+        T_PROCLIST => {
+            let n = r.u32()? as usize;
+            if n > MAX_PROCS {
+                return None;
+            }
+            let mut procs = Vec::with_capacity(n);
+            for _ in 0..n {
+                procs.push(ProcInfo {
+                    pid: r.u32()?,
+                    name: r.str(MAX_NAME)?,
+                    user: r.str(MAX_NAME)?,
+                    cpu: r.u32()?,
+                    mem: r.u64()?,
+                });
+            }
+            Some(Msg::ProcList { procs })
+        }
+        T_PROCKILL => Some(Msg::ProcKill { pid: r.u32()? }),
+        T_PROCSTART => Some(Msg::ProcStart {
+            cmd: r.str(MAX_PATH)?,
+        }),
+        T_PROCRESULT => Some(Msg::ProcResult {
+            ok: r.u8()? != 0,
+            msg: r.str(MAX_NAME)?,
+        }),
+        T_CHAT => Some(Msg::Chat {
+            id: r.u32()?,
+            ts: r.u64()?,
+            text: r.str(MAX_CHAT)?,
+        }),
+        T_CHATACK => Some(Msg::ChatAck { id: r.u32()? }),
+        T_ANNOT => {
+            let id = r.u32()?;
+            let tool = r.u8()?;
+            let n = r.u32()? as usize;
+            if n > MAX_ANNOT_POINTS {
+                return None;
+            }
+            let mut points = Vec::with_capacity(n);
+            for _ in 0..n {
+                points.push((r.u16()?, r.u16()?));
+            }
+            Some(Msg::Annot { id, tool, points })
+        }
+        T_ANNOTCLEAR => Some(Msg::AnnotClear),
+        T_PRIVACY => Some(Msg::Privacy { on: r.u8()? != 0 }),
         T_P2P => {
             let token = r.u64()?;
             let count = r.u32()? as usize;
@@ -693,14 +1900,38 @@ mod tests {
                         y: 64,
                         w: 64,
                         h: 128,
-                        jpeg: vec![9, 8, 7],
+                        format: TileFormat::Jpeg,
+                        data: vec![9, 8, 7],
                     },
                     Tile {
                         x: 1536,
                         y: 640,
                         w: 64,
                         h: 30,
-                        jpeg: vec![1],
+                        format: TileFormat::Jpeg,
+                        data: vec![1],
+                    },
+                ],
+            },
+            Msg::Tiles {
+                width: 1600,
+                height: 670,
+                tiles: vec![
+                    Tile {
+                        x: 64,
+                        y: 0,
+                        w: 128,
+                        h: 64,
+                        format: TileFormat::Palette,
+                        data: vec![0, 1, 2, 3],
+                    },
+                    Tile {
+                        x: 0,
+                        y: 0,
+                        w: 64,
+                        h: 64,
+                        format: TileFormat::Png,
+                        data: vec![5],
                     },
                 ],
             },
@@ -730,7 +1961,51 @@ mod tests {
             Msg::Clipboard {
                 text: "hallo welt \u{00e4}\u{00f6}\u{00fc}".to_string(),
             },
+            Msg::ClipOffer {
+                seq: 9,
+                items: vec![
+                    ClipItem {
+                        kind: CLIP_HTML,
+                        size: 4,
+                        data: b"<b/>".to_vec(),
+                    },
+                    ClipItem {
+                        kind: CLIP_PNG,
+                        size: 1 << 20,
+                        data: Vec::new(),
+                    },
+                ],
+            },
+            Msg::ClipFetch {
+                seq: 9,
+                kind: CLIP_PNG,
+            },
+            Msg::ClipFiles {
+                seq: 10,
+                files: vec![
+                    FsEntry {
+                        name: "Bericht.docx".to_string(),
+                        dir: false,
+                        size: 48_213,
+                        mtime: 1_700_000_000,
+                    },
+                    FsEntry {
+                        name: "Fotos".to_string(),
+                        dir: true,
+                        size: 0,
+                        mtime: 0,
+                    },
+                ],
+            },
+            Msg::ClipPull { seq: 10 },
+            Msg::ClipPulling { seq: 10, id: 77 },
+            Msg::ClipData {
+                seq: 9,
+                kind: CLIP_RTF,
+                data: b"{\\rtf1}".to_vec(),
+            },
             Msg::SetMode { mode: MODE_GAME },
+            Msg::SetQuality { lossless: true },
             Msg::Monitors {
                 active: 1,
                 list: vec![
@@ -753,18 +2028,201 @@ mod tests {
                 id: 7,
                 name: "urlaub \u{00fc}ber alles.zip".to_string(),
                 size: 4_294_967_400,
+                sha256: None,
+                folder: 0,
+                mtime: 0,
+                codecs: 0,
+            },
+            Msg::FileOffer {
+                id: 8,
+                name: "a.txt".to_string(),
+                size: 3,
+                sha256: Some([0xab; 32]),
+                folder: 0,
+                mtime: 0,
+                codecs: CODEC_DEFLATE,
+            },
+            Msg::FileOffer {
+                id: 10,
+                name: "2024/b.jpg".to_string(),
+                size: 9,
+                sha256: None,
+                folder: 9,
+                mtime: 1_700_000_000,
+                codecs: 0,
+            },
+            Msg::DirOffer {
+                id: 9,
+                name: "fotos".to_string(),
+                files: 120,
+                size: 1 << 34,
+            },
+            Msg::DirEntry {
+                dir: 9,
+                path: "leer".to_string(),
+                kind: EntryKind::Dir,
+                target: String::new(),
+                mtime: 1_600_000_000,
+            },
+            Msg::DirEntry {
+                dir: 9,
+                path: "neu".to_string(),
+                kind: EntryKind::DirLink,
+                target: "2024".to_string(),
+                mtime: 0,
+            },
+            Msg::FileResume {
+                id: 8,
+                off: 1 << 33,
+                codec: CODEC_DEFLATE,
             },
             Msg::FileChunk {
                 id: 7,
                 off: 65536,
                 data: vec![3, 1, 4, 1, 5],
             },
+            Msg::FileChunkZ {
+                id: 7,
+                off: 1 << 32,
+                len: 65536,
+                data: vec![9, 9, 9],
+            },
             Msg::FileEnd {
                 id: 7,
                 ok: false,
                 msg: "abgebrochen".to_string(),
             },
             Msg::FileAck { id: 7, got: 131072 },
+            Msg::FsList {
+                req: 1,
+                path: "C:\\Users".to_string(),
+            },
+            Msg::FsListing {
+                req: 1,
+                path: "C:\\Users".to_string(),
+                parent: "C:\\".to_string(),
+                write: true,
+                entries: vec![
+                    FsEntry {
+                        name: "Public".to_string(),
+                        dir: true,
+                        size: 0,
+                        mtime: 1_700_000_000,
+                    },
+                    FsEntry {
+                        name: "b\u{00fc}ro.txt".to_string(),
+                        dir: false,
+                        size: 1 << 40,
+                        mtime: 0,
+                    },
+                ],
+            },
+            Msg::FsStat {
+                req: 2,
+                path: "/etc/hosts".to_string(),
+            },
+            Msg::FsInfo {
+                req: 2,
+                entry: FsEntry {
+                    name: "hosts".to_string(),
+                    dir: false,
+                    size: 220,
+                    mtime: 1,
+                },
+            },
+            Msg::FsGet {
+                req: 3,
+                path: "/home/a/bilder".to_string(),
+            },
+            Msg::FsGetting { req: 3, id: 44 },
+            Msg::FsRename {
+                req: 4,
+                from: "/tmp/a".to_string(),
+                to: "/tmp/b".to_string(),
+            },
+            Msg::FsDelete {
+                req: 5,
+                path: "/tmp/b".to_string(),
+            },
+            Msg::FsMkdir {
+                req: 6,
+                path: "/tmp/neu".to_string(),
+            },
+            Msg::FsPut {
+                id: 45,
+                dir: "/tmp/neu".to_string(),
+            },
+            Msg::FsDone {
+                req: 6,
+                ok: false,
+                msg: "nicht erlaubt".to_string(),
+            },
+            Msg::TermOpen {
+                id: 1,
+                rows: 24,
+                cols: 80,
+            },
+            Msg::TermData {
+                id: 1,
+                data: b"ls -la\r".to_vec(),
+            },
+            Msg::TermResize {
+                id: 1,
+                rows: 50,
+                cols: 132,
+            },
+            Msg::TermClose {
+                id: 1,
+                msg: "beendet (0)".to_string(),
+            },
+            Msg::TunOpen {
+                id: 3,
+                host: "192.168.1.20".to_string(),
+                port: 3389,
+            },
+            Msg::TunOpened {
+                id: 3,
+                ok: false,
+                msg: "nicht freigegeben".to_string(),
+            },
+            Msg::TunData {
+                id: 3,
+                data: vec![3, 0, 0, 19],
+            },
+            Msg::TunAck { id: 3, n: 4 },
+            Msg::TunClose { id: 3 },
+            Msg::SysInfoGet,
+            Msg::ProcListGet,
+            Msg::ProcList {
+                procs: vec![ProcInfo {
+                    pid: 4242,
+                    name: "notepad.exe".to_string(),
+                    user: "anna".to_string(),
+                    cpu: 125,
+                    mem: 24 << 20,
+                }],
+            },
+            Msg::ProcKill { pid: 4242 },
+            Msg::ProcStart {
+                cmd: "notepad C:\\temp\\x.txt".to_string(),
+            },
+            Msg::ProcResult {
+                ok: true,
+                msg: "beendet".to_string(),
+            },
+            Msg::Chat {
+                id: 1,
+                ts: 1_792_300_000_000,
+                text: "Bin gleich da – einen Moment 🙂".to_string(),
+            },
+            Msg::ChatAck { id: 1 },
+            Msg::Annot {
+                id: 7,
+                tool: ANNOT_ARROW,
+                points: vec![(100, 200), (5000, 5000), (10000, 0)],
+            },
+            Msg::AnnotClear,
+            Msg::Privacy { on: true },
             Msg::P2pOffer {
                 token: 0xdead_beef_1234,
                 addrs: vec!["192.168.1.51:41234".to_string(), "84.115.1.2:41234".to_string()],
@@ -780,7 +2238,43 @@ mod tests {
                 key: true,
                 data: vec![0, 0, 0, 1, 0x67, 42],
             },
-            Msg::Caps { h264: true },
+            Msg::Caps {
+                h264: true,
+                copy: true,
+                cache: false,
+            },
+            Msg::CachePaint {
+                width: 1920,
+                height: 1080,
+                cells: vec![CacheRef {
+                    x: 64,
+                    y: 128,
+                    hash: 0x0123_4567_89ab_cdef,
+                }],
+            },
+            Msg::CacheStore {
+                width: 1920,
+                height: 1080,
+                cells: vec![
+                    CacheRef {
+                        x: 0,
+                        y: 0,
+                        hash: 1,
+                    },
+                    CacheRef {
+                        x: 1856,
+                        y: 1024,
+                        hash: u64::MAX,
+                    },
+                ],
+            },
+            Msg::CacheReset,
+            Msg::CopyRect {
+                width: 1920,
+                height: 1080,
+                src: (0, 120, 1600, 900),
+                dst: (0, 80),
+            },
             Msg::Ping { ts: 1234567890 },
         ];
         for m in msgs {
@@ -802,7 +2296,8 @@ mod tests {
                 y: 0,
                 w: 64,
                 h: 64,
-                jpeg: vec![1, 2, 3, 4],
+                format: TileFormat::Jpeg,
+                data: vec![1, 2, 3, 4],
             }],
         });
         for cut in 0..full.len() {
@@ -869,6 +2364,133 @@ mod tests {
         }
     }
 
+    #[test]
+    fn jpeg_only_tiles_keep_the_old_layout() {
+        let tile = |format| Tile {
+            x: 0,
+            y: 0,
+            w: 64,
+            h: 64,
+            format,
+            data: vec![7; 10],
+        };
+        let old = encode(&Msg::Tiles {
+            width: 64,
+            height: 64,
+            tiles: vec![tile(TileFormat::Jpeg)],
+        });
+        // tag, size, count, rect, length, data - no format byte
+        assert_eq!(old[0], T_TILES);
+        assert_eq!(old.len(), 1 + 12 + 16 + 4 + 10);
+        let new = encode(&Msg::Tiles {
+            width: 64,
+            height: 64,
+            tiles: vec![tile(TileFormat::Jpeg), tile(TileFormat::Palette)],
+        });
+        assert_eq!(new[0], T_TILES2);
+        // unknown formats are refused as a whole
+        let mut evil = new.clone();
+        evil[1 + 12 + 16] = 9;
+        assert!(decode(&evil).is_none());
+    }
+
+    #[test]
+    fn old_caps_without_copy_flag_still_decode() {
+        match decode(&[T_CAPS, 1]) {
+            Some(Msg::Caps { h264, copy, cache }) => assert!(h264 && !copy && !cache),
+            other => panic!("caps: {:?}", other),
+        }
+        let full = encode(&Msg::CopyRect {
+            width: 8,
+            height: 8,
+            src: (0, 1, 8, 7),
+            dst: (0, 0),
+        });
+        for cut in 0..full.len() {
+            assert!(decode(&full[..cut]).is_none());
+        }
+        // a cache message cannot make us allocate wildly either
+        let mut evil = vec![T_CPAINT];
+        evil.extend_from_slice(&8u32.to_le_bytes());
+        evil.extend_from_slice(&8u32.to_le_bytes());
+        evil.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode(&evil).is_none());
+    }
+
+    #[test]
+    fn old_file_offer_still_decodes() {
+        // what builds before resume and folders sent: id, size, name
+        let mut old = vec![T_FOFFER];
+        old.extend_from_slice(&5u32.to_le_bytes());
+        old.extend_from_slice(&3u64.to_le_bytes());
+        old.extend_from_slice(&1u32.to_le_bytes());
+        old.push(b'a');
+        match decode(&old) {
+            Some(Msg::FileOffer {
+                id: 5,
+                sha256: None,
+                folder: 0,
+                mtime: 0,
+                codecs: 0,
+                ..
+            }) => {}
+            other => panic!("offer: {:?}", other),
+        }
+        // and a resume without the codec byte means raw
+        let mut old = vec![T_FRESUME];
+        old.extend_from_slice(&5u32.to_le_bytes());
+        old.extend_from_slice(&7u64.to_le_bytes());
+        assert!(matches!(
+            decode(&old),
+            Some(Msg::FileResume {
+                id: 5,
+                off: 7,
+                codec: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn clip_formats_keep_their_limits() {
+        let offer = Msg::ClipOffer {
+            seq: 3,
+            items: vec![
+                ClipItem {
+                    kind: CLIP_TEXT,
+                    size: 5,
+                    data: b"hallo".to_vec(),
+                },
+                ClipItem {
+                    kind: CLIP_PNG,
+                    size: 3_000_000,
+                    data: vec![0; 3_000_000],
+                },
+            ],
+        };
+        match decode(&encode(&offer)) {
+            Some(Msg::ClipOffer { seq: 3, items }) => {
+                assert_eq!(items[0].data, b"hallo");
+                // too big to ride along: only announced
+                assert_eq!((items[1].size, items[1].data.len()), (3_000_000, 0));
+            }
+            other => panic!("offer: {:?}", other),
+        }
+        // an announced size beyond the limit of its format is refused
+        let mut evil = vec![T_CLIPOFFER];
+        evil.extend_from_slice(&1u32.to_le_bytes());
+        evil.push(1);
+        evil.push(CLIP_HTML);
+        evil.extend_from_slice(&(64u32 << 20).to_le_bytes());
+        evil.extend_from_slice(&0u32.to_le_bytes());
+        assert!(decode(&evil).is_none());
+        let mut evil = vec![T_CLIPDATA];
+        evil.extend_from_slice(&1u32.to_le_bytes());
+        evil.push(9);
+        evil.extend_from_slice(&1u32.to_le_bytes());
+        evil.push(0);
+        assert!(decode(&evil).is_none(), "unknown format");
+    }
+
     #[test]
     fn clipboard_is_capped() {
         let mut evil = vec![T_CLIP];
@@ -882,4 +2504,70 @@ mod tests {
             _ => panic!("clipboard roundtrip"),
         }
     }
+
+    #[test]
+    fn recordings_keep_picture_and_input_only() {
+        assert!(is_recorded(&encode(&Msg::CacheReset)));
+        assert!(is_recorded(&encode(&Msg::Cursor {
+            x: 1,
+            y: 2,
+            visible: true
+        })));
+        assert!(is_recorded(&encode(&Msg::Audio {
+            seq: 1,
+            data: vec![0; 4]
+        })));
+        assert!(!is_recorded(&encode(&Msg::Ping { ts: 1 })));
+        assert!(!is_recorded(&encode(&Msg::TermData {
+            id: 1,
+            data: b"ls".to_vec()
+        })));
+        assert!(!is_recorded(&[]));
+        let video = |key| {
+            encode(&Msg::Video {
+                width: 8,
+                height: 8,
+                key,
+                data: vec![0, 0, 1],
+            })
+        };
+        assert!(is_keyframe(&video(true)));
+        assert!(!is_keyframe(&video(false)));
+    }
+
+    #[test]
+    fn sys_report_roundtrip() {
+        let report = SysReport {
+            os: "Windows 11 Pro 23H2".to_string(),
+            kernel: "10.0.22631".to_string(),
+            host: "BUERO-PC".to_string(),
+            user: "anna".to_string(),
+            version: "0.26.11".to_string(),
+            cpu: "Intel(R) Core(TM) i5-8500".to_string(),
+            cores: 6,
+            mem_total: 16 << 30,
+            mem_used: 9 << 30,
+            uptime: 3 * 86400 + 5,
+            disks: vec![DiskInfo {
+                mount: "C:\\".to_string(),
+                fs: "NTFS".to_string(),
+                total: 512 << 30,
+                free: 100 << 30,
+            }],
+            nets: vec![NetInfo {
+                name: "Ethernet".to_string(),
+                mac: "00:11:22:33:44:55".to_string(),
+                addrs: vec!["192.168.1.24/24".to_string(), "fe80::1/64".to_string()],
+            }],
+        };
+        let enc = encode(&Msg::SysInfo {
+            report: report.clone(),
+        });
+        match decode(&enc) {
+            Some(Msg::SysInfo { report: back }) => assert_eq!(back, report),
+            other => panic!("{:?}", other),
+        }
+        // a cut report is refused, not half read
+        assert!(decode(&enc[..enc.len() - 3]).is_none());
+    }
 }
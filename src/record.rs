@@ -0,0 +1,374 @@
+//! Session recordings: the decoded message stream of a session with the
+//! time it arrived, played back by `--play file`.
+//!
+//! A recording keeps what `proto::is_recorded` says - the picture, the
+//! remote cursor, the input and the voice - as the plain protocol bytes
+//! they travelled as. Files, shells and clipboard contents stay out.
+//!
+//! File layout, all numbers little endian:
+//!
+//! ```text
+//! "FVREC01\n"
+//! u64 start (unix ms), u8 side that recorded, u16 + UTF-8 peer
+//! then per message: u32 ms since start, u8 flags, u32 length, bytes
+//! ```
+//!
+//! A recording that ends mid-message (crash, full disk) plays up to there.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::proto::{self, Msg};
+
+const MAGIC: &[u8; 8] = b"FVREC01\n";
+/// The message came from the viewer (input); otherwise from the host.
+const F_VIEWER: u8 = 1;
+/// A picture to start playing from (`proto::is_keyframe`).
+const F_KEY: u8 = 2;
+/// Unwritten data is pushed to the file at least this often.
+const FLUSH_EVERY: Duration = Duration::from_secs(1);
+/// More than any message a session sends.
+const MAX_ENTRY: u32 = 64 << 20;
+
+/// Which end of the session: who recorded, and who sent a message.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Side {
+    Host,
+    Viewer,
+}
+
+/// Where recordings go: <Videos>/FreeViewer.
+pub fn default_dir() -> PathBuf {
+    let home = std::env::var("USERPROFILE")
+        .ok()
+        .or_else(|| std::env::var("HOME").ok())
+        .map(PathBuf::from);
+    match home {
+        Some(h) => h.join("Videos").join(crate::brand::DIR),
+        None => crate::ident::config_dir().join("recordings"),
+    }
+}
+
+/// "2026-10-18_14-03-22" (UTC) for `unix_secs`.
+pub fn stamp(unix_secs: u64) -> String {
+    // days to civil date, after Howard Hinnant's algorithm
+    let days = (unix_secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + (m <= 2) as i64;
+    let s = unix_secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        y,
+        m,
+        d,
+        s / 3600,
+        s % 3600 / 60,
+        s % 60
+    )
+}
+
+fn unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// -------------------------------------------------------------- recorder --
+
+struct Writer {
+    out: BufWriter<File>,
+    path: PathBuf,
+    start: Instant,
+    flushed: Instant,
+    bytes: u64,
+    /// Writing failed once (disk full, ...): the rest is dropped.
+    failed: Option<String>,
+}
+
+/// An open recording. Cheap to clone: the relay loop, the input pipeline
+/// and the direct video path all write into the same file.
+#[derive(Clone)]
+pub struct Recorder(Arc<Mutex<Writer>>);
+
+impl Recorder {
+    /// A new recording in `dir`, named after the time and the partner.
+    pub fn start(dir: &Path, peer: &str, side: Side) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let who: String = peer
+            .chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect();
+        let name = format!("{}_{}.fvrec", stamp(unix_ms() / 1000), who);
+        Self::create(&dir.join(name), peer, side)
+    }
+
+    pub fn create(path: &Path, peer: &str, side: Side) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&unix_ms().to_le_bytes())?;
+        out.write_all(&[(side == Side::Viewer) as u8])?;
+        let peer = &peer.as_bytes()[..peer.len().min(255)];
+        out.write_all(&(peer.len() as u16).to_le_bytes())?;
+        out.write_all(peer)?;
+        out.flush()?;
+        Ok(Self(Arc::new(Mutex::new(Writer {
+            out,
+            path: path.to_path_buf(),
+            start: Instant::now(),
+            flushed: Instant::now(),
+            bytes: 0,
+            failed: None,
+        }))))
+    }
+
+    /// Writes one message (plain protocol bytes) if a recording keeps it.
+    pub fn tap(&self, from: Side, plain: &[u8]) {
+        if !proto::is_recorded(plain) {
+            return;
+        }
+        let mut w = self.0.lock().unwrap();
+        if w.failed.is_some() {
+            return;
+        }
+        let t = w.start.elapsed().as_millis().min(u32::MAX as u128) as u32;
+        let mut flags = 0;
+        if from == Side::Viewer {
+            flags |= F_VIEWER;
+        }
+        if proto::is_keyframe(plain) {
+            flags |= F_KEY;
+        }
+        let mut res = w.out.write_all(&t.to_le_bytes());
+        res = res.and_then(|_| w.out.write_all(&[flags]));
+        res = res.and_then(|_| w.out.write_all(&(plain.len() as u32).to_le_bytes()));
+        res = res.and_then(|_| w.out.write_all(plain));
+        if res.is_ok() && w.flushed.elapsed() >= FLUSH_EVERY {
+            w.flushed = Instant::now();
+            res = w.out.flush();
+        }
+        match res {
+            Ok(()) => w.bytes += plain.len() as u64 + 9,
+            Err(e) => w.failed = Some(e.to_string()),
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.0.lock().unwrap().path.clone()
+    }
+
+    /// How long it runs and how big it is so far.
+    pub fn progress(&self) -> (Duration, u64) {
+        let w = self.0.lock().unwrap();
+        (w.start.elapsed(), w.bytes)
+    }
+
+    /// Why it stopped writing, if it did.
+    pub fn error(&self) -> Option<String> {
+        self.0.lock().unwrap().failed.clone()
+    }
+
+    /// Everything to disk. Dropping the last clone does the same.
+    pub fn finish(&self) {
+        let _ = self.0.lock().unwrap().out.flush();
+    }
+}
+
+// ---------------------------------------------------------------- reader --
+
+/// One message of a recording; the bytes stay in the file until needed.
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    /// Milliseconds since the recording started.
+    pub t: u32,
+    pub from: Side,
+    pub key: bool,
+    /// First byte of the message, to tell the kinds apart without reading.
+    pub tag: u8,
+    at: u64,
+    len: u32,
+}
+
+/// A recording opened for playing.
+pub struct Recording {
+    file: BufReader<File>,
+    /// When it started (unix ms) and who recorded it with whom.
+    pub started: u64,
+    pub side: Side,
+    pub peer: String,
+    pub entries: Vec<Entry>,
+    /// The file ends in the middle of a message.
+    pub cut: bool,
+}
+
+impl Recording {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut f = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; 8];
+        f.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "keine FreeViewer-Aufnahme",
+            ));
+        }
+        let started = u64::from_le_bytes(read_n(&mut f)?);
+        let side = if read_n::<1>(&mut f)?[0] == 1 {
+            Side::Viewer
+        } else {
+            Side::Host
+        };
+        let n = u16::from_le_bytes(read_n(&mut f)?) as usize;
+        let mut peer = vec![0u8; n];
+        f.read_exact(&mut peer)?;
+        let mut at = (8 + 8 + 1 + 2 + n) as u64;
+        let size = f.get_ref().metadata()?.len();
+
+        let mut entries = Vec::new();
+        let cut = loop {
+            let head: [u8; 9] = match read_n(&mut f) {
+                Ok(h) => h,
+                Err(_) => break at < size,
+            };
+            let t = u32::from_le_bytes([head[0], head[1], head[2], head[3]]);
+            let flags = head[4];
+            let len = u32::from_le_bytes([head[5], head[6], head[7], head[8]]);
+            at += 9;
+            if len == 0 || len > MAX_ENTRY || at + len as u64 > size {
+                break true;
+            }
+            let tag = read_n::<1>(&mut f)?[0];
+            f.seek_relative(len as i64 - 1)?;
+            entries.push(Entry {
+                t,
+                from: if flags & F_VIEWER != 0 {
+                    Side::Viewer
+                } else {
+                    Side::Host
+                },
+                key: flags & F_KEY != 0,
+                tag,
+                at,
+                len,
+            });
+            at += len as u64;
+        };
+        Ok(Self {
+            file: f,
+            started,
+            side,
+            peer: String::from_utf8_lossy(&peer).into_owned(),
+            entries,
+            cut,
+        })
+    }
+
+    /// Length in milliseconds.
+    pub fn duration(&self) -> u32 {
+        self.entries.last().map(|e| e.t).unwrap_or(0)
+    }
+
+    /// Bytes of entry `i` as they were recorded.
+    pub fn bytes(&mut self, i: usize) -> Option<Vec<u8>> {
+        let e = *self.entries.get(i)?;
+        self.file.seek(SeekFrom::Start(e.at)).ok()?;
+        let mut buf = vec![0u8; e.len as usize];
+        self.file.read_exact(&mut buf).ok()?;
+        Some(buf)
+    }
+
+    pub fn msg(&mut self, i: usize) -> Option<Msg> {
+        proto::decode(&self.bytes(i)?)
+    }
+
+    /// Number of entries up to and including time `t`.
+    pub fn count_at(&self, t: u32) -> usize {
+        self.entries.partition_point(|e| e.t <= t)
+    }
+
+    /// Where to start painting so that everything before `i` is on screen:
+    /// the last keyframe before it (or the very beginning).
+    pub fn key_before(&self, i: usize) -> usize {
+        self.entries[..i.min(self.entries.len())]
+            .iter()
+            .rposition(|e| e.key && e.from == Side::Host)
+            .unwrap_or(0)
+    }
+}
+
+fn read_n<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut b = [0u8; N];
+    r.read_exact(&mut b)?;
+    Ok(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamps_are_calendar_dates() {
+        assert_eq!(stamp(0), "1970-01-01_00-00-00");
+        assert_eq!(stamp(951_782_400), "2000-02-29_00-00-00");
+        assert_eq!(stamp(1_792_332_245), "2026-10-18_14-04-05");
+    }
+
+    #[test]
+    fn recorded_messages_come_back_in_order() {
+        let dir = std::env::temp_dir().join(format!("fvrec-test-{}", std::process::id()));
+        let rec = Recorder::start(&dir, "Buero PC", Side::Viewer).unwrap();
+        let frame = proto::encode(&Msg::Frame {
+            width: 2,
+            height: 2,
+            jpeg: vec![1, 2, 3],
+        });
+        rec.tap(Side::Host, &frame);
+        // not kept: nothing about files or pings goes into a recording
+        rec.tap(Side::Viewer, &proto::encode(&Msg::Ping { ts: 5 }));
+        rec.tap(
+            Side::Viewer,
+            &proto::encode(&Msg::Key {
+                code: 'a' as u32,
+                named: false,
+                down: true,
+            }),
+        );
+        let path = rec.path();
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .ends_with("_Buero_PC.fvrec"));
+        rec.finish();
+        drop(rec);
+
+        let mut r = Recording::open(&path).unwrap();
+        assert_eq!(r.peer, "Buero PC");
+        assert_eq!(r.side, Side::Viewer);
+        assert!(!r.cut);
+        assert_eq!(r.entries.len(), 2);
+        assert!(r.entries[0].key && r.entries[0].from == Side::Host);
+        assert_eq!(r.entries[1].from, Side::Viewer);
+        assert!(matches!(r.msg(1), Some(Msg::Key { down: true, .. })));
+        assert_eq!(r.bytes(0).unwrap(), frame);
+        assert_eq!(r.key_before(2), 0);
+
+        // a crash mid-message loses that message only
+        let full = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &full[..full.len() - 2]).unwrap();
+        let r = Recording::open(&path).unwrap();
+        assert_eq!(r.entries.len(), 1);
+        assert!(r.cut);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
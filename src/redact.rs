@@ -0,0 +1,103 @@
+//! Windows the viewer never gets to see: password managers, online banking
+//! and whatever else the host lists in `<config dir>/redact`.
+//!
+//! One rule per line. A rule is a pattern with `*` (anything) and `?` (one
+//! character), without regard to case, and matches a window if it fits the
+//! program's file name or the window title:
+//!
+//! ```text
+//! keepass*.exe
+//! *Online-Banking*
+//! ```
+//!
+//! `capture_loop` looks the windows up again for every captured frame, so
+//! the black box follows a window that moves. It also applies while only a
+//! window or region is shared (`share`).
+
+use crate::input::ScreenRect;
+use crate::share::WinInfo;
+
+/// Rectangles of all windows some rule matches.
+pub fn hidden(rules: &[String], wins: &[WinInfo]) -> Vec<ScreenRect> {
+    if rules.is_empty() {
+        return Vec::new();
+    }
+    wins.iter()
+        .filter(|w| {
+            rules
+                .iter()
+                .any(|r| glob(r, &w.process) || glob(r, &w.title))
+        })
+        .map(|w| w.rect)
+        .collect()
+}
+
+/// True if `text` fits `pattern` as a whole (`*`, `?`, any case).
+fn glob(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.trim().to_lowercase().chars().collect();
+    let t: Vec<char> = text.to_lowercase().chars().collect();
+    if p.is_empty() || t.is_empty() {
+        return false;
+    }
+    // the usual two pointers, back to the last star on a mismatch
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_match_whole_names_in_any_case() {
+        assert!(glob("keepass*.exe", "KeePassXC.exe"));
+        assert!(glob("*Banking*", "Sparkasse Online-Banking - Edge"));
+        assert!(glob("?ey", "key"));
+        assert!(glob("1password.exe", "1Password.exe"));
+        assert!(!glob("bank", "Online-Banking"));
+        assert!(!glob("*.exe", ""));
+        assert!(!glob("  ", "x"));
+    }
+
+    #[test]
+    fn matching_windows_are_hidden() {
+        let win = |process: &str, title: &str, x: i32| WinInfo {
+            id: x as isize,
+            pid: 1,
+            title: title.to_string(),
+            process: process.to_string(),
+            rect: ScreenRect {
+                x,
+                y: 0,
+                w: 10,
+                h: 10,
+            },
+            app: true,
+        };
+        let wins = [
+            win("KeePass.exe", "Datenbank.kdbx - KeePass", 0),
+            win("msedge.exe", "Sparkasse Online-Banking", 20),
+            win("notepad.exe", "Notizen", 40),
+        ];
+        let rules = ["keepass.exe".to_string(), "*banking*".to_string()];
+        let xs: Vec<i32> = hidden(&rules, &wins).iter().map(|r| r.x).collect();
+        assert_eq!(xs, vec![0, 20]);
+        assert!(hidden(&[], &wins).is_empty());
+    }
+}
@@ -0,0 +1,449 @@
+//! Remote file browser: the viewer looks at the disks of the host, fetches
+//! files and folders, renames, deletes and creates folders there.
+//!
+//! Requests and answers are small `Msg::Fs*` messages carrying a request
+//! number; the bytes themselves go through `xfer` like every other transfer.
+//! What the viewer may do is decided on the host alone (`ident::host_rights`);
+//! the viewer greys out what the listing says is not allowed, the host
+//! refuses it anyway.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::ident::{RIGHT_FILES_READ, RIGHT_FILES_WRITE};
+use crate::proto::{FsEntry, Msg, MAX_FS_ENTRIES};
+use crate::shared::{Session, Shared};
+
+/// True for the requests of a viewer (handled on the host).
+pub fn is_request(m: &Msg) -> bool {
+    matches!(
+        m,
+        Msg::FsList { .. }
+            | Msg::FsStat { .. }
+            | Msg::FsGet { .. }
+            | Msg::FsRename { .. }
+            | Msg::FsDelete { .. }
+            | Msg::FsMkdir { .. }
+            | Msg::FsPut { .. }
+    )
+}
+
+/// True for the answers of a host (handled on the viewer).
+pub fn is_reply(m: &Msg) -> bool {
+    matches!(
+        m,
+        Msg::FsListing { .. } | Msg::FsInfo { .. } | Msg::FsGetting { .. } | Msg::FsDone { .. }
+    )
+}
+
+/// The right a request needs.
+fn needs(m: &Msg) -> u32 {
+    match m {
+        Msg::FsList { .. } | Msg::FsStat { .. } | Msg::FsGet { .. } => RIGHT_FILES_READ,
+        _ => RIGHT_FILES_WRITE,
+    }
+}
+
+/// `name` inside the host directory `dir`, with the separator the host uses.
+/// The top level ("") lists full paths, so there the name is the path.
+pub fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else if dir.ends_with(['/', '\\']) {
+        format!("{}{}", dir, name)
+    } else if dir.contains('\\') {
+        format!("{}\\{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn entry(name: String, meta: &fs::Metadata) -> FsEntry {
+    FsEntry {
+        name,
+        dir: meta.is_dir(),
+        size: if meta.is_dir() { 0 } else { meta.len() },
+        mtime: crate::xfer::mtime_of(meta),
+    }
+}
+
+/// Content of `dir`: directories first, then by name, at most
+/// `MAX_FS_ENTRIES`. Links are shown as what they point to.
+pub fn read_dir_sorted(dir: &Path) -> std::io::Result<Vec<FsEntry>> {
+    let mut out = Vec::new();
+    for e in fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let meta = match fs::metadata(e.path()).or_else(|_| e.metadata()) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        out.push(entry(e.file_name().to_string_lossy().to_string(), &meta));
+    }
+    out.sort_by(|a, b| {
+        b.dir
+            .cmp(&a.dir)
+            .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    });
+    out.truncate(MAX_FS_ENTRIES);
+    Ok(out)
+}
+
+/// The top level: drive letters on Windows, `/` and the home folder
+/// elsewhere.
+fn roots() -> Vec<FsEntry> {
+    let dir = |name: String| FsEntry {
+        name,
+        dir: true,
+        ..FsEntry::default()
+    };
+    if cfg!(windows) {
+        (b'A'..=b'Z')
+            .map(|c| format!("{}:\\", c as char))
+            .filter(|d| Path::new(d).exists())
+            .map(dir)
+            .collect()
+    } else {
+        let mut out = vec![dir("/".to_string())];
+        if let Ok(home) = std::env::var("HOME") {
+            out.push(dir(home));
+        }
+        out
+    }
+}
+
+/// Only absolute paths: a relative one would depend on where the host
+/// process happens to run.
+fn host_path(p: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(p);
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Err(format!("kein vollstaendiger Pfad: {}", p))
+    }
+}
+
+/// Host: answers one request of the viewer. Deleting runs on its own
+/// thread, a big tree must not hold up the input.
+pub fn serve(shared: &Arc<Shared>, m: Msg, send: &Arc<dyn Fn(Msg) + Send + Sync>) {
+    let rights = shared.rights.load(Ordering::Relaxed);
+    if rights & needs(&m) == 0 {
+        match m {
+            Msg::FsPut { .. } => {} // the upload lands in the download folder
+            Msg::FsList { req, .. }
+            | Msg::FsStat { req, .. }
+            | Msg::FsGet { req, .. }
+            | Msg::FsRename { req, .. }
+            | Msg::FsDelete { req, .. }
+            | Msg::FsMkdir { req, .. } => send(Msg::FsDone {
+                req,
+                ok: false,
+                msg: "auf diesem Rechner nicht erlaubt".to_string(),
+            }),
+            _ => {}
+        }
+        return;
+    }
+    let done = |req: u32, res: Result<(), String>| {
+        send(Msg::FsDone {
+            req,
+            ok: res.is_ok(),
+            msg: res.err().unwrap_or_default(),
+        })
+    };
+    match m {
+        Msg::FsList { req, path } => {
+            let listing = if path.is_empty() {
+                Ok((roots(), String::new()))
+            } else {
+                host_path(&path).and_then(|p| {
+                    let parent = p
+                        .parent()
+                        .map(|x| x.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    read_dir_sorted(&p)
+                        .map(|e| (e, parent))
+                        .map_err(|e| format!("{}", e))
+                })
+            };
+            match listing {
+                Ok((entries, parent)) => send(Msg::FsListing {
+                    req,
+                    path,
+                    parent,
+                    write: rights & RIGHT_FILES_WRITE != 0,
+                    entries,
+                }),
+                Err(e) => done(req, Err(e)),
+            }
+        }
+        Msg::FsStat { req, path } => {
+            let res = host_path(&path).and_then(|p| {
+                let name = p
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.clone());
+                fs::metadata(&p)
+                    .map(|m| entry(name, &m))
+                    .map_err(|e| format!("{}", e))
+            });
+            match res {
+                Ok(entry) => send(Msg::FsInfo { req, entry }),
+                Err(e) => done(req, Err(e)),
+            }
+        }
+        Msg::FsGet { req, path } => {
+            let p = match host_path(&path) {
+                Ok(p) if p.exists() => p,
+                Ok(_) => return done(req, Err(format!("nicht gefunden: {}", path))),
+                Err(e) => return done(req, Err(e)),
+            };
+            let mut guard = shared.xfer.lock().unwrap();
+            match guard.as_mut() {
+                Some(x) => {
+                    // the viewer has to know the id before the offer arrives
+                    let id = x.reserve_id();
+                    send(Msg::FsGetting { req, id });
+                    x.send_path_as(id, p);
+                }
+                None => done(req, Err("keine Sitzung".to_string())),
+            }
+        }
+        Msg::FsRename { req, from, to } => {
+            let res = host_path(&from).and_then(|a| {
+                let b = host_path(&to)?;
+                if b.exists() {
+                    return Err(format!("gibt es schon: {}", to));
+                }
+                fs::rename(a, b).map_err(|e| format!("{}", e))
+            });
+            done(req, res);
+        }
+        Msg::FsDelete { req, path } => {
+            let p = match host_path(&path) {
+                Ok(p) => p,
+                Err(e) => return done(req, Err(e)),
+            };
+            let send = send.clone();
+            std::thread::spawn(move || {
+                // a link to a directory goes, not what it points to
+                let res = match fs::symlink_metadata(&p) {
+                    Ok(m) if m.is_dir() => fs::remove_dir_all(&p),
+                    Ok(_) => fs::remove_file(&p),
+                    Err(e) => Err(e),
+                };
+                send(Msg::FsDone {
+                    req,
+                    ok: res.is_ok(),
+                    msg: res.err().map(|e| format!("{}", e)).unwrap_or_default(),
+                });
+            });
+        }
+        Msg::FsMkdir { req, path } => {
+            let res = host_path(&path).and_then(|p| fs::create_dir(p).map_err(|e| format!("{}", e)));
+            done(req, res);
+        }
+        Msg::FsPut { id, dir } => {
+            if let Ok(d) = host_path(&dir) {
+                if d.is_dir() {
+                    if let Some(x) = shared.xfer.lock().unwrap().as_mut() {
+                        x.expect(id, d);
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Viewer: what the browser shows. Lives in `Session::fs`, filled from the
+/// session thread, read and driven by the GUI.
+#[derive(Default)]
+pub struct Browser {
+    /// Directory on the host ("" = top level).
+    pub path: String,
+    /// Where "up" leads, None at the top level.
+    pub parent: Option<String>,
+    pub entries: Vec<FsEntry>,
+    /// The host lets us change things.
+    pub write: bool,
+    /// Last error, for the status line.
+    pub status: String,
+    /// Details from the last `stat`.
+    pub info: Option<FsEntry>,
+    /// A listing is on its way.
+    pub busy: bool,
+    next_req: u32,
+    list_req: u32,
+    /// fetches on their way: request -> local target folder
+    gets: HashMap<u32, PathBuf>,
+}
+
+impl Browser {
+    fn req(&mut self) -> u32 {
+        self.next_req = self.next_req.wrapping_add(1).max(1);
+        self.next_req
+    }
+
+    pub fn list(&mut self, path: &str) -> Msg {
+        let req = self.req();
+        self.list_req = req;
+        self.busy = true;
+        Msg::FsList {
+            req,
+            path: path.to_string(),
+        }
+    }
+
+    pub fn refresh(&mut self) -> Msg {
+        let path = self.path.clone();
+        self.list(&path)
+    }
+
+    pub fn stat(&mut self, path: &str) -> Msg {
+        self.info = None;
+        Msg::FsStat {
+            req: self.req(),
+            path: path.to_string(),
+        }
+    }
+
+    /// Fetch `path` (file or folder) into the local folder `into`.
+    pub fn get(&mut self, path: &str, into: PathBuf) -> Msg {
+        let req = self.req();
+        self.gets.insert(req, into);
+        Msg::FsGet {
+            req,
+            path: path.to_string(),
+        }
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> Msg {
+        Msg::FsRename {
+            req: self.req(),
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    pub fn delete(&mut self, path: &str) -> Msg {
+        Msg::FsDelete {
+            req: self.req(),
+            path: path.to_string(),
+        }
+    }
+
+    pub fn mkdir(&mut self, path: &str) -> Msg {
+        Msg::FsMkdir {
+            req: self.req(),
+            path: path.to_string(),
+        }
+    }
+}
+
+/// Viewer: an answer of the host. A change that went through is followed
+/// by a fresh listing.
+pub fn on_reply(sess: &Session, m: Msg) {
+    let mut b = sess.fs.lock().unwrap();
+    match m {
+        Msg::FsListing {
+            req,
+            path,
+            parent,
+            write,
+            entries,
+        } => {
+            if req != b.list_req {
+                return; // an older click, the user has moved on
+            }
+            b.busy = false;
+            b.status.clear();
+            b.parent = if path.is_empty() { None } else { Some(parent) };
+            b.path = path;
+            b.write = write;
+            b.entries = entries;
+        }
+        Msg::FsInfo { entry, .. } => b.info = Some(entry),
+        Msg::FsGetting { req, id } => {
+            if let Some(dir) = b.gets.remove(&req) {
+                drop(b);
+                if let Some(x) = sess.xfer.lock().unwrap().as_mut() {
+                    x.expect(id, dir);
+                }
+            }
+        }
+        Msg::FsDone { req, ok, msg } => {
+            b.gets.remove(&req);
+            if req == b.list_req {
+                b.busy = false;
+            }
+            if ok {
+                b.status.clear();
+                let again = b.refresh();
+                drop(b);
+                sess.send_input(again);
+            } else {
+                b.status = msg;
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn join_uses_the_separator_of_the_host() {
+        assert_eq!(join("", "C:\\"), "C:\\");
+        assert_eq!(join("C:\\", "Users"), "C:\\Users");
+        assert_eq!(join("C:\\Users", "a b"), "C:\\Users\\a b");
+        assert_eq!(join("/", "home"), "/home");
+        assert_eq!(join("/home", "x"), "/home/x");
+    }
+
+    #[test]
+    fn changes_need_the_write_right() {
+        let list = Msg::FsList {
+            req: 1,
+            path: "/".to_string(),
+        };
+        let del = Msg::FsDelete {
+            req: 2,
+            path: "/x".to_string(),
+        };
+        let put = Msg::FsPut {
+            id: 3,
+            dir: "/x".to_string(),
+        };
+        assert_eq!(needs(&list), RIGHT_FILES_READ);
+        assert_eq!(needs(&del), RIGHT_FILES_WRITE);
+        assert_eq!(needs(&put), RIGHT_FILES_WRITE);
+        assert!(host_path("relativ/pfad").is_err());
+    }
+
+    #[test]
+    fn listing_shows_directories_first() {
+        let dir = std::env::temp_dir().join(format!("fvls{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("zeta")).unwrap();
+        fs::write(dir.join("Alpha.txt"), b"1234").unwrap();
+        fs::write(dir.join("beta.txt"), b"").unwrap();
+        let names: Vec<(String, bool, u64)> = read_dir_sorted(&dir)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.name, e.dir, e.size))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("zeta".to_string(), true, 0),
+                ("Alpha.txt".to_string(), false, 4),
+                ("beta.txt".to_string(), false, 0),
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -10,7 +10,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use crate::proto::{self, Msg};
-use crate::shared::Shared;
+use crate::shared::{Session, Shared};
 
 fn nap(ms: u64) {
     std::thread::sleep(Duration::from_millis(ms));
@@ -20,14 +20,15 @@ pub fn input_selftest(shared: Arc<Shared>, argv: &[String], pos: usize) -> ! {
     let id = argv.get(pos + 1).cloned().unwrap_or_default();
     let pw = argv.get(pos + 2).cloned().unwrap_or_default();
 
-    let sh = shared.clone();
+    let sess = Arc::new(Session::new());
+    let (sh, se) = (shared.clone(), sess.clone());
     let idc = id.clone();
-    crate::rt().spawn(async move { crate::viewer::run_viewer(sh, idc, pw).await });
+    crate::rt().spawn(async move { crate::viewer::run_viewer(sh, se, idc, pw).await });
 
     let start = Instant::now();
-    while !shared.connected.load(Ordering::Relaxed) {
+    while !sess.connected.load(Ordering::Relaxed) {
         if start.elapsed() > Duration::from_secs(20) {
-            println!("FAIL: {}", shared.viewer_status.lock().unwrap());
+            println!("FAIL: {}", sess.viewer_status.lock().unwrap());
             std::process::exit(1);
         }
         nap(100);
@@ -43,31 +44,31 @@ pub fn input_selftest(shared: Arc<Shared>, argv: &[String], pos: usize) -> ! {
 
     // ---- absolute pointer (Fernwartung) -------------------------------------
     step!("mouse_abs 5000/5000 = Bildmitte");
-    shared.send_input(Msg::MouseMove { x: 5000, y: 5000 });
+    sess.send_input(Msg::MouseMove { x: 5000, y: 5000 });
     nap(1600);
 
     step!("mouse_abs 2500/2500 = oben links");
-    shared.send_input(Msg::MouseMove { x: 2500, y: 2500 });
+    sess.send_input(Msg::MouseMove { x: 2500, y: 2500 });
     nap(1600);
 
     // ---- game mode: relative pointer ---------------------------------------
     step!("SetMode -> Spiel");
-    shared.send_input(Msg::SetMode {
+    sess.send_input(Msg::SetMode {
         mode: proto::MODE_GAME,
     });
-    shared.mode.store(proto::MODE_GAME, Ordering::Relaxed);
+    sess.mode.store(proto::MODE_GAME, Ordering::Relaxed);
     nap(900);
 
     step!("mouse_delta 30x (+10/0) = +300 px relativ");
     for _ in 0..30 {
-        shared.send_input(Msg::MouseDelta { dx: 10, dy: 0 });
+        sess.send_input(Msg::MouseDelta { dx: 10, dy: 0 });
         nap(10);
     }
     nap(1400);
 
     step!("mouse_delta 30x (0/+8) = +240 px relativ");
     for _ in 0..30 {
-        shared.send_input(Msg::MouseDelta { dx: 0, dy: 8 });
+        sess.send_input(Msg::MouseDelta { dx: 0, dy: 8 });
         nap(10);
     }
     nap(1400);
@@ -75,12 +76,12 @@ pub fn input_selftest(shared: Arc<Shared>, argv: &[String], pos: usize) -> ! {
     // ---- keyboard ----------------------------------------------------------
     for n in 1..=2 {
         step!("NumLock tippen #{}", n);
-        shared.send_input(Msg::KeyVk {
+        sess.send_input(Msg::KeyVk {
             vk: 0x90,
             ext: false,
             down: true,
         });
-        shared.send_input(Msg::KeyVk {
+        sess.send_input(Msg::KeyVk {
             vk: 0x90,
             ext: false,
             down: false,
@@ -89,7 +90,7 @@ pub fn input_selftest(shared: Arc<Shared>, argv: &[String], pos: usize) -> ! {
     }
 
     step!("Strg DOWN (bleibt absichtlich haengen)");
-    shared.send_input(Msg::KeyVk {
+    sess.send_input(Msg::KeyVk {
         vk: 0x11,
         ext: false,
         down: true,
@@ -97,7 +98,7 @@ pub fn input_selftest(shared: Arc<Shared>, argv: &[String], pos: usize) -> ! {
     nap(1600);
 
     step!("Special RELEASE (haengende Tasten loesen)");
-    shared.send_input(Msg::Special {
+    sess.send_input(Msg::Special {
         code: proto::SPECIAL_RELEASE,
     });
     nap(1600);
@@ -105,7 +106,7 @@ pub fn input_selftest(shared: Arc<Shared>, argv: &[String], pos: usize) -> ! {
     // ---- clipboard ---------------------------------------------------------
     let text = format!("FV-CLIP-{}", start.elapsed().as_millis());
     step!("Zwischenablage -> {}", text);
-    shared.send_input(Msg::Clipboard { text });
+    sess.send_input(Msg::Clipboard { text });
     nap(2500);
 
     // ---- clipboard the other way round: host -> viewer ----------------------
@@ -114,10 +115,10 @@ pub fn input_selftest(shared: Arc<Shared>, argv: &[String], pos: usize) -> ! {
     // it. What matters is that host -> viewer messages arrive at all.
     step!("warte auf Zwischenablage-Nachrichten vom Host (max 10 s)");
     let deadline = Instant::now() + Duration::from_secs(10);
-    while Instant::now() < deadline && shared.clip_from_host.load(Ordering::Relaxed) == 0 {
+    while Instant::now() < deadline && sess.clip_from_host.load(Ordering::Relaxed) == 0 {
         nap(200);
     }
-    let n = shared.clip_from_host.load(Ordering::Relaxed);
+    let n = sess.clip_from_host.load(Ordering::Relaxed);
     if n > 0 {
         step!("{} Zwischenablage-Update(s) vom Host empfangen", n);
     } else {
@@ -125,13 +126,13 @@ pub fn input_selftest(shared: Arc<Shared>, argv: &[String], pos: usize) -> ! {
     }
 
     step!("SetMode -> Fernwartung");
-    shared.send_input(Msg::SetMode {
+    sess.send_input(Msg::SetMode {
         mode: proto::MODE_ADMIN,
     });
     nap(600);
 
-    let st = *shared.stats.lock().unwrap();
-    let (rw, rh) = *shared.remote_size.lock().unwrap();
+    let st = *sess.stats.lock().unwrap();
+    let (rw, rh) = *sess.remote_size.lock().unwrap();
     println!(
         "OK: inputtest durch, Remote {}x{}, {:.0} fps, {:.0} kbit/s, {:.0} ms rtt",
         rw, rh, st.fps, st.kbps, st.latency_ms
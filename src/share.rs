@@ -0,0 +1,467 @@
+//! Sharing less than the whole screen: one application window or a fixed
+//! rectangle, chosen on the host.
+//!
+//! The picture keeps the size of the monitor; everything outside the shared
+//! area is painted black in `capture_loop` before the encoder sees it (both
+//! the RGB and the NV12 path). For a window, other programs' windows lying
+//! on top of it are blacked out as well, and the area follows the window
+//! when it moves. Mouse input from the viewer is clamped to the area, and
+//! clicks and the wheel only go through while the pointer is on it; keys
+//! only go through while the window in front is that program's (window
+//! mode) or lies at least partly in the rectangle (region mode).
+//!
+//! A shared window that is minimized or closed leaves a black screen, not
+//! the desktop behind it.
+
+use crate::input::ScreenRect;
+
+/// What the host shares.
+#[derive(Clone, Default, PartialEq)]
+pub enum Share {
+    /// The whole monitor, as always.
+    #[default]
+    Screen,
+    /// One window (`HWND` as a number) and the programs own popups.
+    Window { id: isize, title: String },
+    /// A fixed rectangle of the desktop (physical pixels).
+    Region(ScreenRect),
+}
+
+/// A top level window as `windows` finds it.
+pub struct WinInfo {
+    pub id: isize,
+    pub pid: u32,
+    pub title: String,
+    /// File name of the program, `keepass.exe`.
+    pub process: String,
+    /// Where it is on the desktop (physical pixels).
+    pub rect: ScreenRect,
+    /// Shown in the task bar, i.e. something a user would pick.
+    pub app: bool,
+}
+
+/// What the viewer may see and touch, in desktop pixels.
+#[derive(Clone, PartialEq)]
+pub struct Mask {
+    /// Only this is visible; `None` = everything.
+    pub keep: Option<ScreenRect>,
+    /// Black, even inside `keep`.
+    pub hide: Vec<ScreenRect>,
+    /// Keys may go to the host.
+    pub keys: bool,
+}
+
+impl Default for Mask {
+    /// The whole screen.
+    fn default() -> Self {
+        Mask {
+            keep: None,
+            hide: Vec::new(),
+            keys: true,
+        }
+    }
+}
+
+/// The mask for `share` with the windows `wins` (top of the z-order first).
+/// `focus` is the foreground window; one that is not in `wins` takes no keys.
+pub fn mask(share: &Share, wins: &[WinInfo], focus: isize) -> Mask {
+    let front = wins.iter().find(|w| w.id == focus);
+    match share {
+        Share::Screen => Mask::default(),
+        Share::Region(r) => Mask {
+            keep: Some(*r),
+            hide: Vec::new(),
+            keys: front.is_some_and(|w| overlaps(&w.rect, r)),
+        },
+        Share::Window { id, .. } => {
+            let Some(pos) = wins.iter().position(|w| w.id == *id) else {
+                // gone or minimized: nothing to see
+                return Mask {
+                    keep: Some(ScreenRect {
+                        x: 0,
+                        y: 0,
+                        w: 0,
+                        h: 0,
+                    }),
+                    hide: Vec::new(),
+                    keys: false,
+                };
+            };
+            let me = &wins[pos];
+            Mask {
+                keep: Some(me.rect),
+                // what other programs put on top of it
+                hide: wins[..pos]
+                    .iter()
+                    .filter(|w| w.pid != me.pid)
+                    .map(|w| w.rect)
+                    .collect(),
+                keys: front.is_some_and(|w| w.pid == me.pid),
+            }
+        }
+    }
+}
+
+/// True if the two rectangles share at least one pixel.
+fn overlaps(a: &ScreenRect, b: &ScreenRect) -> bool {
+    a.x < b.x + b.w as i32
+        && b.x < a.x + a.w as i32
+        && a.y < b.y + b.h as i32
+        && b.y < a.y + a.h as i32
+}
+
+impl Mask {
+    /// Nothing to hide.
+    pub fn is_open(&self) -> bool {
+        self.keep.is_none() && self.hide.is_empty()
+    }
+
+    /// True if the desktop point is visible.
+    pub fn shows(&self, x: i32, y: i32) -> bool {
+        let inside =
+            |r: &ScreenRect| x >= r.x && y >= r.y && x < r.x + r.w as i32 && y < r.y + r.h as i32;
+        self.keep.as_ref().is_none_or(inside) && !self.hide.iter().any(inside)
+    }
+
+    /// True if a click or wheel turn with the pointer at `at` (desktop
+    /// pixels) lands on something shared. An unknown position only passes
+    /// when nothing is hidden.
+    pub fn takes_click(&self, at: Option<(i32, i32)>) -> bool {
+        self.is_open() || at.is_some_and(|(x, y)| self.shows(x, y))
+    }
+
+    /// A viewer position (0..10000 over `screen`) moved into the shared
+    /// area. `None` when there is no area to move into.
+    pub fn clamp(&self, x: i32, y: i32, screen: ScreenRect) -> Option<(i32, i32)> {
+        let Some(k) = self.keep else {
+            return Some((x, y));
+        };
+        if k.w == 0 || k.h == 0 {
+            return None;
+        }
+        let (sw, sh) = (screen.w.max(1) as i64, screen.h.max(1) as i64);
+        let px = screen.x as i64 + x as i64 * sw / 10000;
+        let py = screen.y as i64 + y as i64 * sh / 10000;
+        let px = px.clamp(k.x as i64, k.x as i64 + k.w as i64 - 1);
+        let py = py.clamp(k.y as i64, k.y as i64 + k.h as i64 - 1);
+        Some((
+            ((px - screen.x as i64) * 10000 / sw) as i32,
+            ((py - screen.y as i64) * 10000 / sh) as i32,
+        ))
+    }
+
+    /// Paints the hidden parts of a `w` x `h` picture of `screen` black.
+    /// `buf` is RGB, or NV12 if `nv12`.
+    pub fn apply(&self, screen: ScreenRect, buf: &mut [u8], w: u32, h: u32, nv12: bool) {
+        if self.is_open() {
+            return;
+        }
+        // desktop -> picture; what is hidden grows, what is kept shrinks
+        let sx = |v: i32, up: bool| {
+            let f = (v - screen.x) as f64 * w as f64 / screen.w.max(1) as f64;
+            (if up { f.ceil() } else { f.floor() }).clamp(0.0, w as f64) as u32
+        };
+        let sy = |v: i32, up: bool| {
+            let f = (v - screen.y) as f64 * h as f64 / screen.h.max(1) as f64;
+            (if up { f.ceil() } else { f.floor() }).clamp(0.0, h as f64) as u32
+        };
+        if let Some(k) = self.keep {
+            let (x0, y0) = (sx(k.x, true), sy(k.y, true));
+            let (x1, y1) = (
+                sx(k.x + k.w as i32, false).max(x0),
+                sy(k.y + k.h as i32, false).max(y0),
+            );
+            fill(buf, w, h, nv12, (0, 0, w, y0));
+            fill(buf, w, h, nv12, (0, y1, w, h));
+            fill(buf, w, h, nv12, (0, y0, x0, y1));
+            fill(buf, w, h, nv12, (x1, y0, w, y1));
+        }
+        for r in &self.hide {
+            let (x0, y0) = (sx(r.x, false), sy(r.y, false));
+            let (x1, y1) = (sx(r.x + r.w as i32, true), sy(r.y + r.h as i32, true));
+            fill(buf, w, h, nv12, (x0, y0, x1, y1));
+        }
+    }
+}
+
+/// Black from `x0,y0` to just before `x1,y1`. In NV12 the rectangle grows
+/// to even edges, one chroma sample covers 2x2 pixels.
+fn fill(buf: &mut [u8], w: u32, h: u32, nv12: bool, (x0, y0, x1, y1): (u32, u32, u32, u32)) {
+    let (w, h) = (w as usize, h as usize);
+    let (mut x0, mut y0) = (x0 as usize, y0 as usize);
+    let (mut x1, mut y1) = ((x1 as usize).min(w), (y1 as usize).min(h));
+    if nv12 {
+        x0 &= !1;
+        y0 &= !1;
+        x1 = ((x1 + 1) & !1).min(w);
+        y1 = ((y1 + 1) & !1).min(h);
+    }
+    if x0 >= x1 || y0 >= y1 {
+        return;
+    }
+    if !nv12 {
+        if buf.len() < w * h * 3 {
+            return;
+        }
+        for y in y0..y1 {
+            buf[(y * w + x0) * 3..(y * w + x1) * 3].fill(0);
+        }
+        return;
+    }
+    if buf.len() < w * h + w * h / 2 {
+        return;
+    }
+    for y in y0..y1 {
+        buf[y * w + x0..y * w + x1].fill(16);
+    }
+    let uv = w * h;
+    for cy in y0 / 2..y1 / 2 {
+        buf[uv + cy * w + x0..uv + cy * w + x1].fill(128);
+    }
+}
+
+/// Program names by process id, kept between calls of `windows_named`.
+pub type Names = std::collections::HashMap<u32, String>;
+
+/// The visible top level windows, top of the z-order first. Windows kept
+/// out of the capture and click-through overlays are left out, they are
+/// not what anybody sees in the picture.
+pub fn windows() -> Vec<WinInfo> {
+    windows_named(&mut Names::new())
+}
+
+/// `windows`, with the program names taken from `names` where known. Only
+/// new processes are opened; those without a window any more are dropped.
+#[cfg(windows)]
+pub fn windows_named(names: &mut Names) -> Vec<WinInfo> {
+    use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT};
+    use windows::Win32::Graphics::Dwm::{
+        DwmGetWindowAttribute, DWMWA_CLOAKED, DWMWA_EXTENDED_FRAME_BOUNDS,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindow, GetWindowDisplayAffinity, GetWindowLongW, GetWindowRect,
+        GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindowVisible, GWL_EXSTYLE, GW_OWNER,
+        WS_EX_APPWINDOW, WS_EX_LAYERED, WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT,
+    };
+
+    unsafe extern "system" fn collect(h: HWND, l: LPARAM) -> BOOL {
+        let out = &mut *(l.0 as *mut Vec<WinInfo>);
+        if !IsWindowVisible(h).as_bool() || IsIconic(h).as_bool() {
+            return true.into();
+        }
+        let mut cloaked = 0u32;
+        let _ = DwmGetWindowAttribute(
+            h,
+            DWMWA_CLOAKED,
+            &mut cloaked as *mut u32 as *mut std::ffi::c_void,
+            4,
+        );
+        let mut affinity = 0u32;
+        let _ = GetWindowDisplayAffinity(h, &mut affinity);
+        let ex = GetWindowLongW(h, GWL_EXSTYLE) as u32;
+        let overlay =
+            ex & (WS_EX_LAYERED.0 | WS_EX_TRANSPARENT.0) == WS_EX_LAYERED.0 | WS_EX_TRANSPARENT.0;
+        if cloaked != 0 || affinity != 0 || overlay {
+            return true.into();
+        }
+        let mut r = RECT::default();
+        if DwmGetWindowAttribute(
+            h,
+            DWMWA_EXTENDED_FRAME_BOUNDS,
+            &mut r as *mut RECT as *mut std::ffi::c_void,
+            std::mem::size_of::<RECT>() as u32,
+        )
+        .is_err()
+            && GetWindowRect(h, &mut r).is_err()
+        {
+            return true.into();
+        }
+        if r.right <= r.left || r.bottom <= r.top {
+            return true.into();
+        }
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(h, Some(&mut pid));
+        let mut buf = [0u16; 256];
+        let n = GetWindowTextW(h, &mut buf).max(0) as usize;
+        let owned = GetWindow(h, GW_OWNER).is_ok_and(|o| !o.is_invalid());
+        let app = n > 0 && ex & WS_EX_TOOLWINDOW.0 == 0 && (!owned || ex & WS_EX_APPWINDOW.0 != 0);
+        out.push(WinInfo {
+            id: h.0 as isize,
+            pid,
+            title: String::from_utf16_lossy(&buf[..n]),
+            process: String::new(),
+            rect: ScreenRect {
+                x: r.left,
+                y: r.top,
+                w: (r.right - r.left) as u32,
+                h: (r.bottom - r.top) as u32,
+            },
+            app,
+        });
+        true.into()
+    }
+
+    let mut out: Vec<WinInfo> = Vec::new();
+    unsafe {
+        let _ = EnumWindows(Some(collect), LPARAM(&mut out as *mut _ as isize));
+    }
+    // one lookup per program, and only once while it has windows; a
+    // process id that has none left may come back as another program
+    names.retain(|pid, _| out.iter().any(|w| w.pid == *pid));
+    for w in &mut out {
+        w.process = names
+            .entry(w.pid)
+            .or_insert_with(|| process_name(w.pid))
+            .clone();
+    }
+    out
+}
+
+/// `keepass.exe` for a process id; empty if it cannot be opened.
+#[cfg(windows)]
+fn process_name(pid: u32) -> String {
+    use windows::core::PWSTR;
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    unsafe {
+        let Ok(h) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) else {
+            return String::new();
+        };
+        let mut buf = [0u16; 512];
+        let mut len = buf.len() as u32;
+        let ok =
+            QueryFullProcessImageNameW(h, PROCESS_NAME_WIN32, PWSTR(buf.as_mut_ptr()), &mut len);
+        let _ = CloseHandle(h);
+        if ok.is_err() {
+            return String::new();
+        }
+        let path = String::from_utf16_lossy(&buf[..len as usize]);
+        path.rsplit(['\\', '/'])
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+#[cfg(not(windows))]
+pub fn windows_named(_names: &mut Names) -> Vec<WinInfo> {
+    Vec::new()
+}
+
+/// The foreground window, as `WinInfo::id`.
+#[cfg(windows)]
+pub fn focus() -> isize {
+    use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+    unsafe { GetForegroundWindow().0 as isize }
+}
+
+#[cfg(not(windows))]
+pub fn focus() -> isize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: u32, h: u32) -> ScreenRect {
+        ScreenRect { x, y, w, h }
+    }
+
+    fn win(id: isize, pid: u32, r: ScreenRect) -> WinInfo {
+        WinInfo {
+            id,
+            pid,
+            title: String::new(),
+            process: String::new(),
+            rect: r,
+            app: true,
+        }
+    }
+
+    #[test]
+    fn region_keeps_only_its_pixels() {
+        // a 2:1 scaled picture of a 40x20 screen at 100,0
+        let screen = rect(100, 0, 40, 20);
+        let m = mask(&Share::Region(rect(110, 4, 20, 8)), &[], 0);
+        let mut rgb = vec![200u8; 20 * 10 * 3];
+        m.apply(screen, &mut rgb, 20, 10, false);
+        let at = |x: usize, y: usize| rgb[(y * 20 + x) * 3];
+        assert_eq!(at(0, 0), 0);
+        assert_eq!(at(4, 1), 0);
+        assert_eq!(at(5, 2), 200);
+        assert_eq!(at(14, 5), 200);
+        assert_eq!(at(15, 5), 0);
+        assert_eq!(at(10, 6), 0);
+    }
+
+    #[test]
+    fn region_takes_keys_only_with_a_window_in_front_inside() {
+        let share = Share::Region(rect(100, 100, 50, 50));
+        let wins = [
+            win(1, 7, rect(140, 140, 100, 100)),
+            win(2, 7, rect(0, 0, 100, 100)),
+        ];
+        assert!(mask(&share, &wins, 1).keys);
+        assert!(!mask(&share, &wins, 2).keys);
+        assert!(!mask(&share, &wins, 3).keys);
+    }
+
+    #[test]
+    fn nv12_goes_black_in_luma_and_chroma() {
+        let (w, h) = (8u32, 4u32);
+        let mut nv = vec![200u8; (w * h + w * h / 2) as usize];
+        let m = Mask {
+            hide: vec![rect(0, 0, 3, 2)],
+            ..Default::default()
+        };
+        m.apply(rect(0, 0, 8, 4), &mut nv, w, h, true);
+        // grown to 4x2: the whole first chroma row's first two samples
+        assert_eq!(&nv[0..5], &[16, 16, 16, 16, 200]);
+        assert_eq!(nv[8 + 3], 16);
+        assert_eq!(nv[16], 200);
+        assert_eq!(&nv[32..37], &[128, 128, 128, 128, 200]);
+        assert_eq!(nv[40], 200);
+    }
+
+    #[test]
+    fn window_hides_what_lies_on_top() {
+        let wins = [
+            win(1, 7, rect(50, 50, 10, 10)),
+            win(2, 9, rect(0, 0, 10, 10)),
+            win(3, 9, rect(0, 0, 100, 100)),
+            win(4, 5, rect(0, 0, 200, 200)),
+        ];
+        let share = Share::Window {
+            id: 3,
+            title: String::new(),
+        };
+        let m = mask(&share, &wins, 2);
+        assert_eq!(m.keep, Some(rect(0, 0, 100, 100)));
+        // the program's own popup stays, the other program's window goes
+        assert_eq!(m.hide, vec![rect(50, 50, 10, 10)]);
+        assert!(m.keys);
+        assert!(m.shows(5, 5) && !m.shows(55, 55) && !m.shows(150, 5));
+        assert!(m.takes_click(Some((5, 5))) && !m.takes_click(Some((55, 55))));
+        assert!(!m.takes_click(None) && Mask::default().takes_click(None));
+        assert!(!mask(&share, &wins, 4).keys);
+        assert!(!mask(&share, &wins, 8).keys);
+        // closed or minimized: nothing at all
+        let gone = mask(&share, &wins[..2], 2);
+        assert!(!gone.shows(5, 5));
+        assert_eq!(gone.clamp(5000, 5000, rect(0, 0, 200, 200)), None);
+        assert!(!gone.takes_click(Some((0, 0))));
+    }
+
+    #[test]
+    fn clamp_keeps_the_mouse_in_the_area() {
+        let screen = rect(0, 0, 1000, 1000);
+        let m = mask(&Share::Region(rect(100, 200, 300, 300)), &[], 0);
+        assert_eq!(m.clamp(2000, 3000, screen), Some((2000, 3000)));
+        assert_eq!(m.clamp(0, 9000, screen), Some((1000, 4990)));
+        assert_eq!(Mask::default().clamp(10000, 0, screen), Some((10000, 0)));
+    }
+}
@@ -1,7 +1,7 @@
 //! State shared between the GUI thread and the async worker tasks.
 
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use tokio::sync::mpsc::UnboundedSender;
 
@@ -19,6 +19,10 @@ pub struct Stats {
     pub fps: f32,
     pub kbps: f32,
     pub latency_ms: f32,
+    /// Share of updated tiles painted from the tile cache (0..1).
+    pub cache_hits: f32,
+    /// Host: share of the screen the viewer still has at low quality (0..1).
+    pub low_quality: f32,
 }
 
 /// Somebody is knocking: a viewer without a password wants in and the person
@@ -33,8 +37,6 @@ pub struct Knock {
 }
 
 pub struct Shared {
-    /// Was der ferne Bildschirm an Aufloesungen wirklich kann (kommt vom Host).
-    pub remote_resolutions: Mutex<Vec<(u32, u32)>>,
     pub relay_url: String,
     // host side
     pub my_id: Mutex<String>,
@@ -49,18 +51,147 @@ pub struct Shared {
     pub knock_answer: AtomicU8,
     /// Session code of the running session, shown on both sides.
     pub session_code: Mutex<String>,
-    // viewer side
+    /// Host: where forwarded ports may lead (`ident::tunnel_allow`).
+    pub tunnel_allow: Mutex<Vec<String>>,
+    /// Host side: which screen the capture thread should grab.
+    pub monitor: AtomicU8,
+    /// File transfers of the incoming session.
+    pub xfers: Arc<Mutex<Vec<crate::xfer::Progress>>>,
+    /// Transfer engine of the incoming session.
+    pub xfer: Mutex<Option<crate::xfer::Xfer>>,
+    /// Where received files are written to.
+    pub drop_dir: Mutex<std::path::PathBuf>,
+    /// Host: what the viewer may do besides screen and input
+    /// (`ident::RIGHT_*`).
+    pub rights: AtomicU32,
+    /// A received file already exists: `xfer::KEEP_BOTH`, `SKIP` or
+    /// `OVERWRITE`.
+    pub on_conflict: AtomicU8,
+    /// Upper bound for all outgoing file bytes, per second (0 = none).
+    pub xfer_limit: AtomicU64,
+    /// The bucket that limit is kept with, shared by all sessions.
+    pub xfer_pace: crate::xfer::Pacer,
+    /// True while a direct peer to peer path carries the video.
+    pub direct: AtomicBool,
+    /// Host: bytes handed to the relay socket that are not written yet.
+    pub backlog: AtomicU64,
+    /// A newer build waiting on the relay.
+    pub update: Mutex<Option<crate::update::Release>>,
+    pub update_status: Mutex<String>,
+    pub auto_update: AtomicBool,
+    /// Microphone/speaker of the incoming session (voice link).
+    pub voice: Arc<crate::audio::VoiceState>,
+    /// Chat of the incoming session.
+    pub chat: Mutex<crate::chat::Chat>,
+    /// Sends into the incoming session while one runs (the chat window
+    /// uses it).
+    pub chat_out: Mutex<Option<crate::chat::Sender>>,
+    /// Host: show the chat window (a line came in or the user opened it).
+    pub chat_open: AtomicBool,
+    /// Host: the whole screen, one window or a region.
+    pub share: Mutex<crate::share::Share>,
+    /// Host: windows that are always blacked out (`ident::redact_rules`).
+    pub redact: Mutex<Vec<String>>,
+    /// Host: what of it the viewer sees right now (kept up to date by the
+    /// capture, read by the input).
+    pub mask: Mutex<crate::share::Mask>,
+    /// Zwischenablage in beide Richtungen abgleichen?
+    pub clip_on: AtomicBool,
+    pub stats: Mutex<Stats>,
+    /// Outgoing sessions that are connecting or connected (viewer side).
+    pub outgoing: AtomicU32,
+}
+
+impl Shared {
+    pub fn new(relay_url: String, password: String) -> Self {
+        Self {
+            relay_url,
+            my_id: Mutex::new(String::new()),
+            password: Mutex::new(password),
+            host_status: Mutex::new("Starte...".to_string()),
+            host_peer: Mutex::new("Keine aktive Sitzung".to_string()),
+            device_name: Mutex::new(crate::presence::device_name()),
+            knock: Mutex::new(None),
+            knock_answer: AtomicU8::new(0),
+            session_code: Mutex::new(String::new()),
+            tunnel_allow: Mutex::new(crate::ident::tunnel_allow()),
+            monitor: AtomicU8::new(0),
+            xfers: Arc::new(Mutex::new(Vec::new())),
+            xfer: Mutex::new(None),
+            drop_dir: Mutex::new(crate::xfer::default_dir()),
+            on_conflict: AtomicU8::new(crate::xfer::KEEP_BOTH),
+            xfer_limit: AtomicU64::new(crate::ident::xfer_limit()),
+            xfer_pace: crate::xfer::Pacer::new(),
+            rights: AtomicU32::new(crate::ident::host_rights()),
+            direct: AtomicBool::new(false),
+            backlog: AtomicU64::new(0),
+            update: Mutex::new(None),
+            update_status: Mutex::new(String::new()),
+            auto_update: AtomicBool::new(crate::ident::auto_update_enabled()),
+            voice: Arc::new(crate::audio::VoiceState::default()),
+            chat: Mutex::new(crate::chat::Chat::default()),
+            chat_out: Mutex::new(None),
+            chat_open: AtomicBool::new(false),
+            share: Mutex::new(crate::share::Share::default()),
+            redact: Mutex::new(crate::ident::redact_rules()),
+            mask: Mutex::new(crate::share::Mask::default()),
+            clip_on: AtomicBool::new(crate::ident::clipboard_enabled()),
+            stats: Mutex::new(Stats::default()),
+            outgoing: AtomicU32::new(0),
+        }
+    }
+
+    pub fn set_host_status(&self, s: impl Into<String>) {
+        *self.host_status.lock().unwrap() = s.into();
+    }
+    pub fn set_update_status(&self, s: impl Into<String>) {
+        *self.update_status.lock().unwrap() = s.into();
+    }
+}
+
+/// One outgoing session (viewer side). The app can hold several at once,
+/// one per tab, each with its own picture, input, transfers and voice.
+pub struct Session {
+    /// Was der ferne Bildschirm an Aufloesungen wirklich kann (kommt vom Host).
+    pub remote_resolutions: Mutex<Vec<(u32, u32)>>,
+    /// Session code, to compare with the one the host shows.
+    pub session_code: Mutex<String>,
     pub viewer_status: Mutex<String>,
     pub frame: Mutex<Option<FrameData>>,
     pub remote_size: Mutex<(u32, u32)>,
     /// Remote pointer, normalized 0..10000 plus visibility.
     pub remote_cursor: Mutex<(i32, i32, bool)>,
     pub input_tx: Mutex<Option<UnboundedSender<Msg>>>,
-    /// Clipboard text that arrived from the host and has to be written into
-    /// the local clipboard by the clipboard worker thread.
-    pub clip_in: Mutex<Option<String>>,
+    /// Clipboard messages from the host, for the clipboard worker thread
+    /// (it owns the local clipboard and answers fetches).
+    pub clip_in: Mutex<Vec<Msg>>,
     /// How many clipboard updates the host has sent us (diagnostics/tests).
     pub clip_from_host: AtomicU32,
+    /// Files the host has in its clipboard (kept by the clipboard worker).
+    pub clip_files: Mutex<Vec<crate::proto::FsEntry>>,
+    /// Set by the UI: paste the host's clipboard files into this folder.
+    pub clip_paste_to: Mutex<Option<std::path::PathBuf>>,
+    /// When this viewer last sent Ctrl+V: only right after it may the host
+    /// pull the files in our clipboard.
+    pub clip_paste_key: Mutex<Option<std::time::Instant>>,
+    /// Counts the pastes that arrived, so the file manager can refresh.
+    pub clip_pasted: AtomicU32,
+    /// The remote terminal of this session.
+    pub term: Mutex<crate::term::View>,
+    /// Port forwarding of this session.
+    pub tunnels: Mutex<Option<crate::tunnel::Tunnels>>,
+    /// The last system report of the host.
+    pub sysinfo: Mutex<Option<crate::proto::SysReport>>,
+    /// Recording of this session, if one runs.
+    pub recorder: Mutex<Option<crate::record::Recorder>>,
+    /// The host's process table.
+    pub procs: Mutex<crate::procs::View>,
+    /// Chat with the person at the host.
+    pub chat: Mutex<crate::chat::Chat>,
+    /// The user asked for the host's screen black and its input locked.
+    pub privacy_want: AtomicBool,
+    /// The host confirmed that it is.
+    pub privacy: AtomicBool,
     /// Pictures the H.264 worker has decoded (feeds the fps counter).
     pub video_frames: AtomicU32,
     /// Bytes that arrived on the direct UDP path (feeds the bitrate counter).
@@ -78,49 +209,48 @@ pub struct Shared {
     /// The host key (right Ctrl) was pressed: 1 = release the input,
     /// 2 = leave the session entirely. The GUI picks this up and resets it.
     pub escape: AtomicU8,
-    /// Host side: which screen the capture thread should grab.
-    pub monitor: AtomicU8,
-    /// Viewer side: the screens the host offers plus the active one.
+    /// The screens the host offers plus the active one.
     pub monitors: Mutex<Vec<crate::proto::MonitorInfo>>,
     pub active_monitor: AtomicU8,
     /// File transfers of this session (both directions).
-    pub xfers: Mutex<Vec<crate::xfer::Progress>>,
-    /// Transfer engine of the running session (host or viewer role).
+    pub xfers: Arc<Mutex<Vec<crate::xfer::Progress>>>,
+    /// Transfer engine of this session.
     pub xfer: Mutex<Option<crate::xfer::Xfer>>,
-    /// Where received files are written to.
-    pub drop_dir: Mutex<std::path::PathBuf>,
+    /// State of the remote file browser.
+    pub fs: Mutex<crate::remotefs::Browser>,
     /// True while a direct peer to peer path carries the video.
     pub direct: AtomicBool,
-    /// A newer build waiting on the relay.
-    pub update: Mutex<Option<crate::update::Release>>,
-    pub update_status: Mutex<String>,
-    pub auto_update: AtomicBool,
-    /// Microphone/speaker of the running session (voice link).
-    pub voice: std::sync::Arc<crate::audio::VoiceState>,    /// Zwischenablage in beide Richtungen abgleichen?
-    pub clip_on: AtomicBool,
+    /// Microphone/speaker of this session (voice link).
+    pub voice: Arc<crate::audio::VoiceState>,
+    /// Ask the host for lossless text instead of JPEG.
+    pub sharp_text: AtomicBool,
     pub stats: Mutex<Stats>,
 }
 
-impl Shared {
-    pub fn new(relay_url: String, password: String) -> Self {
+impl Session {
+    pub fn new() -> Self {
         Self {
-            relay_url,
-            my_id: Mutex::new(String::new()),
-            password: Mutex::new(password),
-            host_status: Mutex::new("Starte...".to_string()),
-            host_peer: Mutex::new("Keine aktive Sitzung".to_string()),
-            device_name: Mutex::new(crate::presence::device_name()),
-            knock: Mutex::new(None),
-            knock_answer: AtomicU8::new(0),
+            remote_resolutions: Mutex::new(Vec::new()),
             session_code: Mutex::new(String::new()),
             viewer_status: Mutex::new(String::new()),
             frame: Mutex::new(None),
             remote_size: Mutex::new((1920, 1080)),
-            remote_resolutions: Mutex::new(Vec::new()),
             remote_cursor: Mutex::new((0, 0, false)),
             input_tx: Mutex::new(None),
-            clip_in: Mutex::new(None),
+            clip_in: Mutex::new(Vec::new()),
             clip_from_host: AtomicU32::new(0),
+            clip_files: Mutex::new(Vec::new()),
+            clip_paste_to: Mutex::new(None),
+            clip_paste_key: Mutex::new(None),
+            clip_pasted: AtomicU32::new(0),
+            term: Mutex::new(crate::term::View::default()),
+            tunnels: Mutex::new(None),
+            sysinfo: Mutex::new(None),
+            recorder: Mutex::new(None),
+            procs: Mutex::new(crate::procs::View::default()),
+            chat: Mutex::new(crate::chat::Chat::default()),
+            privacy_want: AtomicBool::new(false),
+            privacy: AtomicBool::new(false),
             video_frames: AtomicU32::new(0),
             video_bytes: AtomicU64::new(0),
             udp_frames: AtomicU64::new(0),
@@ -129,27 +259,18 @@ impl Shared {
             connecting: AtomicBool::new(false),
             mode: AtomicU8::new(MODE_ADMIN),
             escape: AtomicU8::new(0),
-            monitor: AtomicU8::new(0),
             monitors: Mutex::new(Vec::new()),
             active_monitor: AtomicU8::new(0),
-            xfers: Mutex::new(Vec::new()),
+            xfers: Arc::new(Mutex::new(Vec::new())),
             xfer: Mutex::new(None),
-            drop_dir: Mutex::new(crate::xfer::default_dir()),
+            fs: Mutex::new(crate::remotefs::Browser::default()),
             direct: AtomicBool::new(false),
-            update: Mutex::new(None),
-            update_status: Mutex::new(String::new()),
-            auto_update: AtomicBool::new(crate::ident::auto_update_enabled()),
-            voice: std::sync::Arc::new(crate::audio::VoiceState::default()),            clip_on: AtomicBool::new(crate::ident::clipboard_enabled()),
+            voice: Arc::new(crate::audio::VoiceState::default()),
+            sharp_text: AtomicBool::new(true),
             stats: Mutex::new(Stats::default()),
         }
     }
 
-    pub fn set_host_status(&self, s: impl Into<String>) {
-        *self.host_status.lock().unwrap() = s.into();
-    }
-    pub fn set_update_status(&self, s: impl Into<String>) {
-        *self.update_status.lock().unwrap() = s.into();
-    }
     pub fn set_viewer_status(&self, s: impl Into<String>) {
         *self.viewer_status.lock().unwrap() = s.into();
     }
@@ -161,13 +282,18 @@ impl Shared {
     pub fn game_mode(&self) -> bool {
         self.mode.load(Ordering::Relaxed) == crate::proto::MODE_GAME
     }
-}
-
-impl Shared {
+    /// Still connecting or connected.
+    pub fn alive(&self) -> bool {
+        self.connected.load(Ordering::Relaxed) || self.connecting.load(Ordering::Relaxed)
+    }
     /// Next sequence number for a freshly decoded picture.
     pub fn next_frame_seq(&self) -> u64 {
-        self.frame_seq
-            .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
-            + 1
+        self.frame_seq.fetch_add(1, Ordering::Relaxed) + 1
     }
-}
\ No newline at end of file
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,740 @@
+//! Remote terminal: a shell on the host, typed into from the viewer.
+//!
+//! The viewer opens a channel with `TermOpen` and the size of its widget.
+//! The host starts the login shell of its account (`cmd` on Windows) on a
+//! pseudo terminal and streams everything it prints back as `TermData`;
+//! keys travel the other way as the bytes a terminal would send. The
+//! widget follows its panel with `TermResize`, `TermClose` ends it from
+//! either side. A host only does this with `ident::RIGHT_SHELL`.
+//!
+//! `--shell <id> <password>` uses the same channel from the command line:
+//! the local terminal is switched to raw mode and the bytes go through
+//! untouched, so the local terminal does the drawing.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use portable_pty::{native_pty_system, Child, CommandBuilder, MasterPty, PtySize};
+
+use crate::ident::RIGHT_SHELL;
+use crate::proto::{Msg, MAX_TERM_DATA};
+use crate::shared::{Session, Shared};
+
+/// Shells one viewer may have open on a host at the same time.
+const MAX_TERMS: usize = 4;
+/// Output still in the pipe when the shell exits gets this long.
+const LINGER: Duration = Duration::from_millis(300);
+/// Lines the viewer keeps above the screen.
+const SCROLLBACK: usize = 2000;
+
+/// True for every message of the terminal channel.
+pub fn is_term_msg(m: &Msg) -> bool {
+    matches!(
+        m,
+        Msg::TermOpen { .. } | Msg::TermData { .. } | Msg::TermResize { .. } | Msg::TermClose { .. }
+    )
+}
+
+fn size(rows: u16, cols: u16) -> PtySize {
+    PtySize {
+        rows: rows.clamp(2, 500),
+        cols: cols.clamp(10, 1000),
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+// ------------------------------------------------------------------ host --
+
+struct Pty {
+    master: Box<dyn MasterPty + Send>,
+    /// to the writer thread: the input loop never waits for a busy shell
+    tx: mpsc::Sender<Vec<u8>>,
+    child: Box<dyn Child + Send + Sync>,
+    /// the shell is gone: when it was noticed and what the viewer is told
+    exited: Option<(Instant, String)>,
+}
+
+/// The shells of one session on the host. Dropping it ends them all.
+pub struct Host {
+    shared: Arc<Shared>,
+    send: Arc<dyn Fn(Msg) + Send + Sync>,
+    ptys: HashMap<u32, Pty>,
+}
+
+impl Host {
+    pub fn new(shared: Arc<Shared>, send: Arc<dyn Fn(Msg) + Send + Sync>) -> Self {
+        Self {
+            shared,
+            send,
+            ptys: HashMap::new(),
+        }
+    }
+
+    pub fn on_msg(&mut self, m: Msg) {
+        match m {
+            Msg::TermOpen { id, rows, cols } => {
+                let refuse = if self.shared.rights.load(Ordering::Relaxed) & RIGHT_SHELL == 0 {
+                    Some("Dieser Computer erlaubt kein Terminal".to_string())
+                } else if self.ptys.contains_key(&id) {
+                    return;
+                } else if self.ptys.len() >= MAX_TERMS {
+                    Some("zu viele Terminals offen".to_string())
+                } else {
+                    match spawn(id, rows, cols, self.send.clone()) {
+                        Ok(p) => {
+                            self.ptys.insert(id, p);
+                            self.shared.set_host_status("Terminal geoeffnet");
+                            None
+                        }
+                        Err(e) => Some(format!("Terminal: {}", e)),
+                    }
+                };
+                if let Some(msg) = refuse {
+                    (self.send)(Msg::TermClose { id, msg });
+                }
+            }
+            Msg::TermData { id, data } => {
+                if let Some(p) = self.ptys.get(&id) {
+                    let _ = p.tx.send(data);
+                }
+            }
+            Msg::TermResize { id, rows, cols } => {
+                if let Some(p) = self.ptys.get(&id) {
+                    let _ = p.master.resize(size(rows, cols));
+                }
+            }
+            Msg::TermClose { id, .. } => {
+                if let Some(mut p) = self.ptys.remove(&id) {
+                    let _ = p.child.kill();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Notices shells that have exited and closes their channel once the
+    /// last output is through. Call it regularly.
+    pub fn tick(&mut self) {
+        let mut done = Vec::new();
+        for (id, p) in self.ptys.iter_mut() {
+            if p.exited.is_none() {
+                if let Ok(Some(st)) = p.child.try_wait() {
+                    p.exited = Some((Instant::now(), format!("beendet ({})", st.exit_code())));
+                }
+            }
+            if let Some((at, msg)) = &p.exited {
+                if at.elapsed() >= LINGER {
+                    done.push((*id, msg.clone()));
+                }
+            }
+        }
+        for (id, msg) in done {
+            self.ptys.remove(&id);
+            (self.send)(Msg::TermClose { id, msg });
+        }
+    }
+}
+
+impl Drop for Host {
+    fn drop(&mut self) {
+        for (_, mut p) in self.ptys.drain() {
+            let _ = p.child.kill();
+        }
+    }
+}
+
+/// Starts the shell, a thread that forwards its output until the pty
+/// closes and one that types what the viewer sends.
+fn spawn(
+    id: u32,
+    rows: u16,
+    cols: u16,
+    send: Arc<dyn Fn(Msg) + Send + Sync>,
+) -> anyhow::Result<Pty> {
+    let pair = native_pty_system().openpty(size(rows, cols))?;
+    let mut cmd = CommandBuilder::new_default_prog();
+    cmd.env("TERM", "xterm-256color");
+    let home = std::env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" });
+    if let Some(h) = home {
+        cmd.cwd(h);
+    }
+    let child = pair.slave.spawn_command(cmd)?;
+    // our copy of the slave would keep the pty open after the shell exits
+    drop(pair.slave);
+    let mut reader = pair.master.try_clone_reader()?;
+    let mut writer = pair.master.take_writer()?;
+    let (tx, rx) = mpsc::channel::<Vec<u8>>();
+    std::thread::spawn(move || {
+        // ends with the `Pty`, which drops the sender
+        for data in rx {
+            if writer.write_all(&data).and_then(|_| writer.flush()).is_err() {
+                break;
+            }
+        }
+    });
+    std::thread::spawn(move || {
+        let mut buf = vec![0u8; 16 * 1024];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => send(Msg::TermData {
+                    id,
+                    data: buf[..n].to_vec(),
+                }),
+            }
+        }
+    });
+    Ok(Pty {
+        master: pair.master,
+        tx,
+        child,
+        exited: None,
+    })
+}
+
+// ---------------------------------------------------------------- viewer --
+
+/// The viewer's end of the terminal: one per session.
+pub struct View {
+    pub id: u32,
+    parser: vt100::Parser,
+    size: (u16, u16),
+    /// the host has a shell for `id` (or is about to start one)
+    pub running: bool,
+    /// why it ended, for the panel
+    pub ended: Option<String>,
+    /// command line mode: output is collected for stdout instead
+    raw: Option<Vec<u8>>,
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            parser: vt100::Parser::new(24, 80, SCROLLBACK),
+            size: (24, 80),
+            running: false,
+            ended: None,
+            raw: None,
+        }
+    }
+}
+
+impl View {
+    /// A fresh shell of `rows` x `cols`; the message goes to the host.
+    pub fn open(&mut self, rows: u16, cols: u16) -> Msg {
+        self.id = self.id.wrapping_add(1);
+        self.parser = vt100::Parser::new(rows, cols, SCROLLBACK);
+        self.size = (rows, cols);
+        self.running = true;
+        self.ended = None;
+        Msg::TermOpen {
+            id: self.id,
+            rows,
+            cols,
+        }
+    }
+
+    /// Output and close of the host.
+    pub fn on_msg(&mut self, m: Msg) {
+        match m {
+            Msg::TermData { id, data } if id == self.id => match self.raw.as_mut() {
+                Some(out) => out.extend_from_slice(&data),
+                None => self.parser.process(&data),
+            },
+            Msg::TermClose { id, msg } if id == self.id => {
+                self.running = false;
+                self.ended = Some(msg);
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys for the shell; looking at the history ends there.
+    pub fn input(&mut self, data: Vec<u8>) -> Option<Msg> {
+        if !self.running || data.is_empty() {
+            return None;
+        }
+        self.parser.set_scrollback(0);
+        Some(Msg::TermData {
+            id: self.id,
+            data: data[..data.len().min(MAX_TERM_DATA)].to_vec(),
+        })
+    }
+
+    /// The widget (or the local terminal) changed its size.
+    pub fn resize(&mut self, rows: u16, cols: u16) -> Option<Msg> {
+        if (rows, cols) == self.size || rows == 0 || cols == 0 {
+            return None;
+        }
+        self.size = (rows, cols);
+        self.parser.set_size(rows, cols);
+        self.running.then_some(Msg::TermResize {
+            id: self.id,
+            rows,
+            cols,
+        })
+    }
+
+    pub fn close(&mut self) -> Option<Msg> {
+        if !self.running {
+            return None;
+        }
+        self.running = false;
+        Some(Msg::TermClose {
+            id: self.id,
+            msg: String::new(),
+        })
+    }
+
+    /// Scrolls the history by `lines` (up is positive).
+    pub fn scroll(&mut self, lines: i32) {
+        let now = self.parser.screen().scrollback() as i32;
+        self.parser.set_scrollback((now + lines).max(0) as usize);
+    }
+
+    pub fn screen(&self) -> &vt100::Screen {
+        self.parser.screen()
+    }
+
+    /// Command line mode: output is kept raw for `take_raw`.
+    pub fn set_raw(&mut self) {
+        self.raw = Some(Vec::new());
+    }
+
+    pub fn take_raw(&mut self) -> Vec<u8> {
+        self.raw.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+}
+
+/// The bytes a terminal sends for a key that does not produce text itself
+/// (text comes as `Event::Text`). `app_cursor`: the program asked for the
+/// other form of the arrow keys (vim, less).
+pub fn key_bytes(key: egui::Key, ctrl: bool, app_cursor: bool) -> Option<Vec<u8>> {
+    use egui::Key as K;
+    let csi = |s: &str| Some(format!("\x1b[{}", s).into_bytes());
+    let arrow = |c: char| {
+        Some(if app_cursor {
+            format!("\x1bO{}", c).into_bytes()
+        } else {
+            format!("\x1b[{}", c).into_bytes()
+        })
+    };
+    match key {
+        K::Enter => Some(b"\r".to_vec()),
+        K::Backspace => Some(vec![0x7f]),
+        K::Tab => Some(b"\t".to_vec()),
+        K::Escape => Some(vec![0x1b]),
+        K::ArrowUp => arrow('A'),
+        K::ArrowDown => arrow('B'),
+        K::ArrowRight => arrow('C'),
+        K::ArrowLeft => arrow('D'),
+        K::Home => arrow('H'),
+        K::End => arrow('F'),
+        K::Insert => csi("2~"),
+        K::Delete => csi("3~"),
+        K::PageUp => csi("5~"),
+        K::PageDown => csi("6~"),
+        K::F1 => Some(b"\x1bOP".to_vec()),
+        K::F2 => Some(b"\x1bOQ".to_vec()),
+        K::F3 => Some(b"\x1bOR".to_vec()),
+        K::F4 => Some(b"\x1bOS".to_vec()),
+        K::F5 => csi("15~"),
+        K::F6 => csi("17~"),
+        K::F7 => csi("18~"),
+        K::F8 => csi("19~"),
+        K::F9 => csi("20~"),
+        K::F10 => csi("21~"),
+        K::F11 => csi("23~"),
+        K::F12 => csi("24~"),
+        K::Space if ctrl => Some(vec![0]),
+        K::OpenBracket if ctrl => Some(vec![0x1b]),
+        K::Backslash if ctrl => Some(vec![0x1c]),
+        K::CloseBracket if ctrl => Some(vec![0x1d]),
+        _ if ctrl => {
+            // Ctrl+A .. Ctrl+Z
+            let name = key.name();
+            let c = name.chars().next().filter(|c| c.is_ascii_alphabetic())?;
+            (name.len() == 1).then(|| vec![c.to_ascii_uppercase() as u8 - b'A' + 1])
+        }
+        _ => None,
+    }
+}
+
+/// Draws the screen into `ui` and turns keyboard input into messages for
+/// the host. `rows`/`cols` follow the space the panel gives it.
+pub fn widget(ui: &mut egui::Ui, view: &mut View) -> Vec<Msg> {
+    let mut out = Vec::new();
+    let font = egui::FontId::monospace(13.0);
+    let cell = ui
+        .painter()
+        .layout_no_wrap("M".to_string(), font.clone(), egui::Color32::WHITE)
+        .size();
+    let avail = ui.available_size();
+    let rows = (avail.y / cell.y).floor().clamp(2.0, 500.0) as u16;
+    let cols = (avail.x / cell.x).floor().clamp(10.0, 1000.0) as u16;
+    out.extend(view.resize(rows, cols));
+
+    let (rect, resp) = ui.allocate_exact_size(avail, egui::Sense::click());
+    if resp.clicked() {
+        resp.request_focus();
+    }
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, egui::Color32::from_rgb(12, 12, 12));
+
+    let screen = view.screen();
+    let (srows, scols) = screen.size();
+    for row in 0..srows {
+        let mut job = egui::text::LayoutJob::default();
+        for col in 0..scols {
+            let Some(c) = screen.cell(row, col) else {
+                continue;
+            };
+            if c.is_wide_continuation() {
+                continue;
+            }
+            let mut fg = color(c.fgcolor(), c.bold(), egui::Color32::from_gray(210));
+            let mut bg = color(c.bgcolor(), false, egui::Color32::TRANSPARENT);
+            if c.inverse() {
+                std::mem::swap(&mut fg, &mut bg);
+                if fg == egui::Color32::TRANSPARENT {
+                    fg = egui::Color32::from_rgb(12, 12, 12);
+                }
+                if bg == egui::Color32::TRANSPARENT {
+                    bg = egui::Color32::from_gray(210);
+                }
+            }
+            let mut text = c.contents().to_string();
+            if text.is_empty() {
+                text.push(' ');
+            }
+            job.append(
+                &text,
+                0.0,
+                egui::TextFormat {
+                    font_id: font.clone(),
+                    color: fg,
+                    background: bg,
+                    ..Default::default()
+                },
+            );
+        }
+        let galley = painter.layout_job(job);
+        let pos = rect.min + egui::vec2(0.0, row as f32 * cell.y);
+        painter.galley(pos, galley, egui::Color32::WHITE);
+    }
+    if !screen.hide_cursor() && screen.scrollback() == 0 {
+        let (cr, cc) = screen.cursor_position();
+        let at = rect.min + egui::vec2(cc as f32 * cell.x, cr as f32 * cell.y);
+        let cur = egui::Rect::from_min_size(at, cell);
+        if resp.has_focus() {
+            painter.rect_filled(cur, 0.0, egui::Color32::from_rgba_unmultiplied(220, 220, 220, 140));
+        } else {
+            painter.rect_stroke(
+                cur,
+                0.0,
+                egui::Stroke::new(1.0, egui::Color32::from_gray(160)),
+                egui::StrokeKind::Inside,
+            );
+        }
+    }
+
+    if resp.hovered() {
+        let dy = ui.input(|i| i.raw_scroll_delta.y);
+        if dy != 0.0 {
+            view.scroll((dy / cell.y).round() as i32);
+        }
+    }
+    if !resp.has_focus() {
+        return out;
+    }
+    // Tab, Pfeile und Escape gehoeren der Shell, nicht egui
+    ui.memory_mut(|m| {
+        m.set_focus_lock_filter(
+            resp.id,
+            egui::EventFilter {
+                tab: true,
+                horizontal_arrows: true,
+                vertical_arrows: true,
+                escape: true,
+            },
+        )
+    });
+    let app_cursor = view.screen().application_cursor();
+    let mut bytes = Vec::new();
+    for ev in ui.input(|i| i.events.clone()) {
+        match ev {
+            egui::Event::Text(t) => bytes.extend_from_slice(t.as_bytes()),
+            egui::Event::Paste(t) => bytes.extend_from_slice(t.replace("\r\n", "\r").as_bytes()),
+            // Ctrl+C und Ctrl+X kommen bei egui als Kopieren/Ausschneiden an
+            egui::Event::Copy => bytes.push(0x03),
+            egui::Event::Cut => bytes.push(0x18),
+            egui::Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => {
+                // Strg+Alt ist unter Windows AltGr, das kommt als Text
+                let ctrl = modifiers.ctrl && !modifiers.alt;
+                if let Some(b) = key_bytes(key, ctrl, app_cursor) {
+                    bytes.extend(b);
+                }
+            }
+            _ => {}
+        }
+    }
+    out.extend(view.input(bytes));
+    out
+}
+
+/// The 16 base colours, then the 6x6x6 cube and the grey ramp of xterm.
+fn color(c: vt100::Color, bold: bool, default: egui::Color32) -> egui::Color32 {
+    const BASE: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 49, 49),
+        (13, 188, 121),
+        (229, 229, 16),
+        (36, 114, 200),
+        (188, 63, 188),
+        (17, 168, 205),
+        (229, 229, 229),
+        (102, 102, 102),
+        (241, 76, 76),
+        (35, 209, 139),
+        (245, 245, 67),
+        (59, 142, 234),
+        (214, 112, 214),
+        (41, 184, 219),
+        (255, 255, 255),
+    ];
+    match c {
+        vt100::Color::Default => default,
+        vt100::Color::Rgb(r, g, b) => egui::Color32::from_rgb(r, g, b),
+        vt100::Color::Idx(i) => {
+            // fett heisst bei den acht Grundfarben: die helle Variante
+            let i = if bold && i < 8 { i + 8 } else { i };
+            match i {
+                0..=15 => {
+                    let (r, g, b) = BASE[i as usize];
+                    egui::Color32::from_rgb(r, g, b)
+                }
+                16..=231 => {
+                    let n = i - 16;
+                    let step = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                    egui::Color32::from_rgb(step(n / 36), step(n / 6 % 6), step(n % 6))
+                }
+                _ => egui::Color32::from_gray(8 + (i - 232) * 10),
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------- command line --
+
+/// `--shell <id> <password>`: runs the host's shell in this terminal until
+/// it exits. `run_viewer` must already be connecting. Returns the exit code
+/// for the process.
+pub fn cli(sess: &Arc<Session>) -> i32 {
+    console::attach();
+    let start = Instant::now();
+    while !sess.connected.load(Ordering::Relaxed) {
+        if start.elapsed() > Duration::from_secs(30) {
+            eprintln!("FAIL: {}", sess.viewer_status.lock().unwrap());
+            return 1;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let (rows, cols) = console::size();
+    let open = {
+        let mut v = sess.term.lock().unwrap();
+        v.set_raw();
+        v.open(rows, cols)
+    };
+    sess.send_input(open);
+    let raw = console::raw();
+
+    let sh = sess.clone();
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = [0u8; 4096];
+        while let Ok(n) = stdin.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            let m = sh.term.lock().unwrap().input(buf[..n].to_vec());
+            if let Some(m) = m {
+                sh.send_input(m);
+            }
+        }
+    });
+
+    let mut stdout = std::io::stdout();
+    let mut checked = Instant::now();
+    let end = loop {
+        std::thread::sleep(Duration::from_millis(15));
+        let (data, ended) = {
+            let mut v = sess.term.lock().unwrap();
+            (v.take_raw(), v.ended.clone())
+        };
+        if !data.is_empty() {
+            let _ = stdout.write_all(&data).and_then(|_| stdout.flush());
+        }
+        if let Some(msg) = ended {
+            break (msg, 0);
+        }
+        if !sess.connected.load(Ordering::Relaxed) {
+            break ("Verbindung getrennt".to_string(), 1);
+        }
+        if checked.elapsed() > Duration::from_millis(250) {
+            checked = Instant::now();
+            let (rows, cols) = console::size();
+            let m = sess.term.lock().unwrap().resize(rows, cols);
+            if let Some(m) = m {
+                sess.send_input(m);
+            }
+        }
+    };
+    drop(raw);
+    eprintln!("\r\n[Terminal: {}]", end.0);
+    end.1
+}
+
+#[cfg(unix)]
+mod console {
+    /// Raw mode of the local terminal; dropping it restores the old one.
+    pub struct Raw(Option<libc::termios>);
+
+    pub fn attach() {}
+
+    pub fn raw() -> Raw {
+        unsafe {
+            let mut t: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(0, &mut t) != 0 {
+                return Raw(None);
+            }
+            let old = t;
+            libc::cfmakeraw(&mut t);
+            libc::tcsetattr(0, libc::TCSANOW, &t);
+            Raw(Some(old))
+        }
+    }
+
+    impl Drop for Raw {
+        fn drop(&mut self) {
+            if let Some(t) = self.0 {
+                unsafe {
+                    libc::tcsetattr(0, libc::TCSANOW, &t);
+                }
+            }
+        }
+    }
+
+    /// Rows and columns of the local terminal.
+    pub fn size() -> (u16, u16) {
+        unsafe {
+            let mut w: libc::winsize = std::mem::zeroed();
+            if libc::ioctl(1, libc::TIOCGWINSZ, &mut w) == 0 && w.ws_row > 0 && w.ws_col > 0 {
+                (w.ws_row, w.ws_col)
+            } else {
+                (24, 80)
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod console {
+    use windows::Win32::System::Console::{
+        AttachConsole, GetConsoleMode, GetConsoleScreenBufferInfo, GetStdHandle,
+        SetConsoleMode, ATTACH_PARENT_PROCESS, CONSOLE_MODE, CONSOLE_SCREEN_BUFFER_INFO,
+        DISABLE_NEWLINE_AUTO_RETURN, ENABLE_PROCESSED_OUTPUT, ENABLE_VIRTUAL_TERMINAL_INPUT,
+        ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_INPUT_HANDLE, STD_OUTPUT_HANDLE,
+    };
+
+    /// Console modes before raw mode; dropping it restores them.
+    pub struct Raw(Option<(CONSOLE_MODE, CONSOLE_MODE)>);
+
+    /// The release build has no console of its own: borrow the one it was
+    /// started from (best with `start /wait`, else cmd reads along).
+    pub fn attach() {
+        unsafe {
+            let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+        }
+    }
+
+    pub fn raw() -> Raw {
+        unsafe {
+            let (Ok(inp), Ok(out)) = (GetStdHandle(STD_INPUT_HANDLE), GetStdHandle(STD_OUTPUT_HANDLE))
+            else {
+                return Raw(None);
+            };
+            let mut mi = CONSOLE_MODE::default();
+            let mut mo = CONSOLE_MODE::default();
+            if GetConsoleMode(inp, &mut mi).is_err() || GetConsoleMode(out, &mut mo).is_err() {
+                return Raw(None);
+            }
+            // keine Zeilenbearbeitung, kein Echo, Ctrl+C geht an den Host
+            let _ = SetConsoleMode(inp, ENABLE_VIRTUAL_TERMINAL_INPUT);
+            let _ = SetConsoleMode(
+                out,
+                mo | ENABLE_PROCESSED_OUTPUT
+                    | ENABLE_VIRTUAL_TERMINAL_PROCESSING
+                    | DISABLE_NEWLINE_AUTO_RETURN,
+            );
+            Raw(Some((mi, mo)))
+        }
+    }
+
+    impl Drop for Raw {
+        fn drop(&mut self) {
+            if let Some((mi, mo)) = self.0 {
+                unsafe {
+                    if let Ok(h) = GetStdHandle(STD_INPUT_HANDLE) {
+                        let _ = SetConsoleMode(h, mi);
+                    }
+                    if let Ok(h) = GetStdHandle(STD_OUTPUT_HANDLE) {
+                        let _ = SetConsoleMode(h, mo);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn size() -> (u16, u16) {
+        unsafe {
+            let mut info = CONSOLE_SCREEN_BUFFER_INFO::default();
+            match GetStdHandle(STD_OUTPUT_HANDLE) {
+                Ok(h) if GetConsoleScreenBufferInfo(h, &mut info).is_ok() => {
+                    let w = info.srWindow;
+                    (
+                        (w.Bottom - w.Top + 1).max(2) as u16,
+                        (w.Right - w.Left + 1).max(10) as u16,
+                    )
+                }
+                _ => (24, 80),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_become_terminal_bytes() {
+        assert_eq!(key_bytes(egui::Key::Enter, false, false), Some(b"\r".to_vec()));
+        assert_eq!(key_bytes(egui::Key::ArrowUp, false, false), Some(b"\x1b[A".to_vec()));
+        assert_eq!(key_bytes(egui::Key::ArrowUp, false, true), Some(b"\x1bOA".to_vec()));
+        assert_eq!(key_bytes(egui::Key::D, true, false), Some(vec![4]));
+        assert_eq!(key_bytes(egui::Key::Z, true, false), Some(vec![26]));
+        // plain letters arrive as text, not as keys
+        assert_eq!(key_bytes(egui::Key::D, false, false), None);
+        assert_eq!(key_bytes(egui::Key::Space, false, false), None);
+    }
+}
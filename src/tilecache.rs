@@ -0,0 +1,197 @@
+//! Content addressed tile cache, the same idea as the RDP bitmap cache.
+//!
+//! Switching between two windows or browser tabs brings back pixels the
+//! viewer has already seen a moment ago. Both ends therefore keep the last
+//! `CELLS` grid tiles keyed by a hash of their content: the host only the
+//! keys, the viewer the decoded pixels. When a dirty tile hashes to a key the
+//! viewer still holds, the host sends the 16 byte reference instead of JPEG.
+//!
+//! The two caches are never compared - they stay identical because both
+//! apply the very same operations in the very same order (`touch` for every
+//! painted reference, `insert` for every stored tile) and evict with the
+//! same least-recently-used rule.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Tiles kept on each side. 2048 tiles of 64x64 RGB are 24 MB on the viewer.
+pub const CELLS: usize = 2048;
+
+/// Bounded LRU map from content hash to `V`.
+pub struct Lru<V> {
+    map: HashMap<u64, (V, u64)>,
+    /// use stamp -> key, the oldest stamp is evicted first
+    order: BTreeMap<u64, u64>,
+    stamp: u64,
+    cap: usize,
+}
+
+impl<V> Lru<V> {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            map: HashMap::new(),
+            order: BTreeMap::new(),
+            stamp: 0,
+            cap: cap.max(1),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains(&self, key: u64) -> bool {
+        self.map.contains_key(&key)
+    }
+
+    /// Marks `key` as just used and hands out its value.
+    pub fn touch(&mut self, key: u64) -> Option<&V> {
+        self.stamp += 1;
+        let (_, st) = self.map.get_mut(&key)?;
+        self.order.remove(st);
+        *st = self.stamp;
+        self.order.insert(self.stamp, key);
+        self.map.get(&key).map(|(v, _)| v)
+    }
+
+    /// Stores `value` under `key`, evicting the least recently used entry
+    /// when the cache is full.
+    pub fn insert(&mut self, key: u64, value: V) {
+        self.stamp += 1;
+        if let Some((_, st)) = self.map.remove(&key) {
+            self.order.remove(&st);
+        }
+        while self.map.len() >= self.cap {
+            let oldest = match self.order.pop_first() {
+                Some((_, k)) => k,
+                None => break,
+            };
+            self.map.remove(&oldest);
+        }
+        self.map.insert(key, (value, self.stamp));
+        self.order.insert(self.stamp, key);
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.order.clear();
+    }
+}
+
+/// Hit counters for the statistics line.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct CacheStats {
+    /// Tiles painted from the cache.
+    pub hits: u64,
+    /// Tiles that had to be encoded and were stored.
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Share of dirty tiles that came out of the cache (0..1).
+    pub fn ratio(&self) -> f32 {
+        let all = self.hits + self.misses;
+        if all == 0 {
+            0.0
+        } else {
+            self.hits as f32 / all as f32
+        }
+    }
+}
+
+/// 64 bit content hash of one tile of a packed RGB buffer. The tile size is
+/// part of the hash, so a clipped edge tile never matches a full one.
+pub fn cell_hash(rgb: &[u8], w: u32, x: u32, y: u32, cw: u32, ch: u32) -> u64 {
+    const K: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut h = (cw as u64) << 32 | ch as u64;
+    h = h.wrapping_mul(K);
+    let stride = w as usize * 3;
+    for row in 0..ch as usize {
+        let a = (y as usize + row) * stride + x as usize * 3;
+        let line = &rgb[a..a + cw as usize * 3];
+        let mut words = line.chunks_exact(8);
+        for c in &mut words {
+            let v = u64::from_le_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]);
+            h = (h ^ v).wrapping_mul(K).rotate_left(29);
+        }
+        for &b in words.remainder() {
+            h = (h ^ b as u64).wrapping_mul(K).rotate_left(29);
+        }
+    }
+    h ^ (h >> 31)
+}
+
+/// Copies one tile out of the viewer's RGBA canvas as packed RGB.
+pub fn cut_rgba(rgba: &[u8], w: u32, x: u32, y: u32, cw: u32, ch: u32) -> Vec<u8> {
+    let stride = w as usize * 4;
+    let mut out = Vec::with_capacity(cw as usize * ch as usize * 3);
+    for row in 0..ch as usize {
+        let a = (y as usize + row) * stride + x as usize * 4;
+        let e = a + cw as usize * 4;
+        if e > rgba.len() {
+            break;
+        }
+        for px in rgba[a..e].chunks_exact(4) {
+            out.extend_from_slice(&px[..3]);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_goes_first() {
+        let mut c: Lru<u8> = Lru::new(2);
+        c.insert(1, 10);
+        c.insert(2, 20);
+        assert_eq!(c.touch(1), Some(&10));
+        c.insert(3, 30);
+        assert!(c.contains(1) && c.contains(3));
+        assert!(!c.contains(2), "2 was the oldest");
+        assert_eq!(c.len(), 2);
+    }
+
+    #[test]
+    fn both_sides_stay_in_step() {
+        // host keeps only keys, viewer keeps pixels - same operations, same
+        // content, no matter how often entries are replaced
+        let mut host: Lru<()> = Lru::new(3);
+        let mut viewer: Lru<Vec<u8>> = Lru::new(3);
+        for i in 0..50u64 {
+            let k = i * 7 % 5;
+            if host.contains(k) {
+                host.touch(k);
+                assert!(viewer.touch(k).is_some(), "viewer lost {}", k);
+            } else {
+                host.insert(k, ());
+                viewer.insert(k, vec![k as u8]);
+            }
+        }
+        for k in 0..5 {
+            assert_eq!(host.contains(k), viewer.contains(k));
+        }
+    }
+
+    #[test]
+    fn hash_sees_content_and_size() {
+        let w = 8u32;
+        let mut rgb = vec![0u8; 8 * 8 * 3];
+        let a = cell_hash(&rgb, w, 0, 0, 4, 4);
+        assert_eq!(a, cell_hash(&rgb, w, 4, 4, 4, 4), "same pixels, same key");
+        assert_ne!(a, cell_hash(&rgb, w, 0, 0, 4, 3));
+        rgb[(w as usize + 1) * 3] = 1;
+        assert_ne!(a, cell_hash(&rgb, w, 0, 0, 4, 4));
+    }
+
+    #[test]
+    fn cut_drops_alpha() {
+        let rgba = vec![1, 2, 3, 255, 4, 5, 6, 255, 7, 8, 9, 255, 10, 11, 12, 255];
+        assert_eq!(cut_rgba(&rgba, 2, 1, 0, 1, 2), vec![4, 5, 6, 10, 11, 12]);
+    }
+}
@@ -268,7 +268,7 @@ mod imp {
         let peer = sh.host_peer.lock().unwrap().clone();
         let status = sh.host_status.lock().unwrap().clone();
         let in_session =
-            sh.connected.load(Ordering::Relaxed) || peer.to_lowercase().contains("sitzung mit");
+            sh.outgoing.load(Ordering::Relaxed) > 0 || peer.to_lowercase().contains("sitzung mit");
         tip_for(crate::update::VERSION, &id, &status, &peer, in_session)
     }
 
@@ -294,8 +294,9 @@ mod imp {
         let _ = Shell_NotifyIconW(NIM_MODIFY, &data);
     }
 
-    /// A balloon, used exactly once: when the window disappears into the tray
-    /// for the first time, so nobody thinks the program is gone.
+    /// A balloon next to the tray icon: when the window disappears into the
+    /// tray for the first time, when somebody knocks, when a chat line
+    /// comes in.
     pub unsafe fn balloon(title: &str, text: &str) {
         let hwnd = tray_hwnd();
         if hwnd.0.is_null() {
@@ -0,0 +1,648 @@
+//! TCP port forwarding through a session (`-L local:host:port`).
+//!
+//! The viewer listens on a local port. Every connection it accepts becomes
+//! a stream: `TunOpen` asks the host to connect to `host:port` from its
+//! side of the network, `TunOpened` says whether that worked, then
+//! `TunData` carries the bytes both ways. Streams are multiplexed by id
+//! over the one encrypted channel of the session.
+//!
+//! `TunClose` means "nothing more from me" (like a TCP FIN); a stream is
+//! gone once both directions are closed, so half-closed protocols work.
+//! Each side keeps at most `WINDOW` bytes of a stream in flight and the
+//! other side acknowledges with `TunAck` once it wrote them to its socket:
+//! a slow end slows the sender down instead of filling memory.
+//!
+//! The host only connects where its allowlist (`ident::tunnel_allow`) says.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::proto::{Msg, MAX_TUN_DATA};
+
+/// Bytes of one stream that may be sent but not yet acknowledged.
+const WINDOW: u64 = 1 << 20;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// True for every message of the forwarding streams.
+pub fn is_tun_msg(m: &Msg) -> bool {
+    matches!(
+        m,
+        Msg::TunOpen { .. }
+            | Msg::TunOpened { .. }
+            | Msg::TunData { .. }
+            | Msg::TunAck { .. }
+            | Msg::TunClose { .. }
+    )
+}
+
+/// Whether the allowlist lets a viewer reach `host:port`. Entries are
+/// `host:port` or `host:*`; `*` as host matches every host.
+pub fn allowed(list: &[String], host: &str, port: u16) -> bool {
+    list.iter().any(|e| {
+        let Some((h, p)) = e.trim().rsplit_once(':') else {
+            return false;
+        };
+        let h = h.trim_start_matches('[').trim_end_matches(']');
+        (h == "*" || h.eq_ignore_ascii_case(host)) && (p == "*" || p.parse() == Ok(port))
+    })
+}
+
+/// `local:host:port` as ssh takes it; IPv6 hosts in brackets.
+pub fn parse_spec(spec: &str) -> Option<(u16, String, u16)> {
+    let (rest, port) = spec.trim().rsplit_once(':')?;
+    let (local, host) = rest.split_once(':')?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return None;
+    }
+    Some((local.parse().ok()?, host.to_string(), port.parse().ok()?))
+}
+
+#[derive(Default)]
+struct Window {
+    used: Mutex<u64>,
+    cv: Condvar,
+    closed: AtomicBool,
+}
+
+impl Window {
+    /// Waits until `n` more bytes may go out; false once the stream died.
+    fn take(&self, n: u64) -> bool {
+        let mut used = self.used.lock().unwrap();
+        while *used + n > WINDOW && !self.closed.load(Ordering::Relaxed) {
+            used = self
+                .cv
+                .wait_timeout(used, Duration::from_millis(500))
+                .unwrap()
+                .0;
+        }
+        if self.closed.load(Ordering::Relaxed) {
+            return false;
+        }
+        *used += n;
+        true
+    }
+
+    fn give(&self, n: u64) {
+        let mut used = self.used.lock().unwrap();
+        *used = used.saturating_sub(n);
+        self.cv.notify_all();
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+        self.cv.notify_all();
+    }
+}
+
+/// Counters of one forwarded port, for the session bar.
+#[derive(Default)]
+struct Stats {
+    conns: AtomicU32,
+    bytes: AtomicU64,
+}
+
+struct Stream {
+    sock: TcpStream,
+    /// to the writer thread; None once the peer closed its direction
+    tx: Option<mpsc::Sender<Vec<u8>>>,
+    window: Arc<Window>,
+    stats: Option<Arc<Stats>>,
+    /// our socket said EOF and the peer was told
+    read_done: bool,
+}
+
+struct Forward {
+    local: u16,
+    host: String,
+    port: u16,
+    stop: Arc<AtomicBool>,
+    stats: Arc<Stats>,
+}
+
+impl Forward {
+    /// Ends the accept thread: it sits in a blocking `accept`, so one
+    /// connection of our own gets it to look at `stop`.
+    fn close(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        let _ = TcpStream::connect(("127.0.0.1", self.local));
+    }
+}
+
+/// One forwarded port as the session bar shows it.
+#[derive(Clone, Debug)]
+pub struct ForwardInfo {
+    pub local: u16,
+    pub host: String,
+    pub port: u16,
+    /// connections open right now
+    pub conns: u32,
+    /// bytes through it, both directions
+    pub bytes: u64,
+}
+
+struct Inner {
+    send: Arc<dyn Fn(Msg) + Send + Sync>,
+    streams: Mutex<HashMap<u32, Stream>>,
+    /// viewer: accepted connections waiting for `TunOpened`
+    pending: Mutex<HashMap<u32, (TcpStream, Arc<Stats>)>>,
+    forwards: Mutex<Vec<Forward>>,
+    next: AtomicU32,
+}
+
+/// The forwarding of one side of a session. Cheap to clone; the threads
+/// of its streams hold a handle too, so `shutdown` ends them.
+#[derive(Clone)]
+pub struct Tunnels(Arc<Inner>);
+
+impl Tunnels {
+    pub fn new(send: Arc<dyn Fn(Msg) + Send + Sync>) -> Self {
+        Self(Arc::new(Inner {
+            send,
+            streams: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+            forwards: Mutex::new(Vec::new()),
+            next: AtomicU32::new(0),
+        }))
+    }
+
+    fn send(&self, m: Msg) {
+        (self.0.send)(m)
+    }
+
+    /// Viewer: listens on `127.0.0.1:local` and forwards every connection
+    /// to `host:port` behind the host. Returns the local port (useful with
+    /// local port 0).
+    pub fn forward(&self, spec: &str) -> Result<u16, String> {
+        let (local, host, port) =
+            parse_spec(spec).ok_or_else(|| format!("{}: erwartet lokal:ziel:port", spec))?;
+        if local != 0
+            && self
+                .0
+                .forwards
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|f| f.local == local)
+        {
+            return Err(format!("Port {} wird schon weitergeleitet", local));
+        }
+        let l = TcpListener::bind(("127.0.0.1", local))
+            .map_err(|e| format!("Port {}: {}", local, e))?;
+        let local = l.local_addr().map_err(|e| e.to_string())?.port();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stats = Arc::new(Stats::default());
+        self.0.forwards.lock().unwrap().push(Forward {
+            local,
+            host: host.clone(),
+            port,
+            stop: stop.clone(),
+            stats: stats.clone(),
+        });
+        let me = self.clone();
+        // blocks in accept; `Forward::close` wakes it up to stop
+        std::thread::spawn(move || loop {
+            let got = l.accept();
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            match got {
+                Ok((sock, _)) => {
+                    let _ = sock.set_nodelay(true);
+                    let id = me.0.next.fetch_add(1, Ordering::Relaxed) + 1;
+                    me.0.pending
+                        .lock()
+                        .unwrap()
+                        .insert(id, (sock, stats.clone()));
+                    me.send(Msg::TunOpen {
+                        id,
+                        host: host.clone(),
+                        port,
+                    });
+                }
+                // out of sockets or the like: not in a tight loop
+                Err(_) => std::thread::sleep(Duration::from_millis(100)),
+            }
+        });
+        Ok(local)
+    }
+
+    /// Stops listening on `local`; connections that are open keep running.
+    pub fn stop_forward(&self, local: u16) {
+        self.0.forwards.lock().unwrap().retain(|f| {
+            if f.local == local {
+                f.close();
+            }
+            f.local != local
+        });
+    }
+
+    pub fn forwards(&self) -> Vec<ForwardInfo> {
+        self.0
+            .forwards
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|f| ForwardInfo {
+                local: f.local,
+                host: f.host.clone(),
+                port: f.port,
+                conns: f.stats.conns.load(Ordering::Relaxed),
+                bytes: f.stats.bytes.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Handles a stream message of the peer. `allow` is the host's
+    /// allowlist; the viewer passes nothing and so never connects out.
+    pub fn on_msg(&self, m: Msg, allow: &[String]) {
+        match m {
+            Msg::TunOpen { id, host, port } => {
+                if !allowed(allow, &host, port) {
+                    self.send(Msg::TunOpened {
+                        id,
+                        ok: false,
+                        msg: format!("{}:{} ist nicht freigegeben", host, port),
+                    });
+                    return;
+                }
+                if self.0.streams.lock().unwrap().contains_key(&id) {
+                    self.refuse_taken(id);
+                    return;
+                }
+                // connecting may take a while, the input must not wait
+                let me = self.clone();
+                std::thread::spawn(move || match connect(&host, port) {
+                    Ok(sock) => me.attach(id, sock, None, true),
+                    Err(msg) => me.send(Msg::TunOpened { id, ok: false, msg }),
+                });
+            }
+            Msg::TunOpened { id, ok, msg } => {
+                let Some((sock, stats)) = self.0.pending.lock().unwrap().remove(&id) else {
+                    return;
+                };
+                if ok {
+                    self.attach(id, sock, Some(stats), false);
+                } else {
+                    crate::dbg_line(&format!("Weiterleitung abgelehnt: {}", msg));
+                    let _ = sock.shutdown(Shutdown::Both);
+                }
+            }
+            Msg::TunData { id, data } => {
+                if let Some(tx) = self
+                    .0
+                    .streams
+                    .lock()
+                    .unwrap()
+                    .get(&id)
+                    .and_then(|s| s.tx.as_ref())
+                {
+                    let _ = tx.send(data);
+                }
+            }
+            Msg::TunAck { id, n } => {
+                if let Some(s) = self.0.streams.lock().unwrap().get(&id) {
+                    s.window.give(n as u64);
+                }
+            }
+            Msg::TunClose { id } => {
+                let mut streams = self.0.streams.lock().unwrap();
+                let Some(s) = streams.get_mut(&id) else {
+                    return;
+                };
+                // the writer sends what is queued, then passes the EOF on
+                s.tx = None;
+                if s.read_done {
+                    if let Some(s) = streams.remove(&id) {
+                        forget(&s);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Starts the two threads of a connected stream. The host confirms the
+    /// stream (`opened`) only once it can take data, and before the first
+    /// bytes of its own go out.
+    fn attach(&self, id: u32, sock: TcpStream, stats: Option<Arc<Stats>>, opened: bool) {
+        let (Ok(mut rd), Ok(mut wr)) = (sock.try_clone(), sock.try_clone()) else {
+            self.send(if opened {
+                Msg::TunOpened {
+                    id,
+                    ok: false,
+                    msg: "Socket nicht nutzbar".to_string(),
+                }
+            } else {
+                Msg::TunClose { id }
+            });
+            return;
+        };
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        let window = Arc::new(Window::default());
+        {
+            let mut streams = self.0.streams.lock().unwrap();
+            // a second open with the same id (while the first one was
+            // still connecting) must not take over the live stream
+            let Entry::Vacant(slot) = streams.entry(id) else {
+                drop(streams);
+                let _ = sock.shutdown(Shutdown::Both);
+                if opened {
+                    self.refuse_taken(id);
+                }
+                return;
+            };
+            if let Some(st) = &stats {
+                st.conns.fetch_add(1, Ordering::Relaxed);
+            }
+            slot.insert(Stream {
+                sock,
+                tx: Some(tx),
+                window: window.clone(),
+                stats: stats.clone(),
+                read_done: false,
+            });
+        }
+
+        let me = self.clone();
+        let counted = stats.clone();
+        std::thread::spawn(move || {
+            for data in rx {
+                if wr.write_all(&data).is_err() {
+                    me.abort(id);
+                    return;
+                }
+                if let Some(st) = &counted {
+                    st.bytes.fetch_add(data.len() as u64, Ordering::Relaxed);
+                }
+                me.send(Msg::TunAck {
+                    id,
+                    n: data.len() as u32,
+                });
+            }
+            let _ = wr.shutdown(Shutdown::Write);
+        });
+
+        if opened {
+            self.send(Msg::TunOpened {
+                id,
+                ok: true,
+                msg: String::new(),
+            });
+        }
+
+        let me = self.clone();
+        std::thread::spawn(move || {
+            let mut buf = vec![0u8; MAX_TUN_DATA];
+            loop {
+                let n = match rd.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                if !window.take(n as u64) {
+                    return;
+                }
+                if let Some(st) = &stats {
+                    st.bytes.fetch_add(n as u64, Ordering::Relaxed);
+                }
+                me.send(Msg::TunData {
+                    id,
+                    data: buf[..n].to_vec(),
+                });
+            }
+            me.read_done(id);
+        });
+    }
+
+    /// The peer opened a stream with an id that is in use.
+    fn refuse_taken(&self, id: u32) {
+        self.send(Msg::TunOpened {
+            id,
+            ok: false,
+            msg: format!("Stream {} ist schon offen", id),
+        });
+    }
+
+    /// Our socket reached EOF: tell the peer, and drop the stream if its
+    /// direction is already closed too.
+    fn read_done(&self, id: u32) {
+        let mut streams = self.0.streams.lock().unwrap();
+        let Some(s) = streams.get_mut(&id) else {
+            return;
+        };
+        s.read_done = true;
+        if s.tx.is_none() {
+            if let Some(s) = streams.remove(&id) {
+                forget(&s);
+            }
+        }
+        drop(streams);
+        self.send(Msg::TunClose { id });
+    }
+
+    /// Writing failed: the stream is dead in both directions.
+    fn abort(&self, id: u32) {
+        let s = self.0.streams.lock().unwrap().remove(&id);
+        if let Some(s) = s {
+            forget(&s);
+            let _ = s.sock.shutdown(Shutdown::Both);
+            if !s.read_done {
+                self.send(Msg::TunClose { id });
+            }
+        }
+    }
+
+    /// The session ends: stop listening and cut every stream.
+    pub fn shutdown(&self) {
+        for f in self.0.forwards.lock().unwrap().drain(..) {
+            f.close();
+        }
+        for (_, (sock, _)) in self.0.pending.lock().unwrap().drain() {
+            let _ = sock.shutdown(Shutdown::Both);
+        }
+        for (_, s) in self.0.streams.lock().unwrap().drain() {
+            forget(&s);
+            let _ = s.sock.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Bookkeeping when a stream leaves the table.
+fn forget(s: &Stream) {
+    s.window.close();
+    if let Some(st) = &s.stats {
+        st.conns.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn connect(host: &str, port: u16) -> Result<TcpStream, String> {
+    let addrs: Vec<_> = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("{}: {}", host, e))?
+        .collect();
+    let mut last = format!("{}: keine Adresse", host);
+    for a in addrs {
+        match TcpStream::connect_timeout(&a, CONNECT_TIMEOUT) {
+            Ok(s) => {
+                let _ = s.set_nodelay(true);
+                return Ok(s);
+            }
+            Err(e) => last = format!("{}:{}: {}", host, port, e),
+        }
+    }
+    Err(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowlist_and_specs() {
+        let list = vec![
+            "127.0.0.1:3389".to_string(),
+            "nas.local:*".to_string(),
+            "[::1]:22".to_string(),
+        ];
+        assert!(allowed(&list, "127.0.0.1", 3389));
+        assert!(!allowed(&list, "127.0.0.1", 22));
+        assert!(allowed(&list, "NAS.local", 443));
+        assert!(allowed(&list, "::1", 22));
+        assert!(!allowed(&[], "127.0.0.1", 3389));
+
+        assert_eq!(
+            parse_spec("13389:192.168.1.20:3389"),
+            Some((13389, "192.168.1.20".to_string(), 3389))
+        );
+        assert_eq!(
+            parse_spec("2222:[::1]:22"),
+            Some((2222, "::1".to_string(), 22))
+        );
+        assert_eq!(parse_spec("2222:22"), None);
+        assert_eq!(parse_spec("x:host:22"), None);
+    }
+
+    /// Viewer and host wired back to back, an echo server behind the host.
+    #[test]
+    fn bytes_go_through_and_back() {
+        let echo = TcpListener::bind("127.0.0.1:0").unwrap();
+        let echo_port = echo.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut s, _) = echo.accept().unwrap();
+            let mut buf = [0u8; 4096];
+            loop {
+                match s.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => s.write_all(&buf[..n]).unwrap(),
+                }
+            }
+        });
+
+        let (to_host, host_rx) = mpsc::channel::<Msg>();
+        let (to_viewer, viewer_rx) = mpsc::channel::<Msg>();
+        let to_host = Mutex::new(to_host);
+        let to_viewer = Mutex::new(to_viewer);
+        let viewer = Tunnels::new(Arc::new(move |m| {
+            let _ = to_host.lock().unwrap().send(m);
+        }));
+        let host = Tunnels::new(Arc::new(move |m| {
+            let _ = to_viewer.lock().unwrap().send(m);
+        }));
+        let allow = vec![format!("127.0.0.1:{}", echo_port)];
+        std::thread::spawn(move || {
+            for m in host_rx {
+                host.on_msg(m, &allow);
+            }
+        });
+        let v2 = viewer.clone();
+        std::thread::spawn(move || {
+            for m in viewer_rx {
+                v2.on_msg(m, &[]);
+            }
+        });
+
+        let local = viewer
+            .forward(&format!("0:127.0.0.1:{}", echo_port))
+            .unwrap();
+        let mut c = TcpStream::connect(("127.0.0.1", local)).unwrap();
+        c.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        // more than one window, so the acks have to flow
+        let data: Vec<u8> = (0..3 * WINDOW as usize).map(|i| (i % 251) as u8).collect();
+        let mut w = c.try_clone().unwrap();
+        let sent = data.clone();
+        std::thread::spawn(move || w.write_all(&sent).unwrap());
+        let mut back = vec![0u8; data.len()];
+        c.read_exact(&mut back).unwrap();
+        assert!(back == data);
+        assert_eq!(viewer.forwards()[0].conns, 1);
+        // the last ack may still be on its way
+        std::thread::sleep(Duration::from_millis(100));
+        assert_eq!(viewer.forwards()[0].bytes, 2 * data.len() as u64);
+    }
+
+    #[test]
+    fn refused_destinations_are_closed() {
+        let (to_host, host_rx) = mpsc::channel::<Msg>();
+        let to_host = Mutex::new(to_host);
+        let viewer = Tunnels::new(Arc::new(move |m| {
+            let _ = to_host.lock().unwrap().send(m);
+        }));
+        let host_said = Arc::new(Mutex::new(Vec::new()));
+        let said = host_said.clone();
+        let host = Tunnels::new(Arc::new(move |m| said.lock().unwrap().push(m)));
+
+        let local = viewer.forward("0:10.0.0.1:22").unwrap();
+        let _c = TcpStream::connect(("127.0.0.1", local)).unwrap();
+        let open = host_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        host.on_msg(open, &["10.0.0.1:3389".to_string()]);
+        let reply = host_said.lock().unwrap().pop().unwrap();
+        assert!(matches!(reply, Msg::TunOpened { ok: false, .. }));
+        viewer.on_msg(reply, &[]);
+        assert!(viewer.0.pending.lock().unwrap().is_empty());
+        // the accept thread lets go of the port
+        viewer.stop_forward(local);
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(TcpStream::connect(("127.0.0.1", local)).is_err());
+    }
+
+    #[test]
+    fn a_second_open_does_not_take_over_a_stream() {
+        let target = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = target.local_addr().unwrap().port();
+        let said = Arc::new(Mutex::new(Vec::new()));
+        let s2 = said.clone();
+        let host = Tunnels::new(Arc::new(move |m| s2.lock().unwrap().push(m)));
+        let allow = vec![format!("127.0.0.1:{}", port)];
+        let open = || Msg::TunOpen {
+            id: 7,
+            host: "127.0.0.1".to_string(),
+            port,
+        };
+        host.on_msg(open(), &allow);
+        host.on_msg(open(), &allow);
+        let replies = |ok: bool| {
+            said.lock()
+                .unwrap()
+                .iter()
+                .filter(|m| matches!(m, Msg::TunOpened { id: 7, ok: o, .. } if *o == ok))
+                .count()
+        };
+        for _ in 0..50 {
+            if replies(true) + replies(false) == 2 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!((replies(true), replies(false)), (1, 1));
+        assert_eq!(host.0.streams.lock().unwrap().len(), 1);
+        // and once it is open, a third one is turned away right away
+        host.on_msg(open(), &allow);
+        assert_eq!(replies(false), 2);
+    }
+}
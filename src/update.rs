@@ -353,9 +353,7 @@ pub fn apply_update(fresh: &Path, target: &Path, pid: u32) -> Result<()> {
 
 /// True while a session is running - never interrupt that for an update.
 fn busy(shared: &Arc<Shared>) -> bool {
-    shared.xfer.lock().unwrap().is_some()
-        || shared.connected.load(Ordering::Relaxed)
-        || shared.connecting.load(Ordering::Relaxed)
+    shared.xfer.lock().unwrap().is_some() || shared.outgoing.load(Ordering::Relaxed) > 0
 }
 
 /// Hintergrund-Faden: sucht jetzt und alle paar Stunden nach einem neuen Stand.
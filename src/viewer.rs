@@ -11,69 +11,254 @@ use tokio_tungstenite::tungstenite::Message as WsMsg;
 
 use crate::clip::Clip;
 use crate::crypto::{self, Cipher};
-use crate::encoder::blit_rgb_to_rgba;
+use crate::encoder::{blit_rgb_to_rgba, copy_rect, decode_tile, TILE};
 use crate::net;
-use crate::proto::{decode, encode, Msg};
-use crate::shared::{FrameData, Shared};
+use crate::proto::{decode, encode, CacheRef, Msg};
+use crate::shared::{FrameData, Session, Shared};
+use crate::tilecache::{cut_rgba, CacheStats, Lru, CELLS};
 
 /// Keeps the last complete picture so that delta updates can be painted into it.
-struct Canvas {
-    w: u32,
-    h: u32,
-    rgba: Vec<u8>,
+/// The player of recorded sessions paints through the same one.
+pub(crate) struct Canvas {
+    pub(crate) w: u32,
+    pub(crate) h: u32,
+    pub(crate) rgba: Vec<u8>,
     seq: u64,
+    /// Tiles the host may paint by reference, see `tilecache`.
+    cache: Lru<Vec<u8>>,
+    cache_stats: CacheStats,
+    /// A move could not be applied: the picture is wrong until the next
+    /// full frame ...
+    broken: bool,
+    /// ... and the host still has to be asked for one.
+    ask_key: bool,
 }
 
 impl Canvas {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         Self {
             w: 0,
             h: 0,
             rgba: Vec::new(),
             seq: 0,
+            cache: Lru::new(CELLS),
+            cache_stats: CacheStats::default(),
+            broken: false,
+            ask_key: false,
         }
     }
 
-    fn set_full(&mut self, w: u32, h: u32, rgba: Vec<u8>) {
+    /// True once after a move failed: the tiles that follow would land on
+    /// a picture that was never scrolled, so a full frame has to come.
+    pub(crate) fn wants_keyframe(&mut self) -> bool {
+        std::mem::take(&mut self.ask_key)
+    }
+
+    /// The host's cache did the same for these references, so every one of
+    /// them is touched - even when the canvas cannot be painted right now.
+    fn cache_paint(&mut self, w: u32, h: u32, cells: &[CacheRef]) -> bool {
+        let ok = self.matches(w, h);
+        let mut painted = false;
+        for c in cells {
+            let px = match self.cache.touch(c.hash) {
+                Some(px) if ok => px,
+                _ => continue,
+            };
+            let cw = TILE.min(w.saturating_sub(c.x));
+            let ch = TILE.min(h.saturating_sub(c.y));
+            painted |= blit_rgb_to_rgba(&mut self.rgba, w, h, c.x, c.y, px, cw, ch);
+        }
+        self.cache_stats.hits += cells.len() as u64;
+        painted
+    }
+
+    /// Remembers the tiles the host just sent. A tile that cannot be cut is
+    /// stored empty: it never paints, but keeps both caches in step.
+    fn cache_store(&mut self, w: u32, h: u32, cells: &[CacheRef]) {
+        let ok = self.matches(w, h);
+        for c in cells {
+            let cw = TILE.min(w.saturating_sub(c.x));
+            let ch = TILE.min(h.saturating_sub(c.y));
+            let px = if ok && c.x < w && c.y < h {
+                cut_rgba(&self.rgba, w, c.x, c.y, cw, ch)
+            } else {
+                Vec::new()
+            };
+            self.cache.insert(c.hash, px);
+        }
+        self.cache_stats.misses += cells.len() as u64;
+    }
+
+    pub(crate) fn set_full(&mut self, w: u32, h: u32, rgba: Vec<u8>) {
         self.w = w;
         self.h = h;
         self.rgba = rgba;
         self.seq += 1;
+        self.broken = false;
     }
 
     fn matches(&self, w: u32, h: u32) -> bool {
         self.w == w && self.h == h && self.rgba.len() == w as usize * h as usize * 4
     }
 
-    fn publish(&self, shared: &Arc<Shared>) {
-        *shared.frame.lock().unwrap() = Some(FrameData {
+    /// Paints one screen message (full frame, tiles, moved or cached
+    /// areas) into the picture. True if the picture changed.
+    pub(crate) fn apply(&mut self, m: &Msg) -> bool {
+        let painted = match m {
+            Msg::Frame {
+                width,
+                height,
+                jpeg,
+            } => match image::load_from_memory_with_format(jpeg, image::ImageFormat::Jpeg) {
+                Ok(img) => {
+                    self.set_full(*width, *height, img.to_rgba8().into_raw());
+                    return true;
+                }
+                Err(_) => false,
+            },
+            // scrolled or dragged: move what we already have, the revealed
+            // strip follows as tiles
+            Msg::CopyRect {
+                width,
+                height,
+                src,
+                dst,
+            } => {
+                let ok = self.matches(*width, *height)
+                    && copy_rect(&mut self.rgba, *width, *height, 4, *src, *dst);
+                if !ok && !self.broken {
+                    self.broken = true;
+                    self.ask_key = true;
+                }
+                ok
+            }
+            Msg::CachePaint {
+                width,
+                height,
+                cells,
+            } => self.cache_paint(*width, *height, cells),
+            Msg::CacheStore {
+                width,
+                height,
+                cells,
+            } => {
+                self.cache_store(*width, *height, cells);
+                false
+            }
+            Msg::CacheReset => {
+                self.cache.clear();
+                false
+            }
+            Msg::Tiles {
+                width,
+                height,
+                tiles,
+            } => {
+                // no keyframe for this size yet - ignore until one arrives
+                let (w, h) = (*width, *height);
+                let mut painted = false;
+                if self.matches(w, h) {
+                    for t in tiles {
+                        if let Some((rgb, tw, th)) = decode_tile(t) {
+                            painted |=
+                                blit_rgb_to_rgba(&mut self.rgba, w, h, t.x, t.y, &rgb, tw, th);
+                        }
+                    }
+                }
+                painted
+            }
+            _ => false,
+        };
+        if painted {
+            self.seq += 1;
+        }
+        painted
+    }
+
+    fn publish(&self, sess: &Arc<Session>) {
+        *sess.frame.lock().unwrap() = Some(FrameData {
             width: self.w,
             height: self.h,
             rgba: self.rgba.clone(),
-            seq: shared.next_frame_seq(),
+            seq: sess.next_frame_seq(),
         });
     }
 }
 
+/// Messages `Canvas::apply` paints.
+pub(crate) fn is_screen_msg(m: &Msg) -> bool {
+    matches!(
+        m,
+        Msg::Frame { .. }
+            | Msg::Tiles { .. }
+            | Msg::CopyRect { .. }
+            | Msg::CachePaint { .. }
+            | Msg::CacheStore { .. }
+            | Msg::CacheReset
+    )
+}
+
+/// Picture bytes of a screen message, for the kbit/s in the session bar.
+fn screen_bytes(m: &Msg) -> usize {
+    match m {
+        Msg::Frame { jpeg, .. } => jpeg.len(),
+        Msg::Tiles { tiles, .. } => tiles.iter().map(|t| t.data.len()).sum(),
+        _ => 0,
+    }
+}
+
 /// Keeps the local clipboard in sync with the remote one. Runs on its own
 /// thread because the platform clipboard handles are not `Send`.
-fn clipboard_worker(shared: Arc<Shared>) {
+fn clipboard_worker(shared: Arc<Shared>, sess: Arc<Session>) {
     let mut clip = Clip::new();
     if !clip.available() {
         return;
     }
-    while shared.connected.load(Ordering::Relaxed) {
-        let incoming = shared.clip_in.lock().unwrap().take();
-        if let Some(text) = incoming {
-            if shared.clip_on.load(Ordering::Relaxed) { clip.set(&text); }
+    clip.guard_pulls();
+    while sess.connected.load(Ordering::Relaxed) {
+        let on = shared.clip_on.load(Ordering::Relaxed);
+        if let Some(at) = sess.clip_paste_key.lock().unwrap().take() {
+            clip.allow_pull(at);
+        }
+        let incoming = std::mem::take(&mut *sess.clip_in.lock().unwrap());
+        for m in incoming.into_iter().filter(|_| on) {
+            clip.on_msg(m, &sess.xfer, &|reply| sess.send_input(reply));
+        }
+        for m in clip.poll().into_iter().filter(|_| on) {
+            sess.send_input(m);
+        }
+        *sess.clip_files.lock().unwrap() = clip.remote_files().to_vec();
+        // "Einfuegen" in the file manager: the host's files go straight
+        // into the local folder shown there
+        if let Some(dir) = sess.clip_paste_to.lock().unwrap().take() {
+            if let Some(pull) = clip.start_paste(Some(dir)) {
+                sess.send_input(pull);
+            }
         }
-        if let Some(text) = clip.poll().filter(|_| shared.clip_on.load(Ordering::Relaxed)) {
-            shared.send_input(Msg::Clipboard { text });
+        match clip.paste_ready(&sess.xfers) {
+            Some(Ok(_)) => {
+                sess.clip_pasted.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(Err(e)) => sess.fs.lock().unwrap().status = e,
+            None => {}
         }
         std::thread::sleep(Duration::from_millis(150));
     }
 }
 
+/// Starts recording the running session into `record::default_dir`;
+/// the status line says where it goes.
+pub fn start_recording(sess: &Arc<Session>, peer: &str) {
+    let dir = crate::record::default_dir();
+    match crate::record::Recorder::start(&dir, peer, crate::record::Side::Viewer) {
+        Ok(r) => {
+            sess.set_viewer_status(format!("Aufnahme: {}", r.path().display()));
+            *sess.recorder.lock().unwrap() = Some(r);
+        }
+        Err(e) => sess.set_viewer_status(format!("Aufnahme nicht moeglich: {}", e)),
+    }
+}
+
 /// How this viewer wants to get in.
 #[derive(Clone, Debug)]
 pub enum Auth {
@@ -83,37 +268,90 @@ pub enum Auth {
     Ask,
 }
 
-pub async fn run_viewer(shared: Arc<Shared>, id: String, password: String) {
-    run_viewer_auth(shared, id, Auth::Password(password)).await
+pub async fn run_viewer(shared: Arc<Shared>, sess: Arc<Session>, id: String, password: String) {
+    run_viewer_auth(shared, sess, id, Auth::Password(password)).await
 }
 
 /// Knock instead of unlocking: the host shows a dialog and decides.
-pub async fn run_viewer_ask(shared: Arc<Shared>, id: String) {
-    run_viewer_auth(shared, id, Auth::Ask).await
+pub async fn run_viewer_ask(shared: Arc<Shared>, sess: Arc<Session>, id: String) {
+    run_viewer_auth(shared, sess, id, Auth::Ask).await
+}
+
+/// Counts a running session in `Shared::outgoing`. A guard, because the GUI
+/// aborts the task to hang up and then nothing after the `await` runs.
+struct Outgoing(Arc<Shared>);
+
+impl Outgoing {
+    fn new(shared: &Arc<Shared>) -> Self {
+        shared.outgoing.fetch_add(1, Ordering::Relaxed);
+        Self(shared.clone())
+    }
+}
+
+impl Drop for Outgoing {
+    fn drop(&mut self) {
+        self.0.outgoing.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Writes begin and end of a session into the audit log; a guard for the
+/// same reason as `Outgoing`.
+struct Audited(String);
+
+impl Audited {
+    fn new(id: &str) -> Self {
+        crate::audit::log(id, "Sitzung beginnt (ausgehend)");
+        Self(id.to_string())
+    }
 }
 
-pub async fn run_viewer_auth(shared: Arc<Shared>, id: String, auth: Auth) {
-    shared.connecting.store(true, Ordering::Relaxed);
-    shared.set_viewer_status(format!("Verbinde mit {} ...", id));
+impl Drop for Audited {
+    fn drop(&mut self) {
+        crate::audit::log(&self.0, "Sitzung endet");
+    }
+}
 
-    let result = viewer_once(&shared, &id, &auth).await;
+/// Runs one outgoing session until it ends. `sess` belongs to this session
+/// alone; several of these may run side by side.
+pub async fn run_viewer_auth(shared: Arc<Shared>, sess: Arc<Session>, id: String, auth: Auth) {
+    let _count = Outgoing::new(&shared);
+    sess.connecting.store(true, Ordering::Relaxed);
+    sess.set_viewer_status(format!("Verbinde mit {} ...", id));
 
-    shared.connected.store(false, Ordering::Relaxed);
-    shared.connecting.store(false, Ordering::Relaxed);
-    if let Some(mut x) = shared.xfer.lock().unwrap().take() {
+    let result = viewer_once(&shared, &sess, &id, &auth).await;
+
+    sess.connected.store(false, Ordering::Relaxed);
+    sess.connecting.store(false, Ordering::Relaxed);
+    if let Some(mut x) = sess.xfer.lock().unwrap().take() {
         x.shutdown();
     }
-    *shared.input_tx.lock().unwrap() = None;
-    *shared.frame.lock().unwrap() = None;
-    crate::vinput::set_active(false);
+    if let Some(t) = sess.tunnels.lock().unwrap().take() {
+        t.shutdown();
+    }
+    *sess.input_tx.lock().unwrap() = None;
+    *sess.frame.lock().unwrap() = None;
+    // the host lifts its privacy mode by itself when the session goes
+    sess.privacy_want.store(false, Ordering::Relaxed);
+    sess.privacy.store(false, Ordering::Relaxed);
+    if let Some(r) = sess.recorder.lock().unwrap().take() {
+        r.finish();
+    }
+    if crate::vinput::in_front(&sess) {
+        crate::vinput::set_active(false);
+    }
 
     match result {
-        Ok(()) => shared.set_viewer_status("Sitzung beendet"),
-        Err(e) => shared.set_viewer_status(format!("Fehler: {}", e)),
+        Ok(()) => sess.set_viewer_status("Sitzung beendet"),
+        Err(e) => sess.set_viewer_status(format!("Fehler: {}", e)),
     }
 }
 
-async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()> {
+async fn viewer_once(
+    shared: &Arc<Shared>,
+    sess: &Arc<Session>,
+    id: &str,
+    auth: &Auth,
+) -> Result<()> {
     let ws = net::connect(&shared.relay_url).await?;
     let (mut sink, mut stream) = ws.split();
     let (tx, mut rx) = mpsc::unbounded_channel::<WsMsg>();
@@ -129,6 +367,7 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
     tx.send(WsMsg::text(net::json_connect(id)))?;
 
     let kp = crypto::keypair();
+    let mut _audited: Option<Audited> = None;
     let mut cipher: Option<Arc<Mutex<Cipher>>> = None;
     let mut session_key: Option<[u8; 32]> = None;
     let started = Instant::now();
@@ -161,7 +400,7 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
                     serde_json::from_str(t.as_str()).unwrap_or(serde_json::Value::Null);
                 match net::msg_type(&v) {
                     "paired" => {
-                        shared.set_viewer_status("Gekoppelt - Authentifizierung...");
+                        sess.set_viewer_status("Gekoppelt - Authentifizierung...");
                         let mut hello = Vec::with_capacity(33);
                         hello.push(crypto::TAG_HELLO);
                         hello.extend_from_slice(&kp.public);
@@ -198,7 +437,7 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
                         cipher = Some(Arc::new(Mutex::new(Cipher::new(&key, false))));
                         session_key = Some(key);
                         let code = crypto::session_code(&key);
-                        *shared.session_code.lock().unwrap() = code.clone();
+                        *sess.session_code.lock().unwrap() = code.clone();
 
                         match auth {
                             Auth::Password(password) => {
@@ -217,7 +456,7 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
                                 out.push(crypto::TAG_ASK);
                                 out.extend_from_slice(me.as_bytes());
                                 tx.send(WsMsg::Binary(out.into()))?;
-                                shared.set_viewer_status(format!(
+                                sess.set_viewer_status(format!(
                                     "Warte auf Bestaetigung ... (Code {})",
                                     code
                                 ));
@@ -229,54 +468,83 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
                             Some(c) => c.clone(),
                             None => break Err(anyhow!("Handshake nicht abgeschlossen")),
                         };
-                        shared.connected.store(true, Ordering::Relaxed);
-                        shared.set_viewer_status("Verbunden");
+                        sess.connected.store(true, Ordering::Relaxed);
+                        sess.set_viewer_status("Verbunden");
+                        _audited = Some(Audited::new(id));
 
                         // input pipeline: GUI -> encrypt -> relay
                         let (in_tx, mut in_rx) = mpsc::unbounded_channel::<Msg>();
-                        *shared.input_tx.lock().unwrap() = Some(in_tx);
+                        *sess.input_tx.lock().unwrap() = Some(in_tx);
                         let c2 = c.clone();
                         let tx2 = tx.clone();
+                        let sh_rec = sess.clone();
                         tokio::spawn(async move {
+                            let mut keys = crate::clip::PasteKeys::default();
                             while let Some(m) = in_rx.recv().await {
-                                let sealed = { c2.lock().unwrap().seal(&encode(&m)) };
+                                if keys.pasted(&m) {
+                                    *sh_rec.clip_paste_key.lock().unwrap() = Some(Instant::now());
+                                }
+                                let plain = encode(&m);
+                                if let Some(r) = sh_rec.recorder.lock().unwrap().as_ref() {
+                                    r.tap(crate::record::Side::Viewer, &plain);
+                                }
+                                let sealed = { c2.lock().unwrap().seal(&plain) };
                                 if tx2.send(WsMsg::Binary(sealed.into())).is_err() {
                                     break;
                                 }
                             }
                         });
 
+                        if crate::ident::record_sessions() & crate::ident::RECORD_VIEWER != 0 {
+                            start_recording(sess, id);
+                        }
+
                         // file transfer engine for this session
                         {
-                            let sh = shared.clone();
+                            let sh = sess.clone();
                             let send_msg: Arc<dyn Fn(Msg) + Send + Sync> =
                                 Arc::new(move |m: Msg| sh.send_input(m));
-                            shared.xfers.lock().unwrap().clear();
-                            *shared.xfer.lock().unwrap() =
-                                Some(crate::xfer::Xfer::new(shared.clone(), send_msg));
+                            sess.xfers.lock().unwrap().clear();
+                            *sess.fs.lock().unwrap() = crate::remotefs::Browser::default();
+                            *sess.term.lock().unwrap() = crate::term::View::default();
+                            *sess.sysinfo.lock().unwrap() = None;
+                            *sess.procs.lock().unwrap() = crate::procs::View::default();
+                            sess.chat.lock().unwrap().start(id);
+                            *sess.xfer.lock().unwrap() = Some(crate::xfer::Xfer::new(
+                                shared.clone(),
+                                sess.xfers.clone(),
+                                send_msg.clone(),
+                            ));
+                            *sess.tunnels.lock().unwrap() =
+                                Some(crate::tunnel::Tunnels::new(send_msg));
                         }
 
                         // tell the host what we can decode. Without this the
                         // host keeps sending JPEG tiles, which is exactly what
                         // older builds expect.
-                        shared.send_input(Msg::Caps {
+                        sess.send_input(Msg::Caps {
                             h264: cfg!(windows) && std::env::var("FV_NOH264").is_err(),
+                            copy: true,
+                            cache: true,
+                        });
+                        sess.send_input(Msg::SetQuality {
+                            lossless: sess.sharp_text.load(Ordering::Relaxed),
                         });
 
                         // direct UDP path: video only, everything else stays
                         // on the relay. The decoder pipeline is started here
                         // so both transports can feed the same worker.
-                        let pipe = video.get_or_insert_with(|| VideoPipe::start(shared.clone()));
+                        let pipe = video.get_or_insert_with(|| VideoPipe::start(sess.clone()));
                         if std::env::var("FV_NOP2P").is_err() {
                             let stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
                             match crate::p2p::P2p::new(session_key.unwrap_or([0u8; 32]), false, stop) {
                                 Ok(p) => {
                                     let gate = pipe.gate();
                                     let gate_loss = gate.clone();
-                                    let sh_v = shared.clone();
-                                    let sh_loss = shared.clone();
-                                    let sh_off = shared.clone();
-                                    let sh_state = shared.clone();
+                                    let sh_v = sess.clone();
+                                    let sh_loss = sess.clone();
+                                    let sh_off = sess.clone();
+                                    let sh_state = sess.clone();
                                     let p_off = p.clone();
                                     tokio::spawn(async move {
                                         // STUN shares this socket with the
@@ -300,6 +568,11 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
                                                     plain.len() as u64,
                                                     Ordering::Relaxed,
                                                 );
+                                                if let Some(r) =
+                                                    sh_v.recorder.lock().unwrap().as_ref()
+                                                {
+                                                    r.tap(crate::record::Side::Host, &plain);
+                                                }
                                                 if let Some(Msg::Video {
                                                     width,
                                                     height,
@@ -325,17 +598,17 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
 
                         // voice link: microphone out, speaker in
                         {
-                            let sh = shared.clone();
+                            let sh = sess.clone();
                             let vsend: Arc<dyn Fn(Msg) + Send + Sync> =
                                 Arc::new(move |m: Msg| sh.send_input(m));
                             voice = Some(crate::audio::Voice::start(
-                                shared.voice.clone(),
+                                sess.voice.clone(),
                                 vsend,
                             ));
                         }
                         // clipboard sync (own thread, clipboard handles are not Send)
-                        let sh_clip = shared.clone();
-                        std::thread::spawn(move || clipboard_worker(sh_clip));
+                        let (sh_clip, se_clip) = (shared.clone(), sess.clone());
+                        std::thread::spawn(move || clipboard_worker(sh_clip, se_clip));
 
                         // latency probe
                         let c3 = c.clone();
@@ -369,42 +642,37 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
                                 None => continue,
                             }
                         };
+                        if let Some(r) = sess.recorder.lock().unwrap().as_ref() {
+                            r.tap(crate::record::Side::Host, &plain);
+                        }
                         match decode(&plain) {
                             Some(Msg::ScreenInfo { width, height }) => {
-                                *shared.remote_size.lock().unwrap() = (width, height);
+                                *sess.remote_size.lock().unwrap() = (width, height);
                             }
                             Some(Msg::Resolutions { list }) => {
-                                *shared.remote_resolutions.lock().unwrap() = list;
+                                *sess.remote_resolutions.lock().unwrap() = list;
                             }
                             Some(Msg::Monitors { active, list }) => {
-                                *shared.monitors.lock().unwrap() = list;
-                                shared.active_monitor.store(active, Ordering::Relaxed);
+                                *sess.monitors.lock().unwrap() = list;
+                                sess.active_monitor.store(active, Ordering::Relaxed);
                             }
                             Some(Msg::Audio { seq, data }) => {
                                 if let Some(v) = voice.as_ref() {
                                     v.feed(seq, &data);
                                 }
                             }                            Some(Msg::Cursor { x, y, visible }) => {
-                                *shared.remote_cursor.lock().unwrap() = (x, y, visible);
-                            }
-                            Some(Msg::Clipboard { text }) => {
-                                shared.clip_from_host.fetch_add(1, Ordering::Relaxed);
-                                *shared.clip_in.lock().unwrap() = Some(text);
+                                *sess.remote_cursor.lock().unwrap() = (x, y, visible);
                             }
-                            Some(Msg::Frame {
-                                width,
-                                height,
-                                jpeg,
-                            }) => {
-                                win_frames += 1;
-                                win_bytes += jpeg.len();
-                                if let Ok(img) = image::load_from_memory_with_format(
-                                    &jpeg,
-                                    image::ImageFormat::Jpeg,
-                                ) {
-                                    canvas.set_full(width, height, img.to_rgba8().into_raw());
-                                    canvas.publish(shared);
+                            Some(m) if crate::clip::is_clip_msg(&m) => {
+                                if matches!(m, Msg::Clipboard { .. } | Msg::ClipOffer { .. }) {
+                                    sess.clip_from_host.fetch_add(1, Ordering::Relaxed);
                                 }
+                                let mut q = sess.clip_in.lock().unwrap();
+                                // without a clipboard nobody drains it
+                                if q.len() >= 16 {
+                                    q.remove(0);
+                                }
+                                q.push(m);
                             }
                             Some(Msg::Video {
                                 width,
@@ -414,51 +682,60 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
                             }) => {
                                 win_bytes += data.len();
                                 let pipe = video
-                                    .get_or_insert_with(|| VideoPipe::start(shared.clone()));
-                                pipe.push(shared, width, height, key, data);
+                                    .get_or_insert_with(|| VideoPipe::start(sess.clone()));
+                                pipe.push(sess, width, height, key, data);
                             }
-                            Some(Msg::Tiles {
-                                width,
-                                height,
-                                tiles,
-                            }) => {
-                                if !canvas.matches(width, height) {
-                                    // no keyframe for this size yet - ignore until one arrives
-                                    continue;
-                                }
-                                let mut painted = false;
-                                for t in tiles {
-                                    win_bytes += t.jpeg.len();
-                                    if let Ok(img) = image::load_from_memory_with_format(
-                                        &t.jpeg,
-                                        image::ImageFormat::Jpeg,
-                                    ) {
-                                        let rgb = img.to_rgb8();
-                                        if blit_rgb_to_rgba(
-                                            &mut canvas.rgba,
-                                            width,
-                                            height,
-                                            t.x,
-                                            t.y,
-                                            rgb.as_raw(),
-                                            rgb.width(),
-                                            rgb.height(),
-                                        ) {
-                                            painted = true;
-                                        }
+                            Some(m) if is_screen_msg(&m) => {
+                                win_bytes += screen_bytes(&m);
+                                let counts = matches!(m, Msg::Frame { .. } | Msg::Tiles { .. });
+                                if canvas.apply(&m) {
+                                    if counts {
+                                        win_frames += 1;
                                     }
+                                    canvas.publish(sess);
                                 }
-                                if painted {
-                                    win_frames += 1;
-                                    canvas.seq += 1;
-                                    canvas.publish(shared);
+                                if canvas.wants_keyframe() {
+                                    sess.send_input(Msg::NeedKeyframe);
                                 }
                             }
                             Some(m) if crate::xfer::is_file_msg(&m) => {
-                                if let Some(x) = shared.xfer.lock().unwrap().as_mut() {
+                                if let Some(x) = sess.xfer.lock().unwrap().as_mut() {
                                     x.on_msg(m);
                                 }
                             }
+                            Some(m) if crate::remotefs::is_reply(&m) => {
+                                crate::remotefs::on_reply(sess, m);
+                            }
+                            Some(m) if crate::term::is_term_msg(&m) => {
+                                sess.term.lock().unwrap().on_msg(m);
+                            }
+                            Some(m) if crate::procs::is_proc_msg(&m) => {
+                                sess.procs.lock().unwrap().on_msg(m);
+                            }
+                            Some(Msg::SysInfo { report }) => {
+                                *sess.sysinfo.lock().unwrap() = Some(report);
+                            }
+                            Some(Msg::Privacy { on }) => {
+                                if !on && sess.privacy_want.swap(false, Ordering::Relaxed) {
+                                    sess.set_viewer_status(
+                                        "Privatmodus ist auf dem Host nicht moeglich",
+                                    );
+                                }
+                                sess.privacy.store(on, Ordering::Relaxed);
+                            }
+                            Some(m) if crate::chat::is_chat_msg(&m) => {
+                                let ack = sess.chat.lock().unwrap().on_msg(m);
+                                if let Some(ack) = ack {
+                                    sess.send_input(ack);
+                                }
+                            }
+                            Some(m) if crate::tunnel::is_tun_msg(&m) => {
+                                let t = sess.tunnels.lock().unwrap().clone();
+                                if let Some(t) = t {
+                                    // the viewer never connects out for the host
+                                    t.on_msg(m, &[]);
+                                }
+                            }
                             Some(Msg::P2pOffer { addrs, .. }) => {
                                 if let Some(p) = p2p.as_ref() {
                                     p.set_remote(&addrs);
@@ -467,19 +744,20 @@ async fn viewer_once(shared: &Arc<Shared>, id: &str, auth: &Auth) -> Result<()>
                             Some(Msg::Pong { ts }) => {
                                 let now = started.elapsed().as_millis() as u64;
                                 let rtt = now.saturating_sub(ts) as f32;
-                                shared.stats.lock().unwrap().latency_ms = rtt;
+                                sess.stats.lock().unwrap().latency_ms = rtt;
                             }
                             _ => {}
                         }
 
                         if win_start.elapsed() >= Duration::from_secs(1) {
                             let secs = win_start.elapsed().as_secs_f32();
-                            win_frames += shared.video_frames.swap(0, Ordering::Relaxed);
-                            win_bytes += shared.video_bytes.swap(0, Ordering::Relaxed) as usize;
+                            win_frames += sess.video_frames.swap(0, Ordering::Relaxed);
+                            win_bytes += sess.video_bytes.swap(0, Ordering::Relaxed) as usize;
                             {
-                                let mut st = shared.stats.lock().unwrap();
+                                let mut st = sess.stats.lock().unwrap();
                                 st.fps = win_frames as f32 / secs;
                                 st.kbps = (win_bytes as f32 * 8.0 / 1000.0) / secs;
+                                st.cache_hits = canvas.cache_stats.ratio();
                             }
                             win_frames = 0;
                             win_bytes = 0;
@@ -530,11 +808,11 @@ impl VideoPipe {
         }
     }
 
-    fn start(shared: Arc<Shared>) -> Self {
+    fn start(sess: Arc<Session>) -> Self {
         let (tx, rx) = std::sync::mpsc::channel::<(u32, u32, bool, Vec<u8>)>();
         let pending = Arc::new(std::sync::atomic::AtomicI64::new(0));
         let p2 = pending.clone();
-        std::thread::spawn(move || video_worker(shared, rx, p2));
+        std::thread::spawn(move || video_worker(sess, rx, p2));
         Self {
             tx,
             pending,
@@ -542,8 +820,8 @@ impl VideoPipe {
         }
     }
 
-    fn push(&mut self, shared: &Arc<Shared>, w: u32, h: u32, key: bool, data: Vec<u8>) {
-        self.gate().push(shared, w, h, key, data);
+    fn push(&mut self, sess: &Arc<Session>, w: u32, h: u32, key: bool, data: Vec<u8>) {
+        self.gate().push(sess, w, h, key, data);
     }
 }
 
@@ -559,13 +837,13 @@ struct VideoGate {
 
 impl VideoGate {
     /// Something ate a picture - stop decoding until a keyframe arrives.
-    fn lost(&self, shared: &Arc<Shared>) {
+    fn lost(&self, sess: &Arc<Session>) {
         if !self.resyncing.swap(true, Ordering::Relaxed) {
-            shared.send_input(Msg::NeedKeyframe);
+            sess.send_input(Msg::NeedKeyframe);
         }
     }
 
-    fn push(&self, shared: &Arc<Shared>, w: u32, h: u32, key: bool, data: Vec<u8>) {
+    fn push(&self, sess: &Arc<Session>, w: u32, h: u32, key: bool, data: Vec<u8>) {
         if self.resyncing.load(Ordering::Relaxed) && !key {
             // still waiting for the restart - a P frame on a broken reference
             // chain would paint garbage, so it goes in the bin
@@ -573,7 +851,7 @@ impl VideoGate {
         }
         self.resyncing.store(false, Ordering::Relaxed);
         if self.pending.load(Ordering::Relaxed) > MAX_BACKLOG && !key {
-            self.lost(shared);
+            self.lost(sess);
             return;
         }
         self.pending.fetch_add(1, Ordering::Relaxed);
@@ -584,7 +862,7 @@ impl VideoGate {
 }
 
 fn video_worker(
-    shared: Arc<Shared>,
+    sess: Arc<Session>,
     rx: std::sync::mpsc::Receiver<(u32, u32, bool, Vec<u8>)>,
     pending: Arc<std::sync::atomic::AtomicI64>,
 ) {
@@ -596,12 +874,12 @@ fn video_worker(
         let stale = dec.as_ref().map(|d| d.size() != (w, h)).unwrap_or(true);
         if stale {
             if !key {
-                shared.send_input(Msg::NeedKeyframe);
+                sess.send_input(Msg::NeedKeyframe);
                 continue;
             }
             match crate::h264::Decoder::new(w, h) {
                 Ok(d) => {
-                    shared.set_viewer_status(format!(
+                    sess.set_viewer_status(format!(
                         "Verbunden - H.264 {}x{} ({})",
                         w,
                         h,
@@ -610,12 +888,16 @@ fn video_worker(
                     dec = Some(d);
                 }
                 Err(e) => {
-                    shared.set_viewer_status(format!(
+                    sess.set_viewer_status(format!(
                         "H.264 nicht verfuegbar ({}) - nutze JPEG",
                         e
                     ));
-                    shared.send_input(Msg::Caps { h264: false });
-                    shared.send_input(Msg::NeedKeyframe);
+                    sess.send_input(Msg::Caps {
+                        h264: false,
+                        copy: true,
+                        cache: true,
+                    });
+                    sess.send_input(Msg::NeedKeyframe);
                     continue;
                 }
             }
@@ -626,20 +908,20 @@ fn video_worker(
         };
         match d.decode(&data, &mut rgba) {
             Ok(Some((dw, dh))) => {
-                let seq = shared.next_frame_seq();
-                *shared.frame.lock().unwrap() = Some(FrameData {
+                let seq = sess.next_frame_seq();
+                *sess.frame.lock().unwrap() = Some(FrameData {
                     width: dw,
                     height: dh,
                     rgba: std::mem::take(&mut rgba),
                     seq,
                 });
-                shared.video_frames.fetch_add(1, Ordering::Relaxed);
+                sess.video_frames.fetch_add(1, Ordering::Relaxed);
             }
             Ok(None) => {}
             Err(e) => {
                 crate::capture::log_line(&format!("h264 decode: {}", e));
                 dec = None;
-                shared.send_input(Msg::NeedKeyframe);
+                sess.send_input(Msg::NeedKeyframe);
             }
         }
     }
@@ -16,10 +16,10 @@
 //! "W" pressed for walking would keep running forever on the remote machine.
 
 use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
-use std::sync::{Arc, OnceLock};
+use std::sync::{Arc, Mutex};
 
 use crate::proto::{Msg, SPECIAL_RELEASE};
-use crate::shared::Shared;
+use crate::shared::Session;
 
 static ACTIVE: AtomicBool = AtomicBool::new(false);
 static STARTED: AtomicBool = AtomicBool::new(false);
@@ -28,7 +28,8 @@ static STARTED: AtomicBool = AtomicBool::new(false);
 static REL: AtomicBool = AtomicBool::new(false);
 static CX: AtomicI32 = AtomicI32::new(0);
 static CY: AtomicI32 = AtomicI32::new(0);
-static SHARED: OnceLock<Arc<Shared>> = OnceLock::new();
+/// The session in front, the one keys and mouse counts go to.
+static SESSION: Mutex<Option<Arc<Session>>> = Mutex::new(None);
 /// Timestamps of the last right-Ctrl presses (for the triple tap).
 static PRESSES: std::sync::Mutex<Vec<std::time::Instant>> = std::sync::Mutex::new(Vec::new());
 
@@ -39,7 +40,7 @@ static PRESSES: std::sync::Mutex<Vec<std::time::Instant>> = std::sync::Mutex::ne
 /// within 1.5 seconds are the emergency exit and end the session, for the
 /// moment when the remote side hangs and the picture no longer reacts.
 fn host_key_pressed() {
-    let sh = match SHARED.get() {
+    let sh = match session() {
         Some(s) => s,
         None => return,
     };
@@ -60,6 +61,20 @@ fn host_key_pressed() {
     sh.escape.store(code, Ordering::Relaxed);
 }
 
+fn session() -> Option<Arc<Session>> {
+    SESSION.lock().unwrap().clone()
+}
+
+/// The tab in front changed: input goes to `sess` from now on.
+pub fn set_session(sess: Option<Arc<Session>>) {
+    *SESSION.lock().unwrap() = sess;
+}
+
+/// True if `sess` is the one in front.
+pub fn in_front(sess: &Arc<Session>) -> bool {
+    session().is_some_and(|s| Arc::ptr_eq(&s, sess))
+}
+
 pub fn is_active() -> bool {
     ACTIVE.load(Ordering::Relaxed)
 }
@@ -116,7 +131,7 @@ mod imp {
                     }
                     return LRESULT(1);
                 }
-                if let Some(sh) = SHARED.get() {
+                if let Some(sh) = session() {
                     sh.send_input(Msg::KeyVk { vk, ext, down });
                 }
                 return LRESULT(1); // swallow locally
@@ -125,8 +140,7 @@ mod imp {
         CallNextHookEx(None, code, w, l)
     }
 
-    pub fn init(shared: Arc<Shared>) {
-        let _ = SHARED.set(shared);
+    pub fn init() {
         if STARTED.swap(true, Ordering::SeqCst) {
             return;
         }
@@ -154,7 +168,7 @@ mod imp {
                         if GetCursorPos(&mut p).is_ok() {
                             let (dx, dy) = (p.x - cx, p.y - cy);
                             if dx != 0 || dy != 0 {
-                                if let Some(sh) = SHARED.get() {
+                                if let Some(sh) = session() {
                                     sh.send_input(Msg::MouseDelta { dx, dy });
                                 }
                                 let _ = SetCursorPos(cx, cy);
@@ -173,16 +187,14 @@ mod imp {
 pub use imp::init;
 
 #[cfg(not(windows))]
-pub fn init(shared: Arc<Shared>) {
-    let _ = SHARED.set(shared);
-}
+pub fn init() {}
 
 /// Turns the grab on or off. Letting go always asks the host to release every
 /// key and mouse button it still holds for us.
 pub fn set_active(on: bool) {
     let was = ACTIVE.swap(on, Ordering::Relaxed);
     if was && !on {
-        if let Some(sh) = SHARED.get() {
+        if let Some(sh) = session() {
             sh.send_input(Msg::Special {
                 code: SPECIAL_RELEASE,
             });
@@ -5,16 +5,46 @@
 //! session, i.e. the relay never sees a byte of the file either. A simple
 //! window (unacknowledged bytes) keeps a big file from starving the video
 //! stream and from filling the receiver's memory.
+//!
+//! The offer carries the SHA-256 of the file. The receiver writes into a
+//! `.part` file named after name and hash; if a session breaks, the next
+//! offer of the same file finds it, the receiver answers with `FileResume`
+//! and the sender continues where it stopped. Only a file whose hash matches
+//! loses the `.part` - a mismatch marks the transfer as failed. Such a
+//! `.part` in the download folder that nobody comes back for within a week
+//! is deleted.
+//!
+//! A folder goes as `DirOffer`, then its directories and links as `DirEntry`
+//! and its files as ordinary offers tagged with the folder id, one after the
+//! other. Every path component is cleaned like a file name, links may only
+//! point inside the folder, nothing is written through a link that is
+//! already there, and times are put back once the last file is in.
+//!
+//! Chunks of files that shrink (logs, CSVs, source code) go deflated when
+//! the receiver agrees in its `FileResume`; photos and archives are found
+//! by packing a few samples first and go raw. Offsets, the window and the
+//! acknowledgements count unpacked bytes either way.
+//!
+//! Outgoing transfers wait in one queue and go one after the other, so two
+//! big files never share the line with the video at the same time. All
+//! sending of all sessions draws from one byte budget (`Shared::xfer_pace`,
+//! filled at `Shared::xfer_limit`, 0 = no limit); each queued or running
+//! item can be paused, resumed and cancelled.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::{self, File};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
-use crate::proto::{Msg, CHUNK};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+
+use crate::proto::{EntryKind, Msg, CHUNK, CODEC_DEFLATE};
 use crate::shared::Shared;
 
 /// At most this many bytes may be unacknowledged before the sender waits.
@@ -23,6 +53,22 @@ const WINDOW: u64 = 4 * 1024 * 1024;
 const ACK_EVERY: u64 = 512 * 1024;
 /// If nothing is acknowledged for this long the transfer is given up.
 const STALL: Duration = Duration::from_secs(90);
+/// The sender waits this long for `FileResume` and then starts from the
+/// beginning - older receivers never answer.
+const RESUME_WAIT: Duration = Duration::from_secs(3);
+/// A `.part` nobody came back for in this long is deleted.
+const PART_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// What happens when a received file already exists (`Shared::on_conflict`).
+/// Keep both: the new one becomes "bild (2).png".
+pub const KEEP_BOTH: u8 = 0;
+/// Leave the existing file alone, the sender skips it.
+pub const SKIP: u8 = 1;
+/// Replace the existing file once the new one is complete and verified.
+pub const OVERWRITE: u8 = 2;
+
+/// The transfers of one session, as the GUI lists them.
+pub type List = Arc<Mutex<Vec<Progress>>>;
 
 /// What the GUI shows for one running/finished transfer.
 #[derive(Clone, Debug, PartialEq)]
@@ -35,9 +81,37 @@ pub struct Progress {
     pub incoming: bool,
     pub finished: bool,
     pub error: String,
+    /// Folders: number of files in it and how many are through (0 = a
+    /// single file).
+    pub files: u32,
+    pub files_done: u32,
+    /// Outgoing: still waiting in the queue.
+    pub queued: bool,
+    /// Outgoing: held by the user.
+    pub paused: bool,
+    /// Outgoing: the checksum is still being computed, `done` counts the
+    /// bytes read for it so far.
+    pub hashing: bool,
 }
 
 impl Progress {
+    fn new(id: u32, name: String, size: u64, incoming: bool) -> Self {
+        Self {
+            id,
+            name,
+            size,
+            done: 0,
+            incoming,
+            finished: false,
+            error: String::new(),
+            files: 0,
+            files_done: 0,
+            queued: false,
+            paused: false,
+            hashing: false,
+        }
+    }
+
     pub fn percent(&self) -> f32 {
         if self.size == 0 {
             return if self.finished { 1.0 } else { 0.0 };
@@ -85,6 +159,217 @@ pub fn safe_name(raw: &str) -> String {
     }
 }
 
+/// A relative path from the peer, every component cleaned like a file name.
+/// `..` is refused outright instead of being cleaned into something else.
+pub fn safe_rel(raw: &str) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for part in raw.split(['/', '\\']) {
+        match part.trim() {
+            "" | "." => {}
+            ".." => return None,
+            _ => out.push(safe_name(part)),
+        }
+    }
+    if out.as_os_str().is_empty() {
+        None
+    } else {
+        Some(out)
+    }
+}
+
+/// Does a link at `link` (relative to the folder) pointing to `target`
+/// stay inside the folder? Absolute targets never do.
+fn link_stays_inside(link: &Path, target: &str) -> bool {
+    if target.is_empty() || target.starts_with(['/', '\\']) || target.contains(':') {
+        return false;
+    }
+    let mut depth = link.components().count() as i64 - 1;
+    for part in target.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                depth -= 1;
+                if depth < 0 {
+                    return false;
+                }
+            }
+            _ => depth += 1,
+        }
+    }
+    true
+}
+
+/// True if no part of `rel` below `root` is already a link. The link text
+/// alone proves nothing: `x/d -> ..` stays inside, but a later `x/d/y -> ..`
+/// would then point above the folder, and so would everything written
+/// through it.
+fn no_link_on_the_way(root: &Path, rel: &Path) -> bool {
+    let mut at = root.to_path_buf();
+    for c in rel.components() {
+        at.push(c);
+        match fs::symlink_metadata(&at) {
+            Ok(m) if m.file_type().is_symlink() => return false,
+            Ok(_) => {}
+            // nothing there yet, so nothing below it either
+            Err(_) => return true,
+        }
+    }
+    true
+}
+
+/// Where a file of a folder goes: its directory and its clean name, or
+/// None if the path is invalid or would lead through a link.
+fn folder_target(root: &Path, raw: &str) -> Option<(PathBuf, String)> {
+    let rel = safe_rel(raw)?;
+    if !no_link_on_the_way(root, &rel) {
+        return None;
+    }
+    let file = rel.file_name()?.to_string_lossy().to_string();
+    let dir = match rel.parent() {
+        Some(p) => root.join(p),
+        None => root.to_path_buf(),
+    };
+    Some((dir, file))
+}
+
+/// Creates a directory or link of a folder. Returns the directory, whose
+/// time is put back at the end.
+fn place_entry(root: &Path, raw: &str, kind: EntryKind, target: &str) -> Option<PathBuf> {
+    let rel = safe_rel(raw)?;
+    if !no_link_on_the_way(root, &rel) {
+        return None;
+    }
+    let at = root.join(&rel);
+    match kind {
+        EntryKind::Dir => fs::create_dir_all(&at).ok().map(|_| at),
+        EntryKind::Link | EntryKind::DirLink => {
+            // never a link that leads out of the folder
+            if link_stays_inside(&rel, target) && fs::symlink_metadata(&at).is_err() {
+                if let Some(parent) = at.parent() {
+                    let _ = fs::create_dir_all(parent);
+                }
+                let _ = make_link(target, &at, kind == EntryKind::DirLink);
+            }
+            None
+        }
+    }
+}
+
+#[cfg(unix)]
+fn make_link(target: &str, at: &Path, _dir: bool) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, at)
+}
+
+/// Needs developer mode or admin rights on Windows; without them the link
+/// is simply missing on this side.
+#[cfg(windows)]
+fn make_link(target: &str, at: &Path, dir: bool) -> std::io::Result<()> {
+    let target = target.replace('/', "\\");
+    if dir {
+        std::os::windows::fs::symlink_dir(target, at)
+    } else {
+        std::os::windows::fs::symlink_file(target, at)
+    }
+}
+
+/// Modification time in seconds since 1970, 0 if the system has none.
+pub fn mtime_of(meta: &fs::Metadata) -> u64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Puts back the modification time of a file or directory (best effort).
+fn set_mtime(path: &Path, secs: u64) {
+    if secs == 0 {
+        return;
+    }
+    let mut o = fs::OpenOptions::new();
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::OpenOptionsExt;
+        // FILE_FLAG_BACKUP_SEMANTICS, otherwise directories cannot be opened
+        o.write(true).custom_flags(0x0200_0000);
+    }
+    #[cfg(not(windows))]
+    o.read(true);
+    if let Ok(f) = o.open(path) {
+        let _ = f.set_modified(UNIX_EPOCH + Duration::from_secs(secs));
+    }
+}
+
+/// What `walk` finds in a folder.
+enum Item {
+    Dir,
+    File(u64),
+    Link { target: String, dir: bool },
+}
+
+struct Entry {
+    /// below the folder, `/` between the parts
+    rel: String,
+    path: PathBuf,
+    item: Item,
+    mtime: u64,
+}
+
+/// Everything below `root`, directories before their content, sorted by
+/// name. Links are not followed (no loops, nothing from outside); the ones
+/// that lead out of the folder are left out.
+fn walk(root: &Path) -> std::io::Result<Vec<Entry>> {
+    let mut out = Vec::new();
+    walk_into(root, "", &mut out)?;
+    Ok(out)
+}
+
+fn walk_into(dir: &Path, prefix: &str, out: &mut Vec<Entry>) -> std::io::Result<()> {
+    let mut names: Vec<_> = fs::read_dir(dir)?.filter_map(|e| e.ok()).collect();
+    names.sort_by_key(|e| e.file_name());
+    for e in names {
+        let path = e.path();
+        let rel = format!("{}{}", prefix, e.file_name().to_string_lossy());
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        let mtime = mtime_of(&meta);
+        if meta.file_type().is_symlink() {
+            let Ok(t) = fs::read_link(&path) else {
+                continue;
+            };
+            let target = t.to_string_lossy().replace('\\', "/");
+            if !link_stays_inside(Path::new(&rel), &target) {
+                continue;
+            }
+            let dir = fs::metadata(&path).map(|m| m.is_dir()).unwrap_or(false);
+            out.push(Entry {
+                rel,
+                path,
+                item: Item::Link { target, dir },
+                mtime,
+            });
+        } else if meta.is_dir() {
+            let sub = format!("{}/", rel);
+            out.push(Entry {
+                rel,
+                path: path.clone(),
+                item: Item::Dir,
+                mtime,
+            });
+            walk_into(&path, &sub, out)?;
+        } else if meta.is_file() {
+            out.push(Entry {
+                rel,
+                path,
+                item: Item::File(meta.len()),
+                mtime,
+            });
+        }
+    }
+    Ok(())
+}
+
 /// Never overwrite: "bild.png" -> "bild (2).png".
 fn unique_path(dir: &Path, name: &str) -> PathBuf {
     let p = dir.join(name);
@@ -104,37 +389,644 @@ fn unique_path(dir: &Path, name: &str) -> PathBuf {
     dir.join(format!("{}.{}", name, std::process::id()))
 }
 
+/// SHA-256 of a whole file.
+pub fn sha256_file(path: &Path) -> std::io::Result<[u8; 32]> {
+    sha256_file_with(path, &mut |_| {}, &|| false)
+}
+
+/// SHA-256 of a whole file; `progress` sees the bytes read so far, and
+/// `stop` breaks it off (a multi-GB file takes a while).
+fn sha256_file_with(
+    path: &Path,
+    progress: &mut dyn FnMut(u64),
+    stop: &dyn Fn() -> bool,
+) -> std::io::Result<[u8; 32]> {
+    let mut f = File::open(path)?;
+    let mut h = Sha256::new();
+    let mut buf = vec![0u8; 256 * 1024];
+    let mut done = 0u64;
+    loop {
+        if stop() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Interrupted,
+                "abgebrochen",
+            ));
+        }
+        let n = f.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        h.update(&buf[..n]);
+        done += n as u64;
+        progress(done);
+    }
+    Ok(h.finalize().into())
+}
+
+/// The partial file of an incoming transfer. With a hash the name is
+/// stable, so the next offer of the same file finds it again; half of the
+/// hash keeps two different files of one name apart.
+fn part_path(dir: &Path, name: &str, sha256: Option<&[u8; 32]>) -> PathBuf {
+    match sha256 {
+        Some(h) => {
+            let hex: String = h[..16].iter().map(|b| format!("{:02x}", b)).collect();
+            dir.join(format!("{}.{}.part", name, hex))
+        }
+        None => unique_path(dir, &format!("{}.part", name)),
+    }
+}
+
+/// True for a name `part_path` gives a file with a hash:
+/// `<name>.<32 hex>.part`. Other `.part` files belong to someone else.
+fn is_our_part(name: &str) -> bool {
+    let Some((base, hex)) = name.strip_suffix(".part").and_then(|n| n.rsplit_once('.')) else {
+        return false;
+    };
+    !base.is_empty()
+        && hex.len() == 32
+        && hex.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Deletes our `.part` files in `dir` that were not touched for `max_age`:
+/// transfers that were broken off and never offered again.
+fn sweep_parts(dir: &Path, max_age: Duration) {
+    let Ok(list) = fs::read_dir(dir) else {
+        return;
+    };
+    for e in list.flatten() {
+        if !is_our_part(&e.file_name().to_string_lossy()) {
+            continue;
+        }
+        let old = e
+            .metadata()
+            .ok()
+            .filter(|m| m.is_file())
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.elapsed().ok())
+            .is_some_and(|age| age >= max_age);
+        if old {
+            let _ = fs::remove_file(e.path());
+        }
+    }
+}
+
+/// One file being received.
 struct Incoming {
     file: File,
+    /// the `.part` file, renamed once complete and verified
     path: PathBuf,
+    dir: PathBuf,
+    name: String,
+    size: u64,
+    sha256: Option<[u8; 32]>,
+    /// folder id of the `DirOffer`, 0 = a single file
+    folder: u32,
+    mtime: u64,
+    /// replace an existing file of that name instead of keeping both
+    overwrite: bool,
+    /// running hash while the pieces arrive in order; None after a jump,
+    /// then the finished file is hashed once more
+    hasher: Option<Sha256>,
     got: u64,
     acked: u64,
 }
 
-/// One file transfer engine per session.
-pub struct Xfer {
+impl Incoming {
+    /// Opens (or continues) the `.part` file for an offer. Returns the
+    /// offset the sender should continue at.
+    fn open(
+        dir: &Path,
+        name: &str,
+        size: u64,
+        sha256: Option<[u8; 32]>,
+    ) -> std::io::Result<Self> {
+        let path = part_path(dir, name, sha256.as_ref());
+        let mut have = match fs::metadata(&path) {
+            Ok(m) if sha256.is_some() && m.len() <= size => m.len(),
+            _ => 0,
+        };
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(have == 0)
+            .open(&path)?;
+        let mut hasher = Sha256::new();
+        if have > 0 {
+            // the running hash has to cover what is already there
+            let mut buf = vec![0u8; 256 * 1024];
+            let mut left = have;
+            while left > 0 {
+                let want = left.min(buf.len() as u64) as usize;
+                let n = file.read(&mut buf[..want])?;
+                if n == 0 {
+                    have -= left;
+                    break;
+                }
+                hasher.update(&buf[..n]);
+                left -= n as u64;
+            }
+            file.set_len(have)?;
+            file.seek(SeekFrom::Start(have))?;
+        }
+        Ok(Self {
+            file,
+            path,
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            size,
+            sha256,
+            folder: 0,
+            mtime: 0,
+            overwrite: false,
+            hasher: Some(hasher),
+            got: have,
+            acked: have,
+        })
+    }
+
+    fn write(&mut self, off: u64, data: &[u8]) -> std::io::Result<()> {
+        if off != self.got {
+            self.file.seek(SeekFrom::Start(off))?;
+            self.hasher = None;
+        }
+        self.file.write_all(data)?;
+        if let Some(h) = self.hasher.as_mut() {
+            h.update(data);
+        }
+        self.got = off + data.len() as u64;
+        Ok(())
+    }
+
+    /// Checks the hash and gives the file its real name. On a mismatch the
+    /// `.part` is deleted, a retry has to start from scratch.
+    fn finish(mut self) -> Result<PathBuf, String> {
+        self.file.flush().map_err(|e| format!("{}", e))?;
+        drop(self.file);
+        if let Some(want) = self.sha256 {
+            let got: [u8; 32] = match self.hasher {
+                Some(h) => h.finalize().into(),
+                None => sha256_file(&self.path).map_err(|e| format!("{}", e))?,
+            };
+            if got != want {
+                let _ = fs::remove_file(&self.path);
+                return Err("Pruefsumme stimmt nicht - Datei verworfen".to_string());
+            }
+        }
+        let dest = if self.overwrite {
+            let dest = self.dir.join(&self.name);
+            // Windows does not rename over an existing file
+            let _ = fs::remove_file(&dest);
+            dest
+        } else {
+            unique_path(&self.dir, &self.name)
+        };
+        fs::rename(&self.path, &dest).map_err(|e| format!("{}", e))?;
+        set_mtime(&dest, self.mtime);
+        Ok(dest)
+    }
+
+    /// Broken off: a resumable `.part` stays for the next attempt.
+    fn abandon(self) {
+        drop(self.file);
+        if self.sha256.is_none() {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// One chunk deflated. The fast level: the line is the bottleneck, not
+/// the CPU.
+fn pack(data: &[u8]) -> Vec<u8> {
+    let mut z = DeflateEncoder::new(Vec::with_capacity(data.len() / 2), Compression::fast());
+    let _ = z.write_all(data);
+    z.finish().unwrap_or_default()
+}
+
+/// Unpacks one chunk that has to come out as exactly `len` bytes - never
+/// more, so a forged chunk cannot blow up in memory.
+fn unpack(data: &[u8], len: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    DeflateDecoder::new(data)
+        .take(len as u64 + 1)
+        .read_to_end(&mut out)
+        .ok()?;
+    (out.len() == len).then_some(out)
+}
+
+/// Packs samples from the start, the middle and the end of the file: does
+/// it shrink by at least a tenth? Photos, videos and archives do not.
+fn worth_packing(path: &Path, size: u64) -> bool {
+    const SAMPLE: u64 = 16 * 1024;
+    if size < 4096 {
+        return false;
+    }
+    let Ok(mut f) = File::open(path) else {
+        return false;
+    };
+    let mut buf = vec![0u8; SAMPLE as usize];
+    let (mut raw, mut packed) = (0usize, 0usize);
+    for at in [0, size / 2, size.saturating_sub(SAMPLE)] {
+        if f.seek(SeekFrom::Start(at)).is_err() {
+            return false;
+        }
+        let n = match f.read(&mut buf) {
+            Ok(n) => n,
+            Err(_) => return false,
+        };
+        raw += n;
+        packed += pack(&buf[..n]).len();
+    }
+    packed * 10 < raw * 9
+}
+
+/// What the receiver reported back about one outgoing file.
+struct Reply {
+    acked: AtomicU64,
+    /// where the receiver wants us to continue, `u64::MAX` = no answer yet
+    resume: AtomicU64,
+    /// the codec the receiver chose, valid once `resume` is set
+    codec: AtomicU8,
+    /// the receiver gave up (write error, wrong checksum), `why` says why
+    refused: AtomicBool,
+    why: Mutex<String>,
+}
+
+impl Reply {
+    fn new() -> Self {
+        Self {
+            acked: AtomicU64::new(0),
+            resume: AtomicU64::new(u64::MAX),
+            codec: AtomicU8::new(0),
+            refused: AtomicBool::new(false),
+            why: Mutex::new(String::new()),
+        }
+    }
+
+    fn refused(&self) -> Option<String> {
+        if !self.refused.load(Ordering::Relaxed) {
+            return None;
+        }
+        let why = self.why.lock().unwrap().clone();
+        Some(if why.is_empty() {
+            "abgelehnt".to_string()
+        } else {
+            why
+        })
+    }
+}
+
+/// Switches of one outgoing transfer, flipped by the GUI.
+#[derive(Default)]
+struct Ctl {
+    paused: AtomicBool,
+    cancel: AtomicBool,
+}
+
+/// One outgoing transfer waiting for its turn.
+struct Job {
+    id: u32,
+    path: PathBuf,
+    name: String,
+    dir: bool,
+    ctl: Arc<Ctl>,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: VecDeque<Job>,
+    /// a worker thread is draining `jobs`
+    busy: bool,
+}
+
+/// Token bucket behind the global rate limit; every chunk of every session
+/// draws from the one in `Shared`.
+pub struct Pacer {
+    /// last refill and the bytes that may go out right now
+    state: Mutex<(Instant, f64)>,
+}
+
+impl Pacer {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new((Instant::now(), 0.0)),
+        }
+    }
+
+    /// Waits until `n` more bytes fit into `rate` bytes per second
+    /// (0 = no limit). At most a quarter second worth of bytes is saved up,
+    /// so a pause does not end in a burst.
+    fn take(&self, n: u64, rate: u64, stop: &AtomicBool) {
+        if rate == 0 {
+            return;
+        }
+        loop {
+            let wait = {
+                let mut st = self.state.lock().unwrap();
+                let now = Instant::now();
+                let cap = (rate as f64 / 4.0).max(n as f64);
+                st.1 = (st.1 + now.duration_since(st.0).as_secs_f64() * rate as f64).min(cap);
+                st.0 = now;
+                if st.1 >= n as f64 {
+                    st.1 -= n as f64;
+                    return;
+                }
+                (n as f64 - st.1) / rate as f64
+            };
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+            std::thread::sleep(Duration::from_secs_f64(wait.min(0.05)));
+        }
+    }
+}
+
+/// `2M`, `500k`, `1.5M` or plain bytes per second, for `--limit`.
+pub fn parse_rate(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (num, mul) = match s.chars().last()? {
+        'k' | 'K' => (&s[..s.len() - 1], 1024.0),
+        'm' | 'M' => (&s[..s.len() - 1], 1024.0 * 1024.0),
+        'g' | 'G' => (&s[..s.len() - 1], 1024.0 * 1024.0 * 1024.0),
+        _ => (s, 1.0),
+    };
+    let v: f64 = num.trim().parse().ok()?;
+    if !v.is_finite() || v < 0.0 {
+        return None;
+    }
+    Some((v * mul) as u64)
+}
+
+/// The sending half of the engine; the queue worker gets a clone.
+#[derive(Clone)]
+struct Out {
     shared: Arc<Shared>,
+    /// progress lines of the session this engine belongs to
+    list: List,
     send: Arc<dyn Fn(Msg) + Send + Sync>,
     stop: Arc<AtomicBool>,
+    replies: Arc<Mutex<HashMap<u32, Arc<Reply>>>>,
+    next_id: Arc<AtomicU32>,
+    queue: Arc<Mutex<Queue>>,
+    /// switches of everything queued or running
+    ctls: Arc<Mutex<HashMap<u32, Arc<Ctl>>>>,
+}
+
+impl Out {
+    fn id(&self) -> u32 {
+        loop {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+            if id != 0 {
+                return id;
+            }
+        }
+    }
+
+    /// Sends one file and waits until the receiver has written every byte.
+    /// `progress` sees how far the hashing (`true`) and then the sending got.
+    /// A refusal of the receiver comes back as the error.
+    fn push(
+        &self,
+        id: u32,
+        path: &Path,
+        name: String,
+        folder: u32,
+        ctl: &Ctl,
+        progress: &mut dyn FnMut(u64, bool),
+    ) -> Result<u64, String> {
+        let reply = Arc::new(Reply::new());
+        self.replies.lock().unwrap().insert(id, reply.clone());
+        let res = self.push_with(&reply, id, path, name, folder, ctl, progress);
+        self.replies.lock().unwrap().remove(&id);
+        res
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push_with(
+        &self,
+        reply: &Reply,
+        id: u32,
+        path: &Path,
+        name: String,
+        folder: u32,
+        ctl: &Ctl,
+        progress: &mut dyn FnMut(u64, bool),
+    ) -> Result<u64, String> {
+        let send = &self.send;
+        let stop = &self.stop;
+        let fail = |msg: String| {
+            send(Msg::FileEnd {
+                id,
+                ok: false,
+                msg: msg.clone(),
+            });
+            Err(msg)
+        };
+
+        let meta = fs::metadata(path).map_err(|e| format!("{}", e))?;
+        let size = meta.len();
+        let mut f = File::open(path).map_err(|e| format!("{}", e))?;
+        // the offer needs the hash; on a big file that is worth a progress bar
+        let sha256 = sha256_file_with(path, &mut |n| progress(n, true), &|| {
+            stop.load(Ordering::Relaxed) || ctl.cancel.load(Ordering::Relaxed)
+        })
+        .map_err(|e| format!("{}", e))?;
+        progress(0, false);
+        let codecs = if worth_packing(path, size) {
+            CODEC_DEFLATE
+        } else {
+            0
+        };
+        send(Msg::FileOffer {
+            id,
+            name,
+            size,
+            sha256: Some(sha256),
+            folder,
+            mtime: mtime_of(&meta),
+            codecs,
+        });
+
+        // a receiver that still has part of the file says so right away
+        let asked = Instant::now();
+        while reply.resume.load(Ordering::Relaxed) == u64::MAX
+            && asked.elapsed() < RESUME_WAIT
+            && !stop.load(Ordering::Relaxed)
+            && !reply.refused.load(Ordering::Relaxed)
+        {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let mut off = match reply.resume.load(Ordering::Relaxed) {
+            u64::MAX => 0,
+            o => o.min(size),
+        };
+        let deflate = reply.codec.load(Ordering::Relaxed) & codecs == CODEC_DEFLATE;
+        if off > 0 {
+            if let Err(e) = f.seek(SeekFrom::Start(off)) {
+                return fail(format!("{}", e));
+            }
+            reply.acked.fetch_max(off, Ordering::Relaxed);
+        }
+
+        let acked = &reply.acked;
+        let mut buf = vec![0u8; CHUNK];
+        let mut seen_ack = acked.load(Ordering::Relaxed);
+        let mut since = Instant::now();
+        loop {
+            if let Some(why) = reply.refused() {
+                return Err(why);
+            }
+            if stop.load(Ordering::Relaxed) {
+                return fail("Sitzung beendet".to_string());
+            }
+            if ctl.cancel.load(Ordering::Relaxed) {
+                return fail("abgebrochen".to_string());
+            }
+            if ctl.paused.load(Ordering::Relaxed) {
+                // a held transfer is not a stalled one
+                std::thread::sleep(Duration::from_millis(50));
+                since = Instant::now();
+                continue;
+            }
+            // flow control: never push more than WINDOW unacknowledged bytes
+            while off.saturating_sub(acked.load(Ordering::Relaxed)) >= WINDOW {
+                if stop.load(Ordering::Relaxed) {
+                    return fail("Sitzung beendet".to_string());
+                }
+                if let Some(why) = reply.refused() {
+                    return Err(why);
+                }
+                let now = acked.load(Ordering::Relaxed);
+                if now != seen_ack {
+                    seen_ack = now;
+                    since = Instant::now();
+                } else if since.elapsed() > STALL {
+                    return fail("Gegenstelle antwortet nicht".to_string());
+                }
+                std::thread::sleep(Duration::from_millis(3));
+            }
+
+            let n = match f.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => return fail(format!("{}", e)),
+            };
+            let rate = self.shared.xfer_limit.load(Ordering::Relaxed);
+            // a chunk that shrinks by less than an eighth goes raw
+            let packed = deflate
+                .then(|| pack(&buf[..n]))
+                .filter(|z| z.len() + n / 8 < n);
+            match packed {
+                Some(data) => {
+                    self.shared.xfer_pace.take(data.len() as u64, rate, stop);
+                    send(Msg::FileChunkZ {
+                        id,
+                        off,
+                        len: n as u32,
+                        data,
+                    });
+                }
+                None => {
+                    self.shared.xfer_pace.take(n as u64, rate, stop);
+                    send(Msg::FileChunk {
+                        id,
+                        off,
+                        data: buf[..n].to_vec(),
+                    });
+                }
+            }
+            off += n as u64;
+            progress(off, false);
+            // be nice to the video stream
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        send(Msg::FileEnd {
+            id,
+            ok: true,
+            msg: String::new(),
+        });
+
+        // "done" means the other side has written every byte, not just
+        // that we pushed them into the socket
+        let mut waited = Instant::now();
+        let mut seen = acked.load(Ordering::Relaxed);
+        while acked.load(Ordering::Relaxed) < off {
+            if let Some(why) = reply.refused() {
+                return Err(why);
+            }
+            if stop.load(Ordering::Relaxed) {
+                break;
+            }
+            let now = acked.load(Ordering::Relaxed);
+            if now != seen {
+                seen = now;
+                waited = Instant::now();
+            } else if waited.elapsed() > STALL {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let confirmed = acked.load(Ordering::Relaxed);
+        if confirmed >= off {
+            Ok(off)
+        } else {
+            Err(format!("nur {} von {} Bytes bestaetigt", confirmed, off))
+        }
+    }
+}
+
+/// A folder being received.
+struct Folder {
+    root: PathBuf,
+    /// bytes of the files that are through, for the overall progress
+    base: u64,
+    files_done: u32,
+    failed: u32,
+    last_error: String,
+    /// directories and their original time, set once all files are in
+    dirs: Vec<(PathBuf, u64)>,
+}
+
+/// One file transfer engine per session.
+pub struct Xfer {
+    shared: Arc<Shared>,
+    list: List,
+    out: Out,
     incoming: HashMap<u32, Incoming>,
-    acks: HashMap<u32, Arc<AtomicU64>>,
-    next_id: u32,
+    /// offers answered with "skip": id -> (folder, size)
+    skipped: HashMap<u32, (u32, u64)>,
+    folders: HashMap<u32, Folder>,
+    /// transfers announced to go somewhere else than the drop folder
+    dests: HashMap<u32, PathBuf>,
+    /// drop folders already cleared of stale `.part` files
+    swept: HashSet<PathBuf>,
 }
 
 impl Xfer {
-    pub fn new(shared: Arc<Shared>, send: Arc<dyn Fn(Msg) + Send + Sync>) -> Self {
+    pub fn new(shared: Arc<Shared>, list: List, send: Arc<dyn Fn(Msg) + Send + Sync>) -> Self {
         Self {
+            out: Out {
+                shared: shared.clone(),
+                list: list.clone(),
+                send,
+                stop: Arc::new(AtomicBool::new(false)),
+                replies: Arc::new(Mutex::new(HashMap::new())),
+                next_id: Arc::new(AtomicU32::new(1)),
+                queue: Arc::new(Mutex::new(Queue::default())),
+                ctls: Arc::new(Mutex::new(HashMap::new())),
+            },
             shared,
-            send,
-            stop: Arc::new(AtomicBool::new(false)),
+            list,
             incoming: HashMap::new(),
-            acks: HashMap::new(),
-            next_id: 1,
+            skipped: HashMap::new(),
+            folders: HashMap::new(),
+            dests: HashMap::new(),
+            swept: HashSet::new(),
         }
     }
 
-    fn set_progress(shared: &Arc<Shared>, p: Progress) {
-        let mut list = shared.xfers.lock().unwrap();
+    fn set_progress(list: &List, p: Progress) {
+        let mut list = list.lock().unwrap();
         match list.iter_mut().find(|x| x.id == p.id && x.incoming == p.incoming) {
             Some(slot) => *slot = p,
             None => {
@@ -146,11 +1038,29 @@ impl Xfer {
         }
     }
 
-    /// Starts sending `path` to the other side (own thread, non blocking).
+    fn send(&self, m: Msg) {
+        (self.out.send)(m)
+    }
+
+    /// Queues `path` - a file or a whole folder - for the other side; it
+    /// goes as soon as everything before it is through (non blocking).
     pub fn send_path(&mut self, path: PathBuf) {
-        let id = self.next_id;
-        self.next_id = self.next_id.wrapping_add(1).max(1);
+        let id = self.out.id();
+        self.send_path_as(id, path);
+    }
+
+    /// Id for a transfer the other side has to know about before its offer
+    /// arrives (`send_path_as`).
+    pub fn reserve_id(&self) -> u32 {
+        self.out.id()
+    }
 
+    /// The incoming transfer `id` goes into `dir` instead of the drop folder.
+    pub fn expect(&mut self, id: u32, dir: PathBuf) {
+        self.dests.insert(id, dir);
+    }
+
+    pub fn send_path_as(&mut self, id: u32, path: PathBuf) {
         let name = path
             .file_name()
             .map(|s| s.to_string_lossy().to_string())
@@ -159,287 +1069,437 @@ impl Xfer {
             Ok(m) => m,
             Err(e) => {
                 Self::set_progress(
-                    &self.shared,
+                    &self.list,
                     Progress {
-                        id,
-                        name,
-                        size: 0,
-                        done: 0,
-                        incoming: false,
                         finished: true,
                         error: format!("{}", e),
+                        ..Progress::new(id, name, 0, false)
                     },
                 );
                 return;
             }
         };
-        if meta.is_dir() {
+        let dir = meta.is_dir();
+        let size = if dir { 0 } else { meta.len() };
+        Self::set_progress(
+            &self.list,
+            Progress {
+                queued: true,
+                ..Progress::new(id, name.clone(), size, false)
+            },
+        );
+        let ctl = Arc::new(Ctl::default());
+        self.out.ctls.lock().unwrap().insert(id, ctl.clone());
+        let mut q = self.out.queue.lock().unwrap();
+        q.jobs.push_back(Job {
+            id,
+            path,
+            name,
+            dir,
+            ctl,
+        });
+        if !q.busy {
+            q.busy = true;
+            let out = self.out.clone();
+            std::thread::spawn(move || Self::work(out));
+        }
+    }
+
+    /// The queue worker: takes the first job that is not held, until the
+    /// queue is empty or the session ends.
+    fn work(out: Out) {
+        loop {
+            let job = {
+                let mut q = out.queue.lock().unwrap();
+                if out.stop.load(Ordering::Relaxed) {
+                    q.busy = false;
+                    let left: Vec<Job> = q.jobs.drain(..).collect();
+                    drop(q);
+                    for j in left {
+                        Self::set_progress(
+                            &out.list,
+                            Progress {
+                                finished: true,
+                                error: "Sitzung beendet".to_string(),
+                                ..Progress::new(j.id, j.name, 0, false)
+                            },
+                        );
+                    }
+                    return;
+                }
+                match q.jobs.iter().position(|j| !j.ctl.paused.load(Ordering::Relaxed)) {
+                    Some(i) => q.jobs.remove(i),
+                    None if q.jobs.is_empty() => {
+                        q.busy = false;
+                        return;
+                    }
+                    // only held jobs left - they may be resumed any moment
+                    None => None,
+                }
+            };
+            let Some(job) = job else {
+                std::thread::sleep(Duration::from_millis(100));
+                continue;
+            };
+            if job.dir {
+                Self::run_dir(&out, &job);
+            } else {
+                Self::run_file(&out, &job);
+            }
+            out.ctls.lock().unwrap().remove(&job.id);
+        }
+    }
+
+    fn run_file(out: &Out, job: &Job) {
+        let (id, list) = (job.id, &out.list);
+        let size = fs::metadata(&job.path).map(|m| m.len()).unwrap_or(0);
+        let paused = || job.ctl.paused.load(Ordering::Relaxed);
+        Self::set_progress(list, Progress::new(id, job.name.clone(), size, false));
+        let res = out.push(id, &job.path, job.name.clone(), 0, &job.ctl, &mut |done, hashing| {
             Self::set_progress(
-                &self.shared,
+                list,
                 Progress {
-                    id,
-                    name,
-                    size: 0,
-                    done: 0,
-                    incoming: false,
-                    finished: true,
-                    error: "Ordner werden (noch) nicht uebertragen".to_string(),
+                    done,
+                    hashing,
+                    paused: paused(),
+                    ..Progress::new(id, job.name.clone(), size, false)
                 },
             );
-            return;
+        });
+        let mut p = Progress::new(id, job.name.clone(), size, false);
+        p.finished = true;
+        match res {
+            Ok(n) => p.done = n,
+            Err(e) => p.error = e,
         }
-        let size = meta.len();
-        let acked = Arc::new(AtomicU64::new(0));
-        self.acks.insert(id, acked.clone());
-
-        Self::set_progress(
-            &self.shared,
-            Progress {
-                id,
-                name: name.clone(),
-                size,
-                done: 0,
-                incoming: false,
-                finished: false,
-                error: String::new(),
-            },
-        );
+        Self::set_progress(list, p);
+    }
 
-        let send = self.send.clone();
-        let stop = self.stop.clone();
-        let shared = self.shared.clone();
-        std::thread::spawn(move || {
-            let mut fail = |msg: String| {
+    /// A folder: the tree first (directories, links), then the files one
+    /// after the other, each with its own id but one progress line.
+    fn run_dir(out: &Out, job: &Job) {
+        let (id, list, root) = (job.id, &out.list, &job.path);
+        let name = job.name.clone();
+        Self::set_progress(list, Progress::new(id, name.clone(), 0, false));
+        let entries = match walk(root) {
+            Ok(e) => e,
+            Err(e) => {
                 Self::set_progress(
-                    &shared,
+                    list,
                     Progress {
-                        id,
-                        name: name.clone(),
-                        size,
-                        done: 0,
-                        incoming: false,
                         finished: true,
-                        error: msg.clone(),
+                        error: format!("{}", e),
+                        ..Progress::new(id, name, 0, false)
                     },
                 );
-                send(Msg::FileEnd {
-                    id,
-                    ok: false,
-                    msg,
-                });
-            };
+                return;
+            }
+        };
+        let files = entries
+            .iter()
+            .filter(|e| matches!(e.item, Item::File(_)))
+            .count() as u32;
+        let total: u64 = entries
+            .iter()
+            .map(|e| match e.item {
+                Item::File(n) => n,
+                _ => 0,
+            })
+            .sum();
+        let mut p = Progress {
+            files,
+            ..Progress::new(id, name.clone(), total, false)
+        };
+        Self::set_progress(list, p.clone());
+        (out.send)(Msg::DirOffer {
+            id,
+            name,
+            files,
+            size: total,
+        });
+        (out.send)(Msg::DirEntry {
+            dir: id,
+            path: String::new(),
+            kind: EntryKind::Dir,
+            target: String::new(),
+            mtime: fs::metadata(root).map(|m| mtime_of(&m)).unwrap_or(0),
+        });
 
-            let mut f = match File::open(&path) {
-                Ok(f) => f,
-                Err(e) => return fail(format!("{}", e)),
+        let cancelled = || job.ctl.cancel.load(Ordering::Relaxed);
+        let mut failed = 0u32;
+        let mut last = String::new();
+        for e in &entries {
+            if out.stop.load(Ordering::Relaxed) || cancelled() {
+                break;
+            }
+            let kind = match &e.item {
+                Item::Dir => EntryKind::Dir,
+                Item::Link { dir: true, .. } => EntryKind::DirLink,
+                Item::Link { dir: false, .. } => EntryKind::Link,
+                Item::File(size) => {
+                    let base = p.done;
+                    let res = out.push(out.id(), &e.path, e.rel.clone(), id, &job.ctl, &mut |n, hashing| {
+                        Self::set_progress(
+                            list,
+                            Progress {
+                                done: base + n,
+                                hashing,
+                                paused: job.ctl.paused.load(Ordering::Relaxed),
+                                ..p.clone()
+                            },
+                        );
+                    });
+                    if let Err(err) = res {
+                        failed += 1;
+                        last = err;
+                    }
+                    p.done = base + size;
+                    p.files_done += 1;
+                    Self::set_progress(list, p.clone());
+                    continue;
+                }
             };
-            send(Msg::FileOffer {
-                id,
-                name: name.clone(),
-                size,
+            let target = match &e.item {
+                Item::Link { target, .. } => target.clone(),
+                _ => String::new(),
+            };
+            (out.send)(Msg::DirEntry {
+                dir: id,
+                path: e.rel.clone(),
+                kind,
+                target,
+                mtime: e.mtime,
             });
+        }
+        p.finished = true;
+        if out.stop.load(Ordering::Relaxed) {
+            p.error = "Sitzung beendet".to_string();
+        } else if cancelled() {
+            p.error = "abgebrochen".to_string();
+        } else if failed > 0 {
+            p.error = format!("{} von {} Dateien fehlgeschlagen: {}", failed, files, last);
+        }
+        (out.send)(Msg::FileEnd {
+            id,
+            ok: p.error.is_empty(),
+            msg: p.error.clone(),
+        });
+        Self::set_progress(list, p);
+    }
 
-            let mut buf = vec![0u8; CHUNK];
-            let mut off = 0u64;
-            let mut seen_ack = 0u64;
-            let mut since = Instant::now();
-            loop {
-                if stop.load(Ordering::Relaxed) {
-                    return fail("Sitzung beendet".to_string());
-                }
-                // flow control: never push more than WINDOW unacknowledged bytes
-                while off.saturating_sub(acked.load(Ordering::Relaxed)) >= WINDOW {
-                    if stop.load(Ordering::Relaxed) {
-                        return fail("Sitzung beendet".to_string());
-                    }
-                    let now = acked.load(Ordering::Relaxed);
-                    if now != seen_ack {
-                        seen_ack = now;
-                        since = Instant::now();
-                    } else if since.elapsed() > STALL {
-                        return fail("Gegenstelle antwortet nicht".to_string());
-                    }
-                    std::thread::sleep(Duration::from_millis(3));
-                }
+    /// Holds an outgoing transfer: a running one stops after the current
+    /// chunk, a queued one lets the others pass.
+    pub fn pause(&mut self, id: u32) {
+        self.hold(id, true);
+    }
 
-                let n = match f.read(&mut buf) {
-                    Ok(0) => break,
-                    Ok(n) => n,
-                    Err(e) => return fail(format!("{}", e)),
-                };
-                send(Msg::FileChunk {
-                    id,
-                    off,
-                    data: buf[..n].to_vec(),
-                });
-                off += n as u64;
+    pub fn resume(&mut self, id: u32) {
+        self.hold(id, false);
+    }
+
+    fn hold(&mut self, id: u32, on: bool) {
+        if let Some(c) = self.out.ctls.lock().unwrap().get(&id) {
+            c.paused.store(on, Ordering::Relaxed);
+        } else {
+            return;
+        }
+        let mut list = self.list.lock().unwrap();
+        if let Some(p) = list.iter_mut().find(|x| x.id == id && !x.incoming) {
+            p.paused = on;
+        }
+    }
+
+    /// Cancels transfer `id`. Outgoing: taken out of the queue, or stopped
+    /// with a `FileEnd` the receiver understands. Incoming single files are
+    /// abandoned and the sender is told.
+    pub fn cancel(&mut self, id: u32, incoming: bool) {
+        if incoming {
+            // a file inside a folder stays, the folder runs on the sender
+            if self.incoming.get(&id).is_none_or(|i| i.folder != 0) {
+                return;
+            }
+            self.drop_incoming(id, "abgebrochen".to_string());
+            return;
+        }
+        let queued = {
+            let mut q = self.out.queue.lock().unwrap();
+            let at = q.jobs.iter().position(|j| j.id == id);
+            at.and_then(|i| q.jobs.remove(i))
+        };
+        match queued {
+            Some(job) => {
+                self.out.ctls.lock().unwrap().remove(&id);
                 Self::set_progress(
-                    &shared,
+                    &self.list,
                     Progress {
-                        id,
-                        name: name.clone(),
-                        size,
-                        done: off,
-                        incoming: false,
-                        finished: false,
-                        error: String::new(),
+                        finished: true,
+                        error: "abgebrochen".to_string(),
+                        ..Progress::new(id, job.name, 0, false)
                     },
                 );
-                // be nice to the video stream
-                std::thread::sleep(Duration::from_millis(1));
             }
-
-            send(Msg::FileEnd {
-                id,
-                ok: true,
-                msg: String::new(),
-            });
-
-            // "done" means the other side has written every byte, not just
-            // that we pushed them into the socket
-            let mut waited = Instant::now();
-            let mut seen = acked.load(Ordering::Relaxed);
-            while acked.load(Ordering::Relaxed) < off {
-                if stop.load(Ordering::Relaxed) {
-                    break;
-                }
-                let now = acked.load(Ordering::Relaxed);
-                if now != seen {
-                    seen = now;
-                    waited = Instant::now();
-                } else if waited.elapsed() > STALL {
-                    break;
+            None => {
+                if let Some(c) = self.out.ctls.lock().unwrap().get(&id) {
+                    c.cancel.store(true, Ordering::Relaxed);
+                    c.paused.store(false, Ordering::Relaxed);
                 }
-                std::thread::sleep(Duration::from_millis(5));
             }
-            let confirmed = acked.load(Ordering::Relaxed);
-            Self::set_progress(
-                &shared,
-                Progress {
-                    id,
-                    name,
-                    size,
-                    done: confirmed.min(off),
-                    incoming: false,
-                    finished: true,
-                    error: if confirmed >= off {
-                        String::new()
-                    } else {
-                        format!("nur {} von {} Bytes bestaetigt", confirmed, off)
-                    },
-                },
-            );
-        });
+        }
     }
 
-    /// Feed every `Msg::File*` of the session in here.
+    /// Feed every `Msg::File*`/`Msg::Dir*` of the session in here.
     pub fn on_msg(&mut self, m: Msg) {
         match m {
-            Msg::FileOffer { id, name, size } => {
-                let dir = self.shared.drop_dir.lock().unwrap().clone();
-                let _ = fs::create_dir_all(&dir);
+            Msg::FileOffer {
+                id,
+                name,
+                size,
+                sha256,
+                folder,
+                mtime,
+                codecs,
+            } => self.accept(id, &name, size, sha256, folder, mtime, codecs),
+            Msg::DirOffer {
+                id,
+                name,
+                files,
+                size,
+            } => {
                 let clean = safe_name(&name);
-                let path = unique_path(&dir, &clean);
-                match File::create(&path) {
-                    Ok(file) => {
-                        self.incoming.insert(
+                // folders are merged: what already exists is handled file
+                // by file according to the conflict rule
+                let root = self.drop_dir(id).join(&clean);
+                let mut p = Progress {
+                    files,
+                    ..Progress::new(id, clean, size, true)
+                };
+                match fs::create_dir_all(&root) {
+                    Ok(()) => {
+                        self.folders.insert(
                             id,
-                            Incoming {
-                                file,
-                                path: path.clone(),
-                                got: 0,
-                                acked: 0,
-                            },
-                        );
-                        Self::set_progress(
-                            &self.shared,
-                            Progress {
-                                id,
-                                name: path
-                                    .file_name()
-                                    .map(|s| s.to_string_lossy().to_string())
-                                    .unwrap_or(clean),
-                                size,
-                                done: 0,
-                                incoming: true,
-                                finished: false,
-                                error: String::new(),
+                            Folder {
+                                root,
+                                base: 0,
+                                files_done: 0,
+                                failed: 0,
+                                last_error: String::new(),
+                                dirs: Vec::new(),
                             },
                         );
                     }
                     Err(e) => {
-                        (self.send)(Msg::FileEnd {
+                        p.finished = true;
+                        p.error = format!("{}", e);
+                        self.send(Msg::FileEnd {
                             id,
                             ok: false,
-                            msg: format!("{}", e),
+                            msg: p.error.clone(),
                         });
-                        Self::set_progress(
-                            &self.shared,
-                            Progress {
-                                id,
-                                name: clean,
-                                size,
-                                done: 0,
-                                incoming: true,
-                                finished: true,
-                                error: format!("{}", e),
-                            },
-                        );
                     }
                 }
+                Self::set_progress(&self.list, p);
             }
+            Msg::DirEntry {
+                dir,
+                path,
+                kind,
+                target,
+                mtime,
+            } => {
+                let Some(f) = self.folders.get_mut(&dir) else {
+                    return;
+                };
+                if path.is_empty() {
+                    f.dirs.push((f.root.clone(), mtime));
+                    return;
+                }
+                if let Some(at) = place_entry(&f.root, &path, kind, &target) {
+                    f.dirs.push((at, mtime));
+                }
+            }
+            Msg::FileChunkZ { id, off, len, data } => match unpack(&data, len as usize) {
+                Some(data) => self.on_msg(Msg::FileChunk { id, off, data }),
+                None => self.drop_incoming(id, "beschaedigter Block".to_string()),
+            },
             Msg::FileChunk { id, off, data } => {
                 let mut broken: Option<String> = None;
                 if let Some(inc) = self.incoming.get_mut(&id) {
-                    if off != inc.got {
-                        if inc.file.seek(SeekFrom::Start(off)).is_err() {
-                            broken = Some("Schreibfehler".to_string());
-                        }
-                    }
-                    if broken.is_none() {
-                        match inc.file.write_all(&data) {
-                            Ok(()) => {
-                                inc.got = off + data.len() as u64;
-                                if inc.got - inc.acked >= ACK_EVERY {
-                                    inc.acked = inc.got;
-                                    (self.send)(Msg::FileAck { id, got: inc.got });
-                                }
+                    match inc.write(off, &data) {
+                        Ok(()) => {
+                            if inc.got - inc.acked >= ACK_EVERY {
+                                inc.acked = inc.got;
+                                (self.out.send)(Msg::FileAck { id, got: inc.got });
                             }
-                            Err(e) => broken = Some(format!("{}", e)),
                         }
+                        Err(e) => broken = Some(format!("{}", e)),
                     }
                 }
                 if let Some(err) = broken {
-                    self.incoming.remove(&id);
-                    (self.send)(Msg::FileEnd {
-                        id,
-                        ok: false,
-                        msg: err.clone(),
-                    });
-                    let mut list = self.shared.xfers.lock().unwrap();
-                    if let Some(p) = list.iter_mut().find(|x| x.id == id && x.incoming) {
-                        p.finished = true;
-                        p.error = err;
-                    }
+                    self.drop_incoming(id, err);
                     return;
                 }
-                let done = self.incoming.get(&id).map(|i| i.got);
-                if let Some(done) = done {
-                    let mut list = self.shared.xfers.lock().unwrap();
-                    if let Some(p) = list.iter_mut().find(|x| x.id == id && x.incoming) {
-                        p.done = done;
-                    }
+                let (folder, done) = match self.incoming.get(&id) {
+                    Some(i) => (i.folder, i.got),
+                    None => return,
+                };
+                if folder != 0 {
+                    self.folder_progress(folder, done);
+                    return;
+                }
+                let mut list = self.list.lock().unwrap();
+                if let Some(p) = list.iter_mut().find(|x| x.id == id && x.incoming) {
+                    p.done = done;
                 }
             }
             Msg::FileEnd { id, ok, msg } => {
-                if let Some(mut inc) = self.incoming.remove(&id) {
-                    let _ = inc.file.flush();
-                    (self.send)(Msg::FileAck { id, got: inc.got });
-                    let mut list = self.shared.xfers.lock().unwrap();
+                if let Some(inc) = self.incoming.remove(&id) {
+                    let (folder, size, got) = (inc.folder, inc.size, inc.got);
+                    let name = inc.name.clone();
+                    let verdict = if ok {
+                        inc.finish()
+                    } else {
+                        inc.abandon();
+                        Err(if msg.is_empty() {
+                            "abgebrochen".to_string()
+                        } else {
+                            msg
+                        })
+                    };
+                    match &verdict {
+                        Ok(_) => self.send(Msg::FileAck { id, got }),
+                        Err(e) if ok => self.send(Msg::FileEnd {
+                            id,
+                            ok: false,
+                            msg: e.clone(),
+                        }),
+                        Err(_) => {}
+                    }
+                    let verdict = verdict.map(|dest| {
+                        dest.file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or(name.clone())
+                    });
+                    self.settle(id, folder, size, got, name, verdict);
+                } else if let Some((folder, size)) = self.skipped.remove(&id) {
+                    self.send(Msg::FileAck { id, got: size });
+                    if folder != 0 {
+                        self.settle(id, folder, size, size, String::new(), Ok(String::new()));
+                    }
+                } else if let Some(f) = self.folders.remove(&id) {
+                    for (d, t) in &f.dirs {
+                        set_mtime(d, *t);
+                    }
+                    let mut list = self.list.lock().unwrap();
                     if let Some(p) = list.iter_mut().find(|x| x.id == id && x.incoming) {
                         p.finished = true;
-                        p.done = inc.got;
-                        if !ok {
+                        p.done = f.base;
+                        p.files_done = f.files_done;
+                        if f.failed > 0 {
+                            p.error = format!(
+                                "{} Dateien fehlgeschlagen: {}",
+                                f.failed, f.last_error
+                            );
+                        } else if !ok {
                             p.error = if msg.is_empty() {
                                 "abgebrochen".to_string()
                             } else {
@@ -447,12 +1507,13 @@ impl Xfer {
                             };
                         }
                     }
-                    if !ok {
-                        let _ = fs::remove_file(&inc.path);
-                    }
                 } else {
                     // the other side reports a problem with something we send
-                    let mut list = self.shared.xfers.lock().unwrap();
+                    if let Some(r) = self.out.replies.lock().unwrap().get(&id) {
+                        *r.why.lock().unwrap() = msg.clone();
+                        r.refused.store(!ok, Ordering::Relaxed);
+                    }
+                    let mut list = self.list.lock().unwrap();
                     if let Some(p) = list.iter_mut().find(|x| x.id == id && !x.incoming) {
                         p.finished = true;
                         if !ok {
@@ -461,13 +1522,19 @@ impl Xfer {
                     }
                 }
             }
+            Msg::FileResume { id, off, codec } => {
+                if let Some(r) = self.out.replies.lock().unwrap().get(&id) {
+                    r.codec.store(codec, Ordering::Relaxed);
+                    r.resume.store(off, Ordering::Relaxed);
+                }
+            }
             Msg::FileAck { id, got } => {
-                if let Some(a) = self.acks.get(&id) {
-                    a.fetch_max(got, Ordering::Relaxed);
+                if let Some(r) = self.out.replies.lock().unwrap().get(&id) {
+                    r.acked.fetch_max(got, Ordering::Relaxed);
                 }
-                let mut list = self.shared.xfers.lock().unwrap();
+                let mut list = self.list.lock().unwrap();
                 if let Some(p) = list.iter_mut().find(|x| x.id == id && !x.incoming) {
-                    if p.size > 0 && got >= p.size {
+                    if p.files == 0 && p.size > 0 && got >= p.size {
                         p.finished = true;
                         p.done = p.size;
                     }
@@ -477,22 +1544,199 @@ impl Xfer {
         }
     }
 
+    /// An incoming file breaks off and the sender hears why.
+    fn drop_incoming(&mut self, id: u32, err: String) {
+        if let Some(inc) = self.incoming.remove(&id) {
+            self.send(Msg::FileEnd {
+                id,
+                ok: false,
+                msg: err.clone(),
+            });
+            let (folder, size, got, name) = (inc.folder, inc.size, inc.got, inc.name.clone());
+            inc.abandon();
+            self.settle(id, folder, size, got, name, Err(err));
+        }
+    }
+
+    /// An offer comes in: find its place, apply the conflict rule and
+    /// open (or continue) the `.part` file.
+    #[allow(clippy::too_many_arguments)]
+    fn accept(
+        &mut self,
+        id: u32,
+        name: &str,
+        size: u64,
+        sha256: Option<[u8; 32]>,
+        folder: u32,
+        mtime: u64,
+        codecs: u8,
+    ) {
+        let (dir, clean) = if folder == 0 {
+            (self.drop_dir(id), safe_name(name))
+        } else {
+            let target = self
+                .folders
+                .get(&folder)
+                .and_then(|f| folder_target(&f.root, name));
+            match target {
+                Some(t) => t,
+                None => {
+                    let err = "ungueltiger Pfad".to_string();
+                    self.send(Msg::FileEnd {
+                        id,
+                        ok: false,
+                        msg: err.clone(),
+                    });
+                    self.settle(id, folder, size, 0, safe_name(name), Err(err));
+                    return;
+                }
+            }
+        };
+        let _ = fs::create_dir_all(&dir);
+        // only our own download folder; one picked on the host through the
+        // file manager may hold other programs' downloads
+        if folder == 0
+            && dir == *self.shared.drop_dir.lock().unwrap()
+            && self.swept.insert(dir.clone())
+        {
+            sweep_parts(&dir, PART_MAX_AGE);
+        }
+        let rule = self.shared.on_conflict.load(Ordering::Relaxed);
+        let exists = dir.join(&clean).exists();
+        if exists && rule == SKIP && sha256.is_some() {
+            // the sender jumps to the end and closes, nothing is written
+            self.send(Msg::FileResume {
+                id,
+                off: size,
+                codec: 0,
+            });
+            self.skipped.insert(id, (folder, size));
+            if folder == 0 {
+                Self::set_progress(
+                    &self.list,
+                    Progress {
+                        done: size,
+                        finished: true,
+                        ..Progress::new(id, format!("{} (uebersprungen)", clean), size, true)
+                    },
+                );
+            }
+            return;
+        }
+        match Incoming::open(&dir, &clean, size, sha256) {
+            Ok(mut inc) => {
+                inc.folder = folder;
+                inc.mtime = mtime;
+                inc.overwrite = exists && rule == OVERWRITE;
+                let have = inc.got;
+                if sha256.is_some() {
+                    self.send(Msg::FileResume {
+                        id,
+                        off: have,
+                        codec: codecs & CODEC_DEFLATE,
+                    });
+                }
+                self.incoming.insert(id, inc);
+                if folder != 0 {
+                    self.folder_progress(folder, have);
+                } else {
+                    Self::set_progress(
+                        &self.list,
+                        Progress {
+                            done: have,
+                            ..Progress::new(id, clean, size, true)
+                        },
+                    );
+                }
+            }
+            Err(e) => {
+                self.send(Msg::FileEnd {
+                    id,
+                    ok: false,
+                    msg: format!("{}", e),
+                });
+                self.settle(id, folder, size, 0, clean, Err(format!("{}", e)));
+            }
+        }
+    }
+
+    fn drop_dir(&mut self, id: u32) -> PathBuf {
+        match self.dests.remove(&id) {
+            Some(d) => d,
+            None => self.shared.drop_dir.lock().unwrap().clone(),
+        }
+    }
+
+    /// One incoming file is through, for good or bad. Inside a folder it
+    /// only moves the folder's line on; `result` carries the final name.
+    fn settle(
+        &mut self,
+        id: u32,
+        folder: u32,
+        size: u64,
+        done: u64,
+        name: String,
+        result: Result<String, String>,
+    ) {
+        if let Some(f) = self.folders.get_mut(&folder) {
+            f.base += size;
+            f.files_done += 1;
+            if let Err(e) = result {
+                f.failed += 1;
+                f.last_error = e;
+            }
+            self.folder_progress(folder, 0);
+            return;
+        }
+        let mut p = Progress::new(id, name, size, true);
+        p.finished = true;
+        p.done = done;
+        match result {
+            Ok(n) => p.name = n,
+            Err(e) => p.error = e,
+        }
+        Self::set_progress(&self.list, p);
+    }
+
+    /// Overall line of folder `id`: the finished files plus `running` bytes
+    /// of the one in flight.
+    fn folder_progress(&self, id: u32, running: u64) {
+        let Some(f) = self.folders.get(&id) else {
+            return;
+        };
+        let mut list = self.list.lock().unwrap();
+        if let Some(p) = list.iter_mut().find(|x| x.id == id && x.incoming) {
+            p.done = f.base + running;
+            p.files_done = f.files_done;
+        }
+    }
+
     /// Cancels everything that is still running (session is going away).
     pub fn shutdown(&mut self) {
-        self.stop.store(true, Ordering::Relaxed);
-        let ids: Vec<u32> = self.incoming.keys().copied().collect();
-        for (_, inc) in self.incoming.drain() {
-            drop(inc.file);
-            // do not pretend the fragment is a complete file
-            let mut fragment = inc.path.clone().into_os_string();
-            fragment.push(".unvollstaendig");
-            let _ = fs::rename(&inc.path, PathBuf::from(fragment));
-        }
-        let mut list = self.shared.xfers.lock().unwrap();
+        self.out.stop.store(true, Ordering::Relaxed);
+        let mut ids: Vec<(u32, bool)> = Vec::new();
+        for (id, inc) in self.incoming.drain() {
+            // the fragment keeps its `.part` name, never posing as complete
+            ids.push((id, inc.sha256.is_some()));
+            inc.abandon();
+        }
+        // files of a folder resume one by one as well
+        ids.extend(self.folders.drain().map(|(id, _)| (id, true)));
+        self.skipped.clear();
+        self.dests.clear();
+        let mut list = self.list.lock().unwrap();
         for p in list.iter_mut() {
-            if p.incoming && !p.finished && ids.contains(&p.id) {
+            let resumable = match ids.iter().find(|(id, _)| *id == p.id) {
+                Some(&(_, r)) => r,
+                None => continue,
+            };
+            if p.incoming && !p.finished {
                 p.finished = true;
-                p.error = "Sitzung beendet".to_string();
+                p.error = if resumable {
+                    "Sitzung beendet - erneut senden setzt fort".to_string()
+                } else {
+                    "Sitzung beendet".to_string()
+                };
             }
         }
     }
@@ -500,7 +1744,7 @@ impl Xfer {
 
 impl Drop for Xfer {
     fn drop(&mut self) {
-        self.stop.store(true, Ordering::Relaxed);
+        self.out.stop.store(true, Ordering::Relaxed);
     }
 }
 
@@ -508,7 +1752,14 @@ impl Drop for Xfer {
 pub fn is_file_msg(m: &Msg) -> bool {
     matches!(
         m,
-        Msg::FileOffer { .. } | Msg::FileChunk { .. } | Msg::FileEnd { .. } | Msg::FileAck { .. }
+        Msg::FileOffer { .. }
+            | Msg::DirOffer { .. }
+            | Msg::DirEntry { .. }
+            | Msg::FileResume { .. }
+            | Msg::FileChunk { .. }
+            | Msg::FileChunkZ { .. }
+            | Msg::FileEnd { .. }
+            | Msg::FileAck { .. }
     )
 }
 
@@ -548,7 +1799,235 @@ mod tests {
             incoming: true,
             finished: false,
             error: String::new(),
+            files: 0,
+            files_done: 0,
+            queued: false,
+            paused: false,
+            hashing: false,
         };
         assert!((p.percent() - 0.25).abs() < 0.001);
     }
+
+    #[test]
+    fn chunks_pack_and_unpack_to_the_exact_length() {
+        let text = b"2026-10-18 12:00:01 INFO verbunden\n".repeat(2000);
+        let z = pack(&text);
+        assert!(z.len() * 10 < text.len());
+        assert_eq!(unpack(&z, text.len()).as_deref(), Some(&text[..]));
+        // a chunk that claims less than it holds is refused, not grown
+        assert!(unpack(&z, 100).is_none());
+        assert!(unpack(&[1, 2, 3], 10).is_none());
+    }
+
+    #[test]
+    fn only_what_shrinks_is_packed() {
+        let dir = std::env::temp_dir().join(format!("fvpack{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("a.log");
+        fs::write(&log, b"GET /index.html 200 1234\n".repeat(10_000)).unwrap();
+        assert!(worth_packing(&log, fs::metadata(&log).unwrap().len()));
+        // noise stands in for a JPEG or a ZIP
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        let noise: Vec<u8> = (0..200_000)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect();
+        let zip = dir.join("a.zip");
+        fs::write(&zip, &noise).unwrap();
+        assert!(!worth_packing(&zip, noise.len() as u64));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rates_read_like_humans_write_them() {
+        assert_eq!(parse_rate("2M"), Some(2 * 1024 * 1024));
+        assert_eq!(parse_rate("500k"), Some(500 * 1024));
+        assert_eq!(parse_rate("1.5M"), Some(1024 * 1024 * 3 / 2));
+        assert_eq!(parse_rate("4096"), Some(4096));
+        assert_eq!(parse_rate("0"), Some(0));
+        assert_eq!(parse_rate("schnell"), None);
+        assert_eq!(parse_rate("-1M"), None);
+        assert_eq!(parse_rate(""), None);
+    }
+
+    #[test]
+    fn pacer_keeps_to_the_rate() {
+        let pace = Pacer::new();
+        let stop = AtomicBool::new(false);
+        let start = Instant::now();
+        // 1 MB at 4 MB/s in chunks: about a quarter second
+        for _ in 0..16 {
+            pace.take(64 * 1024, 4 * 1024 * 1024, &stop);
+        }
+        let t = start.elapsed();
+        assert!(t >= Duration::from_millis(200), "too fast: {:?}", t);
+        assert!(t < Duration::from_millis(1500), "too slow: {:?}", t);
+        // no limit, no waiting
+        let start = Instant::now();
+        pace.take(u64::MAX, 0, &stop);
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+
+    fn sha(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+
+    #[test]
+    fn file_hash_is_sha256() {
+        let dir = std::env::temp_dir().join(format!("fvsha{}", std::process::id()));
+        let _ = fs::create_dir_all(&dir);
+        let f = dir.join("abc.txt");
+        fs::write(&f, b"abc").unwrap();
+        let h = sha256_file(&f).unwrap();
+        assert_eq!(h[..4], [0xba, 0x78, 0x16, 0xbf]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn broken_transfer_resumes_from_the_part_file() {
+        let dir = std::env::temp_dir().join(format!("fvresume{}", std::process::id()));
+        let _ = fs::create_dir_all(&dir);
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 7) as u8).collect();
+        let h = sha(&data);
+
+        let mut first = Incoming::open(&dir, "a.bin", data.len() as u64, Some(h)).unwrap();
+        assert_eq!(first.got, 0);
+        first.write(0, &data[..1800]).unwrap();
+        first.abandon();
+
+        let mut again = Incoming::open(&dir, "a.bin", data.len() as u64, Some(h)).unwrap();
+        assert_eq!(again.got, 1800, "continues where the session broke");
+        again.write(1800, &data[1800..]).unwrap();
+        let dest = again.finish().unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), data);
+        assert_eq!(dest.file_name().unwrap(), "a.bin");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "no .part left");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stale_part_files_are_swept() {
+        let dir = std::env::temp_dir().join(format!("fvsweep{}", std::process::id()));
+        let _ = fs::create_dir_all(&dir);
+        let month_ago = mtime_of(&fs::metadata(&dir).unwrap()) - 30 * 24 * 3600;
+        let h = sha(b"x");
+        let part = part_path(&dir, "a.bin", Some(&h));
+        assert!(part.to_string_lossy().ends_with(".2d711642b726b04401627ca9fbac32f5.part"));
+        let new = part_path(&dir, "b.bin", Some(&h));
+        for (path, mtime) in [
+            (part.clone(), month_ago),
+            (new.clone(), 0),
+            (dir.join("foo.part"), month_ago),
+            (dir.join("foo.2D711642B726B04401627CA9FBAC32F5.part"), month_ago),
+            (dir.join("old.txt"), month_ago),
+        ] {
+            fs::write(&path, b"x").unwrap();
+            set_mtime(&path, mtime);
+        }
+        sweep_parts(&dir, PART_MAX_AGE);
+        assert!(!part.exists());
+        assert!(new.exists());
+        assert!(dir.join("foo.part").exists(), "not one of ours");
+        assert!(dir.join("foo.2D711642B726B04401627CA9FBAC32F5.part").exists());
+        assert!(dir.join("old.txt").exists(), "only .part files go");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn wrong_checksum_fails_and_drops_the_part() {
+        let dir = std::env::temp_dir().join(format!("fvbad{}", std::process::id()));
+        let _ = fs::create_dir_all(&dir);
+        let mut inc = Incoming::open(&dir, "b.bin", 3, Some(sha(b"abc"))).unwrap();
+        // out of order on purpose: the check then re-reads the file
+        inc.write(1, b"bd").unwrap();
+        inc.write(0, b"a").unwrap();
+        assert!(inc.finish().is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn folder_paths_are_cleaned_per_component() {
+        assert_eq!(
+            safe_rel("fotos/2024\\a:b.jpg"),
+            Some(PathBuf::from("fotos").join("2024").join("a_b.jpg"))
+        );
+        assert_eq!(safe_rel("/./x//y"), Some(PathBuf::from("x").join("y")));
+        assert_eq!(safe_rel("a/../../evil"), None);
+        assert_eq!(safe_rel(""), None);
+    }
+
+    #[test]
+    fn links_must_stay_in_the_folder() {
+        assert!(link_stays_inside(Path::new("a/l"), "b.txt"));
+        assert!(link_stays_inside(Path::new("a/l"), "../c/d"));
+        assert!(!link_stays_inside(Path::new("a/l"), "../../x"));
+        assert!(!link_stays_inside(Path::new("l"), "/etc/passwd"));
+        assert!(!link_stays_inside(Path::new("l"), "C:\\Windows"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn link_chains_cannot_lead_out_of_the_folder() {
+        let base = std::env::temp_dir().join(format!("fvchain{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let root = base.join("in");
+        fs::create_dir_all(&root).unwrap();
+        place_entry(&root, "x", EntryKind::Dir, "");
+        // each link is harmless by its text, together they climb out
+        place_entry(&root, "x/d", EntryKind::DirLink, "..");
+        assert!(fs::symlink_metadata(root.join("x/d")).is_ok());
+        place_entry(&root, "x/d/y", EntryKind::DirLink, "..");
+        assert!(place_entry(&root, "x/d/z", EntryKind::Dir, "").is_none());
+        assert!(folder_target(&root, "x/d/y/evil.txt").is_none());
+        assert!(folder_target(&root, "x/d/evil.txt").is_none());
+        assert_eq!(
+            folder_target(&root, "x/ok.txt"),
+            Some((root.join("x"), "ok.txt".to_string()))
+        );
+        let outside: Vec<_> = fs::read_dir(&base).unwrap().flatten().map(|e| e.file_name()).collect();
+        assert_eq!(outside, vec![std::ffi::OsString::from("in")]);
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn walk_lists_dirs_before_their_files() {
+        let dir = std::env::temp_dir().join(format!("fvwalk{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("b/leer")).unwrap();
+        fs::write(dir.join("a.txt"), b"12").unwrap();
+        fs::write(dir.join("b/c.txt"), b"345").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("c.txt", dir.join("b/link")).unwrap();
+            std::os::unix::fs::symlink("/etc/passwd", dir.join("b/raus")).unwrap();
+        }
+        let got: Vec<String> = walk(&dir).unwrap().into_iter().map(|e| e.rel).collect();
+        let mut want = vec!["a.txt", "b", "b/c.txt", "b/leer"];
+        if cfg!(unix) {
+            want.push("b/link");
+        }
+        assert_eq!(got, want);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn overwrite_replaces_and_keeps_the_time() {
+        let dir = std::env::temp_dir().join(format!("fvover{}", std::process::id()));
+        let _ = fs::create_dir_all(&dir);
+        fs::write(dir.join("x.txt"), b"old").unwrap();
+        let mut inc = Incoming::open(&dir, "x.txt", 3, Some(sha(b"new"))).unwrap();
+        inc.overwrite = true;
+        inc.mtime = 1_600_000_000;
+        inc.write(0, b"new").unwrap();
+        let dest = inc.finish().unwrap();
+        assert_eq!(dest, dir.join("x.txt"));
+        assert_eq!(fs::read(&dest).unwrap(), b"new");
+        assert_eq!(mtime_of(&fs::metadata(&dest).unwrap()), 1_600_000_000);
+        let _ = fs::remove_dir_all(&dir);
+    }
 }